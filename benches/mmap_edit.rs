@@ -0,0 +1,70 @@
+use colab_engine::domain::traits::dyn_file::{DynemicFileRead, DynemicFileWrite};
+use colab_engine::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+const FILE_SIZE: usize = 16 * 1024 * 1024;
+
+fn large_file(dir: &TempDir, name: &str) -> PathBuf {
+    let path = dir.path().join(name);
+    let line = "let value = compute(input);\n";
+    std::fs::write(&path, line.repeat(FILE_SIZE / line.len())).expect("Failed to write file");
+    path
+}
+
+fn same_length_edit(c: &mut Criterion) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = large_file(&temp_dir, "same_length.rs");
+    let mut group = c.benchmark_group("same_length_edit_16mb");
+    group.sample_size(10);
+
+    let mut read_only = MmapFileSystemSource::new(path.clone()).expect("Failed to map file");
+    group.bench_function("rewrite", |b| {
+        b.iter(|| read_only.set_slice(black_box(4), black_box(9), "VALUE".to_string()))
+    });
+
+    let mut writable = MmapFileSystemSource::new_writable(path).expect("Failed to map file");
+    group.bench_function("in_place", |b| {
+        b.iter(|| writable.set_slice(black_box(4), black_box(9), "VALUE".to_string()))
+    });
+    group.bench_function("in_place_flush", |b| {
+        b.iter(|| {
+            writable.set_slice(black_box(4), black_box(9), "value".to_string());
+            writable.flush().expect("Failed to flush");
+        })
+    });
+    group.finish();
+}
+
+fn append(c: &mut Criterion) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut group = c.benchmark_group("append_16mb");
+    group.sample_size(10);
+
+    let path = large_file(&temp_dir, "append_rewrite.rs");
+    group.bench_function("rewrite", |b| {
+        b.iter_batched(
+            || MmapFileSystemSource::new(path.clone()).expect("Failed to map file"),
+            |mut source| {
+                let len = source.as_bytes().map_or(0, <[u8]>::len);
+                source.set_slice(len, len, "// tail\n".to_string())
+            },
+            BatchSize::PerIteration,
+        )
+    });
+
+    let path = large_file(&temp_dir, "append_in_place.rs");
+    let mut writable = MmapFileSystemSource::new_writable(path).expect("Failed to map file");
+    group.bench_function("in_place", |b| {
+        b.iter(|| {
+            let len = writable.as_bytes().map_or(0, <[u8]>::len);
+            writable.set_slice(len, len, "// tail\n".to_string())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, same_length_edit, append);
+criterion_main!(benches);
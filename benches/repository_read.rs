@@ -0,0 +1,47 @@
+use colab_engine::application::repositories::code_file_repository::CodeFileRepository;
+use colab_engine::domain::code_file::CodeFile;
+use colab_engine::domain::traits::dyn_file::DynemicFileRead;
+use colab_engine::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use colab_engine::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+const FILE_SIZE: usize = 1024 * 1024;
+
+// Reading a viewport out of a stored file: the lookup hands out a clone that
+// shares the mapping, where it used to open and map the file again.
+fn find_and_read(c: &mut Criterion) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("read.rs");
+    let line = "let value = compute(input);\n";
+    std::fs::write(&path, line.repeat(FILE_SIZE / line.len())).expect("Failed to write file");
+
+    let mut repository = InMemoryCodeFileRepository::new();
+    let source = MmapFileSystemSource::new_writable(path.clone()).expect("Failed to map file");
+    let id = repository
+        .save(CodeFile::new(Uuid::new_v4(), "read.rs".to_string(), source))
+        .expect("Failed to save file")
+        .id();
+
+    let mut group = c.benchmark_group("find_and_read_1mb");
+    group.bench_function("remap", |b| {
+        b.iter(|| {
+            let source = MmapFileSystemSource::new_writable(path.clone()).expect("Failed to map");
+            black_box(source.get_slice(0, 80))
+        })
+    });
+    group.bench_function("shared_mapping", |b| {
+        b.iter(|| {
+            let file = repository
+                .find_by_id(black_box(id))
+                .expect("Failed to find file");
+            black_box(file.source.get_slice(0, 80))
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, find_and_read);
+criterion_main!(benches);
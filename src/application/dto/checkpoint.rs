@@ -0,0 +1,53 @@
+use crate::domain::checkpoint::FileChange;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Without a `file_id` the checkpoint covers every file in the workspace.
+pub struct CreateCheckpointRequest {
+    pub name: String,
+    pub file_id: Option<Uuid>,
+}
+
+pub struct CheckpointedFileResponse {
+    pub file_id: Uuid,
+    pub name: String,
+    pub revision: u64,
+}
+
+pub struct CheckpointResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub file_id: Option<Uuid>,
+    pub created_at: SystemTime,
+    pub files: Vec<CheckpointedFileResponse>,
+}
+
+/// Compares the checkpoint with `against`, another checkpoint, or with the
+/// files as they are now when it is `None`.
+pub struct CompareCheckpointRequest {
+    pub checkpoint_id: Uuid,
+    pub against: Option<Uuid>,
+}
+
+pub struct FileComparisonResponse {
+    pub file_id: Uuid,
+    pub name: String,
+    pub change: FileChange,
+}
+
+pub struct RestoreCheckpointRequest {
+    pub checkpoint_id: Uuid,
+    pub author: String,
+}
+
+pub struct RestoredFileResponse {
+    pub file_id: Uuid,
+    pub revision: u64,
+}
+
+/// `restored` lists the files that had changed since the checkpoint, at the
+/// revision the restore produced. `missing` are files deleted since.
+pub struct RestoreCheckpointResponse {
+    pub restored: Vec<RestoredFileResponse>,
+    pub missing: Vec<Uuid>,
+}
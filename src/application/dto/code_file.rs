@@ -11,14 +11,35 @@ pub struct UpdateCodeRequest {
     pub start: u64,
     pub end: u64,
     pub content: String,
+    pub author: String,
 }
 
 pub struct CreateCodeFileRequest {
     pub name: String,
 }
 
+/// `name` is a new file name; the file stays in its directory.
+pub struct RenameCodeFileRequest {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// `name` is relative to where the file's current name starts, and may put
+/// it in another directory.
+pub struct MoveCodeFileRequest {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Copies the file to `name`, relative like a move, under a new id.
+pub struct CopyCodeFileRequest {
+    pub id: Uuid,
+    pub name: String,
+}
+
 pub struct CodeFileResponse {
     pub id: Uuid,
     pub name: String,
+    pub revision: u64,
     pub viewport: ViewportRequest,
 }
@@ -0,0 +1,34 @@
+use crate::domain::execution::ExecutionStatus;
+use uuid::Uuid;
+
+pub struct StartSessionRequest {
+    pub language: String,
+}
+
+pub struct SessionResponse {
+    pub session_id: Uuid,
+    pub language: String,
+}
+
+pub struct ExecuteCodeFileRequest {
+    pub session_id: Uuid,
+    pub file_id: Uuid,
+}
+
+pub struct ExecuteCellRequest {
+    pub session_id: Uuid,
+    pub notebook_id: Uuid,
+    pub cell_id: Uuid,
+}
+
+pub struct ExecutionResponse {
+    pub session_id: Uuid,
+    pub execution_id: u64,
+}
+
+pub struct ExecutionResult {
+    pub execution_id: u64,
+    pub status: ExecutionStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
@@ -0,0 +1,14 @@
+use uuid::Uuid;
+
+/// Author recorded on edits that were made on disk by another program.
+pub const EXTERNAL_AUTHOR: &str = "external";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalChangeEvent {
+    pub file_id: Uuid,
+    pub revision: u64,
+    pub start: u64,
+    pub end: u64,
+    pub text: String,
+    pub author: String,
+}
@@ -0,0 +1,51 @@
+use crate::domain::git::{FileStatus, Signature};
+use uuid::Uuid;
+
+pub struct GitStatusRequest {
+    pub repository: String,
+}
+
+pub struct FileStatusResponse {
+    pub file_id: Uuid,
+    pub name: String,
+    pub status: FileStatus,
+}
+
+pub struct GitStatusResponse {
+    pub branch: String,
+    pub files: Vec<FileStatusResponse>,
+}
+
+pub struct GitDiffRequest {
+    pub repository: String,
+    pub file_id: Uuid,
+}
+
+pub struct GitDiffResponse {
+    pub file_id: Uuid,
+    pub diff: String,
+}
+
+pub struct CommitRequest {
+    pub repository: String,
+    pub message: String,
+    pub author: Signature,
+    pub co_authors: Vec<Signature>,
+}
+
+pub struct CommitResponse {
+    pub commit_id: String,
+    pub files: Vec<Uuid>,
+}
+
+pub struct SwitchBranchRequest {
+    pub repository: String,
+    pub branch: String,
+    pub create: bool,
+}
+
+pub struct SwitchBranchResponse {
+    pub branch: String,
+    pub reloaded: Vec<Uuid>,
+    pub closed: Vec<Uuid>,
+}
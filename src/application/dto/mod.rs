@@ -1 +1,12 @@
+pub mod checkpoint;
 pub mod code_file;
+pub mod execution;
+pub mod external_change;
+pub mod git;
+pub mod notebook;
+pub mod resume;
+pub mod search;
+pub mod sync;
+pub mod trash;
+pub mod webhook;
+pub mod workspace;
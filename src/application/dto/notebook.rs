@@ -0,0 +1,79 @@
+use crate::domain::notebook::CellKind;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+pub struct CreateNotebookRequest {
+    pub name: String,
+}
+
+pub struct ImportNotebookRequest {
+    pub name: String,
+    pub content: String,
+}
+
+pub struct InsertCellRequest {
+    pub notebook_id: Uuid,
+    pub kind: CellKind,
+    pub after: Option<Uuid>,
+    pub source: String,
+}
+
+pub struct MoveCellRequest {
+    pub notebook_id: Uuid,
+    pub cell_id: Uuid,
+    pub after: Option<Uuid>,
+}
+
+pub struct DeleteCellRequest {
+    pub notebook_id: Uuid,
+    pub cell_id: Uuid,
+}
+
+pub struct UpdateCellRequest {
+    pub notebook_id: Uuid,
+    pub cell_id: Uuid,
+    pub start: u64,
+    pub end: u64,
+    pub content: String,
+}
+
+pub struct UpdateCellMetadataRequest {
+    pub notebook_id: Uuid,
+    pub cell_id: Uuid,
+    pub metadata: Map<String, Value>,
+}
+
+pub struct UpdateCellOutputsRequest {
+    pub notebook_id: Uuid,
+    pub cell_id: Uuid,
+    pub outputs: Vec<Value>,
+    pub execution_count: Option<u64>,
+}
+
+pub struct CellResponse {
+    pub id: Uuid,
+    pub kind: CellKind,
+    pub source: String,
+    pub metadata: Map<String, Value>,
+    pub outputs: Vec<Value>,
+    pub execution_count: Option<u64>,
+}
+
+pub struct NotebookResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub revision: u64,
+    pub cells: Vec<CellResponse>,
+}
+
+pub struct CellOperationResponse {
+    pub notebook_id: Uuid,
+    pub cell_id: Uuid,
+    pub revision: u64,
+}
+
+pub struct ExportNotebookResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub content: String,
+}
@@ -0,0 +1,52 @@
+use uuid::Uuid;
+
+/// An edit in char offsets. Pending edits apply one after another on top of
+/// the revision the client last acknowledged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+pub struct ResumeRequest {
+    pub file_id: Uuid,
+    pub last_revision: u64,
+    pub author: String,
+    pub pending: Vec<PendingEdit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissedOperation {
+    pub revision: u64,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub author: String,
+}
+
+/// What brings the client from its last revision to the one the server had
+/// before the pending edits were applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatchUp {
+    Operations(Vec<MissedOperation>),
+    Snapshot { revision: u64, content: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedEdit {
+    pub revision: u64,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// `applied` holds the pending edits as rebased and applied, one revision
+/// each. Edits that could not be rebased because the history was gone come
+/// back in `rejected`, untouched.
+pub struct ResumeResponse {
+    pub catch_up: CatchUp,
+    pub applied: Vec<AppliedEdit>,
+    pub rejected: Vec<PendingEdit>,
+    pub revision: u64,
+}
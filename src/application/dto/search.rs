@@ -0,0 +1,44 @@
+use uuid::Uuid;
+
+pub struct SearchRequest {
+    pub file_id: Uuid,
+    pub pattern: String,
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+}
+
+pub struct WorkspaceSearchRequest {
+    pub pattern: String,
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+}
+
+pub struct ReplaceAllRequest {
+    pub file_id: Uuid,
+    pub pattern: String,
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    pub replacement: String,
+    pub author: String,
+}
+
+pub struct SearchMatchResponse {
+    pub start: u64,
+    pub end: u64,
+    pub line: u64,
+    pub column: u64,
+    pub text: String,
+}
+
+pub struct SearchResponse {
+    pub file_id: Uuid,
+    pub name: String,
+    pub revision: u64,
+    pub matches: Vec<SearchMatchResponse>,
+}
+
+pub struct ReplaceAllResponse {
+    pub file_id: Uuid,
+    pub revision: u64,
+    pub replacements: u64,
+}
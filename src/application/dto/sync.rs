@@ -0,0 +1,27 @@
+use crate::application::dto::resume::PendingEdit;
+use uuid::Uuid;
+
+/// Everything a client did while offline: its edits in order, starting from
+/// `base_revision`, the last revision it got from the server.
+pub struct SyncRequest {
+    pub file_id: Uuid,
+    pub base_revision: u64,
+    pub author: String,
+    pub operations: Vec<PendingEdit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncOperation {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub author: String,
+}
+
+/// `operations` are the server-side changes rebased to apply after the
+/// client's own edits; once applied, the client is at `revision`.
+pub struct SyncResponse {
+    pub revision: u64,
+    pub operations: Vec<SyncOperation>,
+    pub applied: usize,
+}
@@ -0,0 +1,9 @@
+use std::time::SystemTime;
+use uuid::Uuid;
+
+pub struct TrashedFileResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub revision: u64,
+    pub deleted_at: SystemTime,
+}
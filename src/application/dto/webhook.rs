@@ -0,0 +1,27 @@
+use uuid::Uuid;
+
+/// Without a `file_id` the webhook covers every file this process serves. An empty
+/// `events` list subscribes to every kind.
+pub struct RegisterWebhookRequest {
+    pub url: String,
+    pub secret: String,
+    pub file_id: Option<Uuid>,
+    pub events: Vec<String>,
+}
+
+pub struct WebhookResponse {
+    pub id: Uuid,
+    pub url: String,
+    pub file_id: Option<Uuid>,
+    pub events: Vec<String>,
+}
+
+pub struct DeliveryResponse {
+    pub delivery_id: Uuid,
+    pub sequence: u64,
+    pub event: String,
+    pub attempt: u32,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub delivered: bool,
+}
@@ -0,0 +1,22 @@
+use uuid::Uuid;
+
+pub struct ImportDirectoryRequest {
+    pub root: String,
+    pub max_file_size: u64,
+}
+
+pub struct ImportedFileResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub path: String,
+}
+
+pub struct SkippedFileResponse {
+    pub path: String,
+    pub reason: String,
+}
+
+pub struct ImportDirectoryResponse {
+    pub files: Vec<ImportedFileResponse>,
+    pub skipped: Vec<SkippedFileResponse>,
+}
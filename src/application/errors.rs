@@ -1,8 +1,23 @@
+use crate::domain::notebook::NotebookError;
 
 #[derive(Debug)]
 pub enum ApplicationError {
     FileNotFound(String),
+    NotebookNotFound(String),
+    NotebookError(NotebookError),
+    SessionNotFound(String),
+    UnsupportedLanguage(String),
     IoError(std::io::Error),
     ParseError(serde_json::Error),
+    PatternError(regex::Error),
     InvalidRange(usize),
+    NothingToCommit(String),
+    WebhookNotFound(String),
+    InvalidWebhook(String),
+    UnknownRevision(u64),
+    HistoryUnavailable(u64),
+    PeerUnreachable(String),
+    CheckpointNotFound(String),
+    TrashedFileNotFound(String),
+    InvalidFileName(String),
 }
@@ -0,0 +1,11 @@
+use crate::application::errors::ApplicationError;
+use crate::domain::checkpoint::Checkpoint;
+use uuid::Uuid;
+
+pub trait CheckpointRepository: Send + Sync {
+    fn save(&mut self, checkpoint: Checkpoint) -> Result<Checkpoint, ApplicationError>;
+    fn find_by_id(&self, id: Uuid) -> Result<Checkpoint, ApplicationError>;
+    fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError>;
+    /// Every checkpoint, oldest first.
+    fn list(&self) -> Result<Vec<Checkpoint>, ApplicationError>;
+}
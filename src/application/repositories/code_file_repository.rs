@@ -1,15 +1,45 @@
 use crate::application::errors::ApplicationError;
 use crate::domain::code_file::CodeFile;
 use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+use async_trait::async_trait;
 use uuid::Uuid;
 
 pub trait CodeFileRepository<FileSource>: Send + Sync
 where
     FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
 {
-    fn save(&mut self, file: CodeFile<FileSource>) -> Result<CodeFile<FileSource>, ApplicationError>;
+    fn save(
+        &mut self,
+        file: CodeFile<FileSource>,
+    ) -> Result<CodeFile<FileSource>, ApplicationError>;
     fn find_by_id(&self, id: Uuid) -> Result<CodeFile<FileSource>, ApplicationError>;
     fn update(&mut self, file: CodeFile<FileSource>) -> Result<(), ApplicationError>;
     fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError>;
+    /// Every file as stored. Sources a cache has unloaded come back unloaded;
+    /// callers that read them go through `find_by_id`, which reopens them.
     fn list(&self) -> Result<Vec<CodeFile<FileSource>>, ApplicationError>;
+
+    /// The ids of every file, for callers that look files up one at a time
+    /// rather than holding all of them open.
+    fn ids(&self) -> Result<Vec<Uuid>, ApplicationError> {
+        Ok(self.list()?.iter().map(CodeFile::id).collect())
+    }
+}
+
+/// The async counterpart of `CodeFileRepository`. Every method takes `&self`
+/// so callers can share one repository and look files up concurrently;
+/// implementations keep blocking work off the async executor.
+#[async_trait]
+pub trait AsyncCodeFileRepository<FileSource>: Send + Sync
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete + Send + 'static,
+{
+    async fn save(
+        &self,
+        file: CodeFile<FileSource>,
+    ) -> Result<CodeFile<FileSource>, ApplicationError>;
+    async fn find_by_id(&self, id: Uuid) -> Result<CodeFile<FileSource>, ApplicationError>;
+    async fn update(&self, file: CodeFile<FileSource>) -> Result<(), ApplicationError>;
+    async fn delete(&self, id: Uuid) -> Result<(), ApplicationError>;
+    async fn list(&self) -> Result<Vec<CodeFile<FileSource>>, ApplicationError>;
 }
@@ -1 +1,5 @@
+pub mod checkpoint_repository;
 pub mod code_file_repository;
+pub mod notebook_repository;
+pub mod trash_repository;
+pub mod webhook_repository;
@@ -0,0 +1,18 @@
+use crate::application::errors::ApplicationError;
+use crate::domain::notebook::Notebook;
+use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+use uuid::Uuid;
+
+pub trait NotebookRepository<FileSource>: Send + Sync
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    fn save(
+        &mut self,
+        notebook: Notebook<FileSource>,
+    ) -> Result<Notebook<FileSource>, ApplicationError>;
+    fn find_by_id(&self, id: Uuid) -> Result<Notebook<FileSource>, ApplicationError>;
+    fn update(&mut self, notebook: Notebook<FileSource>) -> Result<(), ApplicationError>;
+    fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError>;
+    fn list(&self) -> Result<Vec<Notebook<FileSource>>, ApplicationError>;
+}
@@ -0,0 +1,14 @@
+use crate::application::errors::ApplicationError;
+use crate::domain::trash::TrashedFile;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+pub trait TrashRepository: Send + Sync {
+    fn save(&self, file: TrashedFile) -> Result<TrashedFile, ApplicationError>;
+    fn find_by_id(&self, file_id: Uuid) -> Result<TrashedFile, ApplicationError>;
+    fn delete(&self, file_id: Uuid) -> Result<(), ApplicationError>;
+    /// Every trashed file, oldest deletion first.
+    fn list(&self) -> Result<Vec<TrashedFile>, ApplicationError>;
+    /// Where the file of `file_id` is kept while it is in the trash.
+    fn trashed_path(&self, file_id: Uuid) -> PathBuf;
+}
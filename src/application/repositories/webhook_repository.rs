@@ -0,0 +1,14 @@
+use crate::application::errors::ApplicationError;
+use crate::domain::webhook::{DeliveryAttempt, Webhook};
+use uuid::Uuid;
+
+pub trait WebhookRepository: Send + Sync {
+    fn save(&mut self, webhook: Webhook) -> Result<Webhook, ApplicationError>;
+    fn find_by_id(&self, id: Uuid) -> Result<Webhook, ApplicationError>;
+    fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError>;
+    fn list(&self) -> Result<Vec<Webhook>, ApplicationError>;
+    fn record_delivery(&mut self, attempt: DeliveryAttempt) -> Result<(), ApplicationError>;
+    /// The attempts made for the webhook, oldest first. Implementations may
+    /// keep only the most recent ones.
+    fn deliveries(&self, webhook_id: Uuid) -> Result<Vec<DeliveryAttempt>, ApplicationError>;
+}
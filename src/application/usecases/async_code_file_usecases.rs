@@ -0,0 +1,264 @@
+use crate::application::dto::code_file::{
+    CodeFileResponse, CreateCodeFileRequest, UpdateCodeRequest, ViewportRequest,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::code_file_repository::AsyncCodeFileRepository;
+use crate::application::usecases::code_file_usecases::{CodeFileUsecases, CodeFileUsecasesImpl};
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use crate::infrastructure::persistence::blocking_repository::{
+    BlockingCodeFileRepository, run_blocking,
+};
+use async_trait::async_trait;
+use uuid::Uuid;
+
+/// The async counterpart of `CodeFileUsecases`. It takes `&self`, so one
+/// instance serves every request: reads run concurrently and writes are
+/// serialized per file, leaving edits to different files independent.
+#[async_trait]
+pub trait AsyncCodeFileUsecases: Send + Sync {
+    async fn create_code_file(
+        &self,
+        request: CreateCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError>;
+    async fn update_code_file(&self, request: UpdateCodeRequest) -> Result<(), ApplicationError>;
+    async fn get_code_file(&self, file_id: Uuid) -> Result<CodeFileResponse, ApplicationError>;
+    async fn delete_code_file(&self, file_id: Uuid) -> Result<(), ApplicationError>;
+}
+
+/// Serves the sync usecases to async callers, each call on a clone of them
+/// running on the blocking thread pool. No lock spans every file: a write
+/// holds the writer lock of its file, which the sync usecases take too, so
+/// writes to a file stay in order whichever side makes them.
+#[derive(Clone)]
+pub struct AsyncCodeFileUsecasesImpl {
+    pub repository: BlockingCodeFileRepository<MmapFileSystemSource>,
+    files: CodeFileUsecasesImpl,
+}
+
+impl AsyncCodeFileUsecasesImpl {
+    /// Works on the same files, history and events as `files`.
+    pub fn new(files: &CodeFileUsecasesImpl) -> Self {
+        Self {
+            repository: files.shared_repository(),
+            files: files.clone(),
+        }
+    }
+
+    /// Runs `task` on the blocking pool, side by side with other calls.
+    pub async fn read<T, F>(&self, task: F) -> Result<T, ApplicationError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&CodeFileUsecasesImpl) -> Result<T, ApplicationError> + Send + 'static,
+    {
+        let files = self.files.clone();
+        run_blocking(move || task(&files)).await
+    }
+
+    /// Runs `task` on the blocking pool with the writer lock of `file_id`
+    /// held, so no other write to the file lands while it runs.
+    pub async fn write<T, F>(&self, file_id: Uuid, task: F) -> Result<T, ApplicationError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut CodeFileUsecasesImpl) -> Result<T, ApplicationError> + Send + 'static,
+    {
+        let mut files = self.files.clone();
+        run_blocking(move || {
+            let _writer = files.writers.lock(file_id);
+            task(&mut files)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl AsyncCodeFileUsecases for AsyncCodeFileUsecasesImpl {
+    async fn create_code_file(
+        &self,
+        request: CreateCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError> {
+        let mut files = self.files.clone();
+        run_blocking(move || files.create_code_file(request)).await
+    }
+
+    async fn update_code_file(&self, request: UpdateCodeRequest) -> Result<(), ApplicationError> {
+        self.write(request.id, |files| files.update_code_file(request))
+            .await
+    }
+
+    async fn get_code_file(&self, file_id: Uuid) -> Result<CodeFileResponse, ApplicationError> {
+        let code_file = self.repository.find_by_id(file_id).await?;
+        run_blocking(move || {
+            let code = code_file.source.get_content();
+            Ok(CodeFileResponse {
+                id: code_file.id(),
+                name: code_file.name.clone(),
+                revision: code_file.revision(),
+                viewport: ViewportRequest {
+                    start_index: 0,
+                    end_index: code.len() as u64,
+                    content: code,
+                },
+            })
+        })
+        .await
+    }
+
+    async fn delete_code_file(&self, file_id: Uuid) -> Result<(), ApplicationError> {
+        self.write(file_id, move |files| files.delete_code_file(file_id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::events::DomainEvent;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use std::sync::Arc;
+
+    fn usecases() -> Arc<AsyncCodeFileUsecasesImpl> {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        Arc::new(AsyncCodeFileUsecasesImpl::new(&CodeFileUsecasesImpl::new(
+            repository,
+        )))
+    }
+
+    async fn create(usecases: &AsyncCodeFileUsecasesImpl) -> Uuid {
+        usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("async_{}.txt", Uuid::new_v4()),
+            })
+            .await
+            .unwrap()
+            .id
+    }
+
+    fn append(id: Uuid, start: u64, text: &str) -> UpdateCodeRequest {
+        UpdateCodeRequest {
+            id,
+            start,
+            end: start,
+            content: text.to_string(),
+            author: "ada".to_string(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_writes_to_one_file_are_serialized() {
+        let usecases = usecases();
+        let id = create(&usecases).await;
+        let mut events = usecases.files.events.subscribe();
+
+        // Every task inserts at the start, so each write must see the last one
+        // for the text to come out as exactly one char per task.
+        let tasks: Vec<_> = (0..32)
+            .map(|_| {
+                let usecases = Arc::clone(&usecases);
+                tokio::spawn(async move { usecases.update_code_file(append(id, 0, "x")).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let file = usecases.get_code_file(id).await.unwrap();
+        assert_eq!(file.viewport.content, "x".repeat(32));
+        assert_eq!(file.revision, 32);
+        let mut revisions = Vec::new();
+        while let Ok(published) = events.try_recv() {
+            if let DomainEvent::FileEdited { revision, .. } = published.event {
+                revisions.push(revision);
+            }
+        }
+        assert_eq!(revisions, (1..=32).collect::<Vec<u64>>());
+
+        usecases.delete_code_file(id).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_files_are_read_and_written_independently() {
+        let usecases = usecases();
+        let first = create(&usecases).await;
+        let second = create(&usecases).await;
+
+        // Holding the first file's writer lock doesn't hold up the second
+        // file or reads of the first.
+        let guard = usecases.files.writers.lock(first);
+        usecases
+            .update_code_file(append(second, 0, "two"))
+            .await
+            .unwrap();
+        assert_eq!(usecases.get_code_file(first).await.unwrap().revision, 0);
+
+        let blocked = {
+            let usecases = Arc::clone(&usecases);
+            tokio::spawn(async move { usecases.update_code_file(append(first, 0, "one")).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+        drop(guard);
+        blocked.await.unwrap().unwrap();
+
+        assert_eq!(
+            usecases
+                .get_code_file(first)
+                .await
+                .unwrap()
+                .viewport
+                .content,
+            "one"
+        );
+        assert_eq!(
+            usecases
+                .get_code_file(second)
+                .await
+                .unwrap()
+                .viewport
+                .content,
+            "two"
+        );
+
+        usecases.delete_code_file(first).await.unwrap();
+        usecases.delete_code_file(second).await.unwrap();
+        match usecases.get_code_file(first).await {
+            Err(ApplicationError::FileNotFound(_)) => {},
+            _ => panic!("Expected FileNotFound error"),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shares_files_and_writer_locks_with_the_sync_usecases() {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut sync = CodeFileUsecasesImpl::new(repository);
+        let usecases = AsyncCodeFileUsecasesImpl::new(&sync);
+
+        let id = create(&usecases).await;
+        usecases
+            .update_code_file(append(id, 0, "async"))
+            .await
+            .unwrap();
+        let file = tokio::task::block_in_place(|| sync.get_code_file(id)).unwrap();
+        assert_eq!(
+            (file.revision, file.viewport.content.as_str()),
+            (1, "async")
+        );
+
+        // A sync edit interleaves with async ones without reusing a revision.
+        let sync_edit = {
+            let mut sync = sync.clone();
+            std::thread::spawn(move || sync.update_code_file(append(id, 0, "sync ")))
+        };
+        usecases.update_code_file(append(id, 0, "!")).await.unwrap();
+        sync_edit.join().unwrap().unwrap();
+        let file = usecases.get_code_file(id).await.unwrap();
+        assert_eq!(file.revision, 3);
+        assert_eq!(file.viewport.content.len(), "!sync async".len());
+
+        tokio::task::block_in_place(|| sync.delete_code_file(id)).unwrap();
+        match usecases.get_code_file(id).await {
+            Err(ApplicationError::FileNotFound(_)) => {},
+            _ => panic!("Expected FileNotFound error"),
+        }
+    }
+}
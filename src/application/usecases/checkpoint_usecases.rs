@@ -0,0 +1,674 @@
+use crate::application::dto::checkpoint::{
+    CheckpointResponse, CheckpointedFileResponse, CompareCheckpointRequest,
+    CreateCheckpointRequest, FileComparisonResponse, RestoreCheckpointRequest,
+    RestoreCheckpointResponse, RestoredFileResponse,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::checkpoint_repository::CheckpointRepository;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::domain::checkpoint::{Checkpoint, CheckpointScope, CheckpointedFile, compare};
+use crate::domain::code_file::CodeFileSnapshot;
+use crate::domain::text_diff::diff;
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::mmap_file_sys::MmapSnapshot;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Named copies of files to come back to. Each checkpoint keeps the bytes of
+/// its files, so it outlives the operation history. A restore never rewinds a
+/// file: it is applied as one more edit, so collaborators keep the revisions
+/// in between.
+pub trait CheckpointUsecases: Send + Sync {
+    fn create_checkpoint(
+        &mut self,
+        request: CreateCheckpointRequest,
+    ) -> Result<CheckpointResponse, ApplicationError>;
+    /// Every checkpoint, oldest first, or only those holding `file_id`.
+    fn list_checkpoints(
+        &self,
+        file_id: Option<Uuid>,
+    ) -> Result<Vec<CheckpointResponse>, ApplicationError>;
+    fn compare_checkpoint(
+        &self,
+        request: CompareCheckpointRequest,
+    ) -> Result<Vec<FileComparisonResponse>, ApplicationError>;
+    /// Files created after a workspace checkpoint are left as they are.
+    fn restore_checkpoint(
+        &mut self,
+        request: RestoreCheckpointRequest,
+    ) -> Result<RestoreCheckpointResponse, ApplicationError>;
+    fn delete_checkpoint(&mut self, checkpoint_id: Uuid) -> Result<(), ApplicationError>;
+}
+
+pub struct CheckpointUsecasesImpl {
+    pub repository: Box<dyn CheckpointRepository>,
+    pub files: Arc<Mutex<CodeFileUsecasesImpl>>,
+}
+
+impl CheckpointUsecasesImpl {
+    pub fn new(
+        repository: Box<dyn CheckpointRepository>,
+        files: Arc<Mutex<CodeFileUsecasesImpl>>,
+    ) -> Self {
+        Self { repository, files }
+    }
+
+    // Files deleted since, even while listing, are left out.
+    fn current_files(
+        &self,
+        scope: CheckpointScope,
+    ) -> Result<Vec<CheckpointedFile>, ApplicationError> {
+        let files = self.files.lock().unwrap();
+        let file_ids = match scope {
+            CheckpointScope::Workspace => files.repository.ids()?,
+            CheckpointScope::File(file_id) => vec![file_id],
+        };
+        let mut current = Vec::new();
+        for file_id in file_ids {
+            match files.repository.find_by_id(file_id) {
+                Ok(code_file) => current.push(checkpointed(code_file.snapshot())),
+                Err(ApplicationError::FileNotFound(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(current)
+    }
+}
+
+fn checkpointed(code_file: CodeFileSnapshot<MmapSnapshot>) -> CheckpointedFile {
+    CheckpointedFile {
+        file_id: code_file.id(),
+        revision: code_file.revision(),
+        content: code_file.as_bytes().unwrap_or_default().to_vec(),
+        name: code_file.name,
+    }
+}
+
+// Writes `content` as it is, for collaborators as the edit between the texts.
+// Content that only differs in bytes that aren't UTF-8 makes no revision.
+fn write_content(
+    files: &mut CodeFileUsecasesImpl,
+    file_id: Uuid,
+    content: &[u8],
+    author: &str,
+) -> Result<Option<u64>, ApplicationError> {
+    let mut code_file = files.repository.find_by_id(file_id)?;
+    let edit = diff(
+        &String::from_utf8_lossy(code_file.source.as_bytes().unwrap_or_default()),
+        &String::from_utf8_lossy(content),
+    );
+    code_file
+        .source
+        .try_set_bytes(content)
+        .map_err(ApplicationError::IoError)?;
+    match edit {
+        Some(edit) => files
+            .record_edit(code_file, edit, author.to_string())
+            .map(Some),
+        None => files.repository.update(code_file).map(|()| None),
+    }
+}
+
+fn to_response(checkpoint: Checkpoint) -> CheckpointResponse {
+    CheckpointResponse {
+        id: checkpoint.id,
+        name: checkpoint.name,
+        file_id: match checkpoint.scope {
+            CheckpointScope::Workspace => None,
+            CheckpointScope::File(file_id) => Some(file_id),
+        },
+        created_at: checkpoint.created_at,
+        files: checkpoint
+            .files
+            .into_iter()
+            .map(|file| CheckpointedFileResponse {
+                file_id: file.file_id,
+                name: file.name,
+                revision: file.revision,
+            })
+            .collect(),
+    }
+}
+
+impl CheckpointUsecases for CheckpointUsecasesImpl {
+    fn create_checkpoint(
+        &mut self,
+        request: CreateCheckpointRequest,
+    ) -> Result<CheckpointResponse, ApplicationError> {
+        let scope = match request.file_id {
+            Some(file_id) => CheckpointScope::File(file_id),
+            None => CheckpointScope::Workspace,
+        };
+        let mut files = self.current_files(scope)?;
+        if let CheckpointScope::File(file_id) = scope
+            && files.is_empty()
+        {
+            return Err(ApplicationError::FileNotFound(file_id.to_string()));
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        let checkpoint = self.repository.save(Checkpoint {
+            id: Uuid::new_v4(),
+            name: request.name,
+            scope,
+            created_at: SystemTime::now(),
+            files,
+        })?;
+        Ok(to_response(checkpoint))
+    }
+
+    fn list_checkpoints(
+        &self,
+        file_id: Option<Uuid>,
+    ) -> Result<Vec<CheckpointResponse>, ApplicationError> {
+        Ok(self
+            .repository
+            .list()?
+            .into_iter()
+            .filter(|checkpoint| file_id.is_none_or(|file_id| checkpoint.covers(file_id)))
+            .map(to_response)
+            .collect())
+    }
+
+    fn compare_checkpoint(
+        &self,
+        request: CompareCheckpointRequest,
+    ) -> Result<Vec<FileComparisonResponse>, ApplicationError> {
+        let checkpoint = self.repository.find_by_id(request.checkpoint_id)?;
+        let against = match request.against {
+            Some(other) => self.repository.find_by_id(other)?.files,
+            // A file deleted since compares as removed.
+            None => self.current_files(checkpoint.scope)?,
+        };
+        Ok(compare(&checkpoint.files, &against)
+            .into_iter()
+            .map(|comparison| FileComparisonResponse {
+                file_id: comparison.file_id,
+                name: comparison.name,
+                change: comparison.change,
+            })
+            .collect())
+    }
+
+    // Every file is looked up and compared before any is written, with the
+    // writer locks of all of them held until the last edit, so none changes
+    // in between. Should an edit still fail, the files already restored are
+    // edited back.
+    fn restore_checkpoint(
+        &mut self,
+        request: RestoreCheckpointRequest,
+    ) -> Result<RestoreCheckpointResponse, ApplicationError> {
+        let checkpoint = self.repository.find_by_id(request.checkpoint_id)?;
+        let mut files = self.files.lock().unwrap();
+        let _writers = files
+            .writers
+            .lock_all(checkpoint.files.iter().map(|saved| saved.file_id));
+        let mut response = RestoreCheckpointResponse {
+            restored: Vec::new(),
+            missing: Vec::new(),
+        };
+        let mut changed = Vec::new();
+        for saved in checkpoint.files {
+            let current = match files.repository.find_by_id(saved.file_id) {
+                Ok(code_file) => code_file.source.as_bytes().unwrap_or_default().to_vec(),
+                Err(ApplicationError::FileNotFound(_)) => {
+                    response.missing.push(saved.file_id);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if current != saved.content {
+                changed.push((saved.file_id, current, saved.content));
+            }
+        }
+
+        let mut applied = Vec::new();
+        for (file_id, current, content) in changed {
+            match write_content(&mut files, file_id, &content, &request.author) {
+                Ok(revision) => {
+                    if let Some(revision) = revision {
+                        response
+                            .restored
+                            .push(RestoredFileResponse { file_id, revision });
+                    }
+                    applied.push((file_id, current));
+                }
+                Err(e) => {
+                    for (file_id, previous) in applied.into_iter().rev() {
+                        write_content(&mut files, file_id, &previous, &request.author)?;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(response)
+    }
+
+    fn delete_checkpoint(&mut self, checkpoint_id: Uuid) -> Result<(), ApplicationError> {
+        self.repository.delete(checkpoint_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::repositories::code_file_repository::CodeFileRepository;
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::domain::checkpoint::FileChange;
+    use crate::domain::code_file::CodeFile;
+    use crate::domain::events::DomainEvent;
+    use crate::domain::text_diff::TextEdit;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_checkpoint_repository::InMemoryCheckpointRepository;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+
+    fn usecases() -> CheckpointUsecasesImpl {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        CheckpointUsecasesImpl::new(
+            Box::new(InMemoryCheckpointRepository::new()),
+            Arc::new(Mutex::new(CodeFileUsecasesImpl::new(repository))),
+        )
+    }
+
+    // Refuses to store edits to one file, the way a full disk would.
+    struct FailingUpdates {
+        inner: InMemoryCodeFileRepository<MmapFileSystemSource>,
+        failing: Arc<Mutex<Option<Uuid>>>,
+    }
+
+    impl CodeFileRepository<MmapFileSystemSource> for FailingUpdates {
+        fn save(
+            &mut self,
+            file: CodeFile<MmapFileSystemSource>,
+        ) -> Result<CodeFile<MmapFileSystemSource>, ApplicationError> {
+            self.inner.save(file)
+        }
+
+        fn find_by_id(&self, id: Uuid) -> Result<CodeFile<MmapFileSystemSource>, ApplicationError> {
+            self.inner.find_by_id(id)
+        }
+
+        fn update(&mut self, file: CodeFile<MmapFileSystemSource>) -> Result<(), ApplicationError> {
+            if *self.failing.lock().unwrap() == Some(file.id()) {
+                return Err(ApplicationError::IoError(std::io::Error::other(
+                    "disk full",
+                )));
+            }
+            self.inner.update(file)
+        }
+
+        fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError> {
+            self.inner.delete(id)
+        }
+
+        fn list(&self) -> Result<Vec<CodeFile<MmapFileSystemSource>>, ApplicationError> {
+            self.inner.list()
+        }
+    }
+
+    // Lists a file that is deleted before it can be looked up.
+    struct DeletedWhileListing {
+        inner: InMemoryCodeFileRepository<MmapFileSystemSource>,
+        deleted: Uuid,
+    }
+
+    impl CodeFileRepository<MmapFileSystemSource> for DeletedWhileListing {
+        fn save(
+            &mut self,
+            file: CodeFile<MmapFileSystemSource>,
+        ) -> Result<CodeFile<MmapFileSystemSource>, ApplicationError> {
+            self.inner.save(file)
+        }
+
+        fn find_by_id(&self, id: Uuid) -> Result<CodeFile<MmapFileSystemSource>, ApplicationError> {
+            self.inner.find_by_id(id)
+        }
+
+        fn update(&mut self, file: CodeFile<MmapFileSystemSource>) -> Result<(), ApplicationError> {
+            self.inner.update(file)
+        }
+
+        fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError> {
+            self.inner.delete(id)
+        }
+
+        fn list(&self) -> Result<Vec<CodeFile<MmapFileSystemSource>>, ApplicationError> {
+            self.inner.list()
+        }
+
+        fn ids(&self) -> Result<Vec<Uuid>, ApplicationError> {
+            let mut ids = vec![self.deleted];
+            ids.extend(self.inner.ids()?);
+            Ok(ids)
+        }
+    }
+
+    fn create(usecases: &CheckpointUsecasesImpl, content: &str) -> Uuid {
+        let mut files = usecases.files.lock().unwrap();
+        let id = files
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("checkpoint_{}.rs", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id;
+        set(&mut files, id, content);
+        id
+    }
+
+    fn set(files: &mut CodeFileUsecasesImpl, id: Uuid, content: &str) {
+        let end = files
+            .get_code_file(id)
+            .unwrap()
+            .viewport
+            .content
+            .chars()
+            .count();
+        files
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start: 0,
+                end: end as u64,
+                content: content.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+    }
+
+    fn content(usecases: &CheckpointUsecasesImpl, id: Uuid) -> (u64, String) {
+        let file = usecases.files.lock().unwrap().get_code_file(id).unwrap();
+        (file.revision, file.viewport.content)
+    }
+
+    fn checkpoint(
+        usecases: &mut CheckpointUsecasesImpl,
+        name: &str,
+        file_id: Option<Uuid>,
+    ) -> Uuid {
+        usecases
+            .create_checkpoint(CreateCheckpointRequest {
+                name: name.to_string(),
+                file_id,
+            })
+            .unwrap()
+            .id
+    }
+
+    fn cleanup(usecases: &CheckpointUsecasesImpl, ids: &[Uuid]) {
+        let mut files = usecases.files.lock().unwrap();
+        for id in ids {
+            files.delete_code_file(*id).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_restore_is_a_new_revision() {
+        let mut usecases = usecases();
+        let id = create(&usecases, "fn main() {}");
+        let before = checkpoint(&mut usecases, "before refactor", Some(id));
+        set(&mut usecases.files.lock().unwrap(), id, "fn run() {}");
+        let mut events = usecases.files.lock().unwrap().events.subscribe();
+
+        let response = usecases
+            .restore_checkpoint(RestoreCheckpointRequest {
+                checkpoint_id: before,
+                author: "grace".to_string(),
+            })
+            .unwrap();
+        assert_eq!(response.restored.len(), 1);
+        assert_eq!(response.restored[0].revision, 3);
+        assert!(response.missing.is_empty());
+        assert_eq!(content(&usecases, id), (3, "fn main() {}".to_string()));
+        match events.try_recv().unwrap().event {
+            DomainEvent::FileEdited {
+                author, revision, ..
+            } => assert_eq!((author.as_str(), revision), ("grace", 3)),
+            other => panic!("Expected FileEdited, got {other:?}"),
+        }
+
+        // Restoring what is already there changes nothing.
+        let response = usecases
+            .restore_checkpoint(RestoreCheckpointRequest {
+                checkpoint_id: before,
+                author: "grace".to_string(),
+            })
+            .unwrap();
+        assert!(response.restored.is_empty());
+        assert_eq!(content(&usecases, id).0, 3);
+
+        cleanup(&usecases, &[id]);
+    }
+
+    #[test]
+    fn test_restore_waits_for_writes_in_progress() {
+        let mut usecases = usecases();
+        let id = create(&usecases, "fn main() {}");
+        let before = checkpoint(&mut usecases, "before", Some(id));
+        let mut writer = usecases.files.lock().unwrap().clone();
+
+        // An edit that doesn't go through the mutex is under way while the
+        // restore starts.
+        let held = writer.writers.lock(id);
+        let restore = std::thread::spawn(move || {
+            usecases
+                .restore_checkpoint(RestoreCheckpointRequest {
+                    checkpoint_id: before,
+                    author: "grace".to_string(),
+                })
+                .unwrap();
+            usecases
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        set(&mut writer, id, "fn run() { serve(); }");
+        drop(held);
+
+        let usecases = restore.join().unwrap();
+        assert_eq!(content(&usecases, id), (3, "fn main() {}".to_string()));
+
+        cleanup(&usecases, &[id]);
+    }
+
+    #[test]
+    fn test_workspace_checkpoints_compare_and_list() {
+        let mut usecases = usecases();
+        let first = create(&usecases, "one");
+        let second = create(&usecases, "two");
+        let start = checkpoint(&mut usecases, "start", None);
+        set(&mut usecases.files.lock().unwrap(), first, "ONE");
+        let later = checkpoint(&mut usecases, "later", Some(first));
+
+        let changes: Vec<(Uuid, FileChange)> = usecases
+            .compare_checkpoint(CompareCheckpointRequest {
+                checkpoint_id: start,
+                against: None,
+            })
+            .unwrap()
+            .into_iter()
+            .filter(|comparison| [first, second].contains(&comparison.file_id))
+            .map(|comparison| (comparison.file_id, comparison.change))
+            .collect();
+        assert!(changes.contains(&(
+            first,
+            FileChange::Modified(TextEdit {
+                start: 0,
+                end: 3,
+                text: "ONE".to_string(),
+            })
+        )));
+        assert!(changes.contains(&(second, FileChange::Unchanged)));
+
+        let between = usecases
+            .compare_checkpoint(CompareCheckpointRequest {
+                checkpoint_id: later,
+                against: Some(start),
+            })
+            .unwrap();
+        assert_eq!(between[0].file_id, first);
+        assert!(matches!(between[0].change, FileChange::Modified(_)));
+
+        let names = |usecases: &CheckpointUsecasesImpl, file_id| -> Vec<String> {
+            usecases
+                .list_checkpoints(file_id)
+                .unwrap()
+                .into_iter()
+                .map(|checkpoint| checkpoint.name)
+                .collect()
+        };
+        assert_eq!(names(&usecases, Some(first)), ["start", "later"]);
+        assert_eq!(names(&usecases, Some(second)), ["start"]);
+
+        usecases.delete_checkpoint(later).unwrap();
+        assert_eq!(names(&usecases, Some(first)), ["start"]);
+
+        cleanup(&usecases, &[first, second]);
+    }
+
+    #[test]
+    fn test_failed_restore_puts_restored_files_back() {
+        let failing = Arc::new(Mutex::new(None));
+        let repository = Box::new(FailingUpdates {
+            inner: InMemoryCodeFileRepository::new(),
+            failing: Arc::clone(&failing),
+        });
+        let mut usecases = CheckpointUsecasesImpl::new(
+            Box::new(InMemoryCheckpointRepository::new()),
+            Arc::new(Mutex::new(CodeFileUsecasesImpl::new(repository))),
+        );
+        let ids = [create(&usecases, "one"), create(&usecases, "two")];
+        let start = usecases
+            .create_checkpoint(CreateCheckpointRequest {
+                name: "start".to_string(),
+                file_id: None,
+            })
+            .unwrap();
+        // Files are restored in the checkpoint's order; the second one fails.
+        let order: Vec<Uuid> = start
+            .files
+            .iter()
+            .map(|file| file.file_id)
+            .filter(|file_id| ids.contains(file_id))
+            .collect();
+        for id in ids {
+            set(&mut usecases.files.lock().unwrap(), id, "changed");
+        }
+        let (revision, _) = content(&usecases, order[0]);
+        *failing.lock().unwrap() = Some(order[1]);
+
+        match usecases.restore_checkpoint(RestoreCheckpointRequest {
+            checkpoint_id: start.id,
+            author: "ada".to_string(),
+        }) {
+            Err(ApplicationError::IoError(_)) => {},
+            _ => panic!("Expected IoError"),
+        }
+        assert_eq!(
+            content(&usecases, order[0]),
+            (revision + 2, "changed".to_string())
+        );
+
+        *failing.lock().unwrap() = None;
+        cleanup(&usecases, &ids);
+    }
+
+    #[test]
+    fn test_restore_reports_deleted_files() {
+        let mut usecases = usecases();
+        let kept = create(&usecases, "kept");
+        let deleted = create(&usecases, "deleted");
+        let start = checkpoint(&mut usecases, "start", None);
+        set(&mut usecases.files.lock().unwrap(), kept, "changed");
+        cleanup(&usecases, &[deleted]);
+
+        let response = usecases
+            .restore_checkpoint(RestoreCheckpointRequest {
+                checkpoint_id: start,
+                author: "ada".to_string(),
+            })
+            .unwrap();
+        assert!(response.missing.contains(&deleted));
+        assert!(response.restored.iter().any(|file| file.file_id == kept));
+        assert_eq!(content(&usecases, kept).1, "kept");
+
+        cleanup(&usecases, &[kept]);
+    }
+
+    #[test]
+    fn test_files_deleted_while_listing_are_left_out() {
+        let repository = Box::new(DeletedWhileListing {
+            inner: InMemoryCodeFileRepository::new(),
+            deleted: Uuid::new_v4(),
+        });
+        let mut usecases = CheckpointUsecasesImpl::new(
+            Box::new(InMemoryCheckpointRepository::new()),
+            Arc::new(Mutex::new(CodeFileUsecasesImpl::new(repository))),
+        );
+        let kept = create(&usecases, "kept");
+
+        let start = usecases
+            .create_checkpoint(CreateCheckpointRequest {
+                name: "start".to_string(),
+                file_id: None,
+            })
+            .unwrap();
+        assert_eq!(start.files.len(), 1);
+        let changes: Vec<(Uuid, FileChange)> = usecases
+            .compare_checkpoint(CompareCheckpointRequest {
+                checkpoint_id: start.id,
+                against: None,
+            })
+            .unwrap()
+            .into_iter()
+            .map(|comparison| (comparison.file_id, comparison.change))
+            .collect();
+        assert_eq!(changes, vec![(kept, FileChange::Unchanged)]);
+
+        cleanup(&usecases, &[kept]);
+    }
+
+    #[test]
+    fn test_restore_keeps_bytes_that_are_not_utf8() {
+        let mut usecases = usecases();
+        let id = create(&usecases, "");
+        let bytes = b"caf\xe9 \xff\xfe\n";
+        let path = {
+            let mut files = usecases.files.lock().unwrap();
+            let mut code_file = files.repository.find_by_id(id).unwrap();
+            code_file.source.try_set_bytes(bytes).unwrap();
+            let path = code_file.source.path.clone();
+            files.repository.update(code_file).unwrap();
+            path
+        };
+        let start = checkpoint(&mut usecases, "start", Some(id));
+        set(&mut usecases.files.lock().unwrap(), id, "changed");
+
+        let response = usecases
+            .restore_checkpoint(RestoreCheckpointRequest {
+                checkpoint_id: start,
+                author: "ada".to_string(),
+            })
+            .unwrap();
+        assert_eq!(response.restored.len(), 1);
+        assert_eq!(std::fs::read(path).unwrap(), bytes);
+
+        cleanup(&usecases, &[id]);
+    }
+
+    #[test]
+    fn test_unknown_checkpoint() {
+        let mut usecases = usecases();
+        match usecases.restore_checkpoint(RestoreCheckpointRequest {
+            checkpoint_id: Uuid::new_v4(),
+            author: "ada".to_string(),
+        }) {
+            Err(ApplicationError::CheckpointNotFound(_)) => {},
+            _ => panic!("Expected CheckpointNotFound error"),
+        }
+        match usecases.create_checkpoint(CreateCheckpointRequest {
+            name: "missing".to_string(),
+            file_id: Some(Uuid::new_v4()),
+        }) {
+            Err(ApplicationError::FileNotFound(_)) => {},
+            _ => panic!("Expected FileNotFound error"),
+        }
+    }
+}
@@ -3,12 +3,27 @@ use crate::application::dto::code_file::{
 };
 use crate::application::errors::ApplicationError;
 use crate::application::repositories::code_file_repository::CodeFileRepository;
+use crate::application::repositories::trash_repository::TrashRepository;
 use crate::domain::code_file::CodeFile;
-use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+use crate::domain::events::DomainEvent;
+use crate::domain::text_diff::TextEdit;
+use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileMove, DynemicFileRead};
+use crate::domain::trash::TrashedFile;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::file_locks::FileLocks;
 use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use crate::infrastructure::operation_history::OperationHistory;
+use crate::infrastructure::persistence::blocking_repository::BlockingCodeFileRepository;
+use crate::infrastructure::persistence::in_memory_trash_repository::InMemoryTrashRepository;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
 use uuid::Uuid;
 
+/// The most bytes a file may hold for its content to be snapshotted as it
+/// is created.
+pub(crate) const CREATION_SNAPSHOT_LIMIT: usize = 64 * 1024;
+
 pub trait CodeFileUsecases: Send + Sync {
     fn create_code_file(
         &mut self,
@@ -16,16 +31,125 @@ pub trait CodeFileUsecases: Send + Sync {
     ) -> Result<CodeFileResponse, ApplicationError>;
     fn update_code_file(&mut self, request: UpdateCodeRequest) -> Result<(), ApplicationError>;
     fn get_code_file(&self, file_id: Uuid) -> Result<CodeFileResponse, ApplicationError>;
+    /// Moves the file to the trash, from where it can be restored until it
+    /// is purged. Its history is kept meanwhile.
     fn delete_code_file(&mut self, file_id: Uuid) -> Result<(), ApplicationError>;
 }
 
+/// Clones share the files, trash, history, events and writer locks, so each
+/// caller can work through its own clone; writes to one file are serialized
+/// by its lock in `writers`.
 pub struct CodeFileUsecasesImpl {
     pub repository: Box<dyn CodeFileRepository<MmapFileSystemSource>>,
+    pub events: EventBus,
+    pub history: OperationHistory,
+    pub trash: Arc<dyn TrashRepository>,
+    pub writers: FileLocks,
+    shared: BlockingCodeFileRepository<MmapFileSystemSource>,
 }
 
 impl CodeFileUsecasesImpl {
     pub fn new(repository: Box<dyn CodeFileRepository<MmapFileSystemSource>>) -> Self {
-        Self { repository }
+        let shared = BlockingCodeFileRepository::new(repository);
+        Self {
+            repository: Box::new(shared.clone()),
+            events: EventBus::default(),
+            history: OperationHistory::default(),
+            trash: Arc::new(InMemoryTrashRepository::new()),
+            writers: FileLocks::new(),
+            shared,
+        }
+    }
+
+    /// Keeps deleted files in `trash` rather than in one that lasts as long
+    /// as the process.
+    pub fn with_trash(mut self, trash: Arc<dyn TrashRepository>) -> Self {
+        self.trash = trash;
+        self
+    }
+
+    /// The files as an `AsyncCodeFileRepository`, for async callers.
+    pub fn shared_repository(&self) -> BlockingCodeFileRepository<MmapFileSystemSource> {
+        self.shared.clone()
+    }
+
+    // New files up to `CREATION_SNAPSHOT_LIMIT` are snapshotted so their
+    // history can be played back from the start. Larger ones wait for the
+    // history compactor, which snapshots files that have none on its next run.
+    pub(crate) fn publish(&self, event: DomainEvent) {
+        if let DomainEvent::FileCreated { file_id, revision, .. } = &event
+            && let Ok(code_file) = self.repository.find_by_id(*file_id)
+            && code_file.source.as_bytes().map_or(0, <[u8]>::len) <= CREATION_SNAPSHOT_LIMIT
+        {
+            self.history
+                .record_snapshot(*file_id, *revision, code_file.source.get_content());
+        }
+        self.history.record(&event);
+        self.events.publish(event);
+    }
+
+    /// Applies the edit like `update_code_file`, returning the revision it
+    /// produced.
+    pub(crate) fn apply_edit(
+        &mut self,
+        request: UpdateCodeRequest,
+    ) -> Result<u64, ApplicationError> {
+        let _writer = self.writers.lock(request.id);
+        let mut code_file = self.repository.find_by_id(request.id)?;
+
+        code_file
+            .source
+            .try_set_slice(
+                request.start as usize,
+                request.end as usize,
+                request.content.clone(),
+            )
+            .map_err(ApplicationError::IoError)?;
+
+        let edit = TextEdit {
+            start: request.start as usize,
+            end: request.end as usize,
+            text: request.content,
+        };
+        self.record_edit(code_file, edit, request.author)
+    }
+
+    /// Records an edit the source of `code_file` already holds, such as one
+    /// another program made on disk, returning the revision it produced. The
+    /// caller holds the file's writer lock from before it read `code_file`.
+    pub(crate) fn record_edit(
+        &mut self,
+        mut code_file: CodeFile<MmapFileSystemSource>,
+        edit: TextEdit,
+        author: String,
+    ) -> Result<u64, ApplicationError> {
+        let file_id = code_file.id();
+        let _writer = self.writers.lock(file_id);
+        let revision = code_file.bump_revision();
+
+        self.repository.update(code_file)?;
+        self.publish(DomainEvent::FileEdited {
+            file_id,
+            range: edit.start..edit.end,
+            text: edit.text,
+            author,
+            revision,
+        });
+
+        Ok(revision)
+    }
+}
+
+impl Clone for CodeFileUsecasesImpl {
+    fn clone(&self) -> Self {
+        Self {
+            repository: Box::new(self.shared.clone()),
+            events: self.events.clone(),
+            history: self.history.clone(),
+            trash: Arc::clone(&self.trash),
+            writers: self.writers.clone(),
+            shared: self.shared.clone(),
+        }
     }
 }
 
@@ -43,20 +167,26 @@ impl CodeFileUsecases for CodeFileUsecasesImpl {
 
         temp_source
             .create_file()
-            .map_err(|e| ApplicationError::IoError(e))?;
+            .map_err(ApplicationError::IoError)?;
 
-        let file_sys_source = MmapFileSystemSource::new(file_path)
-            .map_err(|e| ApplicationError::IoError(e))?;
+        let file_sys_source = MmapFileSystemSource::new_writable(file_path)
+            .map_err(ApplicationError::IoError)?;
 
         let id = Uuid::new_v4();
         let code_file = CodeFile::new(id, request.name.clone(), file_sys_source.clone());
 
         let code_file = self.repository.save(code_file)?;
+        self.publish(DomainEvent::FileCreated {
+            file_id: code_file.id(),
+            name: code_file.name.clone(),
+            revision: code_file.revision(),
+        });
         let code = code_file.source.get_content();
 
         Ok(CodeFileResponse {
             id: code_file.id(),
             name: request.name,
+            revision: code_file.revision(),
             viewport: ViewportRequest {
                 start_index: 0,
                 end_index: code.len() as u64,
@@ -66,17 +196,7 @@ impl CodeFileUsecases for CodeFileUsecasesImpl {
     }
 
     fn update_code_file(&mut self, request: UpdateCodeRequest) -> Result<(), ApplicationError> {
-        let mut code_file = self.repository.find_by_id(request.id)?;
-
-        code_file.source.set_slice(
-            request.start as usize,
-            request.end as usize,
-            request.content,
-        );
-
-        self.repository.update(code_file)?;
-
-        Ok(())
+        self.apply_edit(request).map(|_| ())
     }
 
     fn get_code_file(&self, file_id: Uuid) -> Result<CodeFileResponse, ApplicationError> {
@@ -85,6 +205,7 @@ impl CodeFileUsecases for CodeFileUsecasesImpl {
         Ok(CodeFileResponse {
             id: code_file.id(),
             name: code_file.name.clone(),
+            revision: code_file.revision(),
             viewport: ViewportRequest {
                 start_index: 0,
                 end_index: code.len() as u64,
@@ -94,14 +215,40 @@ impl CodeFileUsecases for CodeFileUsecasesImpl {
     }
 
     fn delete_code_file(&mut self, file_id: Uuid) -> Result<(), ApplicationError> {
-        let code_file = self.repository.find_by_id(file_id)?;
+        let _writer = self.writers.lock(file_id);
+        let mut code_file = self.repository.find_by_id(file_id)?;
+        let path = code_file.source.path.clone();
+        let trashed_path = self.trash.trashed_path(file_id);
 
-        code_file
-            .source
-            .delete_file()
-            .map_err(|e| ApplicationError::IoError(e))?;
-
-        self.repository.delete(file_id)?;
+        if let Some(dir) = trashed_path.parent() {
+            std::fs::create_dir_all(dir).map_err(ApplicationError::IoError)?;
+        }
+        // The trash holds the entry before the file, so a file in the trash
+        // always has one; each step that fails undoes the ones before it.
+        self.trash.save(TrashedFile {
+            file_id,
+            name: code_file.name.clone(),
+            revision: code_file.revision(),
+            path: path.clone(),
+            trashed_path: trashed_path.clone(),
+            deleted_at: SystemTime::now(),
+        })?;
+        if let Err(e) = code_file.source.move_file(&trashed_path) {
+            self.trash.delete(file_id)?;
+            return Err(ApplicationError::IoError(e));
+        }
+        if let Err(e) = self.repository.delete(file_id) {
+            code_file
+                .source
+                .move_file(&path)
+                .map_err(ApplicationError::IoError)?;
+            self.trash.delete(file_id)?;
+            return Err(e);
+        }
+        self.publish(DomainEvent::FileDeleted {
+            file_id,
+            revision: code_file.revision(),
+        });
 
         Ok(())
     }
@@ -110,19 +257,13 @@ impl CodeFileUsecases for CodeFileUsecasesImpl {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::application::repositories::code_file_repository::CodeFileRepository;
-    use crate::domain::code_file::CodeFile;
     use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
     use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
-    use std::collections::HashMap;
-    use std::sync::{Arc, Mutex};
-    use tempfile::TempDir;
 
     type MockCodeFileRepository = InMemoryCodeFileRepository<MmapFileSystemSource>;
 
     #[test]
     fn test_create_code_file() {
-        let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let repository = Box::new(MockCodeFileRepository::new());
         let mut usecases = CodeFileUsecasesImpl::new(repository);
 
@@ -141,7 +282,6 @@ mod tests {
 
     #[test]
     fn test_get_code_file() {
-        let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let repository = Box::new(MockCodeFileRepository::new());
         let mut usecases = CodeFileUsecasesImpl::new(repository);
 
@@ -175,7 +315,6 @@ mod tests {
 
     #[test]
     fn test_update_code_file() {
-        let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let repository = Box::new(MockCodeFileRepository::new());
         let mut usecases = CodeFileUsecasesImpl::new(repository);
 
@@ -190,6 +329,7 @@ mod tests {
             start: 0,
             end: 0,
             content: "Hello, World!".to_string(),
+            author: "ada".to_string(),
         };
 
         let result = usecases.update_code_file(update_request);
@@ -197,11 +337,50 @@ mod tests {
 
         let updated_file = usecases.get_code_file(created.id).unwrap();
         assert_eq!(updated_file.viewport.content, "Hello, World!");
+        assert_eq!(updated_file.revision, 1);
+    }
+
+    #[test]
+    fn test_update_code_file_reversed_range() {
+        let repository = Box::new(MockCodeFileRepository::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+
+        let create_request = CreateCodeFileRequest {
+            name: format!("test_file_{}.txt", Uuid::new_v4()),
+        };
+
+        let created = usecases.create_code_file(create_request).unwrap();
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id: created.id,
+                start: 0,
+                end: 0,
+                content: "Hello".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+
+        let update_request = UpdateCodeRequest {
+            id: created.id,
+            start: 4,
+            end: 2,
+            content: "xy".to_string(),
+            author: "ada".to_string(),
+        };
+        match usecases.update_code_file(update_request) {
+            Err(ApplicationError::IoError(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput)
+            }
+            _ => panic!("Expected IoError"),
+        }
+
+        let file = usecases.get_code_file(created.id).unwrap();
+        assert_eq!((file.viewport.content.as_str(), file.revision), ("Hello", 1));
+        usecases.delete_code_file(created.id).unwrap();
     }
 
     #[test]
     fn test_update_code_file_partial() {
-        let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let repository = Box::new(MockCodeFileRepository::new());
         let mut usecases = CodeFileUsecasesImpl::new(repository);
 
@@ -216,6 +395,7 @@ mod tests {
             start: 0,
             end: 0,
             content: "Hello, World!".to_string(),
+            author: "ada".to_string(),
         };
         usecases.update_code_file(initial_update).unwrap();
 
@@ -224,6 +404,7 @@ mod tests {
             start: 0,
             end: 5,
             content: "Goodbye".to_string(),
+            author: "ada".to_string(),
         };
 
         let result = usecases.update_code_file(partial_update);
@@ -243,6 +424,7 @@ mod tests {
             start: 0,
             end: 0,
             content: "Test".to_string(),
+            author: "ada".to_string(),
         };
 
         let result = usecases.update_code_file(update_request);
@@ -255,7 +437,6 @@ mod tests {
 
     #[test]
     fn test_delete_code_file() {
-        let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let repository = Box::new(MockCodeFileRepository::new());
         let mut usecases = CodeFileUsecasesImpl::new(repository);
 
@@ -291,9 +472,70 @@ mod tests {
         }
     }
 
+    // Refuses to keep anything, the way a trash on a full disk would.
+    struct FullTrash(InMemoryTrashRepository);
+
+    impl TrashRepository for FullTrash {
+        fn save(&self, _: TrashedFile) -> Result<TrashedFile, ApplicationError> {
+            Err(ApplicationError::IoError(std::io::Error::other(
+                "disk full",
+            )))
+        }
+
+        fn find_by_id(&self, file_id: Uuid) -> Result<TrashedFile, ApplicationError> {
+            self.0.find_by_id(file_id)
+        }
+
+        fn delete(&self, file_id: Uuid) -> Result<(), ApplicationError> {
+            self.0.delete(file_id)
+        }
+
+        fn list(&self) -> Result<Vec<TrashedFile>, ApplicationError> {
+            self.0.list()
+        }
+
+        fn trashed_path(&self, file_id: Uuid) -> PathBuf {
+            self.0.trashed_path(file_id)
+        }
+    }
+
+    #[test]
+    fn test_delete_keeps_the_file_when_the_trash_fails() {
+        let repository = Box::new(MockCodeFileRepository::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository)
+            .with_trash(Arc::new(FullTrash(InMemoryTrashRepository::new())));
+        let created = usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("trash_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap();
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id: created.id,
+                start: 0,
+                end: 0,
+                content: "kept".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+
+        match usecases.delete_code_file(created.id) {
+            Err(ApplicationError::IoError(_)) => {},
+            _ => panic!("Expected IoError"),
+        }
+        assert_eq!(
+            usecases.get_code_file(created.id).unwrap().viewport.content,
+            "kept"
+        );
+        let code_file = usecases.repository.find_by_id(created.id).unwrap();
+        assert!(code_file.source.path.exists());
+
+        usecases.trash = Arc::new(InMemoryTrashRepository::new());
+        usecases.delete_code_file(created.id).unwrap();
+    }
+
     #[test]
     fn test_multiple_files_isolation() {
-        let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let repository = Box::new(MockCodeFileRepository::new());
         let mut usecases = CodeFileUsecasesImpl::new(repository);
 
@@ -315,6 +557,7 @@ mod tests {
                 start: 0,
                 end: 0,
                 content: "Content 1".to_string(),
+                author: "ada".to_string(),
             })
             .unwrap();
 
@@ -324,6 +567,7 @@ mod tests {
                 start: 0,
                 end: 0,
                 content: "Content 2".to_string(),
+                author: "ada".to_string(),
             })
             .unwrap();
 
@@ -333,4 +577,54 @@ mod tests {
         assert_eq!(retrieved_file1.viewport.content, "Content 1");
         assert_eq!(retrieved_file2.viewport.content, "Content 2");
     }
+
+    #[test]
+    fn test_operations_publish_events() {
+        let repository = Box::new(MockCodeFileRepository::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+        let mut events = usecases.events.subscribe();
+
+        let created = usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("events_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap();
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id: created.id,
+                start: 0,
+                end: 0,
+                content: "Hello".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+        usecases.delete_code_file(created.id).unwrap();
+        assert!(usecases.delete_code_file(created.id).is_err());
+
+        let mut received = Vec::new();
+        while let Ok(published) = events.try_recv() {
+            received.push(published.event);
+        }
+        assert_eq!(
+            received,
+            vec![
+                DomainEvent::FileCreated {
+                    file_id: created.id,
+                    name: created.name.clone(),
+                    revision: 0,
+                },
+                DomainEvent::FileEdited {
+                    file_id: created.id,
+                    range: 0..0,
+                    text: "Hello".to_string(),
+                    author: "ada".to_string(),
+                    revision: 1,
+                },
+                DomainEvent::FileDeleted {
+                    file_id: created.id,
+                    revision: 1,
+                },
+            ]
+        );
+    }
 }
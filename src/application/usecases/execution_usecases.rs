@@ -0,0 +1,371 @@
+use crate::application::dto::execution::{
+    ExecuteCellRequest, ExecuteCodeFileRequest, ExecutionResponse, ExecutionResult,
+    SessionResponse, StartSessionRequest,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::code_file_repository::CodeFileRepository;
+use crate::application::repositories::notebook_repository::NotebookRepository;
+use crate::domain::execution::{ExecutionStatus, KernelEvent};
+use crate::domain::notebook::{CellKind, NotebookError};
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::in_memory_file_source::InMemoryFileSource;
+use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use crate::infrastructure::process_kernel::{KernelSpec, ProcessKernel};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use uuid::Uuid;
+
+pub trait ExecutionUsecases: Send + Sync {
+    fn start_session(
+        &mut self,
+        request: StartSessionRequest,
+    ) -> Result<SessionResponse, ApplicationError>;
+    fn execute_code_file(
+        &mut self,
+        request: ExecuteCodeFileRequest,
+    ) -> Result<ExecutionResponse, ApplicationError>;
+    fn execute_cell(
+        &mut self,
+        request: ExecuteCellRequest,
+    ) -> Result<ExecutionResponse, ApplicationError>;
+    fn subscribe(&self, session_id: Uuid) -> Result<Receiver<KernelEvent>, ApplicationError>;
+    fn interrupt_session(&self, session_id: Uuid) -> Result<(), ApplicationError>;
+    fn restart_session(&mut self, session_id: Uuid) -> Result<(), ApplicationError>;
+    fn shutdown_session(&mut self, session_id: Uuid) -> Result<(), ApplicationError>;
+}
+
+pub struct ExecutionUsecasesImpl {
+    pub code_files: Box<dyn CodeFileRepository<MmapFileSystemSource>>,
+    pub notebooks: Box<dyn NotebookRepository<InMemoryFileSource>>,
+    sessions: HashMap<Uuid, ProcessKernel>,
+}
+
+impl ExecutionUsecasesImpl {
+    pub fn new(
+        code_files: Box<dyn CodeFileRepository<MmapFileSystemSource>>,
+        notebooks: Box<dyn NotebookRepository<InMemoryFileSource>>,
+    ) -> Self {
+        Self {
+            code_files,
+            notebooks,
+            sessions: HashMap::new(),
+        }
+    }
+
+    fn session(&self, session_id: Uuid) -> Result<&ProcessKernel, ApplicationError> {
+        self.sessions
+            .get(&session_id)
+            .ok_or_else(|| ApplicationError::SessionNotFound(session_id.to_string()))
+    }
+
+    fn session_mut(&mut self, session_id: Uuid) -> Result<&mut ProcessKernel, ApplicationError> {
+        self.sessions
+            .get_mut(&session_id)
+            .ok_or_else(|| ApplicationError::SessionNotFound(session_id.to_string()))
+    }
+
+    fn execute(
+        &mut self,
+        session_id: Uuid,
+        code: &str,
+    ) -> Result<ExecutionResponse, ApplicationError> {
+        let execution_id = self
+            .session_mut(session_id)?
+            .execute(code)
+            .map_err(ApplicationError::IoError)?;
+        Ok(ExecutionResponse {
+            session_id,
+            execution_id,
+        })
+    }
+}
+
+/// Blocks until `execution_id` finishes, gathering its output from `receiver`.
+pub fn collect_execution(receiver: &Receiver<KernelEvent>, execution_id: u64) -> ExecutionResult {
+    let mut result = ExecutionResult {
+        execution_id,
+        status: ExecutionStatus::Died,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+    while let Ok(event) = receiver.recv() {
+        match event {
+            KernelEvent::Stdout {
+                execution_id: id,
+                text,
+            } if id == execution_id => result.stdout.push_str(&text),
+            KernelEvent::Stderr {
+                execution_id: id,
+                text,
+            } if id == execution_id => result.stderr.push_str(&text),
+            KernelEvent::Finished {
+                execution_id: id,
+                status,
+            } if id == execution_id => {
+                result.status = status;
+                break;
+            }
+            _ => {},
+        }
+    }
+    result
+}
+
+/// Converts a finished execution into nbformat `stream` outputs for a code cell.
+pub fn to_cell_outputs(result: &ExecutionResult) -> Vec<Value> {
+    [("stdout", &result.stdout), ("stderr", &result.stderr)]
+        .into_iter()
+        .filter(|(_, text)| !text.is_empty())
+        .map(|(name, text)| json!({"output_type": "stream", "name": name, "text": text}))
+        .collect()
+}
+
+impl ExecutionUsecases for ExecutionUsecasesImpl {
+    fn start_session(
+        &mut self,
+        request: StartSessionRequest,
+    ) -> Result<SessionResponse, ApplicationError> {
+        let spec = KernelSpec::for_language(&request.language)
+            .ok_or_else(|| ApplicationError::UnsupportedLanguage(request.language.clone()))?;
+        let kernel = ProcessKernel::spawn(spec).map_err(ApplicationError::IoError)?;
+
+        let session_id = Uuid::new_v4();
+        let language = kernel.language().to_string();
+        self.sessions.insert(session_id, kernel);
+
+        Ok(SessionResponse {
+            session_id,
+            language,
+        })
+    }
+
+    fn execute_code_file(
+        &mut self,
+        request: ExecuteCodeFileRequest,
+    ) -> Result<ExecutionResponse, ApplicationError> {
+        self.session(request.session_id)?;
+        let code = self
+            .code_files
+            .find_by_id(request.file_id)?
+            .source
+            .get_content();
+        self.execute(request.session_id, &code)
+    }
+
+    fn execute_cell(
+        &mut self,
+        request: ExecuteCellRequest,
+    ) -> Result<ExecutionResponse, ApplicationError> {
+        self.session(request.session_id)?;
+        let notebook = self.notebooks.find_by_id(request.notebook_id)?;
+        let cell = notebook
+            .cell(request.cell_id)
+            .ok_or(ApplicationError::NotebookError(
+                NotebookError::CellNotFound(request.cell_id),
+            ))?;
+        if cell.kind != CellKind::Code {
+            return Err(ApplicationError::NotebookError(
+                NotebookError::CellNotExecutable(request.cell_id),
+            ));
+        }
+        let code = cell.source.get_content();
+        self.execute(request.session_id, &code)
+    }
+
+    fn subscribe(&self, session_id: Uuid) -> Result<Receiver<KernelEvent>, ApplicationError> {
+        Ok(self.session(session_id)?.subscribe())
+    }
+
+    fn interrupt_session(&self, session_id: Uuid) -> Result<(), ApplicationError> {
+        self.session(session_id)?
+            .interrupt()
+            .map_err(ApplicationError::IoError)
+    }
+
+    fn restart_session(&mut self, session_id: Uuid) -> Result<(), ApplicationError> {
+        self.session_mut(session_id)?
+            .restart()
+            .map_err(ApplicationError::IoError)
+    }
+
+    fn shutdown_session(&mut self, session_id: Uuid) -> Result<(), ApplicationError> {
+        let kernel = self
+            .sessions
+            .remove(&session_id)
+            .ok_or_else(|| ApplicationError::SessionNotFound(session_id.to_string()))?;
+        kernel.shutdown();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::dto::notebook::{
+        CreateNotebookRequest, InsertCellRequest, UpdateCellOutputsRequest,
+    };
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::application::usecases::notebook_usecases::{NotebookUsecases, NotebookUsecasesImpl};
+    use crate::infrastructure::persistence::in_memory_notebook_repository::InMemoryNotebookRepository;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+
+    struct Fixture {
+        code_files: CodeFileUsecasesImpl,
+        notebooks: NotebookUsecasesImpl,
+        execution: ExecutionUsecasesImpl,
+    }
+
+    fn fixture() -> Fixture {
+        let code_file_repository = InMemoryCodeFileRepository::<MmapFileSystemSource>::new();
+        let notebook_repository = InMemoryNotebookRepository::<InMemoryFileSource>::new();
+        Fixture {
+            code_files: CodeFileUsecasesImpl::new(Box::new(code_file_repository.clone())),
+            notebooks: NotebookUsecasesImpl::new(Box::new(notebook_repository.clone())),
+            execution: ExecutionUsecasesImpl::new(
+                Box::new(code_file_repository),
+                Box::new(notebook_repository),
+            ),
+        }
+    }
+
+    fn start(execution: &mut ExecutionUsecasesImpl, language: &str) -> Uuid {
+        execution
+            .start_session(StartSessionRequest {
+                language: language.to_string(),
+            })
+            .unwrap()
+            .session_id
+    }
+
+    #[test]
+    fn test_execute_code_file() {
+        let mut fixture = fixture();
+        let file = fixture
+            .code_files
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("script_{}.sh", Uuid::new_v4()),
+            })
+            .unwrap();
+        fixture
+            .code_files
+            .update_code_file(UpdateCodeRequest {
+                id: file.id,
+                start: 0,
+                end: 0,
+                content: "echo from file".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+
+        let session_id = start(&mut fixture.execution, "shell");
+        let receiver = fixture.execution.subscribe(session_id).unwrap();
+        let response = fixture
+            .execution
+            .execute_code_file(ExecuteCodeFileRequest {
+                session_id,
+                file_id: file.id,
+            })
+            .unwrap();
+
+        let result = collect_execution(&receiver, response.execution_id);
+        assert_eq!(result.stdout, "from file\n");
+        assert_eq!(result.status, ExecutionStatus::Ok);
+        fixture.execution.shutdown_session(session_id).unwrap();
+    }
+
+    #[test]
+    fn test_execute_cell_and_store_outputs() {
+        let mut fixture = fixture();
+        let notebook_id = fixture
+            .notebooks
+            .create_notebook(CreateNotebookRequest {
+                name: "run.ipynb".to_string(),
+            })
+            .unwrap()
+            .id;
+        let insert = |notebooks: &mut NotebookUsecasesImpl, kind, source: &str| {
+            notebooks
+                .insert_cell(InsertCellRequest {
+                    notebook_id,
+                    kind,
+                    after: None,
+                    source: source.to_string(),
+                })
+                .unwrap()
+                .cell_id
+        };
+        let code_cell = insert(&mut fixture.notebooks, CellKind::Code, "print(6 * 7)");
+        let markdown_cell = insert(&mut fixture.notebooks, CellKind::Markdown, "# Notes");
+
+        let session_id = start(&mut fixture.execution, "python");
+        let receiver = fixture.execution.subscribe(session_id).unwrap();
+        let response = fixture
+            .execution
+            .execute_cell(ExecuteCellRequest {
+                session_id,
+                notebook_id,
+                cell_id: code_cell,
+            })
+            .unwrap();
+        let result = collect_execution(&receiver, response.execution_id);
+
+        fixture
+            .notebooks
+            .update_cell_outputs(UpdateCellOutputsRequest {
+                notebook_id,
+                cell_id: code_cell,
+                outputs: to_cell_outputs(&result),
+                execution_count: Some(result.execution_id),
+            })
+            .unwrap();
+        let notebook = fixture.notebooks.get_notebook(notebook_id).unwrap();
+        let cell = notebook.cells.iter().find(|c| c.id == code_cell).unwrap();
+        assert_eq!(
+            cell.outputs,
+            vec![json!({"output_type": "stream", "name": "stdout", "text": "42\n"})]
+        );
+        assert_eq!(cell.execution_count, Some(1));
+
+        let result = fixture.execution.execute_cell(ExecuteCellRequest {
+            session_id,
+            notebook_id,
+            cell_id: markdown_cell,
+        });
+        match result {
+            Err(ApplicationError::NotebookError(NotebookError::CellNotExecutable(_))) => {},
+            _ => panic!("Expected CellNotExecutable error"),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_language() {
+        let mut fixture = fixture();
+        let result = fixture.execution.start_session(StartSessionRequest {
+            language: "cobol".to_string(),
+        });
+        match result {
+            Err(ApplicationError::UnsupportedLanguage(_)) => {},
+            _ => panic!("Expected UnsupportedLanguage error"),
+        }
+    }
+
+    #[test]
+    fn test_session_not_found() {
+        let mut fixture = fixture();
+        let session_id = start(&mut fixture.execution, "shell");
+        fixture.execution.shutdown_session(session_id).unwrap();
+
+        match fixture.execution.restart_session(session_id) {
+            Err(ApplicationError::SessionNotFound(_)) => {},
+            _ => panic!("Expected SessionNotFound error"),
+        }
+        match fixture.execution.interrupt_session(Uuid::new_v4()) {
+            Err(ApplicationError::SessionNotFound(_)) => {},
+            _ => panic!("Expected SessionNotFound error"),
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use crate::application::dto::external_change::{EXTERNAL_AUTHOR, ExternalChangeEvent};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::domain::text_diff::diff;
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::file_watcher::FileWatcher;
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+pub trait ExternalChangeUsecases: Send + Sync {
+    fn track_file(&mut self, file_id: Uuid) -> Result<(), ApplicationError>;
+    fn untrack_file(&mut self, file_id: Uuid) -> Result<(), ApplicationError>;
+    fn poll_changes(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Vec<ExternalChangeEvent>, ApplicationError>;
+    fn reconcile(&mut self, file_id: Uuid)
+    -> Result<Option<ExternalChangeEvent>, ApplicationError>;
+    fn subscribe(&mut self) -> Receiver<ExternalChangeEvent>;
+}
+
+// What the engine last knew to be on disk. A revision that moved since the
+// shadow was taken means the engine wrote the file itself.
+struct Shadow {
+    revision: u64,
+    content: String,
+}
+
+/// Applies changes other programs make to tracked files through the shared
+/// file usecases, so they are recorded and published like any other edit.
+pub struct ExternalChangeUsecasesImpl {
+    files: Arc<Mutex<CodeFileUsecasesImpl>>,
+    watcher: FileWatcher,
+    shadows: HashMap<Uuid, Shadow>,
+    subscribers: Vec<Sender<ExternalChangeEvent>>,
+}
+
+impl ExternalChangeUsecasesImpl {
+    pub fn new(files: Arc<Mutex<CodeFileUsecasesImpl>>) -> Result<Self, ApplicationError> {
+        Ok(Self {
+            files,
+            watcher: FileWatcher::new().map_err(ApplicationError::IoError)?,
+            shadows: HashMap::new(),
+            subscribers: Vec::new(),
+        })
+    }
+
+    // The shadow with the engine's own edits since replayed on it, or `None`
+    // when the history no longer holds every one of them.
+    fn replay_own_edits(
+        files: &CodeFileUsecasesImpl,
+        file_id: Uuid,
+        shadow: &Shadow,
+        revision: u64,
+    ) -> Option<String> {
+        let mut content = shadow.content.clone();
+        let mut at = shadow.revision;
+        for operation in files.history.operations_after(file_id, shadow.revision) {
+            if operation.base_revision != at || operation.revision > revision {
+                return None;
+            }
+            content = operation.apply(&content);
+            at = operation.revision;
+        }
+        (at == revision).then_some(content)
+    }
+
+    fn read_disk(path: &std::path::Path) -> Result<Option<String>, ApplicationError> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(Some(String::from_utf8_lossy(&bytes).into_owned())),
+            // Tools like git may delete a file and recreate it a moment later.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ApplicationError::IoError(e)),
+        }
+    }
+}
+
+impl ExternalChangeUsecases for ExternalChangeUsecasesImpl {
+    fn track_file(&mut self, file_id: Uuid) -> Result<(), ApplicationError> {
+        // The shadow's revision and content are read under the writer lock, so
+        // they go together.
+        let files = self.files.lock().unwrap();
+        let _writer = files.writers.lock(file_id);
+        let code_file = files.repository.find_by_id(file_id)?;
+        let path = code_file.source.path.clone();
+        let content = Self::read_disk(&path)?.unwrap_or_default();
+        drop(files);
+
+        self.watcher
+            .watch(file_id, path)
+            .map_err(ApplicationError::IoError)?;
+        self.shadows.insert(
+            file_id,
+            Shadow {
+                revision: code_file.revision(),
+                content,
+            },
+        );
+        Ok(())
+    }
+
+    fn untrack_file(&mut self, file_id: Uuid) -> Result<(), ApplicationError> {
+        self.shadows.remove(&file_id);
+        self.watcher
+            .unwatch(file_id)
+            .map_err(ApplicationError::IoError)
+    }
+
+    fn poll_changes(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Vec<ExternalChangeEvent>, ApplicationError> {
+        let changed = self
+            .watcher
+            .poll(timeout)
+            .map_err(ApplicationError::IoError)?;
+
+        let mut events = Vec::new();
+        for file_id in changed {
+            match self.reconcile(file_id) {
+                Ok(Some(event)) => events.push(event),
+                // Deleted in the engine since it was last tracked.
+                Ok(None) | Err(ApplicationError::FileNotFound(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(events)
+    }
+
+    fn reconcile(
+        &mut self,
+        file_id: Uuid,
+    ) -> Result<Option<ExternalChangeEvent>, ApplicationError> {
+        let mut files = self.files.lock().unwrap();
+        let writers = files.writers.clone();
+        let _writer = writers.lock(file_id);
+        let mut code_file = files.repository.find_by_id(file_id)?;
+        let Some(shadow) = self.shadows.get_mut(&file_id) else {
+            return Err(ApplicationError::FileNotFound(file_id.to_string()));
+        };
+        let Some(disk) = Self::read_disk(&code_file.source.path)? else {
+            return Ok(None);
+        };
+
+        // An edit another program made while the engine was writing the file
+        // shows up against what the engine wrote, not against the shadow.
+        let expected = if code_file.revision() == shadow.revision {
+            shadow.content.clone()
+        } else {
+            Self::replay_own_edits(&files, file_id, shadow, code_file.revision())
+                .unwrap_or_else(|| code_file.source.get_content())
+        };
+        let Some(edit) = diff(&expected, &disk) else {
+            shadow.revision = code_file.revision();
+            shadow.content = disk;
+            return Ok(None);
+        };
+
+        // The disk already holds the edited text, so applying the edit means
+        // mapping the current file rather than writing it again.
+        code_file
+            .source
+            .reload()
+            .map_err(ApplicationError::IoError)?;
+        let revision = files.record_edit(code_file, edit.clone(), EXTERNAL_AUTHOR.to_string())?;
+        shadow.revision = revision;
+        shadow.content = disk;
+
+        let event = ExternalChangeEvent {
+            file_id,
+            revision,
+            start: edit.start as u64,
+            end: edit.end as u64,
+            text: edit.text,
+            author: EXTERNAL_AUTHOR.to_string(),
+        };
+        drop(files);
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        Ok(Some(event))
+    }
+
+    fn subscribe(&mut self) -> Receiver<ExternalChangeEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::domain::events::DomainEvent;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use std::fs;
+
+    fn setup(
+        content: &str,
+    ) -> (
+        Arc<Mutex<CodeFileUsecasesImpl>>,
+        ExternalChangeUsecasesImpl,
+        Uuid,
+        std::path::PathBuf,
+    ) {
+        let repository = InMemoryCodeFileRepository::<MmapFileSystemSource>::new();
+        let code_files = Arc::new(Mutex::new(CodeFileUsecasesImpl::new(Box::new(repository))));
+        let mut external = ExternalChangeUsecasesImpl::new(Arc::clone(&code_files)).unwrap();
+
+        let name = format!("external_{}.rs", Uuid::new_v4());
+        let mut files = code_files.lock().unwrap();
+        let created = files
+            .create_code_file(CreateCodeFileRequest { name: name.clone() })
+            .unwrap();
+        files
+            .update_code_file(UpdateCodeRequest {
+                id: created.id,
+                start: 0,
+                end: 0,
+                content: content.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+        drop(files);
+        external.track_file(created.id).unwrap();
+        (
+            code_files,
+            external,
+            created.id,
+            std::path::PathBuf::from(format!("/tmp/{name}")),
+        )
+    }
+
+    #[test]
+    fn test_external_edit_is_applied_and_broadcast() {
+        let (code_files, mut external, file_id, path) = setup("fn main() {}");
+        let receiver = external.subscribe();
+        let mut published = code_files.lock().unwrap().events.subscribe();
+
+        fs::write(&path, "fn main() { run(); }").unwrap();
+        let events = external.poll_changes(Duration::from_secs(2)).unwrap();
+
+        let expected = ExternalChangeEvent {
+            file_id,
+            revision: 2,
+            start: 11,
+            end: 11,
+            text: " run(); ".to_string(),
+            author: EXTERNAL_AUTHOR.to_string(),
+        };
+        assert_eq!(events, vec![expected.clone()]);
+        assert_eq!(receiver.try_recv().unwrap(), expected);
+        assert_eq!(
+            published.try_recv().unwrap().event,
+            DomainEvent::FileEdited {
+                file_id,
+                range: 11..11,
+                text: " run(); ".to_string(),
+                author: EXTERNAL_AUTHOR.to_string(),
+                revision: 2,
+            }
+        );
+
+        let files = code_files.lock().unwrap();
+        let file = files.get_code_file(file_id).unwrap();
+        assert_eq!(file.viewport.content, "fn main() { run(); }");
+        assert_eq!(file.revision, 2);
+        // The edit is in the history like any other.
+        let missed = files.missed_operations(file_id, 1, 2).unwrap();
+        assert_eq!(missed[0].author, EXTERNAL_AUTHOR);
+        drop(files);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_external_truncation() {
+        let (code_files, mut external, file_id, path) = setup(&"x".repeat(10000));
+
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(4)
+            .unwrap();
+        let event = external.reconcile(file_id).unwrap().unwrap();
+        assert_eq!(
+            (event.start, event.end, event.text.as_str()),
+            (4, 10000, "")
+        );
+
+        let file = code_files
+            .lock()
+            .unwrap()
+            .repository
+            .find_by_id(file_id)
+            .unwrap();
+        assert_eq!(file.source.get_content(), "xxxx");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_own_writes_are_not_reported() {
+        let (code_files, mut external, file_id, path) = setup("let a = 1;");
+
+        code_files
+            .lock()
+            .unwrap()
+            .update_code_file(UpdateCodeRequest {
+                id: file_id,
+                start: 8,
+                end: 9,
+                content: "2".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+        assert_eq!(external.reconcile(file_id).unwrap(), None);
+
+        fs::write(&path, "let a = 3;").unwrap();
+        let event = external.reconcile(file_id).unwrap().unwrap();
+        assert_eq!((event.start, event.end, event.text.as_str()), (8, 9, "3"));
+        assert_eq!(event.revision, 3);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_external_edit_racing_an_own_write_is_applied() {
+        let (code_files, mut external, file_id, path) = setup("let a = 1;");
+
+        code_files
+            .lock()
+            .unwrap()
+            .update_code_file(UpdateCodeRequest {
+                id: file_id,
+                start: 8,
+                end: 9,
+                content: "2".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+        fs::write(&path, "let b = 2;").unwrap();
+
+        let event = external.reconcile(file_id).unwrap().unwrap();
+        assert_eq!((event.start, event.end, event.text.as_str()), (4, 5, "b"));
+        assert_eq!(event.revision, 3);
+        let files = code_files.lock().unwrap();
+        assert_eq!(
+            files.get_code_file(file_id).unwrap().viewport.content,
+            "let b = 2;"
+        );
+        drop(files);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_reconcile_untracked_file() {
+        let repository = InMemoryCodeFileRepository::<MmapFileSystemSource>::new();
+        let code_files = CodeFileUsecasesImpl::new(Box::new(repository));
+        let mut external =
+            ExternalChangeUsecasesImpl::new(Arc::new(Mutex::new(code_files))).unwrap();
+
+        match external.reconcile(Uuid::new_v4()) {
+            Err(ApplicationError::FileNotFound(_)) => {},
+            _ => panic!("Expected FileNotFound error"),
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use crate::application::dto::code_file::{
+    CodeFileResponse, CopyCodeFileRequest, MoveCodeFileRequest, RenameCodeFileRequest,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::{CodeFileUsecases, CodeFileUsecasesImpl};
+use crate::domain::code_file::CodeFile;
+use crate::domain::events::DomainEvent;
+use crate::domain::traits::dyn_file::{DynemicFileMove, DynemicFileRead};
+use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+
+pub trait FileOperationsUsecases: Send + Sync {
+    fn rename_code_file(
+        &mut self,
+        request: RenameCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError>;
+    /// Moves the file and its backing file to a new name. Nothing is replaced:
+    /// the move fails if a file already has that name.
+    fn move_code_file(
+        &mut self,
+        request: MoveCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError>;
+    /// Creates a new file with the content the file has now.
+    fn copy_code_file(
+        &mut self,
+        request: CopyCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError>;
+}
+
+// Names are relative and stay below where they start.
+fn relative_name(name: &str) -> Result<PathBuf, ApplicationError> {
+    let path = PathBuf::from(name);
+    let plain = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if name.is_empty() || !plain {
+        return Err(ApplicationError::InvalidFileName(name.to_string()));
+    }
+    Ok(path)
+}
+
+// The directory a file's name is relative to: `/tmp` for a file named
+// `main.rs` at `/tmp/main.rs`, the workspace root for an imported one. Files
+// stored under another name, like replicas, count from their own directory.
+fn root(path: &Path, name: &str) -> PathBuf {
+    let depth = Path::new(name).components().count();
+    let root = path.ends_with(name).then(|| path.ancestors().nth(depth));
+    root.flatten()
+        .or_else(|| path.parent())
+        .unwrap_or(Path::new("/"))
+        .to_path_buf()
+}
+
+impl CodeFileUsecasesImpl {
+    fn relocate(&mut self, file_id: Uuid, name: String) -> Result<(), ApplicationError> {
+        let _writer = self.writers.lock(file_id);
+        let mut code_file = self.repository.find_by_id(file_id)?;
+        if code_file.name == name {
+            return Ok(());
+        }
+        let path = code_file.source.path.clone();
+        let target = root(&path, &code_file.name).join(relative_name(&name)?);
+
+        if let Some(dir) = target.parent() {
+            std::fs::create_dir_all(dir).map_err(ApplicationError::IoError)?;
+        }
+        code_file
+            .source
+            .move_file(&target)
+            .map_err(ApplicationError::IoError)?;
+        code_file.name = name.clone();
+        let revision = code_file.revision();
+
+        if let Err(e) = self.repository.update(code_file) {
+            let mut moved = MmapFileSystemSource {
+                path: target,
+                mmap: None,
+            };
+            let _ = moved.move_file(&path);
+            return Err(e);
+        }
+        self.publish(DomainEvent::FileRenamed {
+            file_id,
+            name,
+            revision,
+        });
+        Ok(())
+    }
+}
+
+impl FileOperationsUsecases for CodeFileUsecasesImpl {
+    fn rename_code_file(
+        &mut self,
+        request: RenameCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError> {
+        if relative_name(&request.name)?.components().count() != 1 {
+            return Err(ApplicationError::InvalidFileName(request.name));
+        }
+        let code_file = self.repository.find_by_id(request.id)?;
+        let name = Path::new(&code_file.name)
+            .with_file_name(&request.name)
+            .display()
+            .to_string();
+
+        self.relocate(request.id, name)?;
+        self.get_code_file(request.id)
+    }
+
+    fn move_code_file(
+        &mut self,
+        request: MoveCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError> {
+        self.relocate(request.id, request.name)?;
+        self.get_code_file(request.id)
+    }
+
+    fn copy_code_file(
+        &mut self,
+        request: CopyCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError> {
+        let code_file = self.repository.find_by_id(request.id)?;
+        let target =
+            root(&code_file.source.path, &code_file.name).join(relative_name(&request.name)?);
+
+        if let Some(dir) = target.parent() {
+            std::fs::create_dir_all(dir).map_err(ApplicationError::IoError)?;
+        }
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)
+            .and_then(|mut file| file.write_all(code_file.source.as_bytes().unwrap_or_default()))
+            .map_err(ApplicationError::IoError)?;
+        let source =
+            MmapFileSystemSource::new_writable(target).map_err(ApplicationError::IoError)?;
+
+        let copy =
+            self.repository
+                .save(CodeFile::new(Uuid::new_v4(), request.name.clone(), source))?;
+        self.publish(DomainEvent::FileCreated {
+            file_id: copy.id(),
+            name: request.name,
+            revision: copy.revision(),
+        });
+
+        self.get_code_file(copy.id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::code_file_usecases::CREATION_SNAPSHOT_LIMIT;
+    use crate::application::usecases::trash_usecases::TrashUsecases;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use std::fs;
+
+    fn usecases() -> CodeFileUsecasesImpl {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        CodeFileUsecasesImpl::new(repository)
+    }
+
+    fn create(usecases: &mut CodeFileUsecasesImpl, name: &str, content: &str) -> Uuid {
+        let id = usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: name.to_string(),
+            })
+            .unwrap()
+            .id;
+        insert(usecases, id, 0, content);
+        id
+    }
+
+    fn insert(usecases: &mut CodeFileUsecasesImpl, id: Uuid, start: u64, text: &str) {
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start,
+                end: start,
+                content: text.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+    }
+
+    fn remove(usecases: &mut CodeFileUsecasesImpl, id: Uuid) {
+        usecases.delete_code_file(id).unwrap();
+        usecases.purge_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_rename_moves_the_backing_file_and_notifies() {
+        let mut usecases = usecases();
+        let mut events = usecases.events.subscribe();
+        let name = format!("ops_{}.rs", Uuid::new_v4());
+        let id = create(&mut usecases, &name, "fn main() {}");
+        let renamed = format!("ops_{}.rs", Uuid::new_v4());
+
+        let response = usecases
+            .rename_code_file(RenameCodeFileRequest {
+                id,
+                name: renamed.clone(),
+            })
+            .unwrap();
+        assert_eq!(
+            (response.name.as_str(), response.revision),
+            (renamed.as_str(), 1)
+        );
+        assert!(!Path::new(&format!("/tmp/{name}")).exists());
+        assert_eq!(
+            fs::read_to_string(format!("/tmp/{renamed}")).unwrap(),
+            "fn main() {}"
+        );
+        let last = std::iter::from_fn(|| events.try_recv().ok())
+            .last()
+            .unwrap();
+        assert_eq!(
+            last.event,
+            DomainEvent::FileRenamed {
+                file_id: id,
+                name: renamed.clone(),
+                revision: 1
+            }
+        );
+
+        insert(&mut usecases, id, 12, "\n");
+        assert_eq!(
+            fs::read_to_string(format!("/tmp/{renamed}")).unwrap(),
+            "fn main() {}\n"
+        );
+
+        match usecases.rename_code_file(RenameCodeFileRequest {
+            id,
+            name: "src/lib.rs".to_string(),
+        }) {
+            Err(ApplicationError::InvalidFileName(_)) => {},
+            _ => panic!("Expected InvalidFileName error"),
+        }
+
+        remove(&mut usecases, id);
+    }
+
+    #[test]
+    fn test_move_into_directory_never_replaces() {
+        let mut usecases = usecases();
+        let dir = format!("ops_dir_{}", Uuid::new_v4());
+        let id = create(
+            &mut usecases,
+            &format!("ops_{}.rs", Uuid::new_v4()),
+            "moved",
+        );
+        let taken = create(
+            &mut usecases,
+            &format!("ops_{}.rs", Uuid::new_v4()),
+            "taken",
+        );
+        let taken_name = usecases.get_code_file(taken).unwrap().name;
+
+        let moved = format!("{dir}/main.rs");
+        let response = usecases
+            .move_code_file(MoveCodeFileRequest {
+                id,
+                name: moved.clone(),
+            })
+            .unwrap();
+        assert_eq!(response.name, moved);
+        assert_eq!(
+            fs::read_to_string(format!("/tmp/{moved}")).unwrap(),
+            "moved"
+        );
+
+        // Renaming keeps the directory.
+        let response = usecases
+            .rename_code_file(RenameCodeFileRequest {
+                id,
+                name: "lib.rs".to_string(),
+            })
+            .unwrap();
+        assert_eq!(response.name, format!("{dir}/lib.rs"));
+
+        match usecases.move_code_file(MoveCodeFileRequest {
+            id,
+            name: taken_name.clone(),
+        }) {
+            Err(ApplicationError::IoError(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists)
+            }
+            _ => panic!("Expected IoError"),
+        }
+        match usecases.move_code_file(MoveCodeFileRequest {
+            id,
+            name: "../escaped.rs".to_string(),
+        }) {
+            Err(ApplicationError::InvalidFileName(_)) => {},
+            _ => panic!("Expected InvalidFileName error"),
+        }
+        assert_eq!(
+            usecases.get_code_file(id).unwrap().name,
+            format!("{dir}/lib.rs")
+        );
+        assert_eq!(
+            fs::read_to_string(format!("/tmp/{taken_name}")).unwrap(),
+            "taken"
+        );
+
+        remove(&mut usecases, id);
+        remove(&mut usecases, taken);
+        fs::remove_dir_all(format!("/tmp/{dir}")).unwrap();
+    }
+
+    #[test]
+    fn test_copy_gets_its_own_id_and_content() {
+        let mut usecases = usecases();
+        let id = create(
+            &mut usecases,
+            &format!("ops_{}.rs", Uuid::new_v4()),
+            "shared",
+        );
+        let name = format!("ops_{}.rs", Uuid::new_v4());
+
+        let copy = usecases
+            .copy_code_file(CopyCodeFileRequest {
+                id,
+                name: name.clone(),
+            })
+            .unwrap();
+        assert_ne!(copy.id, id);
+        assert_eq!(
+            (
+                copy.name.as_str(),
+                copy.revision,
+                copy.viewport.content.as_str()
+            ),
+            (name.as_str(), 0, "shared")
+        );
+
+        insert(&mut usecases, copy.id, 6, " copy");
+        assert_eq!(
+            usecases.get_code_file(id).unwrap().viewport.content,
+            "shared"
+        );
+        assert_eq!(
+            fs::read_to_string(format!("/tmp/{name}")).unwrap(),
+            "shared copy"
+        );
+
+        match usecases.copy_code_file(CopyCodeFileRequest { id, name }) {
+            Err(ApplicationError::IoError(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists)
+            }
+            _ => panic!("Expected IoError"),
+        }
+
+        remove(&mut usecases, id);
+        remove(&mut usecases, copy.id);
+    }
+
+    #[test]
+    fn test_only_small_copies_are_snapshotted_as_they_are_created() {
+        let mut usecases = usecases();
+        let small = create(&mut usecases, &format!("ops_{}.rs", Uuid::new_v4()), "small");
+        let large = "x".repeat(CREATION_SNAPSHOT_LIMIT + 1);
+        let large = create(&mut usecases, &format!("ops_{}.rs", Uuid::new_v4()), &large);
+
+        let copies: Vec<Uuid> = [small, large]
+            .into_iter()
+            .map(|id| {
+                let name = format!("ops_{}.rs", Uuid::new_v4());
+                usecases
+                    .copy_code_file(CopyCodeFileRequest { id, name })
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        let snapshots = usecases.history.snapshots(copies[0]);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].content, "small");
+        assert!(usecases.history.snapshots(copies[1]).is_empty());
+
+        for id in [small, large].into_iter().chain(copies) {
+            remove(&mut usecases, id);
+        }
+    }
+
+    #[test]
+    fn test_copy_keeps_bytes_that_are_not_utf8() {
+        let mut usecases = usecases();
+        let id = create(&mut usecases, &format!("ops_{}.bin", Uuid::new_v4()), "");
+        let bytes = b"caf\xe9 \xff\xfe\n";
+        let mut code_file = usecases.repository.find_by_id(id).unwrap();
+        code_file.source.try_set_bytes(bytes).unwrap();
+        usecases.repository.update(code_file).unwrap();
+        let name = format!("ops_{}.bin", Uuid::new_v4());
+
+        let copy = usecases
+            .copy_code_file(CopyCodeFileRequest {
+                id,
+                name: name.clone(),
+            })
+            .unwrap();
+        assert_eq!(fs::read(format!("/tmp/{name}")).unwrap(), bytes);
+
+        remove(&mut usecases, id);
+        remove(&mut usecases, copy.id);
+    }
+}
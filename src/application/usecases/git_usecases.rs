@@ -0,0 +1,475 @@
+use crate::application::dto::external_change::EXTERNAL_AUTHOR;
+use crate::application::dto::git::{
+    CommitRequest, CommitResponse, FileStatusResponse, GitDiffRequest, GitDiffResponse,
+    GitStatusRequest, GitStatusResponse, SwitchBranchRequest, SwitchBranchResponse,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::domain::code_file::CodeFile;
+use crate::domain::events::DomainEvent;
+use crate::domain::git::{FileStatus, with_co_authors};
+use crate::domain::text_diff::diff;
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::git_repository::GitRepository;
+use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub trait GitUsecases: Send + Sync {
+    fn git_status(&self, request: GitStatusRequest) -> Result<GitStatusResponse, ApplicationError>;
+    fn git_diff(&self, request: GitDiffRequest) -> Result<GitDiffResponse, ApplicationError>;
+    /// Commits every changed code file of the repository. The author is recorded
+    /// as such and every other collaborator as a co-author.
+    fn git_commit(&mut self, request: CommitRequest) -> Result<CommitResponse, ApplicationError>;
+    /// Checks out another branch and brings the open code files in line with it.
+    /// Files that do not exist on that branch are closed.
+    fn switch_branch(
+        &mut self,
+        request: SwitchBranchRequest,
+    ) -> Result<SwitchBranchResponse, ApplicationError>;
+}
+
+fn open(repository: &str) -> Result<GitRepository, ApplicationError> {
+    GitRepository::open(Path::new(repository)).map_err(ApplicationError::IoError)
+}
+
+fn status_of(statuses: &HashMap<PathBuf, FileStatus>, path: &Path) -> FileStatus {
+    if let Some(status) = statuses.get(path) {
+        return *status;
+    }
+    if path
+        .ancestors()
+        .any(|ancestor| statuses.get(ancestor) == Some(&FileStatus::Ignored))
+    {
+        return FileStatus::Ignored;
+    }
+    FileStatus::Unmodified
+}
+
+impl CodeFileUsecasesImpl {
+    // Code files that live inside `git`, paired with their canonical path.
+    fn files_in(
+        &self,
+        git: &GitRepository,
+    ) -> Result<Vec<(CodeFile<MmapFileSystemSource>, PathBuf)>, ApplicationError> {
+        let mut files: Vec<_> = self
+            .repository
+            .list()?
+            .into_iter()
+            .filter_map(|code_file| {
+                let path = code_file
+                    .source
+                    .path
+                    .canonicalize()
+                    .unwrap_or_else(|_| code_file.source.path.clone());
+                path.starts_with(git.root()).then_some((code_file, path))
+            })
+            .collect();
+        files.sort_by(|(_, a), (_, b)| a.cmp(b));
+        Ok(files)
+    }
+}
+
+impl GitUsecases for CodeFileUsecasesImpl {
+    fn git_status(&self, request: GitStatusRequest) -> Result<GitStatusResponse, ApplicationError> {
+        let git = open(&request.repository)?;
+        let statuses = git.status().map_err(ApplicationError::IoError)?;
+
+        let files = self
+            .files_in(&git)?
+            .into_iter()
+            .map(|(code_file, path)| FileStatusResponse {
+                file_id: code_file.id(),
+                name: code_file.name.clone(),
+                status: status_of(&statuses, &path),
+            })
+            .collect();
+
+        Ok(GitStatusResponse {
+            branch: git.current_branch().map_err(ApplicationError::IoError)?,
+            files,
+        })
+    }
+
+    fn git_diff(&self, request: GitDiffRequest) -> Result<GitDiffResponse, ApplicationError> {
+        let git = open(&request.repository)?;
+        let code_file = self.repository.find_by_id(request.file_id)?;
+        let diff = git
+            .diff_head(&code_file.source.path)
+            .map_err(ApplicationError::IoError)?;
+
+        Ok(GitDiffResponse {
+            file_id: code_file.id(),
+            diff,
+        })
+    }
+
+    fn git_commit(&mut self, request: CommitRequest) -> Result<CommitResponse, ApplicationError> {
+        let git = open(&request.repository)?;
+        let statuses = git.status().map_err(ApplicationError::IoError)?;
+
+        let (files, paths): (Vec<_>, Vec<_>) = self
+            .files_in(&git)?
+            .into_iter()
+            .filter(|(_, path)| {
+                !matches!(
+                    status_of(&statuses, path),
+                    FileStatus::Unmodified | FileStatus::Ignored
+                )
+            })
+            .map(|(code_file, path)| (code_file.id(), path))
+            .unzip();
+        if paths.is_empty() {
+            return Err(ApplicationError::NothingToCommit(request.repository));
+        }
+
+        let message = with_co_authors(&request.message, &request.author, &request.co_authors);
+        let commit_id = git
+            .commit(&paths, &request.author, &message)
+            .map_err(ApplicationError::IoError)?;
+
+        Ok(CommitResponse { commit_id, files })
+    }
+
+    fn switch_branch(
+        &mut self,
+        request: SwitchBranchRequest,
+    ) -> Result<SwitchBranchResponse, ApplicationError> {
+        let git = open(&request.repository)?;
+        let open_files = self.files_in(&git)?;
+        // No edit may land between reading the files and reloading them.
+        let _writers = self
+            .writers
+            .lock_all(open_files.iter().map(|(code_file, _)| code_file.id()));
+        // What collaborators currently see, so edits can be expressed against it.
+        let open_files: Vec<_> = open_files
+            .into_iter()
+            .map(|(code_file, path)| {
+                let code_file = self.repository.find_by_id(code_file.id())?;
+                let content = code_file.snapshot().get_content();
+                Ok((code_file, path, content))
+            })
+            .collect::<Result<_, ApplicationError>>()?;
+        let changed = git
+            .switch_branch(&request.branch, request.create)
+            .map_err(ApplicationError::IoError)?;
+
+        let mut response = SwitchBranchResponse {
+            branch: git.current_branch().map_err(ApplicationError::IoError)?,
+            reloaded: Vec::new(),
+            closed: Vec::new(),
+        };
+        for (mut code_file, path, previous) in open_files {
+            if !changed.contains(&path) {
+                continue;
+            }
+            // The bytes go in as they are; the text is only for collaborators.
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    response.closed.push(code_file.id());
+                    self.repository.delete(code_file.id())?;
+                    self.publish(DomainEvent::FileDeleted {
+                        file_id: code_file.id(),
+                        revision: code_file.revision(),
+                    });
+                    continue;
+                }
+                Err(e) => return Err(ApplicationError::IoError(e)),
+            };
+
+            // Even unchanged text needs remapping, as git replaced the file.
+            let edit = diff(&previous, &String::from_utf8_lossy(&bytes));
+            code_file
+                .source
+                .try_set_bytes(&bytes)
+                .map_err(ApplicationError::IoError)?;
+            let Some(edit) = edit else {
+                self.repository.update(code_file)?;
+                continue;
+            };
+            let (file_id, revision) = (code_file.id(), code_file.bump_revision());
+            response.reloaded.push(file_id);
+            self.repository.update(code_file)?;
+            self.publish(DomainEvent::FileEdited {
+                file_id,
+                range: edit.start..edit.end,
+                text: edit.text,
+                author: EXTERNAL_AUTHOR.to_string(),
+                revision,
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::UpdateCodeRequest;
+    use crate::application::dto::workspace::ImportDirectoryRequest;
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::application::usecases::workspace_usecases::WorkspaceUsecases;
+    use crate::domain::git::Signature;
+    use crate::infrastructure::directory_scan::DEFAULT_MAX_FILE_SIZE;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    fn signature(name: &str) -> Signature {
+        Signature {
+            name: name.to_string(),
+            email: format!("{}@example.com", name.to_lowercase()),
+        }
+    }
+
+    fn init_repository() -> (TempDir, GitRepository) {
+        let temp_dir = TempDir::new().unwrap();
+        let status = Command::new("git")
+            .args(["init", "--quiet", "--initial-branch=main"])
+            .arg(temp_dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let git = GitRepository::open(temp_dir.path()).unwrap();
+        (temp_dir, git)
+    }
+
+    struct Workspace {
+        _temp_dir: TempDir,
+        root: String,
+        usecases: CodeFileUsecasesImpl,
+        ids: HashMap<String, Uuid>,
+    }
+
+    fn workspace(files: &[(&str, &str)]) -> Workspace {
+        let (temp_dir, git) = init_repository();
+        for (name, content) in files {
+            fs::write(git.root().join(name), content).unwrap();
+        }
+        let paths: Vec<PathBuf> = files
+            .iter()
+            .map(|(name, _)| git.root().join(name))
+            .collect();
+        git.commit(&paths, &signature("Ada"), "Initial").unwrap();
+
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+        let root = git.root().display().to_string();
+        let imported = usecases
+            .import_directory(ImportDirectoryRequest {
+                root: root.clone(),
+                max_file_size: DEFAULT_MAX_FILE_SIZE,
+            })
+            .unwrap();
+        let ids = imported.files.into_iter().map(|f| (f.name, f.id)).collect();
+        Workspace {
+            _temp_dir: temp_dir,
+            root,
+            usecases,
+            ids,
+        }
+    }
+
+    fn edit(workspace: &mut Workspace, name: &str, start: u64, end: u64, content: &str) {
+        workspace
+            .usecases
+            .update_code_file(UpdateCodeRequest {
+                id: workspace.ids[name],
+                start,
+                end,
+                content: content.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+    }
+
+    fn git_log(root: &str, format: &str) -> String {
+        let output = Command::new("git")
+            .args(["-C", root, "log", "-1", &format!("--format={format}")])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_status_and_diff() {
+        let mut workspace = workspace(&[("a.txt", "alpha\n"), ("b.txt", "beta\n")]);
+        edit(&mut workspace, "b.txt", 0, 4, "BETA");
+
+        let status = workspace
+            .usecases
+            .git_status(GitStatusRequest {
+                repository: workspace.root.clone(),
+            })
+            .unwrap();
+        assert_eq!(status.branch, "main");
+        let statuses: Vec<(&str, FileStatus)> = status
+            .files
+            .iter()
+            .map(|f| (f.name.as_str(), f.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("a.txt", FileStatus::Unmodified),
+                ("b.txt", FileStatus::Modified)
+            ]
+        );
+
+        let diff = workspace
+            .usecases
+            .git_diff(GitDiffRequest {
+                repository: workspace.root.clone(),
+                file_id: workspace.ids["b.txt"],
+            })
+            .unwrap();
+        assert!(diff.diff.contains("-beta\n+BETA\n"));
+    }
+
+    #[test]
+    fn test_commit_records_co_authors() {
+        let mut workspace = workspace(&[("a.txt", "alpha\n"), ("b.txt", "beta\n")]);
+        edit(&mut workspace, "a.txt", 5, 5, "!");
+
+        let response = workspace
+            .usecases
+            .git_commit(CommitRequest {
+                repository: workspace.root.clone(),
+                message: "Shout".to_string(),
+                author: signature("Ada"),
+                co_authors: vec![signature("Ada"), signature("Grace"), signature("Linus")],
+            })
+            .unwrap();
+        assert_eq!(response.files, vec![workspace.ids["a.txt"]]);
+        assert_eq!(
+            git_log(&workspace.root, "%H"),
+            format!("{}\n", response.commit_id)
+        );
+        assert_eq!(
+            git_log(&workspace.root, "%an%n%B"),
+            "Ada\nShout\n\n\
+             Co-authored-by: Grace <grace@example.com>\n\
+             Co-authored-by: Linus <linus@example.com>\n\n"
+        );
+
+        let result = workspace.usecases.git_commit(CommitRequest {
+            repository: workspace.root.clone(),
+            message: "Again".to_string(),
+            author: signature("Ada"),
+            co_authors: Vec::new(),
+        });
+        match result {
+            Err(ApplicationError::NothingToCommit(_)) => {},
+            _ => panic!("Expected NothingToCommit error"),
+        }
+    }
+
+    #[test]
+    fn test_switch_branch_reloads_files() {
+        let mut workspace = workspace(&[("a.txt", "alpha\n"), ("b.txt", "beta\n")]);
+        let switch = |usecases: &mut CodeFileUsecasesImpl, root: &str, branch: &str, create| {
+            usecases
+                .switch_branch(SwitchBranchRequest {
+                    repository: root.to_string(),
+                    branch: branch.to_string(),
+                    create,
+                })
+                .unwrap()
+        };
+
+        switch(&mut workspace.usecases, &workspace.root, "feature", true);
+        edit(&mut workspace, "a.txt", 0, 5, "ALPHA");
+        workspace
+            .usecases
+            .git_commit(CommitRequest {
+                repository: workspace.root.clone(),
+                message: "Upper".to_string(),
+                author: signature("Ada"),
+                co_authors: Vec::new(),
+            })
+            .unwrap();
+        Command::new("git")
+            .args(["-C", &workspace.root, "rm", "-q", "b.txt"])
+            .output()
+            .unwrap();
+        Command::new("git")
+            .args(["-C", &workspace.root])
+            .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+            .args(["commit", "-qm", "Drop b"])
+            .output()
+            .unwrap();
+
+        let response = switch(&mut workspace.usecases, &workspace.root, "main", false);
+        assert_eq!(response.branch, "main");
+        assert_eq!(
+            response.reloaded,
+            vec![workspace.ids["a.txt"], workspace.ids["b.txt"]]
+        );
+        assert!(response.closed.is_empty());
+        let a = workspace
+            .usecases
+            .get_code_file(workspace.ids["a.txt"])
+            .unwrap();
+        assert_eq!(a.viewport.content, "alpha\n");
+        assert_eq!(a.revision, 2);
+
+        let response = switch(&mut workspace.usecases, &workspace.root, "feature", false);
+        assert_eq!(response.reloaded, vec![workspace.ids["a.txt"]]);
+        assert_eq!(response.closed, vec![workspace.ids["b.txt"]]);
+        let a = workspace
+            .usecases
+            .repository
+            .find_by_id(workspace.ids["a.txt"])
+            .unwrap();
+        assert_eq!(a.source.get_content(), "ALPHA\n");
+        assert!(
+            workspace
+                .usecases
+                .get_code_file(workspace.ids["b.txt"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_switch_branch_keeps_bytes_that_are_not_utf8() {
+        let mut workspace = workspace(&[("a.txt", "cafe\n")]);
+        let switch = |usecases: &mut CodeFileUsecasesImpl, root: &str, branch: &str, create| {
+            usecases
+                .switch_branch(SwitchBranchRequest {
+                    repository: root.to_string(),
+                    branch: branch.to_string(),
+                    create,
+                })
+                .unwrap()
+        };
+
+        // Latin-1, as another tool might have saved it.
+        let latin1 = b"caf\xe9\n";
+        switch(&mut workspace.usecases, &workspace.root, "latin1", true);
+        let path = PathBuf::from(&workspace.root).join("a.txt");
+        fs::write(&path, latin1).unwrap();
+        Command::new("git")
+            .args(["-C", &workspace.root])
+            .args(["-c", "user.name=Ada", "-c", "user.email=ada@example.com"])
+            .args(["commit", "-qam", "Latin-1"])
+            .output()
+            .unwrap();
+        switch(&mut workspace.usecases, &workspace.root, "main", false);
+        assert_eq!(fs::read(&path).unwrap(), b"cafe\n");
+
+        let response = switch(&mut workspace.usecases, &workspace.root, "latin1", false);
+        assert_eq!(response.reloaded, vec![workspace.ids["a.txt"]]);
+        assert_eq!(fs::read(&path).unwrap(), latin1);
+        let status = workspace
+            .usecases
+            .git_status(GitStatusRequest {
+                repository: workspace.root.clone(),
+            })
+            .unwrap();
+        assert_eq!(status.files[0].status, FileStatus::Unmodified);
+    }
+}
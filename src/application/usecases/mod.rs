@@ -1 +1,15 @@
+pub mod async_code_file_usecases;
+pub mod checkpoint_usecases;
 pub mod code_file_usecases;
+pub mod execution_usecases;
+pub mod external_change_usecases;
+pub mod file_operations_usecases;
+pub mod git_usecases;
+pub mod notebook_usecases;
+pub mod playback_usecases;
+pub mod resume_usecases;
+pub mod search_usecases;
+pub mod sync_usecases;
+pub mod trash_usecases;
+pub mod webhook_usecases;
+pub mod workspace_usecases;
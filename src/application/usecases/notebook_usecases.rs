@@ -0,0 +1,452 @@
+use crate::application::dto::notebook::{
+    CellOperationResponse, CellResponse, CreateNotebookRequest, DeleteCellRequest,
+    ExportNotebookResponse, ImportNotebookRequest, InsertCellRequest, MoveCellRequest,
+    NotebookResponse, UpdateCellMetadataRequest, UpdateCellOutputsRequest, UpdateCellRequest,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::notebook_repository::NotebookRepository;
+use crate::domain::notebook::{Cell, Notebook, NotebookError};
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::in_memory_file_source::InMemoryFileSource;
+use crate::infrastructure::ipynb;
+use uuid::Uuid;
+
+pub trait NotebookUsecases: Send + Sync {
+    fn create_notebook(
+        &mut self,
+        request: CreateNotebookRequest,
+    ) -> Result<NotebookResponse, ApplicationError>;
+    fn get_notebook(&self, notebook_id: Uuid) -> Result<NotebookResponse, ApplicationError>;
+    fn delete_notebook(&mut self, notebook_id: Uuid) -> Result<(), ApplicationError>;
+    fn import_notebook(
+        &mut self,
+        request: ImportNotebookRequest,
+    ) -> Result<NotebookResponse, ApplicationError>;
+    fn export_notebook(
+        &self,
+        notebook_id: Uuid,
+    ) -> Result<ExportNotebookResponse, ApplicationError>;
+    fn insert_cell(
+        &mut self,
+        request: InsertCellRequest,
+    ) -> Result<CellOperationResponse, ApplicationError>;
+    fn move_cell(
+        &mut self,
+        request: MoveCellRequest,
+    ) -> Result<CellOperationResponse, ApplicationError>;
+    fn delete_cell(
+        &mut self,
+        request: DeleteCellRequest,
+    ) -> Result<CellOperationResponse, ApplicationError>;
+    fn update_cell(
+        &mut self,
+        request: UpdateCellRequest,
+    ) -> Result<CellOperationResponse, ApplicationError>;
+    fn update_cell_metadata(
+        &mut self,
+        request: UpdateCellMetadataRequest,
+    ) -> Result<CellOperationResponse, ApplicationError>;
+    fn update_cell_outputs(
+        &mut self,
+        request: UpdateCellOutputsRequest,
+    ) -> Result<CellOperationResponse, ApplicationError>;
+}
+
+pub struct NotebookUsecasesImpl {
+    pub repository: Box<dyn NotebookRepository<InMemoryFileSource>>,
+}
+
+impl NotebookUsecasesImpl {
+    pub fn new(repository: Box<dyn NotebookRepository<InMemoryFileSource>>) -> Self {
+        Self { repository }
+    }
+
+    fn modify<F>(
+        &mut self,
+        notebook_id: Uuid,
+        cell_id: Uuid,
+        operation: F,
+    ) -> Result<CellOperationResponse, ApplicationError>
+    where
+        F: FnOnce(&mut Notebook<InMemoryFileSource>) -> Result<u64, NotebookError>,
+    {
+        let mut notebook = self.repository.find_by_id(notebook_id)?;
+        let revision = operation(&mut notebook).map_err(ApplicationError::NotebookError)?;
+        self.repository.update(notebook)?;
+
+        Ok(CellOperationResponse {
+            notebook_id,
+            cell_id,
+            revision,
+        })
+    }
+}
+
+pub fn to_notebook_response(notebook: &Notebook<InMemoryFileSource>) -> NotebookResponse {
+    NotebookResponse {
+        id: notebook.id(),
+        name: notebook.name.clone(),
+        revision: notebook.revision(),
+        cells: notebook
+            .cells()
+            .map(|cell| CellResponse {
+                id: cell.id(),
+                kind: cell.kind,
+                source: cell.source.get_content(),
+                metadata: cell.metadata.clone(),
+                outputs: cell.outputs.clone(),
+                execution_count: cell.execution_count,
+            })
+            .collect(),
+    }
+}
+
+impl NotebookUsecases for NotebookUsecasesImpl {
+    fn create_notebook(
+        &mut self,
+        request: CreateNotebookRequest,
+    ) -> Result<NotebookResponse, ApplicationError> {
+        let notebook = Notebook::new(Uuid::new_v4(), request.name);
+        let notebook = self.repository.save(notebook)?;
+        Ok(to_notebook_response(&notebook))
+    }
+
+    fn get_notebook(&self, notebook_id: Uuid) -> Result<NotebookResponse, ApplicationError> {
+        let notebook = self.repository.find_by_id(notebook_id)?;
+        Ok(to_notebook_response(&notebook))
+    }
+
+    fn delete_notebook(&mut self, notebook_id: Uuid) -> Result<(), ApplicationError> {
+        self.repository.delete(notebook_id)
+    }
+
+    fn import_notebook(
+        &mut self,
+        request: ImportNotebookRequest,
+    ) -> Result<NotebookResponse, ApplicationError> {
+        let notebook = ipynb::parse_ipynb(request.name, &request.content)
+            .map_err(ApplicationError::ParseError)?;
+        let notebook = self.repository.save(notebook)?;
+        Ok(to_notebook_response(&notebook))
+    }
+
+    fn export_notebook(
+        &self,
+        notebook_id: Uuid,
+    ) -> Result<ExportNotebookResponse, ApplicationError> {
+        let notebook = self.repository.find_by_id(notebook_id)?;
+        let content = ipynb::to_ipynb(&notebook).map_err(ApplicationError::ParseError)?;
+        Ok(ExportNotebookResponse {
+            id: notebook.id(),
+            name: notebook.name.clone(),
+            content,
+        })
+    }
+
+    fn insert_cell(
+        &mut self,
+        request: InsertCellRequest,
+    ) -> Result<CellOperationResponse, ApplicationError> {
+        let cell_id = Uuid::new_v4();
+        let cell = Cell::new(
+            cell_id,
+            request.kind,
+            InMemoryFileSource::new(request.source),
+        );
+        self.modify(request.notebook_id, cell_id, |notebook| {
+            notebook.insert_cell(cell, request.after)
+        })
+    }
+
+    fn move_cell(
+        &mut self,
+        request: MoveCellRequest,
+    ) -> Result<CellOperationResponse, ApplicationError> {
+        self.modify(request.notebook_id, request.cell_id, |notebook| {
+            notebook.move_cell(request.cell_id, request.after)
+        })
+    }
+
+    fn delete_cell(
+        &mut self,
+        request: DeleteCellRequest,
+    ) -> Result<CellOperationResponse, ApplicationError> {
+        self.modify(request.notebook_id, request.cell_id, |notebook| {
+            notebook.delete_cell(request.cell_id)
+        })
+    }
+
+    fn update_cell(
+        &mut self,
+        request: UpdateCellRequest,
+    ) -> Result<CellOperationResponse, ApplicationError> {
+        self.modify(request.notebook_id, request.cell_id, |notebook| {
+            notebook.edit_cell_source(
+                request.cell_id,
+                request.start as usize,
+                request.end as usize,
+                request.content,
+            )
+        })
+    }
+
+    fn update_cell_metadata(
+        &mut self,
+        request: UpdateCellMetadataRequest,
+    ) -> Result<CellOperationResponse, ApplicationError> {
+        self.modify(request.notebook_id, request.cell_id, |notebook| {
+            let cell = notebook
+                .cell_mut(request.cell_id)
+                .ok_or(NotebookError::CellNotFound(request.cell_id))?;
+            cell.metadata = request.metadata;
+            Ok(notebook.bump_revision())
+        })
+    }
+
+    fn update_cell_outputs(
+        &mut self,
+        request: UpdateCellOutputsRequest,
+    ) -> Result<CellOperationResponse, ApplicationError> {
+        self.modify(request.notebook_id, request.cell_id, |notebook| {
+            let cell = notebook
+                .cell_mut(request.cell_id)
+                .ok_or(NotebookError::CellNotFound(request.cell_id))?;
+            cell.outputs = request.outputs;
+            cell.execution_count = request.execution_count;
+            Ok(notebook.bump_revision())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::notebook::CellKind;
+    use crate::infrastructure::persistence::in_memory_notebook_repository::InMemoryNotebookRepository;
+    use serde_json::{Map, json};
+
+    fn usecases() -> NotebookUsecasesImpl {
+        NotebookUsecasesImpl::new(Box::new(InMemoryNotebookRepository::new()))
+    }
+
+    fn insert(
+        usecases: &mut NotebookUsecasesImpl,
+        notebook_id: Uuid,
+        kind: CellKind,
+        after: Option<Uuid>,
+        source: &str,
+    ) -> Uuid {
+        usecases
+            .insert_cell(InsertCellRequest {
+                notebook_id,
+                kind,
+                after,
+                source: source.to_string(),
+            })
+            .unwrap()
+            .cell_id
+    }
+
+    #[test]
+    fn test_create_and_get_notebook() {
+        let mut usecases = usecases();
+        let created = usecases
+            .create_notebook(CreateNotebookRequest {
+                name: "analysis.ipynb".to_string(),
+            })
+            .unwrap();
+
+        let notebook = usecases.get_notebook(created.id).unwrap();
+        assert_eq!(notebook.name, "analysis.ipynb");
+        assert_eq!(notebook.revision, 0);
+        assert!(notebook.cells.is_empty());
+    }
+
+    #[test]
+    fn test_get_notebook_not_found() {
+        let usecases = usecases();
+        match usecases.get_notebook(Uuid::new_v4()) {
+            Err(ApplicationError::NotebookNotFound(_)) => {},
+            _ => panic!("Expected NotebookNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_cell_operations() {
+        let mut usecases = usecases();
+        let notebook_id = usecases
+            .create_notebook(CreateNotebookRequest {
+                name: "nb".to_string(),
+            })
+            .unwrap()
+            .id;
+
+        let title = insert(
+            &mut usecases,
+            notebook_id,
+            CellKind::Markdown,
+            None,
+            "# Title",
+        );
+        let code = insert(
+            &mut usecases,
+            notebook_id,
+            CellKind::Code,
+            Some(title),
+            "x = 1",
+        );
+        let scratch = insert(
+            &mut usecases,
+            notebook_id,
+            CellKind::Code,
+            Some(code),
+            "tmp",
+        );
+
+        usecases
+            .move_cell(MoveCellRequest {
+                notebook_id,
+                cell_id: title,
+                after: Some(scratch),
+            })
+            .unwrap();
+        usecases
+            .delete_cell(DeleteCellRequest {
+                notebook_id,
+                cell_id: scratch,
+            })
+            .unwrap();
+        let response = usecases
+            .update_cell(UpdateCellRequest {
+                notebook_id,
+                cell_id: code,
+                start: 4,
+                end: 5,
+                content: "42".to_string(),
+            })
+            .unwrap();
+        assert_eq!(response.revision, 6);
+
+        let notebook = usecases.get_notebook(notebook_id).unwrap();
+        let cells: Vec<(CellKind, &str)> = notebook
+            .cells
+            .iter()
+            .map(|c| (c.kind, c.source.as_str()))
+            .collect();
+        assert_eq!(
+            cells,
+            vec![(CellKind::Code, "x = 42"), (CellKind::Markdown, "# Title")]
+        );
+    }
+
+    #[test]
+    fn test_update_cell_outputs_and_metadata() {
+        let mut usecases = usecases();
+        let notebook_id = usecases
+            .create_notebook(CreateNotebookRequest {
+                name: "nb".to_string(),
+            })
+            .unwrap()
+            .id;
+        let cell_id = insert(&mut usecases, notebook_id, CellKind::Code, None, "1 + 1");
+
+        let mut metadata = Map::new();
+        metadata.insert("collapsed".to_string(), json!(true));
+        usecases
+            .update_cell_metadata(UpdateCellMetadataRequest {
+                notebook_id,
+                cell_id,
+                metadata,
+            })
+            .unwrap();
+        usecases
+            .update_cell_outputs(UpdateCellOutputsRequest {
+                notebook_id,
+                cell_id,
+                outputs: vec![
+                    json!({"output_type": "execute_result", "data": {"text/plain": "2"}}),
+                ],
+                execution_count: Some(1),
+            })
+            .unwrap();
+
+        let notebook = usecases.get_notebook(notebook_id).unwrap();
+        let cell = &notebook.cells[0];
+        assert_eq!(cell.metadata["collapsed"], json!(true));
+        assert_eq!(cell.outputs.len(), 1);
+        assert_eq!(cell.execution_count, Some(1));
+    }
+
+    #[test]
+    fn test_import_edit_export() {
+        let mut usecases = usecases();
+        let content = json!({
+            "nbformat": 4,
+            "nbformat_minor": 5,
+            "metadata": {"kernelspec": {"name": "python3"}},
+            "cells": [
+                {"cell_type": "code", "id": "a", "metadata": {}, "source": "x = 1", "outputs": [], "execution_count": null}
+            ]
+        })
+        .to_string();
+
+        let imported = usecases
+            .import_notebook(ImportNotebookRequest {
+                name: "uploaded.ipynb".to_string(),
+                content,
+            })
+            .unwrap();
+        let cell_id = imported.cells[0].id;
+        insert(
+            &mut usecases,
+            imported.id,
+            CellKind::Markdown,
+            Some(cell_id),
+            "notes",
+        );
+
+        let exported = usecases.export_notebook(imported.id).unwrap();
+        assert_eq!(exported.name, "uploaded.ipynb");
+        let document: serde_json::Value = serde_json::from_str(&exported.content).unwrap();
+        assert_eq!(document["metadata"]["kernelspec"]["name"], json!("python3"));
+        assert_eq!(document["cells"][0]["id"], json!("a"));
+        assert_eq!(document["cells"][1]["cell_type"], json!("markdown"));
+        assert_eq!(document["cells"][1]["source"], json!(["notes"]));
+    }
+
+    #[test]
+    fn test_import_invalid_notebook() {
+        let mut usecases = usecases();
+        let result = usecases.import_notebook(ImportNotebookRequest {
+            name: "broken.ipynb".to_string(),
+            content: "{not json".to_string(),
+        });
+        match result {
+            Err(ApplicationError::ParseError(_)) => {},
+            _ => panic!("Expected ParseError"),
+        }
+    }
+
+    #[test]
+    fn test_cell_not_found() {
+        let mut usecases = usecases();
+        let notebook_id = usecases
+            .create_notebook(CreateNotebookRequest {
+                name: "nb".to_string(),
+            })
+            .unwrap()
+            .id;
+
+        let result = usecases.update_cell(UpdateCellRequest {
+            notebook_id,
+            cell_id: Uuid::new_v4(),
+            start: 0,
+            end: 0,
+            content: "x".to_string(),
+        });
+        match result {
+            Err(ApplicationError::NotebookError(NotebookError::CellNotFound(_))) => {},
+            _ => panic!("Expected CellNotFound error"),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::domain::recording::{Recording, RecordingFrame};
+use std::time::UNIX_EPOCH;
+use uuid::Uuid;
+
+pub trait PlaybackUsecases: Send + Sync {
+    /// Everything of the file the operation history can replay: its text at
+    /// the oldest snapshot the kept edits lead on from to the current revision,
+    /// and those edits timed from the first.
+    fn export_recording(&self, file_id: Uuid) -> Result<Recording, ApplicationError>;
+}
+
+fn millis(duration: std::time::Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+impl PlaybackUsecases for CodeFileUsecasesImpl {
+    fn export_recording(&self, file_id: Uuid) -> Result<Recording, ApplicationError> {
+        let code_file = self.repository.find_by_id(file_id)?.snapshot();
+        let current = code_file.revision();
+
+        let (snapshot, operations) = self
+            .history
+            .snapshots(file_id)
+            .into_iter()
+            .find_map(|snapshot| {
+                let operations = self.history.operations_after(file_id, snapshot.revision);
+                let mut at = snapshot.revision;
+                for operation in &operations {
+                    if operation.base_revision != at {
+                        return None;
+                    }
+                    at = operation.revision;
+                }
+                (at == current).then_some((snapshot, operations))
+            })
+            .ok_or(ApplicationError::HistoryUnavailable(current))?;
+
+        let started_at = operations
+            .first()
+            .map_or(snapshot.taken_at, |operation| operation.recorded_at);
+        let frames = operations
+            .into_iter()
+            .map(|operation| RecordingFrame {
+                at_ms: millis(
+                    operation
+                        .recorded_at
+                        .duration_since(started_at)
+                        .unwrap_or_default(),
+                ),
+                base_revision: operation.base_revision,
+                revision: operation.revision,
+                start: operation.range.start,
+                end: operation.range.end,
+                text: operation.text,
+                author: operation.author,
+            })
+            .collect();
+
+        Ok(Recording {
+            file_id,
+            name: code_file.name,
+            started_at_ms: millis(started_at.duration_since(UNIX_EPOCH).unwrap_or_default()),
+            base_revision: snapshot.revision,
+            base_content: snapshot.content,
+            frames,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::operation_history::OperationHistory;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use std::time::Duration;
+
+    fn usecases() -> CodeFileUsecasesImpl {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        CodeFileUsecasesImpl::new(repository)
+    }
+
+    fn create(usecases: &mut CodeFileUsecasesImpl) -> Uuid {
+        usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("playback_{}.rs", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn insert(usecases: &mut CodeFileUsecasesImpl, id: Uuid, start: u64, text: &str) {
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start,
+                end: start,
+                content: text.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_export_recording_replays_to_current_text() {
+        let mut usecases = usecases();
+        let id = create(&mut usecases);
+        insert(&mut usecases, id, 0, "fn main");
+        std::thread::sleep(Duration::from_millis(20));
+        insert(&mut usecases, id, 7, "() {}");
+
+        let recording = usecases.export_recording(id).unwrap();
+        assert_eq!(
+            (recording.base_revision, recording.base_content.as_str()),
+            (0, "")
+        );
+        assert_eq!(recording.frames.len(), 2);
+        assert_eq!(recording.frames[0].at_ms, 0);
+        assert!(recording.duration_ms() >= 20);
+        assert!(recording.started_at_ms > 0);
+        let end = recording.seek(u64::MAX);
+        assert_eq!((end.revision, end.content.as_str()), (2, "fn main() {}"));
+
+        usecases.delete_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_export_needs_a_snapshot_the_edits_lead_on_from() {
+        let mut usecases = usecases();
+        usecases.history = OperationHistory::new(1);
+        let id = create(&mut usecases);
+        insert(&mut usecases, id, 0, "a");
+        insert(&mut usecases, id, 1, "b");
+
+        match usecases.export_recording(id) {
+            Err(ApplicationError::HistoryUnavailable(2)) => {},
+            _ => panic!("Expected HistoryUnavailable error"),
+        }
+
+        usecases.delete_code_file(id).unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+use crate::application::dto::code_file::UpdateCodeRequest;
+use crate::application::dto::resume::{
+    AppliedEdit, CatchUp, MissedOperation, ResumeRequest, ResumeResponse,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::{CodeFileUsecases, CodeFileUsecasesImpl};
+use crate::domain::text_diff::{TextEdit, rebase};
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use uuid::Uuid;
+
+pub trait ResumeUsecases: Send + Sync {
+    /// Catches a reconnecting client up from `last_revision`, replaying the
+    /// missed edits while the operation history still holds all of them and
+    /// sending a snapshot otherwise, then rebases and applies its offline edits.
+    fn resume_session(
+        &mut self,
+        request: ResumeRequest,
+    ) -> Result<ResumeResponse, ApplicationError>;
+}
+
+impl CodeFileUsecasesImpl {
+    /// The edits after `after` up to `current`, or `None` once any of them has
+    /// left the operation history or was squashed into one starting earlier.
+    /// A squashed edit takes the client several revisions at once.
+    pub(crate) fn missed_operations(
+        &self,
+        file_id: Uuid,
+        after: u64,
+        current: u64,
+    ) -> Option<Vec<MissedOperation>> {
+        let recorded = self.history.operations_after(file_id, after);
+        let mut at = after;
+        for operation in &recorded {
+            if operation.base_revision != at {
+                return None;
+            }
+            at = operation.revision;
+        }
+        if at != current {
+            return None;
+        }
+
+        Some(
+            recorded
+                .into_iter()
+                .map(|operation| MissedOperation {
+                    revision: operation.revision,
+                    start: operation.range.start,
+                    end: operation.range.end,
+                    text: operation.text,
+                    author: operation.author,
+                })
+                .collect(),
+        )
+    }
+
+    /// Applies edits that fit the current text, one revision each, leaving out
+    /// the empty ones a rebase can produce. All are checked first, so a bad one
+    /// leaves the file untouched.
+    pub(crate) fn apply_edits(
+        &mut self,
+        file_id: Uuid,
+        edits: Vec<TextEdit>,
+        author: &str,
+    ) -> Result<Vec<AppliedEdit>, ApplicationError> {
+        let _writer = self.writers.lock(file_id);
+        let code_file = self.repository.find_by_id(file_id)?;
+        let edits: Vec<TextEdit> = edits
+            .into_iter()
+            .filter(|edit| edit.start != edit.end || !edit.text.is_empty())
+            .collect();
+        check_edits(&edits, code_file.source.get_content().chars().count())?;
+
+        let mut applied = Vec::with_capacity(edits.len());
+        for (edit, revision) in edits.into_iter().zip(code_file.revision() + 1..) {
+            self.update_code_file(UpdateCodeRequest {
+                id: file_id,
+                start: edit.start as u64,
+                end: edit.end as u64,
+                content: edit.text.clone(),
+                author: author.to_string(),
+            })?;
+            applied.push(AppliedEdit {
+                revision,
+                start: edit.start,
+                end: edit.end,
+                text: edit.text,
+            });
+        }
+        Ok(applied)
+    }
+}
+
+/// Checks that `edits`, applied one after another to a text of `length`
+/// chars, each replace a range of the text they apply to. Edits have to pass
+/// this before they are rebased, which assumes as much.
+pub(crate) fn check_edits(edits: &[TextEdit], mut length: usize) -> Result<(), ApplicationError> {
+    for edit in edits {
+        if edit.start > edit.end || edit.end > length {
+            return Err(ApplicationError::InvalidRange(edit.end));
+        }
+        length = length - (edit.end - edit.start) + edit.text.chars().count();
+    }
+    Ok(())
+}
+
+/// The length of the text `operations` were applied to, given the `length`
+/// they left it at.
+pub(crate) fn base_length(length: usize, operations: &[MissedOperation]) -> usize {
+    operations.iter().rev().fold(length, |length, operation| {
+        (length + (operation.end - operation.start)).saturating_sub(operation.text.chars().count())
+    })
+}
+
+pub(crate) fn to_text_edit(start: usize, end: usize, text: &str) -> TextEdit {
+    TextEdit {
+        start,
+        end,
+        text: text.to_string(),
+    }
+}
+
+impl ResumeUsecases for CodeFileUsecasesImpl {
+    fn resume_session(
+        &mut self,
+        request: ResumeRequest,
+    ) -> Result<ResumeResponse, ApplicationError> {
+        // The edits are rebased over the revisions read here.
+        let _writer = self.writers.lock(request.file_id);
+        let code_file = self.repository.find_by_id(request.file_id)?;
+        let current = code_file.revision();
+        if request.last_revision > current {
+            return Err(ApplicationError::UnknownRevision(request.last_revision));
+        }
+
+        let pending: Vec<TextEdit> = request
+            .pending
+            .iter()
+            .map(|edit| to_text_edit(edit.start, edit.end, &edit.text))
+            .collect();
+        let (catch_up, rebased, rejected) =
+            match self.missed_operations(request.file_id, request.last_revision, current) {
+                Some(operations) => {
+                    let length = code_file.source.get_content().chars().count();
+                    check_edits(&pending, base_length(length, &operations))?;
+                    let remote: Vec<TextEdit> = operations
+                        .iter()
+                        .map(|operation| {
+                            to_text_edit(operation.start, operation.end, &operation.text)
+                        })
+                        .collect();
+                    let rebased = rebase(&pending, &remote);
+                    (CatchUp::Operations(operations), rebased, Vec::new())
+                }
+                None => (
+                    CatchUp::Snapshot {
+                        revision: current,
+                        content: code_file.source.get_content(),
+                    },
+                    Vec::new(),
+                    request.pending,
+                ),
+            };
+
+        let applied = self.apply_edits(request.file_id, rebased, &request.author)?;
+
+        Ok(ResumeResponse {
+            catch_up,
+            revision: current + applied.len() as u64,
+            applied,
+            rejected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::CreateCodeFileRequest;
+    use crate::application::dto::resume::PendingEdit;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::operation_history::OperationHistory;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+
+    fn usecases() -> CodeFileUsecasesImpl {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        CodeFileUsecasesImpl::new(repository)
+    }
+
+    fn create(usecases: &mut CodeFileUsecasesImpl) -> Uuid {
+        usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("resume_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn edit(usecases: &mut CodeFileUsecasesImpl, id: Uuid, start: u64, end: u64, text: &str) {
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start,
+                end,
+                content: text.to_string(),
+                author: "grace".to_string(),
+            })
+            .unwrap();
+    }
+
+    fn pending(start: usize, end: usize, text: &str) -> PendingEdit {
+        PendingEdit {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resume_replays_missed_operations_and_rebases() {
+        let mut usecases = usecases();
+        let id = create(&mut usecases);
+        edit(&mut usecases, id, 0, 0, "let x = 1;");
+        // The client went offline here, at revision 1.
+        edit(&mut usecases, id, 0, 0, "// config\n");
+        edit(&mut usecases, id, 20, 20, "\n");
+
+        let response = usecases
+            .resume_session(ResumeRequest {
+                file_id: id,
+                last_revision: 1,
+                author: "ada".to_string(),
+                pending: vec![pending(8, 9, "2"), pending(10, 10, " // two")],
+            })
+            .unwrap();
+
+        match &response.catch_up {
+            CatchUp::Operations(operations) => {
+                let revisions: Vec<u64> = operations.iter().map(|o| o.revision).collect();
+                assert_eq!(revisions, vec![2, 3]);
+                assert_eq!(operations[0].author, "grace");
+            }
+            CatchUp::Snapshot { .. } => panic!("Expected missed operations"),
+        }
+        assert_eq!(response.revision, 5);
+        assert_eq!(response.applied[0].revision, 4);
+        assert_eq!(
+            (response.applied[0].start, response.applied[0].end),
+            (18, 19)
+        );
+        assert!(response.rejected.is_empty());
+        assert_eq!(
+            usecases.get_code_file(id).unwrap().viewport.content,
+            "// config\nlet x = 2;\n // two"
+        );
+
+        usecases.delete_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_resume_falls_back_to_snapshot_when_history_is_gone() {
+        let mut usecases = usecases();
+        usecases.history = OperationHistory::new(1);
+        let id = create(&mut usecases);
+        edit(&mut usecases, id, 0, 0, "a");
+        edit(&mut usecases, id, 1, 1, "b");
+        edit(&mut usecases, id, 2, 2, "c");
+
+        let response = usecases
+            .resume_session(ResumeRequest {
+                file_id: id,
+                last_revision: 1,
+                author: "ada".to_string(),
+                pending: vec![pending(0, 0, "z")],
+            })
+            .unwrap();
+
+        assert_eq!(
+            response.catch_up,
+            CatchUp::Snapshot {
+                revision: 3,
+                content: "abc".to_string()
+            }
+        );
+        assert!(response.applied.is_empty());
+        assert_eq!(response.rejected, vec![pending(0, 0, "z")]);
+        assert_eq!(response.revision, 3);
+
+        usecases.delete_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_resume_rejects_unknown_revision_and_bad_ranges() {
+        let mut usecases = usecases();
+        let id = create(&mut usecases);
+        edit(&mut usecases, id, 0, 0, "abc");
+
+        let result = usecases.resume_session(ResumeRequest {
+            file_id: id,
+            last_revision: 7,
+            author: "ada".to_string(),
+            pending: Vec::new(),
+        });
+        match result {
+            Err(ApplicationError::UnknownRevision(7)) => {},
+            _ => panic!("Expected UnknownRevision error"),
+        }
+
+        let result = usecases.resume_session(ResumeRequest {
+            file_id: id,
+            last_revision: 1,
+            author: "ada".to_string(),
+            pending: vec![pending(0, 1, "x"), pending(2, 9, "")],
+        });
+        match result {
+            Err(ApplicationError::InvalidRange(9)) => {},
+            _ => panic!("Expected InvalidRange error"),
+        }
+        let file = usecases.get_code_file(id).unwrap();
+        assert_eq!((file.revision, file.viewport.content.as_str()), (1, "abc"));
+
+        // Pending edits are checked against the text they were made on before
+        // they are rebased over the missed edits.
+        edit(&mut usecases, id, 3, 3, "def");
+        edit(&mut usecases, id, 6, 6, "!");
+        for pending in [pending(5, 2, ""), pending(7, 7, "x")] {
+            let result = usecases.resume_session(ResumeRequest {
+                file_id: id,
+                last_revision: 2,
+                author: "ada".to_string(),
+                pending: vec![pending],
+            });
+            match result {
+                Err(ApplicationError::InvalidRange(_)) => {},
+                _ => panic!("Expected InvalidRange error"),
+            }
+        }
+        assert_eq!(usecases.get_code_file(id).unwrap().revision, 3);
+
+        usecases.delete_code_file(id).unwrap();
+    }
+}
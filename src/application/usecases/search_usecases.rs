@@ -0,0 +1,393 @@
+use crate::application::dto::search::{
+    ReplaceAllRequest, ReplaceAllResponse, SearchMatchResponse, SearchRequest, SearchResponse,
+    WorkspaceSearchRequest,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::domain::code_file::CodeFileSnapshot;
+use crate::domain::events::DomainEvent;
+use crate::domain::search::{self, SearchMatch, SearchQuery};
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::mmap_file_sys::MmapSnapshot;
+use regex::bytes::Regex;
+
+pub trait SearchUsecases: Send + Sync {
+    fn search_code_file(&self, request: SearchRequest) -> Result<SearchResponse, ApplicationError>;
+    fn search_workspace(
+        &self,
+        request: WorkspaceSearchRequest,
+    ) -> Result<Vec<SearchResponse>, ApplicationError>;
+    fn replace_all(
+        &mut self,
+        request: ReplaceAllRequest,
+    ) -> Result<ReplaceAllResponse, ApplicationError>;
+}
+
+fn compile(query: &SearchQuery) -> Result<Regex, ApplicationError> {
+    query.compile().map_err(ApplicationError::PatternError)
+}
+
+fn search_source(source: &impl DynemicFileRead, regex: &Regex) -> Vec<SearchMatch> {
+    match source.as_bytes() {
+        Some(bytes) => search::find_matches(regex, bytes),
+        None => search::find_matches(regex, source.get_content().as_bytes()),
+    }
+}
+
+fn to_response(
+    code_file: &CodeFileSnapshot<MmapSnapshot>,
+    matches: Vec<SearchMatch>,
+) -> SearchResponse {
+    SearchResponse {
+        file_id: code_file.id(),
+        name: code_file.name.clone(),
+        revision: code_file.revision(),
+        matches: matches
+            .into_iter()
+            .map(|m| SearchMatchResponse {
+                start: m.start as u64,
+                end: m.end as u64,
+                line: m.line as u64,
+                column: m.column as u64,
+                text: m.text,
+            })
+            .collect(),
+    }
+}
+
+impl SearchUsecases for CodeFileUsecasesImpl {
+    fn search_code_file(&self, request: SearchRequest) -> Result<SearchResponse, ApplicationError> {
+        let regex = compile(&SearchQuery {
+            pattern: request.pattern,
+            is_regex: request.is_regex,
+            case_sensitive: request.case_sensitive,
+        })?;
+        // Edits made meanwhile don't shift the matches found.
+        let code_file = self.repository.find_by_id(request.file_id)?.snapshot();
+        let matches = search_source(&code_file, &regex);
+        Ok(to_response(&code_file, matches))
+    }
+
+    fn search_workspace(
+        &self,
+        request: WorkspaceSearchRequest,
+    ) -> Result<Vec<SearchResponse>, ApplicationError> {
+        let regex = compile(&SearchQuery {
+            pattern: request.pattern,
+            is_regex: request.is_regex,
+            case_sensitive: request.case_sensitive,
+        })?;
+
+        // One file at a time, so a bounded cache stays bounded. A file deleted
+        // after the ids were taken is left out.
+        let mut results = Vec::new();
+        for file_id in self.repository.ids()? {
+            let code_file = match self.repository.find_by_id(file_id) {
+                Ok(code_file) => code_file.snapshot(),
+                Err(ApplicationError::FileNotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            let matches = search_source(&code_file, &regex);
+            if !matches.is_empty() {
+                results.push(to_response(&code_file, matches));
+            }
+        }
+        results.sort_by(|a, b| a.name.cmp(&b.name).then(a.file_id.cmp(&b.file_id)));
+
+        Ok(results)
+    }
+
+    fn replace_all(
+        &mut self,
+        request: ReplaceAllRequest,
+    ) -> Result<ReplaceAllResponse, ApplicationError> {
+        let query = SearchQuery {
+            pattern: request.pattern,
+            is_regex: request.is_regex,
+            case_sensitive: request.case_sensitive,
+        };
+        let regex = compile(&query)?;
+        let _writer = self.writers.lock(request.file_id);
+        let mut code_file = self.repository.find_by_id(request.file_id)?;
+
+        let (content, replacements) = match code_file.source.as_bytes() {
+            Some(bytes) => search::replace_all(&query, &regex, bytes, &request.replacement),
+            None => search::replace_all(
+                &query,
+                &regex,
+                code_file.source.get_content().as_bytes(),
+                &request.replacement,
+            ),
+        };
+
+        if replacements > 0 {
+            let replaced_chars = code_file.source.get_content().chars().count();
+            // Bytes that are not UTF-8 reach the file untouched; only the
+            // event carries them lossily.
+            code_file
+                .source
+                .try_set_bytes(&content)
+                .map_err(ApplicationError::IoError)?;
+            let revision = code_file.bump_revision();
+            self.repository.update(code_file.clone())?;
+            self.publish(DomainEvent::FileEdited {
+                file_id: code_file.id(),
+                range: 0..replaced_chars,
+                text: String::from_utf8_lossy(&content).into_owned(),
+                author: request.author,
+                revision,
+            });
+        }
+
+        Ok(ReplaceAllResponse {
+            file_id: code_file.id(),
+            revision: code_file.revision(),
+            replacements: replacements as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::repositories::code_file_repository::CodeFileRepository;
+    use crate::domain::code_file::CodeFile;
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use uuid::Uuid;
+
+    fn usecases_with_file(content: &str) -> (CodeFileUsecasesImpl, Uuid) {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+        let id = create_file(&mut usecases, content);
+        (usecases, id)
+    }
+
+    fn create_file(usecases: &mut CodeFileUsecasesImpl, content: &str) -> Uuid {
+        let created = usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("search_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap();
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id: created.id,
+                start: 0,
+                end: 0,
+                content: content.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+        created.id
+    }
+
+    #[test]
+    fn test_search_code_file() {
+        let (usecases, id) = usecases_with_file("let a = 1;\nlet b = a + 1;\n");
+
+        let response = usecases
+            .search_code_file(SearchRequest {
+                file_id: id,
+                pattern: "a".to_string(),
+                is_regex: false,
+                case_sensitive: true,
+            })
+            .unwrap();
+
+        let positions: Vec<(u64, u64)> = response
+            .matches
+            .iter()
+            .map(|m| (m.line, m.column))
+            .collect();
+        assert_eq!(positions, vec![(0, 4), (1, 8)]);
+        assert_eq!(response.revision, 1);
+    }
+
+    #[test]
+    fn test_search_code_file_invalid_pattern() {
+        let (usecases, id) = usecases_with_file("content");
+
+        let result = usecases.search_code_file(SearchRequest {
+            file_id: id,
+            pattern: "[".to_string(),
+            is_regex: true,
+            case_sensitive: true,
+        });
+        match result {
+            Err(ApplicationError::PatternError(_)) => {},
+            _ => panic!("Expected PatternError"),
+        }
+    }
+
+    #[test]
+    fn test_search_code_file_not_found() {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let usecases = CodeFileUsecasesImpl::new(repository);
+
+        let result = usecases.search_code_file(SearchRequest {
+            file_id: Uuid::new_v4(),
+            pattern: "x".to_string(),
+            is_regex: false,
+            case_sensitive: true,
+        });
+        match result {
+            Err(ApplicationError::FileNotFound(_)) => {},
+            _ => panic!("Expected NotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_search_workspace() {
+        let (mut usecases, first) = usecases_with_file("fn main() {}\n");
+        let second = create_file(&mut usecases, "fn helper() {}\nfn other() {}\n");
+        create_file(&mut usecases, "no functions here\n");
+
+        let results = usecases
+            .search_workspace(WorkspaceSearchRequest {
+                pattern: r"^fn \w+".to_string(),
+                is_regex: true,
+                case_sensitive: true,
+            })
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        let first_result = results.iter().find(|r| r.file_id == first).unwrap();
+        assert_eq!(first_result.matches.len(), 1);
+        let second_result = results.iter().find(|r| r.file_id == second).unwrap();
+        assert_eq!(second_result.matches.len(), 2);
+        assert_eq!(second_result.matches[1].text, "fn other");
+    }
+
+    // Lists a file that is gone by the time it is looked up, as one deleted
+    // halfway through a search would be.
+    struct DeletedWhileListing {
+        inner: InMemoryCodeFileRepository<MmapFileSystemSource>,
+        deleted: Uuid,
+    }
+
+    impl CodeFileRepository<MmapFileSystemSource> for DeletedWhileListing {
+        fn save(
+            &mut self,
+            file: CodeFile<MmapFileSystemSource>,
+        ) -> Result<CodeFile<MmapFileSystemSource>, ApplicationError> {
+            self.inner.save(file)
+        }
+
+        fn find_by_id(&self, id: Uuid) -> Result<CodeFile<MmapFileSystemSource>, ApplicationError> {
+            self.inner.find_by_id(id)
+        }
+
+        fn update(&mut self, file: CodeFile<MmapFileSystemSource>) -> Result<(), ApplicationError> {
+            self.inner.update(file)
+        }
+
+        fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError> {
+            self.inner.delete(id)
+        }
+
+        fn list(&self) -> Result<Vec<CodeFile<MmapFileSystemSource>>, ApplicationError> {
+            self.inner.list()
+        }
+
+        fn ids(&self) -> Result<Vec<Uuid>, ApplicationError> {
+            let mut ids = vec![self.deleted];
+            ids.extend(self.inner.ids()?);
+            Ok(ids)
+        }
+    }
+
+    #[test]
+    fn test_search_workspace_skips_files_deleted_meanwhile() {
+        let repository = Box::new(DeletedWhileListing {
+            inner: InMemoryCodeFileRepository::new(),
+            deleted: Uuid::new_v4(),
+        });
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+        let kept = create_file(&mut usecases, "fn main() {}\n");
+
+        let results = usecases
+            .search_workspace(WorkspaceSearchRequest {
+                pattern: "fn".to_string(),
+                is_regex: false,
+                case_sensitive: true,
+            })
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file_id, kept);
+    }
+
+    #[test]
+    fn test_replace_all_commits_one_revision() {
+        let (mut usecases, id) = usecases_with_file("foo bar foo\nfoo");
+
+        let response = usecases
+            .replace_all(ReplaceAllRequest {
+                file_id: id,
+                pattern: "foo".to_string(),
+                is_regex: false,
+                case_sensitive: true,
+                replacement: "baz".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+
+        assert_eq!(response.replacements, 3);
+        assert_eq!(response.revision, 2);
+        let file = usecases.get_code_file(id).unwrap();
+        assert_eq!(file.viewport.content, "baz bar baz\nbaz");
+        assert_eq!(file.revision, 2);
+    }
+
+    #[test]
+    fn test_replace_all_keeps_bytes_that_are_not_utf8() {
+        let (mut usecases, id) = usecases_with_file("");
+        let mut code_file = usecases.repository.find_by_id(id).unwrap();
+        std::fs::write(&code_file.source.path, b"caf\xe9 foo").unwrap();
+        code_file.source.reload().unwrap();
+        usecases.repository.update(code_file).unwrap();
+
+        let response = usecases
+            .replace_all(ReplaceAllRequest {
+                file_id: id,
+                pattern: "foo".to_string(),
+                is_regex: false,
+                case_sensitive: true,
+                replacement: "bar".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+
+        assert_eq!(response.replacements, 1);
+        let code_file = usecases.repository.find_by_id(id).unwrap();
+        assert_eq!(
+            std::fs::read(&code_file.source.path).unwrap(),
+            b"caf\xe9 bar"
+        );
+        usecases.delete_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_replace_all_without_matches_keeps_revision() {
+        let (mut usecases, id) = usecases_with_file("unchanged");
+
+        let response = usecases
+            .replace_all(ReplaceAllRequest {
+                file_id: id,
+                pattern: "missing".to_string(),
+                is_regex: false,
+                case_sensitive: true,
+                replacement: "x".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+
+        assert_eq!(response.replacements, 0);
+        assert_eq!(response.revision, 1);
+        assert_eq!(
+            usecases.get_code_file(id).unwrap().viewport.content,
+            "unchanged"
+        );
+    }
+}
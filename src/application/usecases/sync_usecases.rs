@@ -0,0 +1,235 @@
+use crate::application::dto::sync::{SyncOperation, SyncRequest, SyncResponse};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::application::usecases::resume_usecases::{base_length, check_edits, to_text_edit};
+use crate::domain::sync::OperationLog;
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::domain::traits::merge::Mergable;
+
+pub trait SyncUsecases: Send + Sync {
+    /// Merges the operation log of a client that edited offline with what
+    /// happened here since its base revision. Its edits are applied rebased
+    /// over the server's, and the server's come back rebased over its own, so
+    /// both sides end up with the same text.
+    fn sync_file(&mut self, request: SyncRequest) -> Result<SyncResponse, ApplicationError>;
+}
+
+impl SyncUsecases for CodeFileUsecasesImpl {
+    fn sync_file(&mut self, request: SyncRequest) -> Result<SyncResponse, ApplicationError> {
+        // The merge is made against the revisions read here.
+        let _writer = self.writers.lock(request.file_id);
+        let code_file = self.repository.find_by_id(request.file_id)?;
+        let current = code_file.revision();
+        if request.base_revision > current {
+            return Err(ApplicationError::UnknownRevision(request.base_revision));
+        }
+        let missed = self
+            .missed_operations(request.file_id, request.base_revision, current)
+            .ok_or(ApplicationError::HistoryUnavailable(request.base_revision))?;
+
+        let server = OperationLog {
+            base_revision: request.base_revision,
+            edits: missed
+                .iter()
+                .map(|operation| to_text_edit(operation.start, operation.end, &operation.text))
+                .collect(),
+            authoritative: true,
+        };
+        let client = OperationLog {
+            base_revision: request.base_revision,
+            edits: request
+                .operations
+                .iter()
+                .map(|edit| to_text_edit(edit.start, edit.end, &edit.text))
+                .collect(),
+            authoritative: false,
+        };
+        let length = code_file.source.get_content().chars().count();
+        check_edits(&client.edits, base_length(length, &missed))?;
+        let merged_here = server.merge(client.clone());
+        let merged_there = client.merge(server.clone());
+
+        let applied = self.apply_edits(
+            request.file_id,
+            merged_here.after(server.edits.len()).to_vec(),
+            &request.author,
+        )?;
+        let operations = merged_there
+            .after(client.edits.len())
+            .iter()
+            .zip(&missed)
+            .filter(|(edit, _)| edit.start != edit.end || !edit.text.is_empty())
+            .map(|(edit, operation)| SyncOperation {
+                start: edit.start,
+                end: edit.end,
+                text: edit.text.clone(),
+                author: operation.author.clone(),
+            })
+            .collect();
+
+        Ok(SyncResponse {
+            revision: current + applied.len() as u64,
+            operations,
+            applied: applied.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::dto::resume::PendingEdit;
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::operation_history::OperationHistory;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use uuid::Uuid;
+
+    fn usecases() -> CodeFileUsecasesImpl {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        CodeFileUsecasesImpl::new(repository)
+    }
+
+    fn create(usecases: &mut CodeFileUsecasesImpl, content: &str) -> Uuid {
+        let id = usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("sync_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id;
+        edit(usecases, id, 0, 0, content);
+        id
+    }
+
+    fn edit(usecases: &mut CodeFileUsecasesImpl, id: Uuid, start: u64, end: u64, text: &str) {
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start,
+                end,
+                content: text.to_string(),
+                author: "grace".to_string(),
+            })
+            .unwrap();
+    }
+
+    fn operation(start: usize, end: usize, text: &str) -> PendingEdit {
+        PendingEdit {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    fn apply(text: &str, operations: &[SyncOperation]) -> String {
+        operations.iter().fold(text.to_string(), |text, edit| {
+            let mut chars: Vec<char> = text.chars().collect();
+            chars.splice(edit.start..edit.end, edit.text.chars());
+            chars.into_iter().collect()
+        })
+    }
+
+    #[test]
+    fn test_sync_converges_with_concurrent_server_edits() {
+        let mut usecases = usecases();
+        let id = create(&mut usecases, "fn main() {}");
+        // The client went offline at revision 1 and made these meanwhile,
+        // leaving it with "fn start() { work(); }".
+        let offline = vec![operation(11, 11, " work(); "), operation(3, 7, "start")];
+        let client_text = "fn start() { work(); }";
+        edit(&mut usecases, id, 11, 11, " serve(); ");
+        edit(&mut usecases, id, 0, 0, "pub ");
+
+        let response = usecases
+            .sync_file(SyncRequest {
+                file_id: id,
+                base_revision: 1,
+                author: "ada".to_string(),
+                operations: offline,
+            })
+            .unwrap();
+
+        let server_text = usecases.get_code_file(id).unwrap().viewport.content;
+        assert_eq!(server_text, "pub fn start() { serve();  work(); }");
+        assert_eq!(apply(client_text, &response.operations), server_text);
+        assert_eq!((response.applied, response.revision), (2, 5));
+        assert!(response.operations.iter().all(|o| o.author == "grace"));
+
+        // Syncing again from the new revision with nothing to send is a no-op.
+        let response = usecases
+            .sync_file(SyncRequest {
+                file_id: id,
+                base_revision: 5,
+                author: "ada".to_string(),
+                operations: Vec::new(),
+            })
+            .unwrap();
+        assert!(response.operations.is_empty());
+        assert_eq!(response.revision, 5);
+
+        usecases.delete_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_sync_drops_edits_inside_text_deleted_on_the_server() {
+        let mut usecases = usecases();
+        let id = create(&mut usecases, "keep; drop(this); keep;");
+        edit(&mut usecases, id, 6, 18, "");
+
+        let response = usecases
+            .sync_file(SyncRequest {
+                file_id: id,
+                base_revision: 1,
+                author: "ada".to_string(),
+                operations: vec![operation(11, 15, "that")],
+            })
+            .unwrap();
+
+        assert_eq!(response.applied, 0);
+        assert_eq!(response.revision, 2);
+        assert_eq!(
+            apply("keep; drop(that); keep;", &response.operations),
+            "keep; keep;"
+        );
+        assert_eq!(
+            usecases.get_code_file(id).unwrap().viewport.content,
+            "keep; keep;"
+        );
+
+        usecases.delete_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_sync_needs_history_back_to_the_base() {
+        let mut usecases = usecases();
+        usecases.history = OperationHistory::new(1);
+        let id = create(&mut usecases, "a");
+        edit(&mut usecases, id, 1, 1, "b");
+
+        let result = usecases.sync_file(SyncRequest {
+            file_id: id,
+            base_revision: 0,
+            author: "ada".to_string(),
+            operations: vec![operation(0, 0, "z")],
+        });
+        match result {
+            Err(ApplicationError::HistoryUnavailable(0)) => {},
+            _ => panic!("Expected HistoryUnavailable error"),
+        }
+
+        let result = usecases.sync_file(SyncRequest {
+            file_id: id,
+            base_revision: 3,
+            author: "ada".to_string(),
+            operations: Vec::new(),
+        });
+        match result {
+            Err(ApplicationError::UnknownRevision(3)) => {},
+            _ => panic!("Expected UnknownRevision error"),
+        }
+        assert_eq!(usecases.get_code_file(id).unwrap().viewport.content, "ab");
+
+        usecases.delete_code_file(id).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+use crate::application::dto::code_file::CodeFileResponse;
+use crate::application::dto::trash::TrashedFileResponse;
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::{CodeFileUsecases, CodeFileUsecasesImpl};
+use crate::domain::code_file::CodeFile;
+use crate::domain::events::DomainEvent;
+use crate::domain::traits::dyn_file::DynemicFileMove;
+use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+pub trait TrashUsecases: Send + Sync {
+    /// Every file in the trash, oldest deletion first.
+    fn list_trash(&self) -> Result<Vec<TrashedFileResponse>, ApplicationError>;
+    /// Brings a trashed file back to where it was, at the revision it was
+    /// deleted at. Fails while another file takes its place.
+    fn restore_code_file(&mut self, file_id: Uuid) -> Result<CodeFileResponse, ApplicationError>;
+    /// Deletes a trashed file for good, along with its history.
+    fn purge_code_file(&mut self, file_id: Uuid) -> Result<(), ApplicationError>;
+    /// Purges the files that have been in the trash for longer than
+    /// `retention`, returning their ids. A file that can't be purged is left
+    /// for the next run while the others still go, and the first such
+    /// failure is returned once they have.
+    fn purge_expired(&mut self, retention: Duration) -> Result<Vec<Uuid>, ApplicationError>;
+}
+
+impl TrashUsecases for CodeFileUsecasesImpl {
+    fn list_trash(&self) -> Result<Vec<TrashedFileResponse>, ApplicationError> {
+        Ok(self
+            .trash
+            .list()?
+            .into_iter()
+            .map(|file| TrashedFileResponse {
+                id: file.file_id,
+                name: file.name,
+                revision: file.revision,
+                deleted_at: file.deleted_at,
+            })
+            .collect())
+    }
+
+    fn restore_code_file(&mut self, file_id: Uuid) -> Result<CodeFileResponse, ApplicationError> {
+        let _writer = self.writers.lock(file_id);
+        let trashed = self.trash.find_by_id(file_id)?;
+
+        let mut source = MmapFileSystemSource::new_writable(trashed.trashed_path.clone())
+            .map_err(ApplicationError::IoError)?;
+        source
+            .move_file(&trashed.path)
+            .map_err(ApplicationError::IoError)?;
+
+        let code_file =
+            CodeFile::new(file_id, trashed.name.clone(), source).with_revision(trashed.revision);
+        self.repository.save(code_file)?;
+        self.trash.delete(file_id)?;
+        self.publish(DomainEvent::FileCreated {
+            file_id,
+            name: trashed.name,
+            revision: trashed.revision,
+        });
+
+        self.get_code_file(file_id)
+    }
+
+    fn purge_code_file(&mut self, file_id: Uuid) -> Result<(), ApplicationError> {
+        let _writer = self.writers.lock(file_id);
+        let trashed = self.trash.find_by_id(file_id)?;
+
+        match std::fs::remove_file(&trashed.trashed_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(ApplicationError::IoError(e));
+            }
+            _ => {},
+        }
+
+        self.trash.delete(file_id)?;
+        self.history.forget(file_id);
+        Ok(())
+    }
+
+    fn purge_expired(&mut self, retention: Duration) -> Result<Vec<Uuid>, ApplicationError> {
+        let now = SystemTime::now();
+        let mut purged = Vec::new();
+        let mut failure = None;
+        for trashed in self.trash.list()? {
+            if !trashed.expired(retention, now) {
+                continue;
+            }
+            match self.purge_code_file(trashed.file_id) {
+                Ok(()) => purged.push(trashed.file_id),
+                Err(e) => {
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(purged),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::playback_usecases::PlaybackUsecases;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use std::path::PathBuf;
+
+    fn usecases() -> CodeFileUsecasesImpl {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        CodeFileUsecasesImpl::new(repository)
+    }
+
+    fn create(usecases: &mut CodeFileUsecasesImpl, name: &str) -> Uuid {
+        usecases
+            .create_code_file(CreateCodeFileRequest {
+                name: name.to_string(),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn insert(usecases: &mut CodeFileUsecasesImpl, id: Uuid, start: u64, text: &str) {
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start,
+                end: start,
+                content: text.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_restore_brings_back_content_and_history() {
+        let mut usecases = usecases();
+        let name = format!("trash_{}.rs", Uuid::new_v4());
+        let id = create(&mut usecases, &name);
+        insert(&mut usecases, id, 0, "fn main");
+        insert(&mut usecases, id, 7, "() {}");
+        usecases.delete_code_file(id).unwrap();
+
+        assert!(!PathBuf::from(format!("/tmp/{name}")).exists());
+        let trashed = usecases.list_trash().unwrap();
+        assert_eq!(
+            trashed
+                .iter()
+                .map(|file| (file.id, file.revision))
+                .collect::<Vec<_>>(),
+            vec![(id, 2)]
+        );
+        assert!(usecases.get_code_file(id).is_err());
+
+        let restored = usecases.restore_code_file(id).unwrap();
+        assert_eq!(
+            (
+                restored.name.as_str(),
+                restored.revision,
+                restored.viewport.content.as_str()
+            ),
+            (name.as_str(), 2, "fn main() {}")
+        );
+        assert!(usecases.list_trash().unwrap().is_empty());
+        insert(&mut usecases, id, 0, "// entry\n");
+        let recording = usecases.export_recording(id).unwrap();
+        assert_eq!((recording.base_revision, recording.frames.len()), (0, 3));
+
+        usecases.delete_code_file(id).unwrap();
+        usecases.purge_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_restore_never_replaces_a_newer_file() {
+        let mut usecases = usecases();
+        let name = format!("trash_{}.rs", Uuid::new_v4());
+        let id = create(&mut usecases, &name);
+        insert(&mut usecases, id, 0, "old");
+        usecases.delete_code_file(id).unwrap();
+        let newer = create(&mut usecases, &name);
+        insert(&mut usecases, newer, 0, "new");
+
+        match usecases.restore_code_file(id) {
+            Err(ApplicationError::IoError(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists)
+            }
+            _ => panic!("Expected IoError"),
+        }
+        assert_eq!(usecases.list_trash().unwrap().len(), 1);
+        assert_eq!(
+            usecases.get_code_file(newer).unwrap().viewport.content,
+            "new"
+        );
+
+        usecases.delete_code_file(newer).unwrap();
+        usecases.purge_code_file(newer).unwrap();
+        assert_eq!(
+            usecases.restore_code_file(id).unwrap().viewport.content,
+            "old"
+        );
+        usecases.delete_code_file(id).unwrap();
+        usecases.purge_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_purge_expired_deletes_for_good() {
+        let mut usecases = usecases();
+        let id = create(&mut usecases, &format!("trash_{}.rs", Uuid::new_v4()));
+        insert(&mut usecases, id, 0, "gone");
+        usecases.delete_code_file(id).unwrap();
+        let trashed_path = usecases.trash.find_by_id(id).unwrap().trashed_path;
+
+        assert!(
+            usecases
+                .purge_expired(Duration::from_secs(3600))
+                .unwrap()
+                .is_empty()
+        );
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(usecases.purge_expired(Duration::ZERO).unwrap(), vec![id]);
+
+        assert!(!trashed_path.exists());
+        assert!(usecases.history.snapshots(id).is_empty());
+        match usecases.restore_code_file(id) {
+            Err(ApplicationError::TrashedFileNotFound(_)) => {},
+            _ => panic!("Expected TrashedFileNotFound error"),
+        }
+    }
+
+    #[test]
+    fn test_purge_expired_goes_past_a_failure() {
+        let mut usecases = usecases();
+        let stuck = create(&mut usecases, &format!("trash_{}.rs", Uuid::new_v4()));
+        usecases.delete_code_file(stuck).unwrap();
+        let gone = create(&mut usecases, &format!("trash_{}.rs", Uuid::new_v4()));
+        usecases.delete_code_file(gone).unwrap();
+        // A directory where the trashed file was can't be removed as a file.
+        let stuck_path = usecases.trash.find_by_id(stuck).unwrap().trashed_path;
+        std::fs::remove_file(&stuck_path).unwrap();
+        std::fs::create_dir(&stuck_path).unwrap();
+
+        std::thread::sleep(Duration::from_millis(5));
+        match usecases.purge_expired(Duration::ZERO) {
+            Err(ApplicationError::IoError(_)) => {},
+            _ => panic!("Expected IoError"),
+        }
+        let left: Vec<Uuid> = usecases
+            .list_trash()
+            .unwrap()
+            .into_iter()
+            .map(|file| file.id)
+            .collect();
+        assert_eq!(left, vec![stuck]);
+
+        std::fs::remove_dir(&stuck_path).unwrap();
+        assert_eq!(usecases.purge_expired(Duration::ZERO).unwrap(), vec![stuck]);
+    }
+}
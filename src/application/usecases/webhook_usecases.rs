@@ -0,0 +1,440 @@
+use crate::application::dto::webhook::{DeliveryResponse, RegisterWebhookRequest, WebhookResponse};
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::webhook_repository::WebhookRepository;
+use crate::domain::webhook::{
+    DeliveryAttempt, DeliveryOutcome, EVENT_KINDS, Webhook, WebhookScope,
+};
+use uuid::Uuid;
+
+/// Registrations and the delivery log. Sending is done by a
+/// `WebhookDispatcher` registered on the event bus over the same repository.
+pub trait WebhookUsecases: Send + Sync {
+    fn register_webhook(
+        &mut self,
+        request: RegisterWebhookRequest,
+    ) -> Result<WebhookResponse, ApplicationError>;
+    fn unregister_webhook(&mut self, webhook_id: Uuid) -> Result<(), ApplicationError>;
+    fn list_webhooks(&self) -> Result<Vec<WebhookResponse>, ApplicationError>;
+    fn list_deliveries(&self, webhook_id: Uuid) -> Result<Vec<DeliveryResponse>, ApplicationError>;
+}
+
+pub struct WebhookUsecasesImpl {
+    pub repository: Box<dyn WebhookRepository>,
+}
+
+impl WebhookUsecasesImpl {
+    pub fn new(repository: Box<dyn WebhookRepository>) -> Self {
+        Self { repository }
+    }
+}
+
+fn validate(request: &RegisterWebhookRequest) -> Result<(), ApplicationError> {
+    if !(request.url.starts_with("http://") || request.url.starts_with("https://")) {
+        return Err(ApplicationError::InvalidWebhook(format!(
+            "unsupported url: {}",
+            request.url
+        )));
+    }
+    if request.secret.is_empty() {
+        return Err(ApplicationError::InvalidWebhook(
+            "secret must not be empty".to_string(),
+        ));
+    }
+    if let Some(kind) = request
+        .events
+        .iter()
+        .find(|kind| !EVENT_KINDS.contains(&kind.as_str()))
+    {
+        return Err(ApplicationError::InvalidWebhook(format!(
+            "unknown event: {kind}"
+        )));
+    }
+    Ok(())
+}
+
+fn to_response(webhook: Webhook) -> WebhookResponse {
+    WebhookResponse {
+        id: webhook.id,
+        url: webhook.url,
+        file_id: match webhook.scope {
+            WebhookScope::Workspace => None,
+            WebhookScope::File(file_id) => Some(file_id),
+        },
+        events: webhook.events,
+    }
+}
+
+fn to_delivery_response(attempt: DeliveryAttempt) -> DeliveryResponse {
+    let (status, error) = match &attempt.outcome {
+        DeliveryOutcome::Delivered(status) | DeliveryOutcome::Rejected(status) => {
+            (Some(*status), None)
+        }
+        DeliveryOutcome::Failed(error) => (None, Some(error.clone())),
+    };
+    DeliveryResponse {
+        delivery_id: attempt.delivery_id,
+        sequence: attempt.sequence,
+        event: attempt.event,
+        attempt: attempt.attempt,
+        status,
+        error,
+        delivered: attempt.outcome.is_delivered(),
+    }
+}
+
+impl WebhookUsecases for WebhookUsecasesImpl {
+    fn register_webhook(
+        &mut self,
+        request: RegisterWebhookRequest,
+    ) -> Result<WebhookResponse, ApplicationError> {
+        validate(&request)?;
+        let webhook = self.repository.save(Webhook {
+            id: Uuid::new_v4(),
+            url: request.url,
+            secret: request.secret,
+            scope: match request.file_id {
+                Some(file_id) => WebhookScope::File(file_id),
+                None => WebhookScope::Workspace,
+            },
+            events: request.events,
+        })?;
+        Ok(to_response(webhook))
+    }
+
+    fn unregister_webhook(&mut self, webhook_id: Uuid) -> Result<(), ApplicationError> {
+        self.repository.delete(webhook_id)
+    }
+
+    fn list_webhooks(&self) -> Result<Vec<WebhookResponse>, ApplicationError> {
+        Ok(self
+            .repository
+            .list()?
+            .into_iter()
+            .map(to_response)
+            .collect())
+    }
+
+    fn list_deliveries(&self, webhook_id: Uuid) -> Result<Vec<DeliveryResponse>, ApplicationError> {
+        self.repository.find_by_id(webhook_id)?;
+        Ok(self
+            .repository
+            .deliveries(webhook_id)?
+            .into_iter()
+            .map(to_delivery_response)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use crate::infrastructure::persistence::in_memory_webhook_repository::InMemoryWebhookRepository;
+    use crate::infrastructure::webhook_dispatcher::{
+        DELIVERY_HEADER, EVENT_HEADER, RetryPolicy, SIGNATURE_HEADER, WebhookDispatcher, sign,
+    };
+    use axum::Router;
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use serde_json::{Value, json};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // Stand-in for a subscriber's endpoint that answers 500 to the first
+    // `failures` requests.
+    #[derive(Clone, Default)]
+    struct Receiver {
+        requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+        failures: Arc<AtomicUsize>,
+    }
+
+    async fn receive(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        receiver.requests.lock().unwrap().push((headers, body));
+        let failing = receiver
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if failing {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    async fn start_receiver(failures: usize) -> (String, Receiver) {
+        let receiver = Receiver::default();
+        receiver.failures.store(failures, Ordering::SeqCst);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(receiver.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, receiver)
+    }
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    fn usecases(policy: RetryPolicy) -> (CodeFileUsecasesImpl, WebhookUsecasesImpl) {
+        let repository = InMemoryWebhookRepository::new();
+        let files = CodeFileUsecasesImpl::new(Box::new(InMemoryCodeFileRepository::<
+            MmapFileSystemSource,
+        >::new()));
+        files.events.register(Box::new(WebhookDispatcher::new(
+            Box::new(repository.clone()),
+            policy,
+        )));
+        (files, WebhookUsecasesImpl::new(Box::new(repository)))
+    }
+
+    fn register(
+        webhooks: &mut WebhookUsecasesImpl,
+        url: &str,
+        file_id: Option<Uuid>,
+        events: &[&str],
+    ) -> WebhookResponse {
+        webhooks
+            .register_webhook(RegisterWebhookRequest {
+                url: url.to_string(),
+                secret: "s3cret".to_string(),
+                file_id,
+                events: events.iter().map(|e| e.to_string()).collect(),
+            })
+            .unwrap()
+    }
+
+    fn create(files: &mut CodeFileUsecasesImpl) -> Uuid {
+        files
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("webhook_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn update(files: &mut CodeFileUsecasesImpl, id: Uuid, content: &str) {
+        files
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start: 0,
+                end: 0,
+                content: content.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+    }
+
+    async fn wait_for_deliveries(
+        webhooks: &WebhookUsecasesImpl,
+        webhook_id: Uuid,
+        count: usize,
+    ) -> Vec<DeliveryResponse> {
+        for _ in 0..200 {
+            let deliveries = webhooks.list_deliveries(webhook_id).unwrap();
+            if deliveries.len() >= count {
+                return deliveries;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        panic!("timed out waiting for {count} deliveries");
+    }
+
+    #[tokio::test]
+    async fn test_workspace_webhook_receives_filtered_signed_events() {
+        let (url, receiver) = start_receiver(0).await;
+        let (mut files, mut webhooks) = usecases(fast_policy(3));
+        let webhook = register(&mut webhooks, &url, None, &["created", "deleted"]);
+
+        let file_id = create(&mut files);
+        update(&mut files, file_id, "fn main() {}");
+        files.delete_code_file(file_id).unwrap();
+
+        let deliveries = wait_for_deliveries(&webhooks, webhook.id, 2).await;
+        let kinds: Vec<&str> = deliveries.iter().map(|d| d.event.as_str()).collect();
+        assert_eq!(kinds, vec!["created", "deleted"]);
+        assert!(
+            deliveries
+                .iter()
+                .all(|d| d.delivered && d.status == Some(200))
+        );
+
+        let requests = receiver.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for ((headers, body), delivery) in requests.iter().zip(&deliveries) {
+            assert_eq!(headers[SIGNATURE_HEADER], sign("s3cret", body).as_str());
+            assert_eq!(headers[EVENT_HEADER], delivery.event.as_str());
+            assert_eq!(headers["content-type"], "application/json");
+
+            let payload: Value = serde_json::from_slice(body).unwrap();
+            assert_eq!(payload["delivery_id"], json!(delivery.delivery_id));
+            assert_eq!(payload["webhook_id"], json!(webhook.id));
+            assert_eq!(payload["sequence"], json!(delivery.sequence));
+            assert_eq!(payload["event"]["kind"], json!(delivery.event));
+            assert_eq!(payload["event"]["file_id"], json!(file_id));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_webhook_ignores_other_files() {
+        let (url, receiver) = start_receiver(0).await;
+        let (mut files, mut webhooks) = usecases(fast_policy(3));
+        let watched = create(&mut files);
+        let other = create(&mut files);
+        let webhook = register(&mut webhooks, &url, Some(watched), &[]);
+        assert_eq!(webhooks.list_webhooks().unwrap()[0].file_id, Some(watched));
+
+        update(&mut files, other, "elsewhere");
+        update(&mut files, watched, "here");
+
+        // The creation of `watched` may also arrive, since the dispatcher
+        // can pick it up after the webhook was registered.
+        for _ in 0..200 {
+            let deliveries = webhooks.list_deliveries(webhook.id).unwrap();
+            if deliveries.iter().any(|d| d.event == "updated") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let payloads: Vec<Value> = receiver
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, body)| serde_json::from_slice(body).unwrap())
+            .collect();
+        assert!(
+            payloads
+                .iter()
+                .all(|payload| payload["event"]["file_id"] == json!(watched))
+        );
+        let updates: Vec<&Value> = payloads
+            .iter()
+            .filter(|payload| payload["event"]["kind"] == "updated")
+            .collect();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0]["event"]["text"], json!("here"));
+
+        files.delete_code_file(watched).unwrap();
+        files.delete_code_file(other).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried_with_same_payload() {
+        let (url, receiver) = start_receiver(2).await;
+        let (mut files, mut webhooks) = usecases(fast_policy(5));
+        let webhook = register(&mut webhooks, &url, None, &["created"]);
+
+        let file_id = create(&mut files);
+
+        let deliveries = wait_for_deliveries(&webhooks, webhook.id, 3).await;
+        let log: Vec<(u32, Option<u16>, bool)> = deliveries
+            .iter()
+            .map(|d| (d.attempt, d.status, d.delivered))
+            .collect();
+        assert_eq!(
+            log,
+            vec![
+                (1, Some(500), false),
+                (2, Some(500), false),
+                (3, Some(200), true)
+            ]
+        );
+        assert!(
+            deliveries
+                .iter()
+                .all(|d| d.delivery_id == deliveries[0].delivery_id)
+        );
+
+        let requests = receiver.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|(headers, body)| {
+            headers[DELIVERY_HEADER] == deliveries[0].delivery_id.to_string().as_str()
+                && *body == requests[0].1
+        }));
+        drop(requests);
+
+        files.delete_code_file(file_id).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delivery_gives_up_after_max_attempts() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+        let (mut files, mut webhooks) = usecases(fast_policy(2));
+        let webhook = register(&mut webhooks, &url, None, &["created"]);
+
+        let file_id = create(&mut files);
+
+        let deliveries = wait_for_deliveries(&webhooks, webhook.id, 2).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let deliveries_after = webhooks.list_deliveries(webhook.id).unwrap();
+        assert_eq!(deliveries_after.len(), 2);
+        assert!(
+            deliveries
+                .iter()
+                .all(|d| !d.delivered && d.status.is_none() && d.error.is_some())
+        );
+
+        files.delete_code_file(file_id).unwrap();
+    }
+
+    #[test]
+    fn test_register_rejects_invalid_webhooks() {
+        let (_, mut webhooks) = usecases(fast_policy(1));
+        let invalid = [
+            ("ftp://example.com/hook", "s3cret", "created"),
+            ("http://example.com/hook", "", "created"),
+            ("http://example.com/hook", "s3cret", "archived"),
+        ];
+        for (url, secret, event) in invalid {
+            let result = webhooks.register_webhook(RegisterWebhookRequest {
+                url: url.to_string(),
+                secret: secret.to_string(),
+                file_id: None,
+                events: vec![event.to_string()],
+            });
+            match result {
+                Err(ApplicationError::InvalidWebhook(_)) => {}
+                _ => panic!("Expected InvalidWebhook error"),
+            }
+        }
+        assert!(webhooks.list_webhooks().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unregister_unknown_webhook() {
+        let (_, mut webhooks) = usecases(fast_policy(1));
+        let webhook = register(&mut webhooks, "http://example.com/hook", None, &[]);
+        webhooks.unregister_webhook(webhook.id).unwrap();
+
+        match webhooks.unregister_webhook(webhook.id) {
+            Err(ApplicationError::WebhookNotFound(_)) => {}
+            _ => panic!("Expected WebhookNotFound error"),
+        }
+        match webhooks.list_deliveries(webhook.id) {
+            Err(ApplicationError::WebhookNotFound(_)) => {}
+            _ => panic!("Expected WebhookNotFound error"),
+        }
+    }
+}
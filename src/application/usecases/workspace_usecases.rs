@@ -0,0 +1,203 @@
+use crate::application::dto::workspace::{
+    ImportDirectoryRequest, ImportDirectoryResponse, ImportedFileResponse, SkippedFileResponse,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::domain::code_file::CodeFile;
+use crate::domain::events::DomainEvent;
+use crate::infrastructure::directory_scan::scan_directory;
+use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub trait WorkspaceUsecases: Send + Sync {
+    /// Registers every text file under `root` as a code file mapped at its real
+    /// path, so edits go straight to the checkout. Files are named by their path
+    /// relative to `root`; importing the same directory twice adds nothing.
+    fn import_directory(
+        &mut self,
+        request: ImportDirectoryRequest,
+    ) -> Result<ImportDirectoryResponse, ApplicationError>;
+}
+
+fn skipped(path: &Path, reason: impl ToString) -> SkippedFileResponse {
+    SkippedFileResponse {
+        path: path.display().to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+impl WorkspaceUsecases for CodeFileUsecasesImpl {
+    fn import_directory(
+        &mut self,
+        request: ImportDirectoryRequest,
+    ) -> Result<ImportDirectoryResponse, ApplicationError> {
+        let root = PathBuf::from(&request.root)
+            .canonicalize()
+            .map_err(ApplicationError::IoError)?;
+        let scan =
+            scan_directory(&root, request.max_file_size).map_err(ApplicationError::IoError)?;
+
+        let mut open: HashSet<PathBuf> = self
+            .repository
+            .list()?
+            .into_iter()
+            .map(|code_file| code_file.source.path)
+            .collect();
+
+        let mut response = ImportDirectoryResponse {
+            files: Vec::new(),
+            skipped: scan
+                .skipped
+                .iter()
+                .map(|(path, reason)| skipped(path, reason))
+                .collect(),
+        };
+        for path in scan.files {
+            if !open.insert(path.clone()) {
+                response
+                    .skipped
+                    .push(skipped(&path, "already in workspace"));
+                continue;
+            }
+            let source = match MmapFileSystemSource::new_writable(path.clone()) {
+                Ok(source) => source,
+                Err(e) => {
+                    response.skipped.push(skipped(&path, e));
+                    continue;
+                }
+            };
+
+            let name = path
+                .strip_prefix(&root)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            let code_file =
+                self.repository
+                    .save(CodeFile::new(Uuid::new_v4(), name.clone(), source))?;
+            self.publish(DomainEvent::FileCreated {
+                file_id: code_file.id(),
+                name: name.clone(),
+                revision: code_file.revision(),
+            });
+            response.files.push(ImportedFileResponse {
+                id: code_file.id(),
+                name,
+                path: path.display().to_string(),
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::UpdateCodeRequest;
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::infrastructure::directory_scan::DEFAULT_MAX_FILE_SIZE;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn project() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "/target\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn add() {}").unwrap();
+        fs::write(root.join("target/app"), "built").unwrap();
+        fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G', 0]).unwrap();
+        temp_dir
+    }
+
+    fn import(usecases: &mut CodeFileUsecasesImpl, root: &Path) -> ImportDirectoryResponse {
+        usecases
+            .import_directory(ImportDirectoryRequest {
+                root: root.display().to_string(),
+                max_file_size: DEFAULT_MAX_FILE_SIZE,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_import_directory() {
+        let temp_dir = project();
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+
+        let response = import(&mut usecases, temp_dir.path());
+        let names: Vec<&str> = response.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec![".gitignore", "src/lib.rs"]);
+        assert_eq!(response.skipped.len(), 1);
+        assert!(response.skipped[0].path.ends_with("logo.png"));
+        assert_eq!(response.skipped[0].reason, "binary file");
+
+        let lib = &response.files[1];
+        let real_path = temp_dir.path().canonicalize().unwrap().join("src/lib.rs");
+        assert_eq!(lib.path, real_path.display().to_string());
+        assert_eq!(
+            usecases.get_code_file(lib.id).unwrap().viewport.content,
+            "pub fn add() {}"
+        );
+    }
+
+    #[test]
+    fn test_edits_write_to_real_path() {
+        let temp_dir = project();
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+        let response = import(&mut usecases, temp_dir.path());
+
+        usecases
+            .update_code_file(UpdateCodeRequest {
+                id: response.files[1].id,
+                start: 15,
+                end: 15,
+                content: "\n".to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+        let on_disk = fs::read_to_string(temp_dir.path().join("src/lib.rs")).unwrap();
+        assert_eq!(on_disk, "pub fn add() {}\n");
+    }
+
+    #[test]
+    fn test_reimport_skips_open_files() {
+        let temp_dir = project();
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+        import(&mut usecases, temp_dir.path());
+
+        let response = import(&mut usecases, temp_dir.path());
+        assert!(response.files.is_empty());
+        let reasons: Vec<&str> = response.skipped.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "binary file",
+                "already in workspace",
+                "already in workspace"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_missing_directory() {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+
+        let result = usecases.import_directory(ImportDirectoryRequest {
+            root: format!("/tmp/missing_{}", Uuid::new_v4()),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        });
+        match result {
+            Err(ApplicationError::IoError(_)) => {},
+            _ => panic!("Expected IoError"),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::domain::text_diff::{TextEdit, diff};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointScope {
+    Workspace,
+    File(Uuid),
+}
+
+/// A file's content at the revision the checkpoint was taken, as the bytes on
+/// disk, which need not be UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointedFile {
+    pub file_id: Uuid,
+    pub name: String,
+    pub revision: u64,
+    pub content: Vec<u8>,
+}
+
+/// A named copy of one file, or of every file in the workspace, to compare
+/// against and return to later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub id: Uuid,
+    pub name: String,
+    pub scope: CheckpointScope,
+    pub created_at: SystemTime,
+    pub files: Vec<CheckpointedFile>,
+}
+
+impl Checkpoint {
+    pub fn covers(&self, file_id: Uuid) -> bool {
+        self.files.iter().any(|file| file.file_id == file_id)
+    }
+}
+
+/// How a file differs between two sets of files. `Modified` carries the edit
+/// that turns the older content into the newer one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    Unchanged,
+    Modified(TextEdit),
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileComparison {
+    pub file_id: Uuid,
+    pub name: String,
+    pub change: FileChange,
+}
+
+/// Compares every file of `from` with its counterpart in `to`, followed by the
+/// files only `to` has.
+pub fn compare(from: &[CheckpointedFile], to: &[CheckpointedFile]) -> Vec<FileComparison> {
+    let counterpart = |file_id: Uuid| to.iter().find(|file| file.file_id == file_id);
+    let mut comparisons: Vec<FileComparison> = from
+        .iter()
+        .map(|file| {
+            let (name, change) = match counterpart(file.file_id) {
+                Some(other) => (
+                    other.name.clone(),
+                    diff(
+                        &String::from_utf8_lossy(&file.content),
+                        &String::from_utf8_lossy(&other.content),
+                    )
+                    .map_or(FileChange::Unchanged, FileChange::Modified),
+                ),
+                None => (file.name.clone(), FileChange::Removed),
+            };
+            FileComparison {
+                file_id: file.file_id,
+                name,
+                change,
+            }
+        })
+        .collect();
+    comparisons.extend(
+        to.iter()
+            .filter(|file| !from.iter().any(|old| old.file_id == file.file_id))
+            .map(|file| FileComparison {
+                file_id: file.file_id,
+                name: file.name.clone(),
+                change: FileChange::Added,
+            }),
+    );
+    comparisons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(file_id: Uuid, content: &str) -> CheckpointedFile {
+        CheckpointedFile {
+            file_id,
+            name: format!("{file_id}.rs"),
+            revision: 0,
+            content: content.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let (kept, edited, removed, added) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        let from = [
+            file(kept, "same"),
+            file(edited, "fn old() {}"),
+            file(removed, "gone"),
+        ];
+        let to = [
+            file(added, "new"),
+            file(edited, "fn new() {}"),
+            file(kept, "same"),
+        ];
+
+        let changes: Vec<(Uuid, FileChange)> = compare(&from, &to)
+            .into_iter()
+            .map(|comparison| (comparison.file_id, comparison.change))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (kept, FileChange::Unchanged),
+                (
+                    edited,
+                    FileChange::Modified(TextEdit {
+                        start: 3,
+                        end: 6,
+                        text: "new".to_string(),
+                    })
+                ),
+                (removed, FileChange::Removed),
+                (added, FileChange::Added),
+            ]
+        );
+    }
+}
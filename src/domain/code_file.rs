@@ -1,6 +1,8 @@
 use uuid::Uuid;
 
-use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+use crate::domain::traits::dyn_file::{
+    DynemicFileCreateDelete, DynemicFileRead, DynemicFileSnapshot, DynemicFileWrite,
+};
 
 #[derive(Debug, Clone)]
 pub struct CodeFile<FileSource>
@@ -10,6 +12,7 @@ where
     id: Uuid,
     pub name: String,
     pub source: FileSource,
+    revision: u64,
 }
 
 impl<FileSource> CodeFile<FileSource>
@@ -17,17 +20,86 @@ where
     FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
 {
     pub fn new(id: Uuid, name: String, source: FileSource) -> Self {
-        CodeFile { id, name, source }
+        CodeFile {
+            id,
+            name,
+            source,
+            revision: 0,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The file as it was at `revision`, for files coming back from elsewhere.
+    pub fn with_revision(mut self, revision: u64) -> Self {
+        self.revision = revision;
+        self
     }
 
+    pub fn bump_revision(&mut self) -> u64 {
+        self.revision += 1;
+        self.revision
+    }
+}
+
+impl<FileSource> CodeFile<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete + DynemicFileSnapshot,
+{
+    pub fn snapshot(&self) -> CodeFileSnapshot<FileSource::Snapshot> {
+        CodeFileSnapshot {
+            id: self.id,
+            name: self.name.clone(),
+            source: self.source.snapshot(),
+            revision: self.revision,
+        }
+    }
+}
+
+/// A read-only view of a `CodeFile` pinned to the revision it was taken at,
+/// for readers that must not see edits made while they run.
+#[derive(Debug, Clone)]
+pub struct CodeFileSnapshot<Snapshot> {
+    id: Uuid,
+    pub name: String,
+    pub source: Snapshot,
+    revision: u64,
+}
+
+impl<Snapshot> CodeFileSnapshot<Snapshot> {
     pub fn id(&self) -> Uuid {
         self.id
     }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+}
+
+impl<Snapshot: DynemicFileRead> DynemicFileRead for CodeFileSnapshot<Snapshot> {
+    fn get_slice(&self, start: usize, end: usize) -> String {
+        self.source.get_slice(start, end)
+    }
+
+    fn get_content(&self) -> String {
+        self.source.get_content()
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        self.source.as_bytes()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::infrastructure::in_memory_file_source::InMemoryFileSource;
 
     struct TestFileWrapper {
         content: String,
@@ -82,7 +154,7 @@ mod tests {
 
     #[test]
     fn test_code_file_set_content() {
-        let mut file_wrapper = TestFileWrapper {
+        let file_wrapper = TestFileWrapper {
             content: "Lorem ipsum dolor sit amet, consectetur adipiscing elit.".to_string(),
         };
         let mut code_file =
@@ -90,4 +162,32 @@ mod tests {
         code_file.source.set_content("New content".to_string());
         assert_eq!(code_file.source.get_content(), "New content".to_string());
     }
+
+    #[test]
+    fn test_code_file_revision() {
+        let file_wrapper = TestFileWrapper {
+            content: String::new(),
+        };
+        let mut code_file =
+            CodeFile::new(Uuid::new_v4(), "test_file.txt".to_string(), file_wrapper);
+        assert_eq!(code_file.revision(), 0);
+        assert_eq!(code_file.bump_revision(), 1);
+        assert_eq!(code_file.bump_revision(), 2);
+        assert_eq!(code_file.revision(), 2);
+    }
+
+    #[test]
+    fn test_snapshot_is_pinned_to_its_revision() {
+        let source = InMemoryFileSource::new("before".to_string());
+        let mut code_file = CodeFile::new(Uuid::new_v4(), "test_file.txt".to_string(), source);
+        code_file.bump_revision();
+
+        let snapshot = code_file.snapshot();
+        code_file.source.set_content("after".to_string());
+        code_file.bump_revision();
+
+        assert_eq!((snapshot.id(), snapshot.revision()), (code_file.id(), 1));
+        assert_eq!(snapshot.get_content(), "before");
+        assert_eq!(code_file.source.get_content(), "after");
+    }
 }
@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::ops::Range;
+use uuid::Uuid;
+
+/// Something that happened to a code file. `revision` is the file's revision
+/// once the change was applied; a rename or deletion leaves it as it was.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum DomainEvent {
+    #[serde(rename = "created")]
+    FileCreated {
+        file_id: Uuid,
+        name: String,
+        revision: u64,
+    },
+    /// The chars in `range` of the previous revision were replaced by `text`.
+    #[serde(rename = "updated")]
+    FileEdited {
+        file_id: Uuid,
+        range: Range<usize>,
+        text: String,
+        author: String,
+        revision: u64,
+    },
+    #[serde(rename = "deleted")]
+    FileDeleted { file_id: Uuid, revision: u64 },
+    /// The file was renamed or moved to `name`; its text is unchanged.
+    #[serde(rename = "renamed")]
+    FileRenamed {
+        file_id: Uuid,
+        name: String,
+        revision: u64,
+    },
+}
+
+impl DomainEvent {
+    pub fn file_id(&self) -> Uuid {
+        match self {
+            DomainEvent::FileCreated { file_id, .. }
+            | DomainEvent::FileEdited { file_id, .. }
+            | DomainEvent::FileDeleted { file_id, .. }
+            | DomainEvent::FileRenamed { file_id, .. } => *file_id,
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        match self {
+            DomainEvent::FileCreated { revision, .. }
+            | DomainEvent::FileEdited { revision, .. }
+            | DomainEvent::FileDeleted { revision, .. }
+            | DomainEvent::FileRenamed { revision, .. } => *revision,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomainEvent::FileCreated { .. } => "created",
+            DomainEvent::FileEdited { .. } => "updated",
+            DomainEvent::FileDeleted { .. } => "deleted",
+            DomainEvent::FileRenamed { .. } => "renamed",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize_edit() {
+        let file_id = Uuid::new_v4();
+        let event = DomainEvent::FileEdited {
+            file_id,
+            range: 3..5,
+            text: "xy".to_string(),
+            author: "ada".to_string(),
+            revision: 7,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "kind": "updated",
+                "file_id": file_id,
+                "range": {"start": 3, "end": 5},
+                "text": "xy",
+                "author": "ada",
+                "revision": 7
+            })
+        );
+        assert_eq!(event.file_id(), file_id);
+        assert_eq!(event.revision(), 7);
+    }
+}
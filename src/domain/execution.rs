@@ -0,0 +1,25 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    Ok,
+    Error,
+    Interrupted,
+    Died,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KernelEvent {
+    Stdout {
+        execution_id: u64,
+        text: String,
+    },
+    Stderr {
+        execution_id: u64,
+        text: String,
+    },
+    Finished {
+        execution_id: u64,
+        status: ExecutionStatus,
+    },
+    Restarted,
+    Died,
+}
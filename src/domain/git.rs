@@ -0,0 +1,112 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Unmodified,
+    Modified,
+    Added,
+    Deleted,
+    Renamed,
+    Untracked,
+    Ignored,
+    Conflicted,
+}
+
+impl FileStatus {
+    /// Maps the two-letter `XY` code of `git status --porcelain` to a single
+    /// status, letting working tree changes win over staged ones.
+    pub fn from_porcelain(code: &str) -> Self {
+        let mut chars = code.chars();
+        let index = chars.next().unwrap_or(' ');
+        let worktree = chars.next().unwrap_or(' ');
+        match (index, worktree) {
+            ('?', '?') => FileStatus::Untracked,
+            ('!', '!') => FileStatus::Ignored,
+            ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => FileStatus::Conflicted,
+            (_, 'D') | ('D', _) => FileStatus::Deleted,
+            (_, 'M') | (_, 'T') => FileStatus::Modified,
+            ('R', _) | ('C', _) => FileStatus::Renamed,
+            ('A', _) => FileStatus::Added,
+            ('M', _) | ('T', _) => FileStatus::Modified,
+            _ => FileStatus::Unmodified,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+/// Appends a `Co-authored-by` trailer for every collaborator except the author,
+/// each person once, in the order given.
+pub fn with_co_authors(message: &str, author: &Signature, co_authors: &[Signature]) -> String {
+    let mut seen = vec![author.email.to_lowercase()];
+    let mut trailers = Vec::new();
+    for co_author in co_authors {
+        let email = co_author.email.to_lowercase();
+        if seen.contains(&email) {
+            continue;
+        }
+        seen.push(email);
+        trailers.push(format!("Co-authored-by: {co_author}"));
+    }
+
+    let message = message.trim_end();
+    if trailers.is_empty() {
+        return format!("{message}\n");
+    }
+    format!("{message}\n\n{}\n", trailers.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(name: &str, email: &str) -> Signature {
+        Signature {
+            name: name.to_string(),
+            email: email.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_from_porcelain() {
+        assert_eq!(FileStatus::from_porcelain(" M"), FileStatus::Modified);
+        assert_eq!(FileStatus::from_porcelain("M "), FileStatus::Modified);
+        assert_eq!(FileStatus::from_porcelain("A "), FileStatus::Added);
+        assert_eq!(FileStatus::from_porcelain("AM"), FileStatus::Modified);
+        assert_eq!(FileStatus::from_porcelain(" D"), FileStatus::Deleted);
+        assert_eq!(FileStatus::from_porcelain("R "), FileStatus::Renamed);
+        assert_eq!(FileStatus::from_porcelain("??"), FileStatus::Untracked);
+        assert_eq!(FileStatus::from_porcelain("!!"), FileStatus::Ignored);
+        assert_eq!(FileStatus::from_porcelain("UU"), FileStatus::Conflicted);
+    }
+
+    #[test]
+    fn test_with_co_authors() {
+        let author = signature("Ada", "ada@example.com");
+        let message = with_co_authors(
+            "Fix parser\n",
+            &author,
+            &[
+                signature("Grace", "grace@example.com"),
+                signature("Ada L.", "ADA@example.com"),
+                signature("Grace H.", "grace@example.com"),
+                signature("Linus", "linus@example.com"),
+            ],
+        );
+        assert_eq!(
+            message,
+            "Fix parser\n\n\
+             Co-authored-by: Grace <grace@example.com>\n\
+             Co-authored-by: Linus <linus@example.com>\n"
+        );
+        assert_eq!(with_co_authors("Solo", &author, &[]), "Solo\n");
+    }
+}
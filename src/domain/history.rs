@@ -0,0 +1,114 @@
+use std::ops::Range;
+use std::time::SystemTime;
+
+/// An edit as it was applied to a file: the chars in `range` of revision
+/// `base_revision` replaced by `text`, producing `revision`. A fresh edit
+/// advances one revision; compaction squashes runs of them into one that
+/// advances several.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedOperation {
+    pub base_revision: u64,
+    pub revision: u64,
+    pub range: Range<usize>,
+    pub text: String,
+    pub author: String,
+    pub recorded_at: SystemTime,
+}
+
+impl RecordedOperation {
+    /// The single edit that does what `self` and then `next` do. Only edits by
+    /// the same author that follow each other and touch or abut the text
+    /// `self` left behind squash, as typing and backspacing do.
+    pub fn squash(&self, next: &RecordedOperation) -> Option<RecordedOperation> {
+        let (start, end) = (self.range.start, self.range.end);
+        let inserted: Vec<char> = self.text.chars().collect();
+        let written = start + inserted.len();
+        if next.base_revision != self.revision
+            || next.author != self.author
+            || next.range.start > written
+            || next.range.end < start
+        {
+            return None;
+        }
+
+        // Whatever of the span lies outside `self`'s text is inside `next`'s
+        // range, so only `self`'s text around `next` survives.
+        let covered_end = written.max(next.range.end);
+        let before = next.range.start.saturating_sub(start).min(inserted.len());
+        let after = next.range.end.saturating_sub(start).min(inserted.len());
+        let mut text: String = inserted[..before].iter().collect();
+        text.push_str(&next.text);
+        text.extend(&inserted[after..]);
+
+        Some(RecordedOperation {
+            base_revision: self.base_revision,
+            revision: next.revision,
+            range: start.min(next.range.start)..covered_end - inserted.len() + (end - start),
+            text,
+            author: self.author.clone(),
+            recorded_at: next.recorded_at,
+        })
+    }
+
+    /// Applies the edit to `content`, the text at `base_revision`.
+    pub fn apply(&self, content: &str) -> String {
+        let mut chars: Vec<char> = content.chars().collect();
+        let end = self.range.end.min(chars.len());
+        chars.splice(self.range.start.min(end)..end, self.text.chars());
+        chars.into_iter().collect()
+    }
+}
+
+/// The full text of a file at `revision`, kept so the history can drop the
+/// edits that led there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistorySnapshot {
+    pub revision: u64,
+    pub content: String,
+    pub taken_at: SystemTime,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(revision: u64, range: Range<usize>, text: &str) -> RecordedOperation {
+        RecordedOperation {
+            base_revision: revision - 1,
+            revision,
+            range,
+            text: text.to_string(),
+            author: "ada".to_string(),
+            recorded_at: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_squash_matches_applying_both() {
+        let content = "fn main() {}";
+        let cases = [
+            // Typing, then backspacing into what was typed.
+            (operation(1, 11..11, "x"), operation(2, 12..12, "y")),
+            (operation(1, 11..11, "abc"), operation(2, 13..14, "")),
+            // A deletion retyped, and an edit reaching over both sides.
+            (operation(1, 3..7, ""), operation(2, 3..3, "run")),
+            (operation(1, 5..5, "b"), operation(2, 3..7, "Z")),
+        ];
+        for (first, second) in cases {
+            let squashed = first.squash(&second).unwrap();
+            assert_eq!((squashed.base_revision, squashed.revision), (0, 2));
+            assert_eq!(squashed.apply(content), second.apply(&first.apply(content)));
+        }
+    }
+
+    #[test]
+    fn test_squash_needs_touching_edits_by_one_author() {
+        let first = operation(1, 0..0, "a");
+        assert_eq!(first.squash(&operation(2, 5..5, "b")), None);
+        assert_eq!(first.squash(&operation(3, 1..1, "b")), None);
+
+        let mut other = operation(2, 1..1, "b");
+        other.author = "grace".to_string();
+        assert_eq!(first.squash(&other), None);
+    }
+}
@@ -1,2 +1,16 @@
+pub mod checkpoint;
 pub mod code_file;
+pub mod events;
+pub mod execution;
+pub mod git;
+pub mod history;
+pub mod notebook;
+pub mod recording;
+pub mod replica;
+pub mod search;
+pub mod shard;
+pub mod sync;
+pub mod text_diff;
 pub mod traits;
+pub mod trash;
+pub mod webhook;
@@ -0,0 +1,341 @@
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellKind {
+    Code,
+    Markdown,
+    Raw,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotebookError {
+    CellNotFound(Uuid),
+    CellNotExecutable(Uuid),
+    DuplicateCell(Uuid),
+    /// The char range of an edit is reversed or runs past the cell's source.
+    InvalidRange(Uuid),
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    id: Uuid,
+    pub kind: CellKind,
+    pub source: FileSource,
+    pub metadata: Map<String, Value>,
+    pub outputs: Vec<Value>,
+    pub execution_count: Option<u64>,
+    pub extra: Map<String, Value>,
+}
+
+impl<FileSource> Cell<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    pub fn new(id: Uuid, kind: CellKind, source: FileSource) -> Self {
+        Cell {
+            id,
+            kind,
+            source,
+            metadata: Map::new(),
+            outputs: Vec::new(),
+            execution_count: None,
+            extra: Map::new(),
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+// Deleted cells stay behind as tombstones so that operations anchored on them
+// (issued by a collaborator who had not yet seen the delete) still resolve to
+// a stable position instead of failing or landing somewhere arbitrary.
+#[derive(Debug, Clone)]
+struct CellSlot<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    id: Uuid,
+    cell: Option<Cell<FileSource>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notebook<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    id: Uuid,
+    pub name: String,
+    pub metadata: Map<String, Value>,
+    pub extra: Map<String, Value>,
+    slots: Vec<CellSlot<FileSource>>,
+    revision: u64,
+}
+
+impl<FileSource> Notebook<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    pub fn new(id: Uuid, name: String) -> Self {
+        Notebook {
+            id,
+            name,
+            metadata: Map::new(),
+            extra: Map::new(),
+            slots: Vec::new(),
+            revision: 0,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn bump_revision(&mut self) -> u64 {
+        self.revision += 1;
+        self.revision
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = &Cell<FileSource>> {
+        self.slots.iter().filter_map(|slot| slot.cell.as_ref())
+    }
+
+    pub fn cell(&self, cell_id: Uuid) -> Option<&Cell<FileSource>> {
+        self.slots
+            .iter()
+            .find(|slot| slot.id == cell_id)
+            .and_then(|slot| slot.cell.as_ref())
+    }
+
+    pub fn cell_mut(&mut self, cell_id: Uuid) -> Option<&mut Cell<FileSource>> {
+        self.slots
+            .iter_mut()
+            .find(|slot| slot.id == cell_id)
+            .and_then(|slot| slot.cell.as_mut())
+    }
+
+    fn slot_index(&self, cell_id: Uuid) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.id == cell_id)
+    }
+
+    fn insert_position(&self, after: Option<Uuid>) -> Result<usize, NotebookError> {
+        match after {
+            None => Ok(0),
+            Some(anchor) => self
+                .slot_index(anchor)
+                .map(|index| index + 1)
+                .ok_or(NotebookError::CellNotFound(anchor)),
+        }
+    }
+
+    /// Inserts `cell` right after `after` (or at the top when `None`). The anchor
+    /// may be a deleted cell.
+    pub fn insert_cell(
+        &mut self,
+        cell: Cell<FileSource>,
+        after: Option<Uuid>,
+    ) -> Result<u64, NotebookError> {
+        if self.slot_index(cell.id()).is_some() {
+            return Err(NotebookError::DuplicateCell(cell.id()));
+        }
+        let position = self.insert_position(after)?;
+        self.slots.insert(
+            position,
+            CellSlot {
+                id: cell.id(),
+                cell: Some(cell),
+            },
+        );
+        Ok(self.bump_revision())
+    }
+
+    /// Moves a cell right after `after`. Moving a deleted cell is a no-op, since
+    /// the collaborator who issued it could not have seen the delete.
+    pub fn move_cell(&mut self, cell_id: Uuid, after: Option<Uuid>) -> Result<u64, NotebookError> {
+        let from = self
+            .slot_index(cell_id)
+            .ok_or(NotebookError::CellNotFound(cell_id))?;
+        if after == Some(cell_id) || self.slots[from].cell.is_none() {
+            return Ok(self.revision);
+        }
+        self.insert_position(after)?;
+
+        let slot = self.slots.remove(from);
+        let position = self
+            .insert_position(after)
+            .expect("anchor resolved before removal");
+        self.slots.insert(position, slot);
+        Ok(self.bump_revision())
+    }
+
+    /// Deletes a cell, leaving a tombstone behind. Deleting an already deleted
+    /// cell is a no-op.
+    pub fn delete_cell(&mut self, cell_id: Uuid) -> Result<u64, NotebookError> {
+        let index = self
+            .slot_index(cell_id)
+            .ok_or(NotebookError::CellNotFound(cell_id))?;
+        match self.slots[index].cell.take() {
+            Some(_) => Ok(self.bump_revision()),
+            None => Ok(self.revision),
+        }
+    }
+
+    pub fn edit_cell_source(
+        &mut self,
+        cell_id: Uuid,
+        start: usize,
+        end: usize,
+        content: String,
+    ) -> Result<u64, NotebookError> {
+        let cell = self
+            .cell_mut(cell_id)
+            .ok_or(NotebookError::CellNotFound(cell_id))?;
+        if start > end || end > cell.source.get_content().chars().count() {
+            return Err(NotebookError::InvalidRange(cell_id));
+        }
+        cell.source.set_slice(start, end, content);
+        Ok(self.bump_revision())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::in_memory_file_source::InMemoryFileSource;
+
+    fn cell(source: &str) -> Cell<InMemoryFileSource> {
+        Cell::new(
+            Uuid::new_v4(),
+            CellKind::Code,
+            InMemoryFileSource::new(source.to_string()),
+        )
+    }
+
+    fn sources(notebook: &Notebook<InMemoryFileSource>) -> Vec<String> {
+        notebook.cells().map(|c| c.source.get_content()).collect()
+    }
+
+    #[test]
+    fn test_insert_cells() {
+        let mut notebook = Notebook::new(Uuid::new_v4(), "nb".to_string());
+        let first = cell("a");
+        let first_id = first.id();
+        notebook.insert_cell(first, None).unwrap();
+        notebook.insert_cell(cell("c"), Some(first_id)).unwrap();
+        notebook.insert_cell(cell("b"), Some(first_id)).unwrap();
+        notebook.insert_cell(cell("top"), None).unwrap();
+
+        assert_eq!(sources(&notebook), vec!["top", "a", "b", "c"]);
+        assert_eq!(notebook.revision(), 4);
+    }
+
+    #[test]
+    fn test_insert_duplicate_cell() {
+        let mut notebook = Notebook::new(Uuid::new_v4(), "nb".to_string());
+        let first = cell("a");
+        let duplicate = first.clone();
+        notebook.insert_cell(first, None).unwrap();
+
+        let result = notebook.insert_cell(duplicate.clone(), None);
+        assert_eq!(result, Err(NotebookError::DuplicateCell(duplicate.id())));
+    }
+
+    #[test]
+    fn test_insert_after_unknown_cell() {
+        let mut notebook = Notebook::new(Uuid::new_v4(), "nb".to_string());
+        let anchor = Uuid::new_v4();
+        let result = notebook.insert_cell(cell("a"), Some(anchor));
+        assert_eq!(result, Err(NotebookError::CellNotFound(anchor)));
+        assert_eq!(notebook.revision(), 0);
+    }
+
+    #[test]
+    fn test_edit_cell_source_rejects_bad_ranges() {
+        let mut notebook = Notebook::new(Uuid::new_v4(), "nb".to_string());
+        let cell = cell("print(1)");
+        let cell_id = cell.id();
+        notebook.insert_cell(cell, None).unwrap();
+
+        for (start, end) in [(3, 2), (0, 9), (9, 9)] {
+            let result = notebook.edit_cell_source(cell_id, start, end, "x".to_string());
+            assert_eq!(result, Err(NotebookError::InvalidRange(cell_id)));
+        }
+        assert_eq!(notebook.revision(), 1);
+
+        assert_eq!(
+            notebook.edit_cell_source(cell_id, 8, 8, "\n".to_string()),
+            Ok(2)
+        );
+        assert_eq!(sources(&notebook), vec!["print(1)\n"]);
+    }
+
+    #[test]
+    fn test_move_cell() {
+        let mut notebook = Notebook::new(Uuid::new_v4(), "nb".to_string());
+        let (a, b, c) = (cell("a"), cell("b"), cell("c"));
+        let (a_id, b_id, c_id) = (a.id(), b.id(), c.id());
+        notebook.insert_cell(a, None).unwrap();
+        notebook.insert_cell(b, Some(a_id)).unwrap();
+        notebook.insert_cell(c, Some(b_id)).unwrap();
+
+        notebook.move_cell(a_id, Some(c_id)).unwrap();
+        assert_eq!(sources(&notebook), vec!["b", "c", "a"]);
+
+        notebook.move_cell(c_id, None).unwrap();
+        assert_eq!(sources(&notebook), vec!["c", "b", "a"]);
+
+        let revision = notebook.revision();
+        notebook.move_cell(b_id, Some(b_id)).unwrap();
+        assert_eq!(notebook.revision(), revision);
+    }
+
+    #[test]
+    fn test_delete_leaves_anchor_usable() {
+        let mut notebook = Notebook::new(Uuid::new_v4(), "nb".to_string());
+        let (a, b) = (cell("a"), cell("b"));
+        let (a_id, b_id) = (a.id(), b.id());
+        notebook.insert_cell(a, None).unwrap();
+        notebook.insert_cell(b, Some(a_id)).unwrap();
+
+        notebook.delete_cell(a_id).unwrap();
+        assert_eq!(sources(&notebook), vec!["b"]);
+        assert!(notebook.cell(a_id).is_none());
+
+        // A collaborator who has not seen the delete inserts after the deleted cell.
+        notebook.insert_cell(cell("after a"), Some(a_id)).unwrap();
+        assert_eq!(sources(&notebook), vec!["after a", "b"]);
+
+        let revision = notebook.revision();
+        notebook.delete_cell(a_id).unwrap();
+        notebook.move_cell(a_id, Some(b_id)).unwrap();
+        assert_eq!(notebook.revision(), revision);
+    }
+
+    #[test]
+    fn test_edit_cell_source() {
+        let mut notebook = Notebook::new(Uuid::new_v4(), "nb".to_string());
+        let a = cell("print('hi')");
+        let a_id = a.id();
+        notebook.insert_cell(a, None).unwrap();
+
+        notebook
+            .edit_cell_source(a_id, 7, 9, "bye".to_string())
+            .unwrap();
+        assert_eq!(sources(&notebook), vec!["print('bye')"]);
+
+        notebook.delete_cell(a_id).unwrap();
+        let result = notebook.edit_cell_source(a_id, 0, 0, "x".to_string());
+        assert_eq!(result, Err(NotebookError::CellNotFound(a_id)));
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One edit of a recording, `at_ms` after the recording starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingFrame {
+    pub at_ms: u64,
+    pub base_revision: u64,
+    pub revision: u64,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub author: String,
+}
+
+impl RecordingFrame {
+    fn apply(&self, content: &str) -> String {
+        let mut chars: Vec<char> = content.chars().collect();
+        let end = self.end.min(chars.len());
+        chars.splice(self.start.min(end)..end, self.text.chars());
+        chars.into_iter().collect()
+    }
+}
+
+/// How a file was written: its text at `base_revision` and the edits made
+/// since, timed from the first. It holds everything needed to play it back,
+/// so it can be exported as JSON and replayed elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    pub file_id: Uuid,
+    pub name: String,
+    /// Milliseconds since the Unix epoch.
+    pub started_at_ms: u64,
+    pub base_revision: u64,
+    pub base_content: String,
+    pub frames: Vec<RecordingFrame>,
+}
+
+/// Where a playback stands: the text once every frame before `position_ms`
+/// has played, and the index of the frame to play next, which may be due at
+/// `position_ms` itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaybackPosition {
+    pub position_ms: u64,
+    pub revision: u64,
+    pub content: String,
+    pub next_frame: usize,
+}
+
+impl Recording {
+    pub fn duration_ms(&self) -> u64 {
+        self.frames.last().map_or(0, |frame| frame.at_ms)
+    }
+
+    pub fn seek(&self, position_ms: u64) -> PlaybackPosition {
+        let mut position = PlaybackPosition {
+            position_ms,
+            revision: self.base_revision,
+            content: self.base_content.clone(),
+            next_frame: 0,
+        };
+        for frame in self
+            .frames
+            .iter()
+            .take_while(|frame| frame.at_ms < position_ms)
+        {
+            position.content = frame.apply(&position.content);
+            position.revision = frame.revision;
+            position.next_frame += 1;
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(at_ms: u64, revision: u64, start: usize, text: &str) -> RecordingFrame {
+        RecordingFrame {
+            at_ms,
+            base_revision: revision - 1,
+            revision,
+            start,
+            end: start,
+            text: text.to_string(),
+            author: "ada".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_seek() {
+        let recording = Recording {
+            file_id: Uuid::new_v4(),
+            name: "main.rs".to_string(),
+            started_at_ms: 0,
+            base_revision: 3,
+            base_content: "fn".to_string(),
+            frames: vec![
+                frame(0, 4, 2, " main"),
+                frame(250, 5, 7, "()"),
+                frame(900, 6, 9, " {}"),
+            ],
+        };
+        assert_eq!(recording.duration_ms(), 900);
+
+        let position = recording.seek(500);
+        assert_eq!(
+            (
+                position.revision,
+                position.content.as_str(),
+                position.next_frame
+            ),
+            (5, "fn main()", 2)
+        );
+        assert_eq!(recording.seek(250).next_frame, 1);
+        assert_eq!(recording.seek(10_000).content, "fn main() {}");
+
+        let json = serde_json::to_string(&recording).unwrap();
+        assert_eq!(serde_json::from_str::<Recording>(&json).unwrap(), recording);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::domain::text_diff::TextEdit;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Names one char ever inserted into a replicated text: a Lamport timestamp
+/// and the node that inserted it. A char inserted by a node that had already
+/// seen another one always gets the greater id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct CharId {
+    pub clock: u64,
+    pub node: Uuid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TextOperation {
+    /// `text` goes right after the char `after`, or at the start without one.
+    /// Its chars are numbered from `id` upwards on the same node.
+    Insert {
+        id: CharId,
+        after: Option<CharId>,
+        text: String,
+    },
+    Delete {
+        ids: Vec<CharId>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    Create { name: String },
+    Text { operation: TextOperation },
+    Remove,
+}
+
+/// A change as exchanged between nodes. `seq` counts the changes made on
+/// `origin`, so a node knows it has everything from a peer up to some point.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicatedOperation {
+    pub origin: Uuid,
+    pub seq: u64,
+    pub file_id: Uuid,
+    pub author: String,
+    pub change: Change,
+}
+
+/// The highest `seq` seen from every origin.
+pub type VersionVector = HashMap<Uuid, u64>;
+
+#[derive(Debug, Clone)]
+struct Element {
+    id: CharId,
+    ch: char,
+    visible: bool,
+}
+
+/// A replicated growable array: every char ever inserted stays in place,
+/// deleted ones as tombstones, so replicas that integrate the same operations
+/// end with the same text whatever order concurrent ones arrive in, as long
+/// as each comes after the ones it refers to.
+#[derive(Debug, Clone, Default)]
+pub struct ReplicatedText {
+    elements: Vec<Element>,
+    clock: u64,
+}
+
+impl ReplicatedText {
+    pub fn text(&self) -> String {
+        self.elements
+            .iter()
+            .filter(|element| element.visible)
+            .map(|element| element.ch)
+            .collect()
+    }
+
+    /// How many chars are kept, deleted ones included.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Drops the deleted chars. Only safe once every replica has integrated
+    /// the same operations: anything made after that refers to visible chars
+    /// only and carries ids above all kept ones, so it lands in the same place
+    /// with or without the tombstones.
+    pub fn purge_tombstones(&mut self) {
+        self.elements.retain(|element| element.visible);
+    }
+
+    fn position(&self, id: CharId) -> Option<usize> {
+        self.elements.iter().position(|element| element.id == id)
+    }
+
+    fn visible_before(&self, index: usize) -> usize {
+        self.elements[..index]
+            .iter()
+            .filter(|element| element.visible)
+            .count()
+    }
+
+    /// Applies an edit of the visible text made on `node` and returns the
+    /// operations that carry it to other replicas. The edit must fit the text.
+    pub fn local_edit(&mut self, node: Uuid, edit: &TextEdit) -> Vec<TextOperation> {
+        let visible: Vec<usize> = (0..self.elements.len())
+            .filter(|&index| self.elements[index].visible)
+            .collect();
+        let mut operations = Vec::new();
+
+        if edit.end > edit.start {
+            let ids = visible[edit.start..edit.end]
+                .iter()
+                .map(|&index| self.elements[index].id)
+                .collect();
+            operations.push(TextOperation::Delete { ids });
+        }
+        if !edit.text.is_empty() {
+            let after = edit
+                .start
+                .checked_sub(1)
+                .map(|index| self.elements[visible[index]].id);
+            operations.push(TextOperation::Insert {
+                id: CharId {
+                    clock: self.clock + 1,
+                    node,
+                },
+                after,
+                text: edit.text.clone(),
+            });
+        }
+        for operation in &operations {
+            self.integrate(operation);
+        }
+        operations
+    }
+
+    /// Applies an operation from any replica and returns the same change as
+    /// edits of the visible text, to be applied in order. Returns `None`, and
+    /// changes nothing, when the operation refers to a char not seen yet.
+    pub fn integrate(&mut self, operation: &TextOperation) -> Option<Vec<TextEdit>> {
+        match operation {
+            TextOperation::Insert { id, after, text } => {
+                if text.is_empty() || self.position(*id).is_some() {
+                    return Some(Vec::new());
+                }
+                let mut index = match after {
+                    Some(after) => self.position(*after)? + 1,
+                    None => 0,
+                };
+                // Concurrent inserts at the same place are ordered by id,
+                // newest first, skipping everything that came after them.
+                while index < self.elements.len() && self.elements[index].id > *id {
+                    index += 1;
+                }
+
+                let start = self.visible_before(index);
+                let inserted = text.chars().enumerate().map(|(offset, ch)| Element {
+                    id: CharId {
+                        clock: id.clock + offset as u64,
+                        node: id.node,
+                    },
+                    ch,
+                    visible: true,
+                });
+                self.elements.splice(index..index, inserted);
+                self.clock = self.clock.max(id.clock + text.chars().count() as u64 - 1);
+                Some(vec![TextEdit {
+                    start,
+                    end: start,
+                    text: text.clone(),
+                }])
+            }
+            TextOperation::Delete { ids } => {
+                let positions = ids
+                    .iter()
+                    .map(|id| self.position(*id))
+                    .collect::<Option<Vec<usize>>>()?;
+                let mut removed: Vec<(usize, usize)> = positions
+                    .into_iter()
+                    .filter(|&index| self.elements[index].visible)
+                    .map(|index| (index, self.visible_before(index)))
+                    .collect();
+                for (index, _) in &removed {
+                    self.elements[*index].visible = false;
+                }
+                removed.sort_unstable();
+                removed.dedup();
+
+                // Runs of neighbouring chars, last first so earlier offsets
+                // stay valid while the edits are applied in turn.
+                let mut edits: Vec<TextEdit> = Vec::new();
+                for (_, visible) in removed.into_iter().rev() {
+                    match edits.last_mut() {
+                        Some(edit) if edit.start == visible + 1 => edit.start = visible,
+                        _ => edits.push(TextEdit {
+                            start: visible,
+                            end: visible + 1,
+                            text: String::new(),
+                        }),
+                    }
+                }
+                Some(edits)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start: usize, end: usize, text: &str) -> TextEdit {
+        TextEdit {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        edits.iter().fold(text.to_string(), |text, edit| {
+            let mut chars: Vec<char> = text.chars().collect();
+            chars.splice(edit.start..edit.end, edit.text.chars());
+            chars.into_iter().collect()
+        })
+    }
+
+    #[test]
+    fn test_concurrent_edits_converge_in_any_order() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut origin = ReplicatedText::default();
+        let base = origin.local_edit(a, &edit(0, 0, "fn main() {}"));
+
+        let mut left = origin.clone();
+        let mut right = origin.clone();
+        let from_left = [
+            left.local_edit(a, &edit(11, 11, " serve(); ")),
+            left.local_edit(a, &edit(3, 7, "start")),
+        ]
+        .concat();
+        let from_right = [
+            right.local_edit(b, &edit(11, 11, " work(); ")),
+            right.local_edit(b, &edit(0, 0, "pub ")),
+        ]
+        .concat();
+
+        let mut left_text = left.text();
+        for operation in &from_right {
+            left_text = apply(&left_text, &left.integrate(operation).unwrap());
+        }
+        let mut right_text = right.text();
+        for operation in &from_left {
+            right_text = apply(&right_text, &right.integrate(operation).unwrap());
+        }
+
+        assert_eq!(left.text(), right.text());
+        assert_eq!(left_text, left.text());
+        assert_eq!(right_text, right.text());
+        assert!(left.text().starts_with("pub fn start() { "));
+        assert!(left.text().contains("serve();") && left.text().contains("work();"));
+
+        // Replaying what was already integrated changes nothing.
+        assert_eq!(left.integrate(&base[0]), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_delete_reports_runs_and_waits_for_unknown_chars() {
+        let node = Uuid::new_v4();
+        let mut text = ReplicatedText::default();
+        text.local_edit(node, &edit(0, 0, "abcdef"));
+        let mut copy = text.clone();
+
+        let operations = text.local_edit(node, &edit(1, 3, ""));
+        let operations = [operations, text.local_edit(node, &edit(2, 3, ""))].concat();
+        let edits: Vec<TextEdit> = operations
+            .iter()
+            .flat_map(|operation| copy.integrate(operation).unwrap())
+            .collect();
+        assert_eq!(apply("abcdef", &edits), "adf");
+        assert_eq!(copy.text(), "adf");
+
+        let unknown = TextOperation::Delete {
+            ids: vec![CharId {
+                clock: 99,
+                node: Uuid::new_v4(),
+            }],
+        };
+        assert_eq!(copy.integrate(&unknown), None);
+        assert_eq!(copy.text(), "adf");
+    }
+
+    #[test]
+    fn test_later_edits_converge_after_one_side_purges() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut left = ReplicatedText::default();
+        let mut operations = left.local_edit(a, &edit(0, 0, "abcdef"));
+        operations.extend(left.local_edit(a, &edit(1, 4, "")));
+        let mut right = ReplicatedText::default();
+        for operation in &operations {
+            right.integrate(operation).unwrap();
+        }
+        right.purge_tombstones();
+        assert_eq!((left.len(), right.len()), (6, 3));
+
+        // Both insert where the tombstones were before hearing of the other.
+        let from_left = left.local_edit(a, &edit(1, 1, "L"));
+        let from_right = right.local_edit(b, &edit(1, 1, "R"));
+        for operation in &from_right {
+            left.integrate(operation).unwrap();
+        }
+        for operation in &from_left {
+            right.integrate(operation).unwrap();
+        }
+        assert_eq!(left.text(), right.text());
+        assert_eq!(left.text().len(), 5);
+    }
+}
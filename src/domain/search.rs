@@ -0,0 +1,212 @@
+use regex::bytes::{NoExpand, Regex, RegexBuilder};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+}
+
+/// A single hit. `start`/`end` are char offsets (the same unit `set_slice` takes),
+/// `line` and `column` are zero-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+impl SearchQuery {
+    pub fn literal(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            is_regex: false,
+            case_sensitive: true,
+        }
+    }
+
+    pub fn regex(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            is_regex: true,
+            case_sensitive: true,
+        }
+    }
+
+    pub fn compile(&self) -> Result<Regex, regex::Error> {
+        let pattern = if self.is_regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+    }
+}
+
+struct Cursor {
+    byte: usize,
+    char: usize,
+    line: usize,
+    line_start_char: usize,
+}
+
+impl Cursor {
+    fn advance_to(&mut self, haystack: &[u8], byte: usize) {
+        for &b in &haystack[self.byte..byte] {
+            // Continuation bytes belong to the char that was already counted.
+            if b & 0xC0 != 0x80 {
+                self.char += 1;
+            }
+            if b == b'\n' {
+                self.line += 1;
+                self.line_start_char = self.char;
+            }
+        }
+        self.byte = byte;
+    }
+}
+
+pub fn find_matches(regex: &Regex, haystack: &[u8]) -> Vec<SearchMatch> {
+    let mut cursor = Cursor {
+        byte: 0,
+        char: 0,
+        line: 0,
+        line_start_char: 0,
+    };
+    let mut matches = Vec::new();
+
+    for m in regex.find_iter(haystack) {
+        cursor.advance_to(haystack, m.start());
+        let start = cursor.char;
+        let line = cursor.line;
+        let column = cursor.char - cursor.line_start_char;
+
+        cursor.advance_to(haystack, m.end());
+        matches.push(SearchMatch {
+            start,
+            end: cursor.char,
+            line,
+            column,
+            text: String::from_utf8_lossy(m.as_bytes()).to_string(),
+        });
+    }
+
+    matches
+}
+
+/// Returns the rewritten bytes and the number of replaced matches. Regex queries
+/// expand `$1`/`${name}` in `replacement`, literal queries insert it verbatim.
+/// Bytes outside the matches are kept as they are, even when not UTF-8.
+pub fn replace_all(
+    query: &SearchQuery,
+    regex: &Regex,
+    haystack: &[u8],
+    replacement: &str,
+) -> (Vec<u8>, usize) {
+    let count = regex.find_iter(haystack).count();
+    if count == 0 {
+        return (haystack.to_vec(), 0);
+    }
+
+    let replaced = if query.is_regex {
+        regex.replace_all(haystack, replacement.as_bytes())
+    } else {
+        regex.replace_all(haystack, NoExpand(replacement.as_bytes()))
+    };
+    (replaced.into_owned(), count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_query_escapes_pattern() {
+        let regex = SearchQuery::literal("a.b").compile().unwrap();
+        let matches = find_matches(&regex, b"axb a.b");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].start, 4);
+        assert_eq!(matches[0].end, 7);
+    }
+
+    #[test]
+    fn test_line_and_column() {
+        let regex = SearchQuery::literal("foo").compile().unwrap();
+        let matches = find_matches(&regex, b"foo\nbar foo\n\n  foo");
+        let positions: Vec<(usize, usize)> = matches.iter().map(|m| (m.line, m.column)).collect();
+        assert_eq!(positions, vec![(0, 0), (1, 4), (3, 2)]);
+    }
+
+    #[test]
+    fn test_offsets_are_in_chars() {
+        let regex = SearchQuery::literal("🦀").compile().unwrap();
+        let matches = find_matches(&regex, "世界 🦀\n🦀".as_bytes());
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].start, matches[0].end), (3, 4));
+        assert_eq!((matches[0].line, matches[0].column), (0, 3));
+        assert_eq!((matches[1].start, matches[1].end), (5, 6));
+        assert_eq!((matches[1].line, matches[1].column), (1, 0));
+    }
+
+    #[test]
+    fn test_regex_is_multi_line() {
+        let regex = SearchQuery::regex(r"^fn (\w+)").compile().unwrap();
+        let matches = find_matches(&regex, b"fn main() {}\n  fn nested() {}\nfn helper() {}");
+        let texts: Vec<&str> = matches.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, vec!["fn main", "fn helper"]);
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let mut query = SearchQuery::literal("hello");
+        query.case_sensitive = false;
+        let regex = query.compile().unwrap();
+        assert_eq!(find_matches(&regex, b"Hello HELLO hello").len(), 3);
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(SearchQuery::regex("(unclosed").compile().is_err());
+    }
+
+    #[test]
+    fn test_replace_all_regex_expands_groups() {
+        let query = SearchQuery::regex(r"(\w+)@(\w+)");
+        let regex = query.compile().unwrap();
+        let (text, count) = replace_all(&query, &regex, b"a@b c@d", "$2@$1");
+        assert_eq!(text, b"b@a d@c");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_replace_all_literal_does_not_expand() {
+        let query = SearchQuery::literal("x");
+        let regex = query.compile().unwrap();
+        let (text, count) = replace_all(&query, &regex, b"x + x", "$1");
+        assert_eq!(text, b"$1 + $1");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_replace_all_keeps_bytes_that_are_not_utf8() {
+        let query = SearchQuery::literal("foo");
+        let regex = query.compile().unwrap();
+        let (text, count) = replace_all(&query, &regex, b"caf\xe9 foo", "bar");
+        assert_eq!(text, b"caf\xe9 bar");
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_replace_all_no_match() {
+        let query = SearchQuery::literal("missing");
+        let regex = query.compile().unwrap();
+        let (text, count) = replace_all(&query, &regex, b"unchanged", "x");
+        assert_eq!(text, b"unchanged");
+        assert_eq!(count, 0);
+    }
+}
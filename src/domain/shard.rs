@@ -0,0 +1,117 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+pub const DEFAULT_VIRTUAL_NODES: usize = 64;
+
+fn point(key: &[u8]) -> u64 {
+    let digest = Sha256::digest(key);
+    u64::from_be_bytes(
+        digest[..8]
+            .try_into()
+            .expect("a SHA-256 digest has 32 bytes"),
+    )
+}
+
+/// Assigns every file to one node by consistent hashing, so adding or
+/// removing a node only moves the files that land on it. Nodes are named by
+/// their base URL and each sits at several points of the ring to even out
+/// the load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashRing {
+    virtual_nodes: usize,
+    ring: BTreeMap<u64, String>,
+}
+
+impl HashRing {
+    pub fn new<I: IntoIterator<Item = String>>(virtual_nodes: usize, nodes: I) -> Self {
+        let mut ring = Self {
+            virtual_nodes: virtual_nodes.max(1),
+            ring: BTreeMap::new(),
+        };
+        for node in nodes {
+            ring.add(node);
+        }
+        ring
+    }
+
+    pub fn add(&mut self, node: String) {
+        for index in 0..self.virtual_nodes {
+            self.ring
+                .insert(point(format!("{node}#{index}").as_bytes()), node.clone());
+        }
+    }
+
+    pub fn remove(&mut self, node: &str) {
+        self.ring.retain(|_, owner| owner != node);
+    }
+
+    pub fn nodes(&self) -> Vec<String> {
+        let mut nodes: Vec<String> = self.ring.values().cloned().collect();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+    }
+
+    /// The node owning `file_id`: the first one clockwise from its hash.
+    pub fn owner(&self, file_id: Uuid) -> Option<&str> {
+        let point = point(file_id.as_bytes());
+        self.ring
+            .range(point..)
+            .chain(&self.ring)
+            .next()
+            .map(|(_, node)| node.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|index| format!("http://127.0.0.1:{}", 3000 + index))
+            .collect()
+    }
+
+    #[test]
+    fn test_owner_is_stable_and_spread() {
+        let ring = HashRing::new(DEFAULT_VIRTUAL_NODES, nodes(3));
+        let same = HashRing::new(DEFAULT_VIRTUAL_NODES, nodes(3).into_iter().rev());
+        let ids: Vec<Uuid> = (0..600).map(|_| Uuid::new_v4()).collect();
+
+        for node in nodes(3) {
+            let owned = ids
+                .iter()
+                .filter(|id| ring.owner(**id) == Some(node.as_str()))
+                .count();
+            assert!(owned > 100, "{node} owns only {owned} of 600");
+        }
+        assert!(ids.iter().all(|id| ring.owner(*id) == same.owner(*id)));
+        assert_eq!(ring.nodes(), nodes(3));
+        assert_eq!(HashRing::new(8, Vec::new()).owner(ids[0]), None);
+    }
+
+    #[test]
+    fn test_adding_a_node_only_moves_files_to_it() {
+        let before = HashRing::new(DEFAULT_VIRTUAL_NODES, nodes(3));
+        let mut after = before.clone();
+        let added = nodes(4).pop().unwrap();
+        after.add(added.clone());
+
+        let ids: Vec<Uuid> = (0..400).map(|_| Uuid::new_v4()).collect();
+        let moved: Vec<&Uuid> = ids
+            .iter()
+            .filter(|id| before.owner(**id) != after.owner(**id))
+            .collect();
+        assert!(!moved.is_empty());
+        assert!(
+            moved
+                .iter()
+                .all(|id| after.owner(**id) == Some(added.as_str()))
+        );
+
+        after.remove(&added);
+        assert_eq!(after, before);
+    }
+}
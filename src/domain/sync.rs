@@ -0,0 +1,84 @@
+use crate::domain::text_diff::{TextEdit, transform_logs};
+use crate::domain::traits::merge::Mergable;
+
+/// Edits applied one after another on top of `base_revision`. The server's log
+/// is authoritative: it wins ties against a client's log made from the same base.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationLog {
+    pub base_revision: u64,
+    pub edits: Vec<TextEdit>,
+    pub authoritative: bool,
+}
+
+impl Mergable for OperationLog {
+    /// Combines two logs made concurrently from the same base into one that
+    /// applies to that base: this log's edits, then `other`'s rebased over them.
+    /// Ties go to the authoritative log, or to `self` when that settles nothing,
+    /// so merging in either order ends in the same text.
+    fn merge(&self, other: Self) -> Self {
+        let rebased = if other.authoritative && !self.authoritative {
+            transform_logs(&self.edits, &other.edits).1
+        } else {
+            transform_logs(&other.edits, &self.edits).0
+        };
+        OperationLog {
+            base_revision: self.base_revision,
+            edits: self.edits.iter().cloned().chain(rebased).collect(),
+            authoritative: self.authoritative || other.authoritative,
+        }
+    }
+}
+
+impl OperationLog {
+    /// The edits that come after the first `count`, i.e. what a merge added.
+    pub fn after(&self, count: usize) -> &[TextEdit] {
+        &self.edits[count.min(self.edits.len())..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start: usize, end: usize, text: &str) -> TextEdit {
+        TextEdit {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    fn apply(text: &str, log: &OperationLog) -> String {
+        log.edits.iter().fold(text.to_string(), |text, edit| {
+            let mut chars: Vec<char> = text.chars().collect();
+            chars.splice(edit.start..edit.end, edit.text.chars());
+            chars.into_iter().collect()
+        })
+    }
+
+    #[test]
+    fn test_merge_converges_in_either_order() {
+        let base = "fn main() {}";
+        let server = OperationLog {
+            base_revision: 4,
+            edits: vec![edit(11, 11, " serve(); "), edit(0, 0, "pub ")],
+            authoritative: true,
+        };
+        let client = OperationLog {
+            base_revision: 4,
+            edits: vec![edit(11, 11, " work(); "), edit(3, 7, "start")],
+            authoritative: false,
+        };
+
+        let on_server = server.merge(client.clone());
+        let on_client = client.merge(server.clone());
+        assert_eq!(on_server.edits.len(), 4);
+        assert_eq!(apply(base, &on_server), apply(base, &on_client));
+        assert_eq!(
+            apply(base, &on_server),
+            "pub fn start() { serve();  work(); }"
+        );
+        assert!(on_server.authoritative && on_client.authoritative);
+        assert_eq!(on_client.after(2).len(), 2);
+    }
+}
@@ -0,0 +1,203 @@
+/// A single replacement of the char range `start..end` of the old text with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Describes how to turn `old` into `new` as one edit covering everything between
+/// their common prefix and common suffix. Returns `None` when they are equal.
+pub fn diff(old: &str, new: &str) -> Option<TextEdit> {
+    if old == new {
+        return None;
+    }
+    let old_chars: Vec<char> = old.chars().collect();
+    let new_chars: Vec<char> = new.chars().collect();
+
+    let prefix = old_chars
+        .iter()
+        .zip(&new_chars)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let max_suffix = old_chars.len().min(new_chars.len()) - prefix;
+    let suffix = old_chars
+        .iter()
+        .rev()
+        .zip(new_chars.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    Some(TextEdit {
+        start: prefix,
+        end: old_chars.len() - suffix,
+        text: new_chars[prefix..new_chars.len() - suffix].iter().collect(),
+    })
+}
+
+impl TextEdit {
+    /// Moves this edit past `other`, both having been made against the same
+    /// text, so that it applies after `other`. Where the ranges overlap, an
+    /// edit whose range contains the other's wins and the other is dropped;
+    /// on equal ranges, or insertions at the same point, the `first` one wins.
+    pub fn transform(&self, other: &TextEdit, first: bool) -> TextEdit {
+        let inserted = other.text.chars().count();
+        let shift = |position: usize| position + inserted - (other.end - other.start);
+        let moved = |start: usize, end: usize| TextEdit {
+            start,
+            end,
+            text: self.text.clone(),
+        };
+        let both_insert = self.start == self.end && other.start == other.end;
+        let covers = self.start <= other.start && other.end <= self.end;
+        let covered = other.start <= self.start && self.end <= other.end;
+
+        if self.end < other.start || (self.end == other.start && (first || !both_insert)) {
+            self.clone()
+        } else if self.start >= other.end {
+            moved(shift(self.start), shift(self.end))
+        } else if covers && (first || !covered) {
+            moved(self.start, shift(self.end))
+        } else if covered {
+            TextEdit {
+                start: other.start,
+                end: other.start,
+                text: String::new(),
+            }
+        } else if self.start < other.start {
+            moved(self.start, other.start)
+        } else {
+            moved(other.start + inserted, shift(self.end))
+        }
+    }
+}
+
+/// Transforms two logs, each applied one edit after another to the same text,
+/// past each other: `local` so it applies after `remote`, and `remote` so it
+/// applies after `local`. `remote` wins every tie, so both orders converge.
+pub fn transform_logs(local: &[TextEdit], remote: &[TextEdit]) -> (Vec<TextEdit>, Vec<TextEdit>) {
+    let mut remote = remote.to_vec();
+    let local = local
+        .iter()
+        .map(|edit| {
+            let mut edit = edit.clone();
+            for remote_edit in remote.iter_mut() {
+                let rebased = edit.transform(remote_edit, false);
+                *remote_edit = remote_edit.transform(&edit, true);
+                edit = rebased;
+            }
+            edit
+        })
+        .collect();
+    (local, remote)
+}
+
+/// Rebases `local`, edits applied one after another to some text, onto
+/// `remote`, edits made to the same text meanwhile. The result applies after
+/// `remote`, which wins every tie.
+pub fn rebase(local: &[TextEdit], remote: &[TextEdit]) -> Vec<TextEdit> {
+    transform_logs(local, remote).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(old: &str, edit: &TextEdit) -> String {
+        let mut chars: Vec<char> = old.chars().collect();
+        chars.splice(edit.start..edit.end, edit.text.chars());
+        chars.into_iter().collect()
+    }
+
+    #[test]
+    fn test_equal_texts() {
+        assert_eq!(diff("same", "same"), None);
+    }
+
+    #[test]
+    fn test_insertion() {
+        let edit = diff("fn main() {}", "fn main() { run(); }").unwrap();
+        assert_eq!(
+            edit,
+            TextEdit {
+                start: 11,
+                end: 11,
+                text: " run(); ".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_deletion_and_truncation() {
+        let edit = diff("Hello, World!", "Hello").unwrap();
+        assert_eq!((edit.start, edit.end, edit.text.as_str()), (5, 13, ""));
+
+        let edit = diff("content", "").unwrap();
+        assert_eq!((edit.start, edit.end, edit.text.as_str()), (0, 7, ""));
+    }
+
+    #[test]
+    fn test_repeated_chars_do_not_overlap() {
+        let edit = diff("aaa", "aaaa").unwrap();
+        assert_eq!(apply("aaa", &edit), "aaaa");
+        assert_eq!((edit.start, edit.end), (3, 3));
+    }
+
+    #[test]
+    fn test_unicode_offsets() {
+        let old = "let s = \"世界\";";
+        let new = "let s = \"🦀\";";
+        let edit = diff(old, new).unwrap();
+        assert_eq!((edit.start, edit.end, edit.text.as_str()), (9, 11, "🦀"));
+        assert_eq!(apply(old, &edit), new);
+    }
+
+    fn edit(start: usize, end: usize, text: &str) -> TextEdit {
+        TextEdit {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    fn apply_all(old: &str, edits: &[TextEdit]) -> String {
+        edits
+            .iter()
+            .fold(old.to_string(), |text, edit| apply(&text, edit))
+    }
+
+    #[test]
+    fn test_transform_converges() {
+        let base = "fn main() { run(); }";
+        let cases = [
+            (edit(0, 2, "pub fn"), edit(12, 18, "go();")),
+            (edit(12, 18, ""), edit(15, 15, "_all")),
+            (edit(3, 7, "start"), edit(0, 20, "")),
+            (edit(11, 11, " a();"), edit(11, 11, " b();")),
+            (edit(3, 9, "run"), edit(3, 9, "walk")),
+            (edit(0, 5, "pub "), edit(3, 11, "start() ")),
+            (edit(9, 9, ";"), edit(3, 9, "x")),
+        ];
+        for (local, remote) in cases {
+            for first in [false, true] {
+                let left = apply_all(base, &[remote.clone(), local.transform(&remote, first)]);
+                let right = apply_all(base, &[local.clone(), remote.transform(&local, !first)]);
+                assert_eq!(left, right, "{local:?} against {remote:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_rebase_puts_remote_insertions_first() {
+        let base = "let x = 1;";
+        let local = [edit(8, 9, "2"), edit(10, 10, " // two")];
+        let remote = [edit(10, 10, "\n"), edit(0, 0, "// config\n")];
+
+        let rebased = rebase(&local, &remote);
+        assert_eq!(
+            apply_all(&apply_all(base, &remote), &rebased),
+            "// config\nlet x = 2;\n // two"
+        );
+    }
+}
@@ -3,12 +3,41 @@ pub trait DynemicFileCreateDelete {
     fn delete_file(&self) -> Result<(), std::io::Error>;
 }
 
+/// Sources that live at a path and can move to another one, keeping what
+/// they hold. A move never replaces a file already at `to`.
+pub trait DynemicFileMove {
+    fn move_file(&mut self, to: &std::path::Path) -> Result<(), std::io::Error>;
+}
+
 pub trait DynemicFileRead {
     fn get_slice(&self, start: usize, end: usize) -> String;
     fn get_content(&self) -> String;
+
+    /// Raw view of the backing bytes, for sources that can expose them without copying.
+    fn as_bytes(&self) -> Option<&[u8]> {
+        None
+    }
 }
 
 pub trait DynemicFileWrite {
     fn set_slice(&mut self, start: usize, end: usize, content: String);
     fn set_content(&mut self, content: String);
 }
+
+/// Sources that can let go of what they hold in memory and pick it up again
+/// from where they live, so a cache can bound how many stay open.
+pub trait DynemicFileResidency {
+    fn is_loaded(&self) -> bool;
+    /// The bytes held in memory while loaded.
+    fn resident_size(&self) -> usize;
+    fn unload(&mut self) -> std::io::Result<()>;
+    fn load(&mut self) -> std::io::Result<()>;
+}
+
+/// Sources that can hand out a read-only view of their current content which
+/// later writes leave untouched.
+pub trait DynemicFileSnapshot {
+    type Snapshot: DynemicFileRead + Clone + Send + Sync;
+
+    fn snapshot(&self) -> Self::Snapshot;
+}
@@ -0,0 +1,12 @@
+use crate::domain::events::DomainEvent;
+
+/// Receives every published event in publish order. Returning an error makes
+/// the bus deliver the same event again, up to its attempt limit, so handlers
+/// must tolerate repeats.
+pub trait EventSubscriber: Send {
+    fn handle(
+        &mut self,
+        sequence: u64,
+        event: &DomainEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
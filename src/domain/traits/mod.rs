@@ -1,2 +1,3 @@
 pub mod dyn_file;
+pub mod event_subscriber;
 pub mod merge;
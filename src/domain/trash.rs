@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+/// A deleted file waiting in the trash: its backing file now lives at
+/// `trashed_path` and goes back to `path` on restore, at the same revision.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashedFile {
+    pub file_id: Uuid,
+    pub name: String,
+    pub revision: u64,
+    pub path: PathBuf,
+    pub trashed_path: PathBuf,
+    pub deleted_at: SystemTime,
+}
+
+impl TrashedFile {
+    /// Whether it has been in the trash for longer than `retention` at `now`.
+    pub fn expired(&self, retention: Duration, now: SystemTime) -> bool {
+        now.duration_since(self.deleted_at)
+            .is_ok_and(|age| age > retention)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired() {
+        let deleted_at = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let trashed = TrashedFile {
+            file_id: Uuid::new_v4(),
+            name: "main.rs".to_string(),
+            revision: 3,
+            path: PathBuf::from("/tmp/main.rs"),
+            trashed_path: PathBuf::from("/var/lib/colab-engine/trash/main.rs"),
+            deleted_at,
+        };
+        let retention = Duration::from_secs(60);
+        assert!(!trashed.expired(retention, deleted_at + retention));
+        assert!(trashed.expired(retention, deleted_at + Duration::from_secs(61)));
+        // A clock set back never expires anything.
+        assert!(!trashed.expired(Duration::ZERO, SystemTime::UNIX_EPOCH));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::domain::events::DomainEvent;
+use serde::Serialize;
+use uuid::Uuid;
+
+pub const EVENT_KINDS: [&str; 4] = ["created", "updated", "deleted", "renamed"];
+
+/// `Workspace` covers every file this process publishes events for. In a
+/// cluster each node only publishes for the edits it takes, so a webhook
+/// registered on one node hears nothing about files owned by another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookScope {
+    Workspace,
+    File(Uuid),
+}
+
+/// An HTTP endpoint that is told about events in its scope. An empty `events`
+/// filter means every kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+    pub scope: WebhookScope,
+    pub events: Vec<String>,
+}
+
+impl Webhook {
+    pub fn matches(&self, event: &DomainEvent) -> bool {
+        let in_scope = match self.scope {
+            WebhookScope::Workspace => true,
+            WebhookScope::File(file_id) => event.file_id() == file_id,
+        };
+        in_scope && (self.events.is_empty() || self.events.iter().any(|k| k == event.name()))
+    }
+}
+
+/// The JSON body POSTed to a webhook. Retries resend the same bytes, so
+/// receivers can drop repeats by `delivery_id`.
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload<'a> {
+    pub delivery_id: Uuid,
+    pub webhook_id: Uuid,
+    pub sequence: u64,
+    pub event: &'a DomainEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    Delivered(u16),
+    Rejected(u16),
+    Failed(String),
+}
+
+impl DeliveryOutcome {
+    pub fn is_delivered(&self) -> bool {
+        matches!(self, DeliveryOutcome::Delivered(_))
+    }
+}
+
+/// One POST of one event to one webhook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryAttempt {
+    pub delivery_id: Uuid,
+    pub webhook_id: Uuid,
+    pub sequence: u64,
+    pub event: String,
+    pub attempt: u32,
+    pub outcome: DeliveryOutcome,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(scope: WebhookScope, events: &[&str]) -> Webhook {
+        Webhook {
+            id: Uuid::new_v4(),
+            url: "http://127.0.0.1:1/hook".to_string(),
+            secret: "s3cret".to_string(),
+            scope,
+            events: events.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_matches_scope_and_filter() {
+        let file_id = Uuid::new_v4();
+        let created = DomainEvent::FileCreated {
+            file_id,
+            name: "main.rs".to_string(),
+            revision: 0,
+        };
+        let deleted = DomainEvent::FileDeleted {
+            file_id: Uuid::new_v4(),
+            revision: 1,
+        };
+
+        assert!(webhook(WebhookScope::Workspace, &[]).matches(&created));
+        assert!(webhook(WebhookScope::Workspace, &[]).matches(&deleted));
+        assert!(webhook(WebhookScope::File(file_id), &[]).matches(&created));
+        assert!(!webhook(WebhookScope::File(file_id), &[]).matches(&deleted));
+        assert!(webhook(WebhookScope::Workspace, &["deleted"]).matches(&deleted));
+        assert!(!webhook(WebhookScope::Workspace, &["deleted"]).matches(&created));
+    }
+}
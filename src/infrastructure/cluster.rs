@@ -0,0 +1,98 @@
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::domain::shard::{DEFAULT_VIRTUAL_NODES, HashRing};
+use crate::infrastructure::http::auth::ClusterToken;
+use crate::infrastructure::replication::ReplicationNode;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex, RwLock};
+use uuid::Uuid;
+
+/// This node's view of the cluster: which node owns each file. Only the owner
+/// takes edits to a file, so concurrent edits of one document are ordered in
+/// one place; the others redirect to it and keep a replica.
+#[derive(Clone)]
+pub struct Cluster {
+    local: String,
+    ring: Arc<RwLock<HashRing>>,
+    files: Arc<Mutex<CodeFileUsecasesImpl>>,
+    replication: ReplicationNode,
+}
+
+impl Cluster {
+    /// `local` is the base URL other nodes reach this one at; `members`
+    /// should include it.
+    pub fn new(
+        local: String,
+        members: Vec<String>,
+        files: Arc<Mutex<CodeFileUsecasesImpl>>,
+        replication: ReplicationNode,
+    ) -> Self {
+        Self {
+            local,
+            ring: Arc::new(RwLock::new(HashRing::new(DEFAULT_VIRTUAL_NODES, members))),
+            files,
+            replication,
+        }
+    }
+
+    pub fn local(&self) -> &str {
+        &self.local
+    }
+
+    pub fn members(&self) -> Vec<String> {
+        self.ring.read().unwrap().nodes()
+    }
+
+    /// The owner of `file_id`; this node when the ring is empty.
+    pub fn owner(&self, file_id: Uuid) -> String {
+        self.ring
+            .read()
+            .unwrap()
+            .owner(file_id)
+            .unwrap_or(&self.local)
+            .to_string()
+    }
+
+    pub fn is_owner(&self, file_id: Uuid) -> bool {
+        self.owner(file_id) == self.local
+    }
+
+    pub fn token(&self) -> Option<ClusterToken> {
+        self.replication.token()
+    }
+
+    /// Replaces the membership, after a node joined or left. Each new owner is
+    /// sent every operation known here first, then requests for the files this
+    /// node gives up are redirected, and whatever was edited in between
+    /// follows. A node leaving is given the membership without itself and
+    /// hands off all its files. Returns the files handed off.
+    pub fn set_members(&self, members: Vec<String>) -> Result<Vec<Uuid>, ApplicationError> {
+        let next = HashRing::new(DEFAULT_VIRTUAL_NODES, members);
+        let previous = self.ring.read().unwrap().clone();
+
+        let file_ids = self.files.lock().unwrap().repository.ids()?;
+        let mut handed_off = Vec::new();
+        let mut new_owners = BTreeSet::new();
+        for file_id in file_ids {
+            let was_local = previous
+                .owner(file_id)
+                .is_none_or(|owner| owner == self.local);
+            match next.owner(file_id) {
+                Some(owner) if was_local && owner != self.local => {
+                    handed_off.push(file_id);
+                    new_owners.insert(owner.to_string());
+                }
+                _ => {},
+            }
+        }
+
+        for owner in &new_owners {
+            self.replication.exchange(owner)?;
+        }
+        *self.ring.write().unwrap() = next;
+        for owner in &new_owners {
+            self.replication.exchange(owner)?;
+        }
+        Ok(handed_off)
+    }
+}
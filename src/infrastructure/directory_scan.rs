@@ -0,0 +1,174 @@
+use ignore::WalkBuilder;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+// Same heuristic as git: a NUL byte near the start means the file is binary.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    Binary,
+    TooLarge(u64),
+    Unreadable(String),
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::Binary => write!(f, "binary file"),
+            SkipReason::TooLarge(size) => write!(f, "file too large ({size} bytes)"),
+            SkipReason::Unreadable(error) => write!(f, "unreadable: {error}"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DirectoryScan {
+    pub files: Vec<PathBuf>,
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+}
+
+/// Lists the text files under `root`, honouring `.gitignore`, `.ignore` and
+/// `.git/info/exclude` even when `root` is not itself a git repository.
+pub fn scan_directory(root: &Path, max_file_size: u64) -> std::io::Result<DirectoryScan> {
+    if !root.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotADirectory,
+            format!("{} is not a directory", root.display()),
+        ));
+    }
+
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .sort_by_file_path(Path::cmp)
+        .build();
+
+    let mut scan = DirectoryScan::default();
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let path = match &e {
+                    ignore::Error::WithPath { path, .. } => path.clone(),
+                    _ => root.to_path_buf(),
+                };
+                scan.skipped
+                    .push((path, SkipReason::Unreadable(e.to_string())));
+                continue;
+            }
+        };
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+
+        let path = entry.into_path();
+        match classify(&path, max_file_size) {
+            Ok(None) => scan.files.push(path),
+            Ok(Some(reason)) => scan.skipped.push((path, reason)),
+            Err(e) => scan
+                .skipped
+                .push((path, SkipReason::Unreadable(e.to_string()))),
+        }
+    }
+    Ok(scan)
+}
+
+fn classify(path: &Path, max_file_size: u64) -> std::io::Result<Option<SkipReason>> {
+    let size = std::fs::metadata(path)?.len();
+    if size > max_file_size {
+        return Ok(Some(SkipReason::TooLarge(size)));
+    }
+
+    let mut head = Vec::with_capacity(BINARY_SNIFF_LEN);
+    File::open(path)?
+        .take(BINARY_SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    if head.contains(&0) {
+        return Ok(Some(SkipReason::Binary));
+    }
+    // A multi-byte character may be cut off at the end of the sniffed prefix.
+    match std::str::from_utf8(&head) {
+        Ok(_) => Ok(None),
+        Err(e) if e.error_len().is_none() && head.len() == BINARY_SNIFF_LEN => Ok(None),
+        Err(_) => Ok(Some(SkipReason::Binary)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn relative(root: &Path, paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.strip_prefix(root).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_respects_gitignore() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("README.md"), "# Project").unwrap();
+        fs::write(root.join("debug.log"), "noise").unwrap();
+        fs::write(root.join("target/debug/out"), "artifact").unwrap();
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+
+        let scan = scan_directory(root, DEFAULT_MAX_FILE_SIZE).unwrap();
+        assert_eq!(
+            relative(root, &scan.files),
+            vec![".gitignore", "README.md", "src/main.rs"]
+        );
+        assert!(scan.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_scan_skips_binary_and_oversized_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("image.png"), [0x89, b'P', b'N', b'G', 0, 0, 1]).unwrap();
+        fs::write(root.join("latin1.txt"), [b'c', b'a', b'f', 0xe9]).unwrap();
+        fs::write(root.join("big.txt"), "x".repeat(100)).unwrap();
+        fs::write(root.join("small.txt"), "日本語").unwrap();
+
+        let scan = scan_directory(root, 50).unwrap();
+        assert_eq!(relative(root, &scan.files), vec!["small.txt"]);
+
+        let skipped: Vec<(String, SkipReason)> = scan
+            .skipped
+            .into_iter()
+            .map(|(path, reason)| (relative(root, &[path]).remove(0), reason))
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![
+                ("big.txt".to_string(), SkipReason::TooLarge(100)),
+                ("image.png".to_string(), SkipReason::Binary),
+                ("latin1.txt".to_string(), SkipReason::Binary),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_requires_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("file.txt");
+        fs::write(&file, "text").unwrap();
+        assert!(scan_directory(&file, DEFAULT_MAX_FILE_SIZE).is_err());
+    }
+}
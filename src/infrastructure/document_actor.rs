@@ -0,0 +1,365 @@
+use crate::application::dto::code_file::{
+    CodeFileResponse, CreateCodeFileRequest, UpdateCodeRequest,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::async_code_file_usecases::{
+    AsyncCodeFileUsecases, AsyncCodeFileUsecasesImpl,
+};
+use crate::application::usecases::code_file_usecases::{CodeFileUsecases, CodeFileUsecasesImpl};
+use crate::domain::traits::dyn_file::DynemicFileResidency;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Job = Box<dyn FnOnce(&mut CodeFileUsecasesImpl) + Send>;
+
+struct Mailbox {
+    actor: u64,
+    sender: UnboundedSender<Job>,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_actor: u64,
+    mailboxes: HashMap<Uuid, Mailbox>,
+}
+
+impl Registry {
+    fn retire(&mut self, file_id: Uuid, actor: u64) {
+        if self
+            .mailboxes
+            .get(&file_id)
+            .is_some_and(|mailbox| mailbox.actor == actor)
+        {
+            self.mailboxes.remove(&file_id);
+        }
+    }
+}
+
+/// Runs every open document in a task of its own, which applies the writes
+/// sent to it one at a time in arrival order, each under the file's writer
+/// lock, so no lock is held across files. The revisions they produce go out
+/// to subscribers on the event bus as they are applied. A document left alone
+/// for the idle timeout has its file unloaded and its task stopped; the next
+/// write starts a task again, which loads the file. Reads don't queue.
+///
+/// A task orders the writes queued on it but doesn't own its file: replication,
+/// checkpoints and the other usecases write to it without queueing, and the
+/// writer lock is what keeps those apart from the queued writes.
+#[derive(Clone)]
+pub struct DocumentActors {
+    files: AsyncCodeFileUsecasesImpl,
+    idle_timeout: Duration,
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl DocumentActors {
+    pub fn new(files: AsyncCodeFileUsecasesImpl, idle_timeout: Duration) -> Self {
+        Self {
+            files,
+            idle_timeout,
+            registry: Arc::new(Mutex::new(Registry::default())),
+        }
+    }
+
+    /// The documents that have a task right now.
+    pub fn loaded(&self) -> Vec<Uuid> {
+        self.registry
+            .lock()
+            .unwrap()
+            .mailboxes
+            .keys()
+            .copied()
+            .collect()
+    }
+
+    /// Runs `task` on the blocking pool, side by side with the documents.
+    pub async fn read<T, F>(&self, task: F) -> Result<T, ApplicationError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&CodeFileUsecasesImpl) -> Result<T, ApplicationError> + Send + 'static,
+    {
+        self.files.read(task).await
+    }
+
+    /// Queues `task` on the document of `file_id` right away, so it runs
+    /// after the writes queued before it; the future waits for its result.
+    pub fn write<T, F>(
+        &self,
+        file_id: Uuid,
+        task: F,
+    ) -> impl Future<Output = Result<T, ApplicationError>> + use<T, F>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut CodeFileUsecasesImpl) -> Result<T, ApplicationError> + Send + 'static,
+    {
+        let (reply, response) = oneshot::channel();
+        self.deliver(
+            file_id,
+            Box::new(move |files| {
+                let _ = reply.send(task(files));
+            }),
+        );
+        async move {
+            response.await.map_err(|_| {
+                ApplicationError::IoError(std::io::Error::other(
+                    "the document stopped unexpectedly",
+                ))
+            })?
+        }
+    }
+
+    // Sends are made under the registry lock, which an idle document also
+    // takes to check its queue is empty before leaving, so nothing is lost.
+    fn deliver(&self, file_id: Uuid, job: Job) {
+        let mut registry = self.registry.lock().unwrap();
+        let job = match registry.mailboxes.get(&file_id) {
+            Some(mailbox) => match mailbox.sender.send(job) {
+                Ok(()) => return,
+                // The task died without retiring; start another.
+                Err(unsent) => unsent.0,
+            },
+            None => job,
+        };
+
+        registry.next_actor += 1;
+        let actor = registry.next_actor;
+        let (sender, receiver) = unbounded_channel();
+        sender
+            .send(job)
+            .unwrap_or_else(|_| unreachable!("the receiver is still here"));
+        registry
+            .mailboxes
+            .insert(file_id, Mailbox { actor, sender });
+
+        let document = DocumentActor {
+            file_id,
+            actor,
+            files: self.files.clone(),
+            registry: Arc::clone(&self.registry),
+        };
+        tokio::spawn(document.run(receiver, self.idle_timeout));
+    }
+}
+
+struct DocumentActor {
+    file_id: Uuid,
+    actor: u64,
+    files: AsyncCodeFileUsecasesImpl,
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl DocumentActor {
+    async fn run(self, mut jobs: UnboundedReceiver<Job>, idle_timeout: Duration) {
+        loop {
+            match tokio::time::timeout(idle_timeout, jobs.recv()).await {
+                Ok(Some(job)) => {
+                    // A job that panics drops its reply, which tells the caller.
+                    let _ = self
+                        .files
+                        .write(self.file_id, move |files| {
+                            job(files);
+                            Ok(())
+                        })
+                        .await;
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    let mut registry = self.registry.lock().unwrap();
+                    if jobs.is_empty() {
+                        registry.retire(self.file_id, self.actor);
+                        break;
+                    }
+                }
+            }
+        }
+        self.registry
+            .lock()
+            .unwrap()
+            .retire(self.file_id, self.actor);
+
+        // A deleted document has nothing left to unload.
+        let file_id = self.file_id;
+        let _ = self
+            .files
+            .write(file_id, move |files| {
+                let mut code_file = files.repository.find_by_id(file_id)?;
+                code_file
+                    .source
+                    .unload()
+                    .map_err(ApplicationError::IoError)?;
+                files.repository.update(code_file)
+            })
+            .await;
+    }
+}
+
+#[async_trait]
+impl AsyncCodeFileUsecases for DocumentActors {
+    async fn create_code_file(
+        &self,
+        request: CreateCodeFileRequest,
+    ) -> Result<CodeFileResponse, ApplicationError> {
+        self.files.create_code_file(request).await
+    }
+
+    async fn update_code_file(&self, request: UpdateCodeRequest) -> Result<(), ApplicationError> {
+        self.write(request.id, |files| files.update_code_file(request))
+            .await
+    }
+
+    async fn get_code_file(&self, file_id: Uuid) -> Result<CodeFileResponse, ApplicationError> {
+        self.files.get_code_file(file_id).await
+    }
+
+    async fn delete_code_file(&self, file_id: Uuid) -> Result<(), ApplicationError> {
+        self.write(file_id, move |files| files.delete_code_file(file_id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::events::DomainEvent;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+
+    fn actors(idle_timeout: Duration) -> (CodeFileUsecasesImpl, DocumentActors) {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let files = CodeFileUsecasesImpl::new(repository);
+        let actors = DocumentActors::new(AsyncCodeFileUsecasesImpl::new(&files), idle_timeout);
+        (files, actors)
+    }
+
+    async fn create(actors: &DocumentActors) -> Uuid {
+        actors
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("actor_{}.txt", Uuid::new_v4()),
+            })
+            .await
+            .unwrap()
+            .id
+    }
+
+    fn insert(id: Uuid, start: u64, text: &str) -> UpdateCodeRequest {
+        UpdateCodeRequest {
+            id,
+            start,
+            end: start,
+            content: text.to_string(),
+            author: "ada".to_string(),
+        }
+    }
+
+    fn edited_revisions(
+        events: &mut UnboundedReceiver<crate::infrastructure::event_bus::PublishedEvent>,
+    ) -> Vec<u64> {
+        let mut revisions = Vec::new();
+        while let Ok(published) = events.try_recv() {
+            if let DomainEvent::FileEdited { revision, .. } = published.event {
+                revisions.push(revision);
+            }
+        }
+        revisions
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_writes_are_applied_in_arrival_order_and_fanned_out() {
+        let (files, actors) = actors(DEFAULT_IDLE_TIMEOUT);
+        let id = create(&actors).await;
+        let mut events = files.events.subscribe();
+
+        // Each write is queued as it is made, before any of them is awaited.
+        let writes: Vec<_> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|text| {
+                let request = insert(id, 0, text);
+                tokio::spawn(actors.write(id, |files| files.apply_edit(request)))
+            })
+            .collect();
+        for write in writes {
+            write.await.unwrap().unwrap();
+        }
+
+        let file = actors.get_code_file(id).await.unwrap();
+        assert_eq!(
+            (file.revision, file.viewport.content.as_str()),
+            (5, "edcba")
+        );
+        assert_eq!(edited_revisions(&mut events), (1..=5).collect::<Vec<u64>>());
+
+        actors.delete_code_file(id).await.unwrap();
+        match actors.get_code_file(id).await {
+            Err(ApplicationError::FileNotFound(_)) => {},
+            _ => panic!("Expected FileNotFound error"),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_writes_get_consecutive_revisions() {
+        let (files, actors) = actors(DEFAULT_IDLE_TIMEOUT);
+        let id = create(&actors).await;
+        let mut events = files.events.subscribe();
+
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let actors = actors.clone();
+                tokio::spawn(async move { actors.update_code_file(insert(id, 0, "x")).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let file = actors.get_code_file(id).await.unwrap();
+        assert_eq!((file.revision, file.viewport.content), (20, "x".repeat(20)));
+        assert_eq!(
+            edited_revisions(&mut events),
+            (1..=20).collect::<Vec<u64>>()
+        );
+        assert_eq!(actors.loaded(), vec![id]);
+
+        actors.delete_code_file(id).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_idle_documents_are_unloaded_and_reloaded() {
+        let (files, actors) = actors(Duration::from_millis(30));
+        let id = create(&actors).await;
+        let is_loaded =
+            |files: &CodeFileUsecasesImpl| files.repository.list().unwrap()[0].source.is_loaded();
+        actors
+            .update_code_file(insert(id, 0, "kept"))
+            .await
+            .unwrap();
+        assert_eq!(actors.loaded(), vec![id]);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(actors.loaded().is_empty());
+        assert!(!is_loaded(&files));
+
+        let file = actors.get_code_file(id).await.unwrap();
+        assert_eq!((file.revision, file.viewport.content.as_str()), (1, "kept"));
+
+        // Writes keep their order and revisions across unloads.
+        for (index, word) in ["a", "b", "c", "d", "e"].iter().enumerate() {
+            tokio::time::sleep(Duration::from_millis(index as u64 * 15)).await;
+            actors.update_code_file(insert(id, 4, word)).await.unwrap();
+        }
+        assert!(is_loaded(&files));
+        let file = actors.get_code_file(id).await.unwrap();
+        assert_eq!(
+            (file.revision, file.viewport.content.as_str()),
+            (6, "keptedcba")
+        );
+
+        actors.delete_code_file(id).await.unwrap();
+    }
+}
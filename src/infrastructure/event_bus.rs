@@ -0,0 +1,340 @@
+use crate::domain::events::DomainEvent;
+use crate::domain::traits::event_subscriber::EventSubscriber;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1024;
+pub const DEFAULT_MAX_ATTEMPTS: usize = 10;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// An event together with its position in the bus, which increases by one with
+/// every publish across all files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedEvent {
+    pub sequence: u64,
+    pub event: DomainEvent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriberId(u64);
+
+/// An event a registered subscriber still failed to handle on its last attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub subscriber: SubscriberId,
+    pub event: PublishedEvent,
+    pub error: String,
+}
+
+struct Subscriber {
+    id: SubscriberId,
+    sender: UnboundedSender<PublishedEvent>,
+}
+
+struct Inner {
+    next_sequence: u64,
+    next_subscriber: u64,
+    history: VecDeque<PublishedEvent>,
+    subscribers: Vec<Subscriber>,
+    dead_letters: VecDeque<DeadLetter>,
+}
+
+/// In-process publish/subscribe. Clones share the same subscribers and history,
+/// and the most recent events are kept so that late subscribers can catch up.
+///
+/// Every subscriber has its own unbounded queue, so a slow one never makes the
+/// publisher wait or another subscriber miss an event.
+#[derive(Clone)]
+pub struct EventBus {
+    inner: Arc<Mutex<Inner>>,
+    history_capacity: usize,
+    max_attempts: usize,
+}
+
+impl EventBus {
+    pub fn new(history_capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                next_sequence: 1,
+                next_subscriber: 1,
+                history: VecDeque::new(),
+                subscribers: Vec::new(),
+                dead_letters: VecDeque::new(),
+            })),
+            history_capacity,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+
+    /// How many times a registered subscriber is handed an event before it is
+    /// given up on; at least once.
+    pub fn with_max_attempts(mut self, attempts: usize) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    pub fn publish(&self, event: DomainEvent) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        let published = PublishedEvent {
+            sequence: inner.next_sequence,
+            event,
+        };
+        inner.next_sequence += 1;
+
+        if self.history_capacity > 0 {
+            if inner.history.len() == self.history_capacity {
+                inner.history.pop_front();
+            }
+            inner.history.push_back(published.clone());
+        }
+        inner
+            .subscribers
+            .retain(|subscriber| subscriber.sender.send(published.clone()).is_ok());
+        published.sequence
+    }
+
+    /// The events registered subscribers gave up on, oldest first. As many are
+    /// kept as events in the history.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.inner
+            .lock()
+            .unwrap()
+            .dead_letters
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// The retained events, oldest first.
+    pub fn history(&self) -> Vec<PublishedEvent> {
+        self.inner.lock().unwrap().history.iter().cloned().collect()
+    }
+
+    pub fn subscribe(&self) -> UnboundedReceiver<PublishedEvent> {
+        self.replay_and_subscribe().1
+    }
+
+    /// Returns the retained history and a receiver for everything published
+    /// after it, with nothing lost or repeated in between.
+    pub fn replay_and_subscribe(&self) -> (Vec<PublishedEvent>, UnboundedReceiver<PublishedEvent>) {
+        let mut inner = self.inner.lock().unwrap();
+        let (_, receiver) = Self::add_subscriber(&mut inner);
+        (inner.history.iter().cloned().collect(), receiver)
+    }
+
+    /// Hands every event published from now on to `subscriber` on a dedicated
+    /// thread, in order. A failed delivery is retried with backoff, and later
+    /// events wait behind it; after `max_attempts` failures the event is kept
+    /// as a dead letter, along with the error, and the next one is handed on.
+    pub fn register(&self, mut subscriber: Box<dyn EventSubscriber>) -> SubscriberId {
+        let (id, mut receiver) = Self::add_subscriber(&mut self.inner.lock().unwrap());
+        // A weak reference, so the thread ends once the last bus is dropped.
+        let inner = Arc::downgrade(&self.inner);
+        let (history_capacity, max_attempts) = (self.history_capacity, self.max_attempts);
+        thread::spawn(move || {
+            while let Some(published) = receiver.blocking_recv() {
+                let mut delay = INITIAL_RETRY_DELAY;
+                let mut attempts = 1;
+                while let Err(e) = subscriber.handle(published.sequence, &published.event) {
+                    if attempts == max_attempts {
+                        let letter = DeadLetter {
+                            subscriber: id,
+                            event: published,
+                            error: e.to_string(),
+                        };
+                        Self::keep_dead_letter(&inner, history_capacity, letter);
+                        break;
+                    }
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    attempts += 1;
+                }
+            }
+        });
+        id
+    }
+
+    fn keep_dead_letter(inner: &Weak<Mutex<Inner>>, capacity: usize, letter: DeadLetter) {
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let mut inner = inner.lock().unwrap();
+        if capacity == 0 {
+            return;
+        }
+        if inner.dead_letters.len() == capacity {
+            inner.dead_letters.pop_front();
+        }
+        inner.dead_letters.push_back(letter);
+    }
+
+    /// Stops delivering to a registered subscriber once the events already
+    /// queued for it have been handled.
+    pub fn unregister(&self, id: SubscriberId) {
+        self.inner
+            .lock()
+            .unwrap()
+            .subscribers
+            .retain(|subscriber| subscriber.id != id);
+    }
+
+    fn add_subscriber(inner: &mut Inner) -> (SubscriberId, UnboundedReceiver<PublishedEvent>) {
+        let id = SubscriberId(inner.next_subscriber);
+        inner.next_subscriber += 1;
+        let (sender, receiver) = unbounded_channel();
+        inner.subscribers.push(Subscriber { id, sender });
+        (id, receiver)
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{Sender, channel};
+    use uuid::Uuid;
+
+    fn event(file_id: Uuid, revision: u64) -> DomainEvent {
+        DomainEvent::FileEdited {
+            file_id,
+            range: 0..0,
+            text: "x".to_string(),
+            author: "ada".to_string(),
+            revision,
+        }
+    }
+
+    // Fails the first `failures` attempts of every event, then reports it.
+    struct Flaky {
+        failures: usize,
+        attempts: usize,
+        handled: Sender<(u64, usize)>,
+    }
+
+    impl EventSubscriber for Flaky {
+        fn handle(
+            &mut self,
+            sequence: u64,
+            _event: &DomainEvent,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.attempts += 1;
+            if self.attempts <= self.failures {
+                return Err("not yet".into());
+            }
+            self.handled.send((sequence, self.attempts)).unwrap();
+            self.attempts = 0;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_publish_reaches_subscribers_in_order() {
+        let bus = EventBus::default();
+        let mut first = bus.subscribe();
+        let mut second = bus.clone().subscribe();
+        let file_id = Uuid::new_v4();
+
+        assert_eq!(bus.publish(event(file_id, 1)), 1);
+        assert_eq!(bus.publish(event(file_id, 2)), 2);
+
+        for receiver in [&mut first, &mut second] {
+            assert_eq!(receiver.try_recv().unwrap().event.revision(), 1);
+            assert_eq!(receiver.try_recv().unwrap().event.revision(), 2);
+            assert!(receiver.try_recv().is_err());
+        }
+    }
+
+    #[test]
+    fn test_replay_keeps_recent_history() {
+        let bus = EventBus::new(2);
+        let file_id = Uuid::new_v4();
+        for revision in 1..=3 {
+            bus.publish(event(file_id, revision));
+        }
+
+        let (history, mut receiver) = bus.replay_and_subscribe();
+        let sequences: Vec<u64> = history.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, vec![2, 3]);
+
+        bus.publish(event(file_id, 4));
+        assert_eq!(receiver.try_recv().unwrap().sequence, 4);
+    }
+
+    #[test]
+    fn test_dropped_subscribers_are_removed() {
+        let bus = EventBus::default();
+        drop(bus.subscribe());
+        bus.publish(event(Uuid::new_v4(), 1));
+        assert!(bus.inner.lock().unwrap().subscribers.is_empty());
+    }
+
+    #[test]
+    fn test_registered_subscriber_retries_in_order() {
+        let bus = EventBus::default();
+        let (sender, handled) = channel();
+        bus.register(Box::new(Flaky {
+            failures: 2,
+            attempts: 0,
+            handled: sender,
+        }));
+
+        let file_id = Uuid::new_v4();
+        for revision in 1..=3 {
+            bus.publish(event(file_id, revision));
+        }
+        let received: Vec<(u64, usize)> = (0..3)
+            .map(|_| handled.recv_timeout(Duration::from_secs(2)).unwrap())
+            .collect();
+        assert_eq!(received, vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn test_registered_subscriber_gives_up_after_max_attempts() {
+        let bus = EventBus::default().with_max_attempts(3);
+        let (sender, handled) = channel();
+        let id = bus.register(Box::new(Flaky {
+            failures: 3,
+            attempts: 0,
+            handled: sender,
+        }));
+
+        let file_id = Uuid::new_v4();
+        bus.publish(event(file_id, 1));
+        bus.publish(event(file_id, 2));
+
+        // The first event used up its attempts, and the second is handed on.
+        assert_eq!(handled.recv_timeout(Duration::from_secs(2)).unwrap().0, 2);
+        let dead_letters = bus.dead_letters();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].subscriber, id);
+        assert_eq!(dead_letters[0].event.sequence, 1);
+        assert_eq!(dead_letters[0].error, "not yet");
+    }
+
+    #[test]
+    fn test_unregister_stops_delivery() {
+        let bus = EventBus::default();
+        let (sender, handled) = channel();
+        let id = bus.register(Box::new(Flaky {
+            failures: 0,
+            attempts: 0,
+            handled: sender,
+        }));
+
+        bus.publish(event(Uuid::new_v4(), 1));
+        bus.unregister(id);
+        bus.publish(event(Uuid::new_v4(), 1));
+
+        assert_eq!(handled.recv_timeout(Duration::from_secs(2)).unwrap().0, 1);
+        assert!(handled.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+}
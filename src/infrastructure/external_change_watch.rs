@@ -0,0 +1,125 @@
+use crate::application::dto::external_change::ExternalChangeEvent;
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::application::usecases::external_change_usecases::{
+    ExternalChangeUsecases, ExternalChangeUsecasesImpl,
+};
+use crate::domain::events::DomainEvent;
+use crate::infrastructure::event_bus::PublishedEvent;
+use crate::infrastructure::periodic::spawn_periodic;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use uuid::Uuid;
+
+/// Applies what other programs write to the files of a workspace. Every file
+/// is tracked from the start, and the files created, restored, renamed or
+/// deleted later are followed through their events.
+pub struct ExternalChangeWatcher {
+    external: ExternalChangeUsecasesImpl,
+    changes: UnboundedReceiver<PublishedEvent>,
+}
+
+impl ExternalChangeWatcher {
+    pub fn new(files: Arc<Mutex<CodeFileUsecasesImpl>>) -> Result<Self, ApplicationError> {
+        // Subscribed before listing, so a file created in between isn't missed.
+        let (changes, file_ids) = {
+            let files = files.lock().unwrap();
+            (files.events.subscribe(), files.repository.ids()?)
+        };
+        let mut external = ExternalChangeUsecasesImpl::new(files)?;
+        for file_id in file_ids {
+            track(&mut external, file_id)?;
+        }
+        Ok(Self { external, changes })
+    }
+
+    /// Follows the files that came and went since the last run, then waits up
+    /// to `timeout` for changes on disk and applies them.
+    pub fn run_once(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Vec<ExternalChangeEvent>, ApplicationError> {
+        while let Ok(published) = self.changes.try_recv() {
+            match published.event {
+                DomainEvent::FileCreated { file_id, .. } => track(&mut self.external, file_id)?,
+                // Watched again at its new path.
+                DomainEvent::FileRenamed { file_id, .. } => {
+                    self.external.untrack_file(file_id)?;
+                    track(&mut self.external, file_id)?;
+                }
+                DomainEvent::FileDeleted { file_id, .. } => {
+                    self.external.untrack_file(file_id)?
+                }
+                DomainEvent::FileEdited { .. } => {},
+            }
+        }
+        self.external.poll_changes(timeout)
+    }
+
+    /// Watches until the process exits, handing the error of a run that fails
+    /// to `failed`.
+    pub fn spawn<E>(mut self, timeout: Duration, failed: E) -> thread::JoinHandle<()>
+    where
+        E: FnMut(ApplicationError) + Send + 'static,
+    {
+        spawn_periodic(
+            "external change watch",
+            Duration::ZERO,
+            move || self.run_once(timeout),
+            failed,
+        )
+    }
+}
+
+// A file deleted before it was tracked has nothing left to watch.
+fn track(external: &mut ExternalChangeUsecasesImpl, file_id: Uuid) -> Result<(), ApplicationError> {
+    match external.track_file(file_id) {
+        Err(ApplicationError::FileNotFound(_)) => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::CreateCodeFileRequest;
+    use crate::application::dto::external_change::EXTERNAL_AUTHOR;
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use std::fs;
+
+    #[test]
+    fn test_files_created_after_the_start_are_watched() {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let files = Arc::new(Mutex::new(CodeFileUsecasesImpl::new(repository)));
+        let mut watcher = ExternalChangeWatcher::new(Arc::clone(&files)).unwrap();
+
+        let name = format!("watched_{}.rs", Uuid::new_v4());
+        let file_id = files
+            .lock()
+            .unwrap()
+            .create_code_file(CreateCodeFileRequest { name: name.clone() })
+            .unwrap()
+            .id;
+        assert!(watcher.run_once(Duration::ZERO).unwrap().is_empty());
+
+        let path = format!("/tmp/{name}");
+        fs::write(&path, "fn main() {}").unwrap();
+        let events = watcher.run_once(Duration::from_secs(2)).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            (events[0].file_id, events[0].author.as_str()),
+            (file_id, EXTERNAL_AUTHOR)
+        );
+        let content = files.lock().unwrap().get_code_file(file_id).unwrap();
+        assert_eq!(content.viewport.content, "fn main() {}");
+
+        // Deleted files are let go of.
+        files.lock().unwrap().delete_code_file(file_id).unwrap();
+        assert!(watcher.run_once(Duration::from_millis(50)).unwrap().is_empty());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, ThreadId};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct Held {
+    // The thread writing to each locked file, and how many times it took it.
+    files: HashMap<Uuid, (ThreadId, usize)>,
+}
+
+/// One writer lock per file, so writes to a file are serialized while
+/// different files are written side by side. A thread may take a lock it
+/// already holds, which lets a write made of several edits hold it throughout.
+/// The locks block, so async code takes them on the blocking pool only.
+/// Clones share the same locks.
+///
+/// This is the only lock that keeps writes to a file apart: whatever changes
+/// a file's content or revision, or reads them to compute such a change,
+/// holds the file's lock from the read through the write, whether it runs on
+/// a document actor, an async usecase or a sync one. A lock over the whole
+/// usecases, like the mutex in `AppState`, keeps out none of the others.
+/// Several files are locked in id order.
+#[derive(Clone, Default)]
+pub struct FileLocks {
+    inner: Arc<(Mutex<Held>, Condvar)>,
+}
+
+/// Holds a file's writer lock until dropped.
+pub struct FileLock {
+    locks: FileLocks,
+    file_id: Uuid,
+}
+
+impl FileLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits until no other thread writes to `file_id`.
+    pub fn lock(&self, file_id: Uuid) -> FileLock {
+        let (held, released) = &*self.inner;
+        let me = thread::current().id();
+        let mut held = held.lock().unwrap();
+        loop {
+            match held.files.get_mut(&file_id) {
+                None => {
+                    held.files.insert(file_id, (me, 1));
+                    break;
+                }
+                Some((writer, depth)) if *writer == me => {
+                    *depth += 1;
+                    break;
+                }
+                Some(_) => held = released.wait(held).unwrap(),
+            }
+        }
+        FileLock {
+            locks: self.clone(),
+            file_id,
+        }
+    }
+
+    /// Takes the locks of every file in `file_ids`, in id order, so two
+    /// callers locking overlapping files can't wait on each other.
+    pub fn lock_all(&self, file_ids: impl IntoIterator<Item = Uuid>) -> Vec<FileLock> {
+        let mut file_ids: Vec<Uuid> = file_ids.into_iter().collect();
+        file_ids.sort();
+        file_ids.dedup();
+        file_ids.into_iter().map(|id| self.lock(id)).collect()
+    }
+
+    /// Whether some thread is writing to `file_id` right now.
+    pub fn is_locked(&self, file_id: Uuid) -> bool {
+        self.inner.0.lock().unwrap().files.contains_key(&file_id)
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let (held, released) = &*self.locks.inner;
+        let mut held = held.lock().unwrap();
+        if let Some((_, depth)) = held.files.get_mut(&self.file_id) {
+            *depth -= 1;
+            if *depth == 0 {
+                held.files.remove(&self.file_id);
+                released.notify_all();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_a_file_has_one_writer_at_a_time() {
+        let locks = FileLocks::new();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let held = locks.lock(first);
+        // Taking it again on the same thread doesn't wait.
+        let again = locks.lock(first);
+
+        let (sender, receiver) = mpsc::channel();
+        let writer = {
+            let locks = locks.clone();
+            thread::spawn(move || {
+                drop(locks.lock(second));
+                sender.send("second").unwrap();
+                let _lock = locks.lock(first);
+                sender.send("first").unwrap();
+            })
+        };
+        assert_eq!(receiver.recv().unwrap(), "second");
+        assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+
+        drop(again);
+        assert!(locks.is_locked(first));
+        drop(held);
+        assert_eq!(receiver.recv().unwrap(), "first");
+        writer.join().unwrap();
+        assert!(!locks.is_locked(first));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::infrastructure::mmap_file_sys;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+// Editors and tools like git replace files by renaming a temporary file over
+// them, which a watch on the file itself would lose track of. Watching the
+// parent directory catches in-place writes and replacements alike.
+const DIRECTORY_MASK: WatchMask = WatchMask::MODIFY
+    .union(WatchMask::CLOSE_WRITE)
+    .union(WatchMask::CREATE)
+    .union(WatchMask::DELETE)
+    .union(WatchMask::MOVED_TO)
+    .union(WatchMask::MOVED_FROM);
+
+/// Reports which watched files were touched on disk by someone else.
+pub struct FileWatcher {
+    inotify: Inotify,
+    directories: HashMap<PathBuf, WatchDescriptor>,
+    files: HashMap<Uuid, PathBuf>,
+    buffer: Vec<u8>,
+}
+
+impl FileWatcher {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            inotify: Inotify::init()?,
+            directories: HashMap::new(),
+            files: HashMap::new(),
+            buffer: vec![0; 4096],
+        })
+    }
+
+    pub fn watch(&mut self, file_id: Uuid, path: PathBuf) -> std::io::Result<()> {
+        let directory = parent(&path);
+        if !self.directories.contains_key(&directory) {
+            let descriptor = self.inotify.watches().add(&directory, DIRECTORY_MASK)?;
+            self.directories.insert(directory, descriptor);
+        }
+        self.files.insert(file_id, path);
+        Ok(())
+    }
+
+    pub fn unwatch(&mut self, file_id: Uuid) -> std::io::Result<()> {
+        let Some(path) = self.files.remove(&file_id) else {
+            return Ok(());
+        };
+        let directory = parent(&path);
+        if self.files.values().any(|other| parent(other) == directory) {
+            return Ok(());
+        }
+        if let Some(descriptor) = self.directories.remove(&directory) {
+            self.inotify.watches().remove(descriptor)?;
+        }
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for changes and returns the ids of the files that
+    /// were modified, replaced, truncated or removed, each at most once. Once
+    /// the kernel's event queue overflowed, events are lost and there is no
+    /// telling which files changed, so every watched file is returned.
+    pub fn poll(&mut self, timeout: Duration) -> std::io::Result<Vec<Uuid>> {
+        if !self.wait(timeout)? {
+            return Ok(Vec::new());
+        }
+
+        let mut changed = Vec::new();
+        let mut overflowed = false;
+        loop {
+            let events = match self.inotify.read_events(&mut self.buffer) {
+                Ok(events) => events,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+            let mut read = 0;
+            let mut touched: Vec<(WatchDescriptor, OsString)> = Vec::new();
+            for event in events {
+                read += 1;
+                if event.mask.contains(EventMask::Q_OVERFLOW) {
+                    overflowed = true;
+                }
+                if let Some(name) = event.name {
+                    touched.push((event.wd.clone(), name.to_os_string()));
+                }
+            }
+            if read == 0 {
+                break;
+            }
+            // A batch of events on the directories themselves names no file.
+            if touched.is_empty() {
+                continue;
+            }
+            mmap_file_sys::disk_changed();
+            for (descriptor, name) in touched {
+                for (file_id, path) in &self.files {
+                    if path.file_name() == Some(name.as_os_str())
+                        && self.directories.get(&parent(path)) == Some(&descriptor)
+                        && !changed.contains(file_id)
+                    {
+                        changed.push(*file_id);
+                    }
+                }
+            }
+        }
+        if overflowed {
+            mmap_file_sys::disk_changed();
+            return Ok(self.files.keys().copied().collect());
+        }
+        Ok(changed)
+    }
+
+    fn wait(&self, timeout: Duration) -> std::io::Result<bool> {
+        let mut descriptor = libc::pollfd {
+            fd: self.inotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let ready = unsafe { libc::poll(&mut descriptor, 1, timeout) };
+        if ready < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() == std::io::ErrorKind::Interrupted {
+                return Ok(false);
+            }
+            return Err(error);
+        }
+        Ok(ready > 0)
+    }
+}
+
+fn parent(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reports_in_place_write() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("main.rs");
+        fs::write(&path, "fn main() {}").unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+        let file_id = Uuid::new_v4();
+        watcher.watch(file_id, path.clone()).unwrap();
+
+        fs::write(&path, "fn main() { run(); }").unwrap();
+        assert_eq!(watcher.poll(Duration::from_secs(2)).unwrap(), vec![file_id]);
+        assert!(watcher.poll(Duration::from_millis(50)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reports_replacement_by_rename() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("lib.rs");
+        let other = temp_dir.path().join("other.rs");
+        fs::write(&path, "old").unwrap();
+        fs::write(&other, "untouched").unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+        let file_id = Uuid::new_v4();
+        let other_id = Uuid::new_v4();
+        watcher.watch(file_id, path.clone()).unwrap();
+        watcher.watch(other_id, other).unwrap();
+
+        let temporary = temp_dir.path().join(".lib.rs.tmp");
+        fs::write(&temporary, "new").unwrap();
+        fs::rename(&temporary, &path).unwrap();
+        assert_eq!(watcher.poll(Duration::from_secs(2)).unwrap(), vec![file_id]);
+    }
+
+    #[test]
+    fn test_reports_every_file_after_an_overflow() {
+        let temp_dir = TempDir::new().unwrap();
+        let (path, other) = (temp_dir.path().join("a.txt"), temp_dir.path().join("b.txt"));
+        fs::write(&path, "a").unwrap();
+        fs::write(&other, "b").unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+        let (file_id, other_id) = (Uuid::new_v4(), Uuid::new_v4());
+        watcher.watch(file_id, path.clone()).unwrap();
+        watcher.watch(other_id, other).unwrap();
+
+        // Each write queues at least two events, more than the queue holds.
+        let queued: usize = fs::read_to_string("/proc/sys/fs/inotify/max_queued_events")
+            .map_or(16384, |max| max.trim().parse().unwrap());
+        for _ in 0..queued / 2 + 1 {
+            fs::write(&path, "a").unwrap();
+        }
+        let mut changed = watcher.poll(Duration::from_secs(2)).unwrap();
+        changed.sort();
+        let mut expected = vec![file_id, other_id];
+        expected.sort();
+        assert_eq!(changed, expected);
+    }
+
+    #[test]
+    fn test_unwatch_stops_reporting() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("notes.txt");
+        fs::write(&path, "a").unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+        let file_id = Uuid::new_v4();
+        watcher.watch(file_id, path.clone()).unwrap();
+        watcher.unwatch(file_id).unwrap();
+
+        fs::write(&path, "b").unwrap();
+        assert!(watcher.poll(Duration::from_millis(100)).unwrap().is_empty());
+    }
+}
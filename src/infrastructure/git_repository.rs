@@ -0,0 +1,333 @@
+use crate::domain::git::{FileStatus, Signature};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A local git repository driven through the `git` command line, so that the
+/// user's own configuration, hooks and attributes apply as they would in a shell.
+pub struct GitRepository {
+    root: PathBuf,
+}
+
+fn check(output: Output) -> std::io::Result<String> {
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(std::io::Error::other(stderr.trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl GitRepository {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(["rev-parse", "--show-toplevel"])
+            .output()?;
+        let root = PathBuf::from(check(output)?.trim_end()).canonicalize()?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn git<I, S>(&self, args: I) -> std::io::Result<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.root)
+            .args(args)
+            .output()?;
+        check(output)
+    }
+
+    fn head(&self) -> Option<String> {
+        self.git(["rev-parse", "--verify", "--quiet", "HEAD"])
+            .ok()
+            .map(|commit| commit.trim_end().to_string())
+    }
+
+    pub fn current_branch(&self) -> std::io::Result<String> {
+        Ok(self
+            .git(["symbolic-ref", "--short", "HEAD"])?
+            .trim_end()
+            .to_string())
+    }
+
+    /// Status of every path that is not clean, keyed by absolute path. A fully
+    /// ignored directory is reported once rather than file by file.
+    pub fn status(&self) -> std::io::Result<HashMap<PathBuf, FileStatus>> {
+        let output = self.git([
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--untracked-files=all",
+            "--ignored=matching",
+        ])?;
+
+        let mut statuses = HashMap::new();
+        let mut entries = output.split('\0');
+        while let Some(entry) = entries.next() {
+            let (Some(code), Some(path)) = (entry.get(..2), entry.get(3..)) else {
+                continue;
+            };
+            if path.is_empty() {
+                continue;
+            }
+            // The next entry holds the original path of a rename or copy, whatever
+            // status the working tree change gives the file.
+            if code.contains(['R', 'C']) {
+                entries.next();
+            }
+            let status = FileStatus::from_porcelain(code);
+            statuses.insert(self.root.join(path), status);
+        }
+        Ok(statuses)
+    }
+
+    /// Unified diff of `path` in the working tree against HEAD. Files that are not
+    /// part of HEAD are shown as entirely added.
+    pub fn diff_head(&self, path: &Path) -> std::io::Result<String> {
+        let in_head = match (self.head(), path.strip_prefix(&self.root)) {
+            (Some(_), Ok(relative)) => self
+                .git(["cat-file", "-e", &format!("HEAD:{}", relative.display())])
+                .is_ok(),
+            _ => false,
+        };
+        if in_head {
+            return self.git([
+                OsStr::new("diff"),
+                OsStr::new("HEAD"),
+                OsStr::new("--"),
+                path.as_os_str(),
+            ]);
+        }
+
+        // `--no-index` exits with 1 whenever the files differ.
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.root)
+            .args(["diff", "--no-index", "--", "/dev/null"])
+            .arg(path)
+            .output()?;
+        if output.status.code() == Some(1) {
+            return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+        }
+        check(output)
+    }
+
+    /// Commits exactly `paths`, leaving anything else that is staged alone, and
+    /// returns the new commit id.
+    pub fn commit(
+        &self,
+        paths: &[PathBuf],
+        author: &Signature,
+        message: &str,
+    ) -> std::io::Result<String> {
+        let mut add: Vec<&OsStr> = vec![OsStr::new("add"), OsStr::new("--")];
+        add.extend(paths.iter().map(|path| path.as_os_str()));
+        self.git(add)?;
+
+        let name = format!("user.name={}", author.name);
+        let email = format!("user.email={}", author.email);
+        let author = author.to_string();
+        let mut commit: Vec<&OsStr> = vec![
+            OsStr::new("-c"),
+            OsStr::new(&name),
+            OsStr::new("-c"),
+            OsStr::new(&email),
+            OsStr::new("commit"),
+            OsStr::new("--quiet"),
+            OsStr::new("--author"),
+            OsStr::new(&author),
+            OsStr::new("--message"),
+            OsStr::new(message),
+            OsStr::new("--"),
+        ];
+        commit.extend(paths.iter().map(|path| path.as_os_str()));
+        self.git(commit)?;
+
+        self.head()
+            .ok_or_else(|| std::io::Error::other("HEAD missing after commit"))
+    }
+
+    /// Checks out `branch`, creating it from HEAD when `create` is set, and
+    /// returns the absolute paths whose content differs between the two commits.
+    pub fn switch_branch(&self, branch: &str, create: bool) -> std::io::Result<Vec<PathBuf>> {
+        // A name starting with `-` would be taken for an option of `git
+        // switch`; `check-ref-format` refuses it along with any other name a
+        // branch can't have.
+        let branch = self
+            .git(["check-ref-format", "--branch", branch])
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+        let branch = branch.trim_end();
+        let before = self.head();
+        if create {
+            self.git(["switch", "--quiet", "--create", branch])?;
+        } else {
+            self.git(["switch", "--quiet", branch])?;
+        }
+        let after = self.head();
+
+        let changed = match (before, after) {
+            (Some(before), Some(after)) if before != after => {
+                self.git(["diff", "--name-only", "-z", &before, &after])?
+            }
+            _ => String::new(),
+        };
+        Ok(changed
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(|path| self.root.join(path))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn signature(name: &str) -> Signature {
+        Signature {
+            name: name.to_string(),
+            email: format!("{}@example.com", name.to_lowercase()),
+        }
+    }
+
+    fn init_repository() -> (TempDir, GitRepository) {
+        let temp_dir = TempDir::new().unwrap();
+        let output = Command::new("git")
+            .args(["init", "--quiet", "--initial-branch=main"])
+            .arg(temp_dir.path())
+            .output()
+            .unwrap();
+        check(output).unwrap();
+        let repository = GitRepository::open(temp_dir.path()).unwrap();
+        (temp_dir, repository)
+    }
+
+    #[test]
+    fn test_status_and_commit() {
+        let (_temp_dir, repository) = init_repository();
+        let root = repository.root().to_path_buf();
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::write(root.join("build/out"), "x").unwrap();
+        fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+
+        let status = repository.status().unwrap();
+        assert_eq!(status[&root.join("main.rs")], FileStatus::Untracked);
+        assert_eq!(status[&root.join("build/")], FileStatus::Ignored);
+
+        let commit = repository
+            .commit(&[root.join("main.rs")], &signature("Ada"), "Initial")
+            .unwrap();
+        assert_eq!(commit.len(), 40);
+        let status = repository.status().unwrap();
+        assert!(!status.contains_key(&root.join("main.rs")));
+        assert_eq!(status[&root.join(".gitignore")], FileStatus::Untracked);
+
+        let log = repository.git(["log", "--format=%an <%ae>|%cn"]).unwrap();
+        assert_eq!(log.trim_end(), "Ada <ada@example.com>|Ada");
+    }
+
+    #[test]
+    fn test_status_of_a_renamed_and_modified_file() {
+        let (_temp_dir, repository) = init_repository();
+        let root = repository.root().to_path_buf();
+        fs::write(root.join("old.rs"), "fn main() {}\n").unwrap();
+        repository
+            .commit(&[root.join("old.rs")], &signature("Ada"), "Initial")
+            .unwrap();
+
+        repository.git(["mv", "old.rs", "new.rs"]).unwrap();
+        fs::write(root.join("new.rs"), "fn main() { run(); }\n").unwrap();
+
+        let status = repository.status().unwrap();
+        assert_eq!(
+            status,
+            HashMap::from([(root.join("new.rs"), FileStatus::Modified)])
+        );
+    }
+
+    #[test]
+    fn test_diff_head() {
+        let (_temp_dir, repository) = init_repository();
+        let root = repository.root().to_path_buf();
+        let path = root.join("lib.rs");
+        fs::write(&path, "one\n").unwrap();
+        assert!(repository.diff_head(&path).unwrap().contains("+one"));
+
+        repository
+            .commit(std::slice::from_ref(&path), &signature("Ada"), "Add lib")
+            .unwrap();
+        assert_eq!(repository.diff_head(&path).unwrap(), "");
+
+        fs::write(&path, "two\n").unwrap();
+        let diff = repository.diff_head(&path).unwrap();
+        assert!(diff.contains("-one\n+two\n"));
+    }
+
+    #[test]
+    fn test_switch_branch_reports_changed_paths() {
+        let (_temp_dir, repository) = init_repository();
+        let root = repository.root().to_path_buf();
+        let (a, b) = (root.join("a.txt"), root.join("b.txt"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        repository
+            .commit(&[a.clone(), b.clone()], &signature("Ada"), "Initial")
+            .unwrap();
+
+        assert!(
+            repository
+                .switch_branch("feature", true)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(repository.current_branch().unwrap(), "feature");
+        fs::write(&b, "changed").unwrap();
+        repository
+            .commit(std::slice::from_ref(&b), &signature("Ada"), "Change b")
+            .unwrap();
+
+        assert_eq!(
+            repository.switch_branch("main", false).unwrap(),
+            vec![b.clone()]
+        );
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+        assert!(repository.switch_branch("missing", false).is_err());
+    }
+
+    #[test]
+    fn test_switch_branch_refuses_option_names() {
+        let (_temp_dir, repository) = init_repository();
+        let root = repository.root().to_path_buf();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        repository
+            .commit(&[root.join("a.txt")], &signature("Ada"), "Initial")
+            .unwrap();
+
+        for branch in ["--orphan=gone", "-", "-c"] {
+            for create in [false, true] {
+                let error = repository.switch_branch(branch, create).unwrap_err();
+                assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+            }
+        }
+        assert_eq!(repository.current_branch().unwrap(), "main");
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_open_outside_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(GitRepository::open(temp_dir.path()).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::operation_history::{CompactionPolicy, CompactionReport};
+use crate::infrastructure::periodic::spawn_periodic;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Keeps the operation history of a workspace in check: snapshots every file
+/// each `snapshot_every` revisions, then compacts the history by the policy.
+#[derive(Clone)]
+pub struct HistoryCompactor {
+    files: Arc<Mutex<CodeFileUsecasesImpl>>,
+    policy: CompactionPolicy,
+}
+
+impl HistoryCompactor {
+    pub fn new(files: Arc<Mutex<CodeFileUsecasesImpl>>, policy: CompactionPolicy) -> Self {
+        Self { files, policy }
+    }
+
+    pub fn run_once(&self) -> Result<CompactionReport, ApplicationError> {
+        // Each snapshot is taken under the file's writer lock, so it matches
+        // its revision.
+        let history = {
+            let files = self.files.lock().unwrap();
+            for code_file in files.repository.list()? {
+                let due = files
+                    .history
+                    .snapshots(code_file.id())
+                    .last()
+                    .is_none_or(|last| {
+                        code_file.revision() >= last.revision + self.policy.snapshot_every
+                    });
+                // Listed files may be unloaded; the lookup reopens them.
+                if due {
+                    let _writer = files.writers.lock(code_file.id());
+                    let code_file = files.repository.find_by_id(code_file.id())?;
+                    files.history.record_snapshot(
+                        code_file.id(),
+                        code_file.revision(),
+                        code_file.source.get_content(),
+                    );
+                }
+            }
+            files.history.clone()
+        };
+        Ok(history.compact(&self.policy))
+    }
+
+    /// Runs every `interval` until the process exits, handing the error of
+    /// a run that fails to `failed`.
+    pub fn spawn<E>(&self, interval: Duration, failed: E) -> thread::JoinHandle<()>
+    where
+        E: FnMut(ApplicationError) + Send + 'static,
+    {
+        let compactor = self.clone();
+        spawn_periodic(
+            "history compaction",
+            interval,
+            move || compactor.run_once(),
+            failed,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::dto::resume::{CatchUp, ResumeRequest};
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::application::usecases::resume_usecases::ResumeUsecases;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use uuid::Uuid;
+
+    fn files() -> Arc<Mutex<CodeFileUsecasesImpl>> {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        Arc::new(Mutex::new(CodeFileUsecasesImpl::new(repository)))
+    }
+
+    fn create(files: &Arc<Mutex<CodeFileUsecasesImpl>>) -> Uuid {
+        files
+            .lock()
+            .unwrap()
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("compaction_{}.rs", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn type_text(files: &Arc<Mutex<CodeFileUsecasesImpl>>, id: Uuid, at: usize, text: &str) {
+        for (offset, c) in text.chars().enumerate() {
+            let start = (at + offset) as u64;
+            files
+                .lock()
+                .unwrap()
+                .update_code_file(UpdateCodeRequest {
+                    id,
+                    start,
+                    end: start,
+                    content: c.to_string(),
+                    author: "ada".to_string(),
+                })
+                .unwrap();
+        }
+    }
+
+    fn resume(files: &Arc<Mutex<CodeFileUsecasesImpl>>, id: Uuid, last_revision: u64) -> CatchUp {
+        files
+            .lock()
+            .unwrap()
+            .resume_session(ResumeRequest {
+                file_id: id,
+                last_revision,
+                author: "grace".to_string(),
+                pending: Vec::new(),
+            })
+            .unwrap()
+            .catch_up
+    }
+
+    fn immediate() -> CompactionPolicy {
+        CompactionPolicy {
+            squash_after: Duration::ZERO,
+            squash_gap: Duration::from_secs(60),
+            ..CompactionPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_keystrokes_squash_around_pinned_revisions() {
+        let files = files();
+        let id = create(&files);
+        let compactor = HistoryCompactor::new(Arc::clone(&files), immediate());
+        compactor.run_once().unwrap();
+        type_text(&files, id, 0, "fn main");
+        let history = files.lock().unwrap().history.clone();
+        history.pin(id, "client", 2);
+
+        let report = compactor.run_once().unwrap();
+        assert_eq!(report.squashed, 5);
+        let spans: Vec<(u64, u64, String)> = history
+            .operations_after(id, 0)
+            .into_iter()
+            .map(|operation| (operation.base_revision, operation.revision, operation.text))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(0, 2, "fn".to_string()), (2, 7, " main".to_string())]
+        );
+        assert_eq!(history.content_at(id, 2).as_deref(), Some("fn"));
+        assert_eq!(history.content_at(id, 7).as_deref(), Some("fn main"));
+
+        // The pinned client still catches up with edits; one in between can't.
+        match resume(&files, id, 2) {
+            CatchUp::Operations(operations) => assert_eq!(operations.len(), 1),
+            CatchUp::Snapshot { .. } => panic!("Expected operations"),
+        }
+        match resume(&files, id, 4) {
+            CatchUp::Snapshot { revision, content } => {
+                assert_eq!((revision, content.as_str()), (7, "fn main"))
+            }
+            CatchUp::Operations(_) => panic!("Expected a snapshot"),
+        }
+
+        files.lock().unwrap().delete_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_retention_keeps_pinned_and_unsnapshotted_edits() {
+        let files = files();
+        let first = create(&files);
+        let second = create(&files);
+        let policy = CompactionPolicy {
+            squash_after: Duration::from_secs(3600),
+            snapshot_every: 4,
+            max_operations: Some(2),
+            ..CompactionPolicy::default()
+        };
+        let compactor = HistoryCompactor::new(Arc::clone(&files), policy);
+        compactor.run_once().unwrap();
+        type_text(&files, first, 0, "abcd");
+        type_text(&files, second, 0, "xy");
+        let history = files.lock().unwrap().history.clone();
+        history.pin(first, "client", 1);
+
+        // The pin needs the snapshot at 0 and every edit since, and the second
+        // file has no newer snapshot, so nothing can go yet.
+        compactor.run_once().unwrap();
+        assert_eq!(history.len(), 6);
+        assert_eq!(history.content_at(first, 1).as_deref(), Some("a"));
+
+        history.unpin(first, "client");
+        let report = compactor.run_once().unwrap();
+        assert_eq!(report.dropped, 4);
+        assert!(history.operations_after(first, 0).is_empty());
+        assert_eq!(history.len(), 2);
+        let revisions: Vec<u64> = history
+            .snapshots(first)
+            .iter()
+            .map(|snapshot| snapshot.revision)
+            .collect();
+        assert_eq!(revisions, vec![4]);
+        assert_eq!(history.content_at(first, 4).as_deref(), Some("abcd"));
+
+        files.lock().unwrap().delete_code_file(first).unwrap();
+        files.lock().unwrap().delete_code_file(second).unwrap();
+    }
+}
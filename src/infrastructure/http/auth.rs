@@ -0,0 +1,78 @@
+use axum::Json;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::json;
+use std::sync::Arc;
+
+/// The secret the nodes of a cluster share, sent as a bearer token on the
+/// requests they make of each other.
+#[derive(Clone)]
+pub struct ClusterToken(Arc<str>);
+
+impl ClusterToken {
+    pub fn new(secret: &str) -> Self {
+        Self(Arc::from(secret))
+    }
+
+    pub fn header_value(&self) -> String {
+        format!("Bearer {}", self.0)
+    }
+
+    /// Whether `headers` carry this token. The comparison takes as long
+    /// wherever the first difference is.
+    pub fn accepts(&self, headers: &HeaderMap) -> bool {
+        let Some(presented) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        presented.len() == self.0.len()
+            && presented
+                .bytes()
+                .zip(self.0.bytes())
+                .fold(0, |difference, (a, b)| difference | (a ^ b))
+                == 0
+    }
+}
+
+/// Turns away requests without the cluster token with a 401. Without a token
+/// configured every request is let through.
+pub async fn require_token(
+    State(token): State<Option<ClusterToken>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match token {
+        Some(token) if !token.accepts(request.headers()) => (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "missing or wrong cluster token" })),
+        )
+            .into_response(),
+        _ => next.run(request).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_accepts_only_the_exact_bearer_token() {
+        let token = ClusterToken::new("s3cret");
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert!(token.accepts(&headers(&token.header_value())));
+        assert!(!token.accepts(&headers("Bearer s3cre")));
+        assert!(!token.accepts(&headers("Bearer s3cret!")));
+        assert!(!token.accepts(&headers("Basic s3cret")));
+        assert!(!token.accepts(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::application::dto::checkpoint::{
+    CheckpointResponse, CompareCheckpointRequest, CreateCheckpointRequest, FileComparisonResponse,
+    RestoreCheckpointRequest, RestoreCheckpointResponse,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::checkpoint_usecases::{
+    CheckpointUsecases, CheckpointUsecasesImpl,
+};
+use crate::domain::checkpoint::FileChange;
+use crate::infrastructure::http::AppState;
+use crate::infrastructure::persistence::blocking_repository::run_blocking;
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBody {
+    name: String,
+    #[serde(default)]
+    file_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    file_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    #[serde(default)]
+    against: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreBody {
+    author: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckpointedFileBody {
+    file_id: Uuid,
+    name: String,
+    revision: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckpointBody {
+    id: Uuid,
+    name: String,
+    file_id: Option<Uuid>,
+    created_at_ms: u64,
+    files: Vec<CheckpointedFileBody>,
+}
+
+/// `edit` turns the checkpoint's text into the other side's when the file
+/// was modified.
+#[derive(Debug, Serialize)]
+pub struct ComparisonBody {
+    file_id: Uuid,
+    name: String,
+    change: &'static str,
+    edit: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoredFileBody {
+    file_id: Uuid,
+    revision: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReply {
+    restored: Vec<RestoredFileBody>,
+    missing: Vec<Uuid>,
+}
+
+impl From<CheckpointResponse> for CheckpointBody {
+    fn from(checkpoint: CheckpointResponse) -> Self {
+        let created_at = checkpoint
+            .created_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            id: checkpoint.id,
+            name: checkpoint.name,
+            file_id: checkpoint.file_id,
+            created_at_ms: created_at.as_millis().try_into().unwrap_or(u64::MAX),
+            files: checkpoint
+                .files
+                .into_iter()
+                .map(|file| CheckpointedFileBody {
+                    file_id: file.file_id,
+                    name: file.name,
+                    revision: file.revision,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<FileComparisonResponse> for ComparisonBody {
+    fn from(comparison: FileComparisonResponse) -> Self {
+        let (change, edit) = match comparison.change {
+            FileChange::Unchanged => ("unchanged", None),
+            FileChange::Modified(edit) => (
+                "modified",
+                Some(json!({ "start": edit.start, "end": edit.end, "text": edit.text })),
+            ),
+            FileChange::Added => ("added", None),
+            FileChange::Removed => ("removed", None),
+        };
+        Self {
+            file_id: comparison.file_id,
+            name: comparison.name,
+            change,
+            edit,
+        }
+    }
+}
+
+impl From<RestoreCheckpointResponse> for RestoreReply {
+    fn from(response: RestoreCheckpointResponse) -> Self {
+        Self {
+            restored: response
+                .restored
+                .into_iter()
+                .map(|file| RestoredFileBody {
+                    file_id: file.file_id,
+                    revision: file.revision,
+                })
+                .collect(),
+            missing: response.missing,
+        }
+    }
+}
+
+fn error_reply(e: ApplicationError) -> (StatusCode, Json<Value>) {
+    let status = match e {
+        ApplicationError::CheckpointNotFound(_) | ApplicationError::FileNotFound(_) => {
+            StatusCode::NOT_FOUND
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "error": format!("{e:?}") })))
+}
+
+// Checkpoints copy and rewrite whole files, so they run on the blocking pool.
+async fn with_checkpoints<T, F>(state: &AppState, task: F) -> Result<T, (StatusCode, Json<Value>)>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut CheckpointUsecasesImpl) -> Result<T, ApplicationError> + Send + 'static,
+{
+    let checkpoints = Arc::clone(&state.checkpoints);
+    run_blocking(move || task(&mut checkpoints.lock().unwrap()))
+        .await
+        .map_err(error_reply)
+}
+
+/// Checkpoints the file named by `file_id`, or every file without one.
+pub async fn create_checkpoint(
+    State(state): State<AppState>,
+    Json(body): Json<CreateBody>,
+) -> Result<(StatusCode, Json<CheckpointBody>), (StatusCode, Json<Value>)> {
+    let checkpoint = with_checkpoints(&state, move |checkpoints| {
+        checkpoints.create_checkpoint(CreateCheckpointRequest {
+            name: body.name,
+            file_id: body.file_id,
+        })
+    })
+    .await?;
+    Ok((StatusCode::CREATED, Json(checkpoint.into())))
+}
+
+pub async fn list_checkpoints(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<CheckpointBody>>, (StatusCode, Json<Value>)> {
+    let checkpoints = with_checkpoints(&state, move |checkpoints| {
+        checkpoints.list_checkpoints(query.file_id)
+    })
+    .await?;
+    Ok(Json(checkpoints.into_iter().map(Into::into).collect()))
+}
+
+/// Compares the checkpoint with the one named by `against`, or with the files
+/// as they are now.
+pub async fn compare_checkpoint(
+    State(state): State<AppState>,
+    Path(checkpoint_id): Path<Uuid>,
+    Query(query): Query<CompareQuery>,
+) -> Result<Json<Vec<ComparisonBody>>, (StatusCode, Json<Value>)> {
+    let comparisons = with_checkpoints(&state, move |checkpoints| {
+        checkpoints.compare_checkpoint(CompareCheckpointRequest {
+            checkpoint_id,
+            against: query.against,
+        })
+    })
+    .await?;
+    Ok(Json(comparisons.into_iter().map(Into::into).collect()))
+}
+
+pub async fn restore_checkpoint(
+    State(state): State<AppState>,
+    Path(checkpoint_id): Path<Uuid>,
+    Json(body): Json<RestoreBody>,
+) -> Result<Json<RestoreReply>, (StatusCode, Json<Value>)> {
+    let response = with_checkpoints(&state, move |checkpoints| {
+        checkpoints.restore_checkpoint(RestoreCheckpointRequest {
+            checkpoint_id,
+            author: body.author,
+        })
+    })
+    .await?;
+    Ok(Json(response.into()))
+}
+
+pub async fn delete_checkpoint(
+    State(state): State<AppState>,
+    Path(checkpoint_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    with_checkpoints(&state, move |checkpoints| {
+        checkpoints.delete_checkpoint(checkpoint_id)
+    })
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::infrastructure::http::router;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn call(state: &AppState, request: Request<Body>) -> (StatusCode, Value) {
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, body)
+    }
+
+    fn post(uri: &str, body: Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints_can_be_taken_compared_and_restored() {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let state = AppState::new(CodeFileUsecasesImpl::new(repository));
+        let id = state
+            .files
+            .lock()
+            .unwrap()
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("checkpoint_{}.rs", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id;
+        let edit = |start: u64, end: u64, content: &str| {
+            state
+                .files
+                .lock()
+                .unwrap()
+                .update_code_file(UpdateCodeRequest {
+                    id,
+                    start,
+                    end,
+                    content: content.to_string(),
+                    author: "ada".to_string(),
+                })
+                .unwrap();
+        };
+        edit(0, 0, "fn main() {}");
+
+        let (status, checkpoint) = call(
+            &state,
+            post("/checkpoints", json!({ "name": "start", "file_id": id })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(checkpoint["files"][0]["revision"], json!(1));
+        let checkpoint_id = checkpoint["id"].as_str().unwrap().to_string();
+
+        edit(3, 7, "walk");
+        let compare = Request::get(format!("/checkpoints/{checkpoint_id}/compare"))
+            .body(Body::empty())
+            .unwrap();
+        let (_, comparisons) = call(&state, compare).await;
+        assert_eq!(comparisons[0]["change"], "modified");
+        assert_eq!(
+            comparisons[0]["edit"],
+            json!({ "start": 3, "end": 7, "text": "walk" })
+        );
+
+        let restore = format!("/checkpoints/{checkpoint_id}/restore");
+        let (status, restored) = call(&state, post(&restore, json!({ "author": "grace" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            restored,
+            json!({ "restored": [{ "file_id": id, "revision": 3 }], "missing": [] })
+        );
+
+        let list = Request::get(format!("/checkpoints?file_id={id}"))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(call(&state, list).await.1[0]["id"], json!(checkpoint_id));
+        let delete = || {
+            Request::delete(format!("/checkpoints/{checkpoint_id}"))
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(call(&state, delete()).await.0, StatusCode::NO_CONTENT);
+        assert_eq!(call(&state, delete()).await.0, StatusCode::NOT_FOUND);
+
+        state.files.lock().unwrap().delete_code_file(id).unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+use crate::application::errors::ApplicationError;
+use crate::infrastructure::http::AppState;
+use axum::Json;
+use axum::extract::{Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MembersBody {
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HandoffReply {
+    pub members: Vec<String>,
+    pub handed_off: Vec<Uuid>,
+}
+
+fn error_reply(status: StatusCode, message: String) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "error": message })))
+}
+
+fn not_clustered() -> (StatusCode, Json<Value>) {
+    error_reply(
+        StatusCode::NOT_FOUND,
+        "this node is not part of a cluster".to_string(),
+    )
+}
+
+/// Marks a request already redirected once, so nodes that disagree about the
+/// owner while the membership changes don't send it back and forth.
+pub const REDIRECTED_PARAM: &str = "redirected";
+
+fn was_redirected(request: &Request) -> bool {
+    request.uri().query().is_some_and(|query| {
+        query
+            .split('&')
+            .any(|pair| pair.split('=').next() == Some(REDIRECTED_PARAM))
+    })
+}
+
+/// Redirects a request for a file owned by another node there with a 307,
+/// which keeps the method and body; event streams and sessions reconnect to
+/// the owner the same way. A request is redirected at most once; after that
+/// it is served from the replica here.
+pub async fn route_to_owner(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(cluster) = &state.cluster
+        && !cluster.is_owner(file_id)
+        && !was_redirected(&request)
+    {
+        let uri = request.uri();
+        let location = match uri.query() {
+            Some(query) => format!(
+                "{}{}?{query}&{REDIRECTED_PARAM}=1",
+                cluster.owner(file_id),
+                uri.path()
+            ),
+            None => format!(
+                "{}{}?{REDIRECTED_PARAM}=1",
+                cluster.owner(file_id),
+                uri.path()
+            ),
+        };
+        return (
+            StatusCode::TEMPORARY_REDIRECT,
+            [(header::LOCATION, location)],
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+pub async fn list_members(
+    State(state): State<AppState>,
+) -> Result<Json<MembersBody>, (StatusCode, Json<Value>)> {
+    let cluster = state.cluster.ok_or_else(not_clustered)?;
+    Ok(Json(MembersBody {
+        members: cluster.members(),
+    }))
+}
+
+/// Takes the membership after a node joined or left. Every node, the leaving
+/// one included, should be given it; each hands off the files it no longer
+/// owns before replying.
+pub async fn set_members(
+    State(state): State<AppState>,
+    Json(body): Json<MembersBody>,
+) -> Result<Json<HandoffReply>, (StatusCode, Json<Value>)> {
+    let cluster = state.cluster.ok_or_else(not_clustered)?;
+    let members = cluster.clone();
+    let handed_off = tokio::task::spawn_blocking(move || cluster.set_members(body.members))
+        .await
+        .map_err(|e| error_reply(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| {
+            let status = match e {
+                ApplicationError::PeerUnreachable(_) => StatusCode::BAD_GATEWAY,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            error_reply(status, format!("{e:?}"))
+        })?;
+    Ok(Json(HandoffReply {
+        members: members.members(),
+        handed_off,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::domain::shard::{DEFAULT_VIRTUAL_NODES, HashRing};
+    use crate::infrastructure::cluster::Cluster;
+    use crate::infrastructure::http::auth::ClusterToken;
+    use crate::infrastructure::http::router;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use crate::infrastructure::replication::{self, ReplicationNode};
+    use axum::body::Body;
+    use futures_util::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tokio_tungstenite::connect_async;
+    use tower::ServiceExt;
+
+    struct Node {
+        state: AppState,
+        cluster: Cluster,
+        replication: ReplicationNode,
+        url: String,
+        _data_dir: TempDir,
+    }
+
+    // Starts `count` nodes on localhost; the first `members` of them form
+    // the cluster, and every node replicates with all the others.
+    async fn start(count: usize, members: usize) -> Vec<Node> {
+        let mut listeners = Vec::new();
+        for _ in 0..count {
+            listeners.push(tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap());
+        }
+        let urls: Vec<String> = listeners
+            .iter()
+            .map(|listener| format!("http://{}", listener.local_addr().unwrap()))
+            .collect();
+
+        let mut nodes = Vec::new();
+        for (listener, url) in listeners.into_iter().zip(&urls) {
+            let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+            let state = AppState::new(CodeFileUsecasesImpl::new(repository));
+            let data_dir = TempDir::new().unwrap();
+            let peers = urls.iter().filter(|peer| *peer != url).cloned().collect();
+            let replication = ReplicationNode::new(
+                Uuid::new_v4(),
+                state.files.clone(),
+                data_dir.path().into(),
+                peers,
+            )
+            .unwrap();
+            let cluster = Cluster::new(
+                url.clone(),
+                urls[..members].to_vec(),
+                state.files.clone(),
+                replication.clone(),
+            );
+            let state = state.with_cluster(cluster.clone());
+            let app = router(state.clone()).merge(replication::router(replication.clone()));
+            tokio::spawn(axum::serve(listener, app).into_future());
+            nodes.push(Node {
+                state,
+                cluster,
+                replication,
+                url: url.clone(),
+                _data_dir: data_dir,
+            });
+        }
+        nodes
+    }
+
+    // Creates files on `node` until one lands where `wanted` says.
+    fn create_where(node: &Node, wanted: impl Fn(Uuid) -> bool) -> Uuid {
+        let mut files = node.state.files.lock().unwrap();
+        loop {
+            let id = files
+                .create_code_file(CreateCodeFileRequest {
+                    name: format!("shard_{}.txt", Uuid::new_v4()),
+                })
+                .unwrap()
+                .id;
+            if wanted(id) {
+                return id;
+            }
+            files.delete_code_file(id).unwrap();
+        }
+    }
+
+    fn edit(node: &Node, id: Uuid, text: &str) {
+        let mut files = node.state.files.lock().unwrap();
+        let length = files
+            .get_code_file(id)
+            .unwrap()
+            .viewport
+            .content
+            .chars()
+            .count() as u64;
+        files
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start: length,
+                end: length,
+                content: text.to_string(),
+                author: "grace".to_string(),
+            })
+            .unwrap();
+    }
+
+    fn content(node: &Node, id: Uuid) -> Option<String> {
+        let files = node.state.files.lock().unwrap();
+        files
+            .get_code_file(id)
+            .ok()
+            .map(|file| file.viewport.content)
+    }
+
+    async fn set_members(node: &Node, members: &[&Node]) -> Vec<Uuid> {
+        let cluster = node.cluster.clone();
+        let members = members.iter().map(|node| node.url.clone()).collect();
+        tokio::task::spawn_blocking(move || cluster.set_members(members))
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn sync(node: &Node, id: Uuid) -> Response {
+        sync_at(node, &format!("/files/{id}/sync")).await
+    }
+
+    async fn sync_at(node: &Node, path: &str) -> Response {
+        let request = axum::http::Request::post(path)
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "base_revision": 0, "author": "ada" }).to_string(),
+            ))
+            .unwrap();
+        router(node.state.clone()).oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_requests_are_redirected_to_the_owner() {
+        let nodes = start(2, 2).await;
+        let id = create_where(&nodes[0], |id| nodes[0].cluster.owner(id) == nodes[1].url);
+        edit(&nodes[0], id, "shared");
+        let replication = nodes[0].replication.clone();
+        let peer = nodes[1].url.clone();
+        tokio::task::spawn_blocking(move || replication.exchange(&peer))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let response = sync(&nodes[0], id).await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            format!("{}/files/{id}/sync?redirected=1", nodes[1].url)
+        );
+        assert_eq!(sync(&nodes[1], id).await.status(), StatusCode::OK);
+        // A node that still thinks another owns the file serves the second hop.
+        let redirected = format!("/files/{id}/sync?redirected=1");
+        assert_eq!(
+            sync_at(&nodes[0], &redirected).await.status(),
+            StatusCode::OK
+        );
+
+        let response = router(nodes[1].state.clone())
+            .oneshot(
+                axum::http::Request::get("/cluster/members")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let members: MembersBody = serde_json::from_slice(&body).unwrap();
+        let mut expected = vec![nodes[0].url.clone(), nodes[1].url.clone()];
+        expected.sort();
+        assert_eq!(members.members, expected);
+
+        nodes[0]
+            .state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(id)
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_changing_members_needs_the_cluster_token() {
+        let url = "http://127.0.0.1:1".to_string();
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let state = AppState::new(CodeFileUsecasesImpl::new(repository));
+        let data_dir = TempDir::new().unwrap();
+        let token = ClusterToken::new("s3cret");
+        let replication = ReplicationNode::new(
+            Uuid::new_v4(),
+            state.files.clone(),
+            data_dir.path().into(),
+            vec![],
+        )
+        .unwrap()
+        .with_token(token.clone());
+        let cluster = Cluster::new(
+            url.clone(),
+            vec![url.clone()],
+            state.files.clone(),
+            replication,
+        );
+        let state = state.with_cluster(cluster);
+
+        let put = |authorization: Option<String>| {
+            let mut request = axum::http::Request::put("/cluster/members")
+                .header("content-type", "application/json");
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+            request
+                .body(Body::from(json!({ "members": [url] }).to_string()))
+                .unwrap()
+        };
+        let response = router(state.clone()).oneshot(put(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let wrong = Some("Bearer guess".to_string());
+        let response = router(state.clone()).oneshot(put(wrong)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = router(state.clone())
+            .oneshot(put(Some(token.header_value())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let list = axum::http::Request::get("/cluster/members")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state).oneshot(list).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_join_and_leave_hand_files_off() {
+        let nodes = start(3, 2).await;
+        let grown = HashRing::new(
+            DEFAULT_VIRTUAL_NODES,
+            nodes.iter().map(|node| node.url.clone()),
+        );
+        // Owned by the first node now and by the joining third one after.
+        let id = create_where(&nodes[0], |id| {
+            nodes[0].cluster.is_owner(id) && grown.owner(id) == Some(nodes[2].url.as_str())
+        });
+        edit(&nodes[0], id, "fn main() {}");
+
+        let (mut client, _) = connect_async(format!(
+            "ws://{}/files/{id}/session",
+            nodes[0].url.trim_start_matches("http://")
+        ))
+        .await
+        .unwrap();
+        let resume = json!({ "type": "resume", "last_revision": 1, "author": "ada" });
+        client.send(resume.to_string().into()).await.unwrap();
+
+        let all: Vec<&Node> = nodes.iter().collect();
+        assert_eq!(set_members(&nodes[0], &all).await, vec![id]);
+        assert!(set_members(&nodes[1], &all).await.is_empty());
+        assert!(set_members(&nodes[2], &all).await.is_empty());
+        assert_eq!(content(&nodes[2], id).as_deref(), Some("fn main() {}"));
+        assert_eq!(
+            sync(&nodes[0], id).await.status(),
+            StatusCode::TEMPORARY_REDIRECT
+        );
+
+        // The open session is told where the file went on its next edit.
+        let edit_message =
+            json!({ "type": "edit", "base_revision": 1, "start": 0, "end": 0, "text": "x" });
+        client.send(edit_message.to_string().into()).await.unwrap();
+        let moved = loop {
+            let message = tokio::time::timeout(Duration::from_secs(2), client.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            let value: Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+            if value["type"] == "moved" {
+                break value;
+            }
+        };
+        assert_eq!(moved["owner"], nodes[2].url);
+        assert_eq!(content(&nodes[0], id).as_deref(), Some("fn main() {}"));
+
+        // The third node edits as owner, then leaves and hands the file back.
+        edit(&nodes[2], id, "\n");
+        let remaining = [&nodes[0], &nodes[1]];
+        assert_eq!(set_members(&nodes[2], &remaining).await, vec![id]);
+        set_members(&nodes[0], &remaining).await;
+        set_members(&nodes[1], &remaining).await;
+        assert!(nodes[0].cluster.is_owner(id));
+        assert_eq!(content(&nodes[0], id).as_deref(), Some("fn main() {}\n"));
+        assert_eq!(sync(&nodes[0], id).await.status(), StatusCode::OK);
+
+        nodes[0]
+            .state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(id)
+            .unwrap();
+    }
+}
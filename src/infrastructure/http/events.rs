@@ -0,0 +1,335 @@
+use crate::domain::events::DomainEvent;
+use crate::infrastructure::event_bus::PublishedEvent;
+use crate::infrastructure::http::AppState;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use serde_json::json;
+use std::convert::Infallible;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use uuid::Uuid;
+
+fn last_event_id(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get("last-event-id")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn to_sse(id: u64, event: &DomainEvent) -> Event {
+    Event::default()
+        .id(id.to_string())
+        .event(event.name())
+        .json_data(event)
+        .expect("file events serialize to JSON")
+}
+
+// Whether the bus dropped events after `sequence` from its history, or the id
+// was handed out before a restart, so that replaying would miss some.
+fn is_stale(history: &[PublishedEvent], sequence: u64) -> bool {
+    match (history.first(), history.last()) {
+        (Some(oldest), Some(latest)) => {
+            sequence + 1 < oldest.sequence || sequence > latest.sequence
+        }
+        _ => sequence > 0,
+    }
+}
+
+/// Tells a client that resumed from a stale id to fetch what it shows afresh.
+/// Its id is the latest sequence held, which the live events follow on from.
+fn reset(history: &[PublishedEvent]) -> Event {
+    let latest = history.last().map_or(0, |published| published.sequence);
+    Event::default()
+        .id(latest.to_string())
+        .event("reset")
+        .json_data(json!({ "kind": "reset" }))
+        .expect("reset events serialize to JSON")
+}
+
+// What goes out before the live feed: the held events after `resume_after`
+// that `wanted` keeps, or a reset when the history no longer reaches back.
+fn replay(
+    history: Vec<PublishedEvent>,
+    resume_after: Option<u64>,
+    wanted: impl Fn(&PublishedEvent) -> bool,
+) -> Vec<Event> {
+    let Some(sequence) = resume_after else {
+        return Vec::new();
+    };
+    if is_stale(&history, sequence) {
+        return vec![reset(&history)];
+    }
+    history
+        .iter()
+        .filter(|published| published.sequence > sequence && wanted(published))
+        .map(|published| to_sse(published.sequence, &published.event))
+        .collect()
+}
+
+/// Changes to a single file. A rename or deletion leaves the revision as it
+/// was, so event ids are the bus sequence numbers, as on the workspace stream;
+/// a client that reconnects with `Last-Event-ID` gets every later event for
+/// the file still held by the bus before the live feed, or a `reset` when
+/// some are no longer held. Each payload carries the file's revision.
+pub async fn file_events(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let resume_after = last_event_id(&headers);
+    let (history, receiver) = state.events.replay_and_subscribe();
+
+    let replay = replay(history, resume_after, |published| {
+        published.event.file_id() == file_id
+    });
+    let live = UnboundedReceiverStream::new(receiver)
+        .filter(move |published| published.event.file_id() == file_id)
+        .map(|published| to_sse(published.sequence, &published.event));
+
+    let stream = tokio_stream::iter(replay).chain(live).map(Ok);
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Changes to every file, with the bus sequence numbers as event ids, resumed
+/// like the stream of a single file.
+pub async fn workspace_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let resume_after = last_event_id(&headers);
+    let (history, receiver) = state.events.replay_and_subscribe();
+
+    let replay = replay(history, resume_after, |_| true);
+    let live = UnboundedReceiverStream::new(receiver)
+        .map(|published| to_sse(published.sequence, &published.event));
+
+    let stream = tokio_stream::iter(replay).chain(live).map(Ok);
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{
+        CreateCodeFileRequest, RenameCodeFileRequest, UpdateCodeRequest,
+    };
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::application::usecases::file_operations_usecases::FileOperationsUsecases;
+    use crate::infrastructure::event_bus::EventBus;
+    use crate::infrastructure::http::router;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use serde_json::Value;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    struct ReceivedEvent {
+        id: String,
+        event: String,
+        data: Value,
+    }
+
+    fn state() -> AppState {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        AppState::new(CodeFileUsecasesImpl::new(repository))
+    }
+
+    fn create(state: &AppState) -> Uuid {
+        state
+            .files
+            .lock()
+            .unwrap()
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("sse_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn update(state: &AppState, id: Uuid, content: &str) {
+        state
+            .files
+            .lock()
+            .unwrap()
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start: 0,
+                end: 0,
+                content: content.to_string(),
+                author: "ada".to_string(),
+            })
+            .unwrap();
+    }
+
+    async fn open_stream(
+        state: &AppState,
+        uri: &str,
+        last_event_id: Option<&str>,
+    ) -> axum::body::BodyDataStream {
+        let mut request = Request::get(uri);
+        if let Some(id) = last_event_id {
+            request = request.header("last-event-id", id);
+        }
+        let response = router(state.clone())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        response.into_body().into_data_stream()
+    }
+
+    async fn read_events(
+        body: &mut axum::body::BodyDataStream,
+        count: usize,
+    ) -> Vec<ReceivedEvent> {
+        let mut text = String::new();
+        while text.matches("\n\n").count() < count {
+            let chunk = tokio::time::timeout(Duration::from_secs(2), body.next())
+                .await
+                .expect("timed out waiting for events")
+                .unwrap()
+                .unwrap();
+            text.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+
+        text.split("\n\n")
+            .filter(|block| !block.is_empty())
+            .map(|block| {
+                let field = |name: &str| {
+                    block
+                        .lines()
+                        .find_map(|line| line.strip_prefix(&format!("{name}: ")))
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                ReceivedEvent {
+                    id: field("id"),
+                    event: field("event"),
+                    data: serde_json::from_str(&field("data")).unwrap(),
+                }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_file_stream_resumes_after_last_event_id() {
+        let state = state();
+        let file_id = create(&state);
+        update(&state, file_id, "a");
+        update(&state, file_id, "b");
+
+        let uri = format!("/files/{file_id}/events");
+        let mut body = open_stream(&state, &uri, Some("2")).await;
+        update(&state, file_id, "c");
+        state
+            .files
+            .lock()
+            .unwrap()
+            .rename_code_file(RenameCodeFileRequest {
+                id: file_id,
+                name: format!("sse_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap();
+        state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(file_id)
+            .unwrap();
+
+        let events = read_events(&mut body, 4).await;
+        let ids: Vec<&str> = events.iter().map(|e| e.id.as_str()).collect();
+        let kinds: Vec<&str> = events.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(ids, vec!["3", "4", "5", "6"]);
+        assert_eq!(kinds, vec!["updated", "updated", "renamed", "deleted"]);
+        assert_eq!(
+            events[0].data,
+            json!({
+                "kind": "updated",
+                "file_id": file_id,
+                "range": {"start": 0, "end": 0},
+                "text": "b",
+                "author": "ada",
+                "revision": 2
+            })
+        );
+
+        // The rename keeps the revision of the edit before it, and is still
+        // sent to a client that saw that edit.
+        assert_eq!(events[2].data["revision"], json!(3));
+        let mut body = open_stream(&state, &uri, Some("4")).await;
+        let events = read_events(&mut body, 2).await;
+        let kinds: Vec<&str> = events.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(kinds, vec!["renamed", "deleted"]);
+    }
+
+    #[tokio::test]
+    async fn test_file_stream_without_last_event_id_is_live_only() {
+        let state = state();
+        let file_id = create(&state);
+        let other_id = create(&state);
+        update(&state, file_id, "before");
+
+        let uri = format!("/files/{file_id}/events");
+        let mut body = open_stream(&state, &uri, None).await;
+        update(&state, other_id, "elsewhere");
+        update(&state, file_id, "after");
+
+        let events = read_events(&mut body, 1).await;
+        assert_eq!(events[0].id, "5");
+        assert_eq!(events[0].data["file_id"], json!(file_id));
+    }
+
+    #[tokio::test]
+    async fn test_stale_last_event_id_gets_a_reset() {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut files = CodeFileUsecasesImpl::new(repository);
+        files.events = EventBus::new(2);
+        let state = AppState::new(files);
+        let file_id = create(&state);
+        for content in ["a", "b", "c"] {
+            update(&state, file_id, content);
+        }
+
+        // Sequence 2 is no longer held, and 99 was never handed out.
+        let uri = format!("/files/{file_id}/events");
+        let cases = [(uri.as_str(), "1", "4"), ("/workspace/events", "99", "5")];
+        for (uri, last_event_id, latest) in cases {
+            let mut body = open_stream(&state, uri, Some(last_event_id)).await;
+            update(&state, file_id, "d");
+
+            let events = read_events(&mut body, 2).await;
+            assert_eq!(
+                (events[0].id.as_str(), events[0].event.as_str()),
+                (latest, "reset")
+            );
+            assert_eq!(events[1].event, "updated");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_workspace_stream_uses_sequence_ids() {
+        let state = state();
+        let file_id = create(&state);
+        update(&state, file_id, "a");
+
+        let mut body = open_stream(&state, "/workspace/events", Some("1")).await;
+        let created_id = create(&state);
+
+        let events = read_events(&mut body, 2).await;
+        let ids: Vec<&str> = events.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["2", "3"]);
+        assert_eq!(events[0].data["revision"], json!(1));
+        assert_eq!(events[1].event, "created");
+        assert_eq!(events[1].data["file_id"], json!(created_id));
+    }
+}
@@ -0,0 +1,133 @@
+pub mod auth;
+pub mod checkpoints;
+pub mod cluster;
+pub mod events;
+pub mod playback;
+pub mod session;
+pub mod sync;
+pub mod webhooks;
+
+use crate::application::usecases::async_code_file_usecases::AsyncCodeFileUsecasesImpl;
+use crate::application::usecases::checkpoint_usecases::CheckpointUsecasesImpl;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::application::usecases::webhook_usecases::WebhookUsecasesImpl;
+use crate::infrastructure::cluster::Cluster;
+use crate::infrastructure::document_actor::{DEFAULT_IDLE_TIMEOUT, DocumentActors};
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::persistence::in_memory_checkpoint_repository::InMemoryCheckpointRepository;
+use crate::infrastructure::persistence::in_memory_webhook_repository::InMemoryWebhookRepository;
+use axum::Router;
+use axum::middleware;
+use axum::routing::{delete, get, post, put};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub events: EventBus,
+    /// For the background tasks and usecases that need `&mut` access. The
+    /// mutex doesn't keep them apart from `documents`: the usecases they call
+    /// take the writer lock of each file they change, as the documents do.
+    pub files: Arc<Mutex<CodeFileUsecasesImpl>>,
+    /// The same files for handlers: writes queue on their document, so
+    /// handlers never wait on a lock over every file, and file IO stays off
+    /// the executor.
+    pub documents: DocumentActors,
+    pub webhooks: Arc<Mutex<WebhookUsecasesImpl>>,
+    pub checkpoints: Arc<Mutex<CheckpointUsecasesImpl>>,
+    pub cluster: Option<Cluster>,
+}
+
+impl AppState {
+    pub fn new(files: CodeFileUsecasesImpl) -> Self {
+        let events = files.events.clone();
+        let documents =
+            DocumentActors::new(AsyncCodeFileUsecasesImpl::new(&files), DEFAULT_IDLE_TIMEOUT);
+        let files = Arc::new(Mutex::new(files));
+        Self {
+            events,
+            documents,
+            checkpoints: Arc::new(Mutex::new(CheckpointUsecasesImpl::new(
+                Box::new(InMemoryCheckpointRepository::new()),
+                Arc::clone(&files),
+            ))),
+            files,
+            webhooks: Arc::new(Mutex::new(WebhookUsecasesImpl::new(Box::new(
+                InMemoryWebhookRepository::new(),
+            )))),
+            cluster: None,
+        }
+    }
+
+    /// Serves these registrations instead. Deliveries only go out once a
+    /// `WebhookDispatcher` over the same repository is registered on `events`.
+    pub fn with_webhooks(mut self, webhooks: WebhookUsecasesImpl) -> Self {
+        self.webhooks = Arc::new(Mutex::new(webhooks));
+        self
+    }
+
+    pub fn with_cluster(mut self, cluster: Cluster) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    let files = Router::new()
+        .route("/files/{file_id}/events", get(events::file_events))
+        .route("/files/{file_id}/playback", get(playback::file_playback))
+        .route(
+            "/files/{file_id}/recording",
+            get(playback::export_recording),
+        )
+        .route("/files/{file_id}/session", get(session::file_session))
+        .route("/files/{file_id}/sync", post(sync::sync_file))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            cluster::route_to_owner,
+        ));
+    // Only nodes holding the cluster token may change the membership.
+    let token = state.cluster.as_ref().and_then(Cluster::token);
+    Router::new()
+        .merge(files)
+        .route(
+            "/cluster/members",
+            get(cluster::list_members).merge(
+                put(cluster::set_members)
+                    .route_layer(middleware::from_fn_with_state(token, auth::require_token)),
+            ),
+        )
+        .route(
+            "/checkpoints",
+            get(checkpoints::list_checkpoints).post(checkpoints::create_checkpoint),
+        )
+        .route(
+            "/checkpoints/{checkpoint_id}",
+            delete(checkpoints::delete_checkpoint),
+        )
+        .route(
+            "/checkpoints/{checkpoint_id}/compare",
+            get(checkpoints::compare_checkpoint),
+        )
+        .route(
+            "/checkpoints/{checkpoint_id}/restore",
+            post(checkpoints::restore_checkpoint),
+        )
+        .route(
+            "/webhooks",
+            get(webhooks::list_webhooks).post(webhooks::register_webhook),
+        )
+        .route(
+            "/webhooks/{webhook_id}",
+            delete(webhooks::unregister_webhook),
+        )
+        .route(
+            "/webhooks/{webhook_id}/deliveries",
+            get(webhooks::list_deliveries),
+        )
+        .route("/workspace/events", get(events::workspace_events))
+        .with_state(state)
+}
+
+pub async fn serve(listener: tokio::net::TcpListener, state: AppState) -> std::io::Result<()> {
+    axum::serve(listener, router(state)).await
+}
@@ -0,0 +1,442 @@
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::playback_usecases::PlaybackUsecases;
+use crate::domain::recording::{Recording, RecordingFrame};
+use crate::infrastructure::http::AppState;
+use axum::Json;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct PlaybackQuery {
+    speed: Option<f64>,
+    position_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Seek { position_ms: u64 },
+    Speed { speed: f64 },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Position {
+        position_ms: u64,
+        duration_ms: u64,
+        revision: u64,
+        content: String,
+    },
+    Operation(RecordingFrame),
+    End {
+        revision: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+fn status(e: &ApplicationError) -> StatusCode {
+    match e {
+        ApplicationError::FileNotFound(_) => StatusCode::NOT_FOUND,
+        ApplicationError::HistoryUnavailable(_) => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn recording(state: &AppState, file_id: Uuid) -> Result<Recording, ApplicationError> {
+    state
+        .documents
+        .read(move |files| files.export_recording(file_id))
+        .await
+}
+
+const MIN_SPEED: f64 = 0.01;
+const MAX_SPEED: f64 = 100.0;
+
+// Any positive speed is taken, held to what the clock can keep up with.
+fn playback_speed(speed: f64) -> Option<f64> {
+    (speed.is_finite() && speed > 0.0).then(|| speed.clamp(MIN_SPEED, MAX_SPEED))
+}
+
+/// Downloads the recording of a file as a JSON file that can be played back
+/// without the server. A 409 means its history no longer reaches back to a
+/// snapshot.
+pub async fn export_recording(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+) -> Response {
+    match recording(&state, file_id).await {
+        Ok(recording) => {
+            let disposition = format!(
+                "attachment; filename=\"{}.recording.json\"",
+                recording.name.replace(['"', '\\'], "_")
+            );
+            (
+                [(header::CONTENT_DISPOSITION, disposition)],
+                Json(recording),
+            )
+                .into_response()
+        }
+        Err(e) => (status(&e), Json(json!({ "error": format!("{e:?}") }))).into_response(),
+    }
+}
+
+/// Plays a file's recording back with its original timing, scaled by `speed`.
+/// The server opens with `position`, the text at `position_ms`, then sends
+/// each later edit as an `operation` when it falls due, and `end` after the
+/// last. The client can `seek` to another position, answered by a fresh
+/// `position`, or change the `speed` at any time.
+pub async fn file_playback(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<PlaybackQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| run_playback(socket, state, file_id, query))
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> bool {
+    let text = serde_json::to_string(message).expect("playback messages serialize to JSON");
+    socket.send(Message::Text(text.into())).await.is_ok()
+}
+
+async fn next_message(socket: &mut WebSocket) -> Option<Result<ClientMessage, String>> {
+    loop {
+        match socket.recv().await? {
+            Ok(Message::Text(text)) => {
+                return Some(serde_json::from_str(text.as_str()).map_err(|e| e.to_string()));
+            }
+            Ok(Message::Close(_)) | Err(_) => return None,
+            Ok(_) => {},
+        }
+    }
+}
+
+/// The playback clock: `position_ms` of the recording was reached at `since`,
+/// and it has advanced `speed` times as fast as real time since.
+struct Clock {
+    position_ms: u64,
+    since: Instant,
+    speed: f64,
+}
+
+impl Clock {
+    fn new(position_ms: u64, speed: f64) -> Self {
+        Self {
+            position_ms,
+            since: Instant::now(),
+            speed,
+        }
+    }
+
+    fn position_ms(&self) -> u64 {
+        let elapsed = self.since.elapsed().as_secs_f64() * 1000.0 * self.speed;
+        self.position_ms.saturating_add(elapsed as u64)
+    }
+
+    /// When the recording reaches `at_ms`; `None` if that is too far off to
+    /// represent.
+    fn due(&self, at_ms: u64) -> Option<Instant> {
+        let wait = at_ms.saturating_sub(self.position_ms) as f64 / self.speed;
+        let wait = Duration::try_from_secs_f64(wait / 1000.0).ok()?;
+        self.since.checked_add(wait)
+    }
+}
+
+struct Playback {
+    recording: Recording,
+    clock: Clock,
+    next_frame: usize,
+    ended: bool,
+}
+
+impl Playback {
+    async fn seek(&mut self, socket: &mut WebSocket, position_ms: u64) -> bool {
+        let position = self.recording.seek(position_ms);
+        self.clock = Clock::new(position_ms, self.clock.speed);
+        self.next_frame = position.next_frame;
+        self.ended = false;
+        send(
+            socket,
+            &ServerMessage::Position {
+                position_ms,
+                duration_ms: self.recording.duration_ms(),
+                revision: position.revision,
+                content: position.content,
+            },
+        )
+        .await
+    }
+
+    async fn play_due(&mut self, socket: &mut WebSocket) -> bool {
+        let now = self.clock.position_ms();
+        while let Some(frame) = self.recording.frames.get(self.next_frame) {
+            if frame.at_ms > now {
+                return true;
+            }
+            if !send(socket, &ServerMessage::Operation(frame.clone())).await {
+                return false;
+            }
+            self.next_frame += 1;
+        }
+        if !self.ended {
+            self.ended = true;
+            let revision = self
+                .recording
+                .frames
+                .last()
+                .map_or(self.recording.base_revision, |frame| frame.revision);
+            return send(socket, &ServerMessage::End { revision }).await;
+        }
+        true
+    }
+
+    async fn handle(&mut self, socket: &mut WebSocket, message: ClientMessage) -> bool {
+        match message {
+            ClientMessage::Seek { position_ms } => self.seek(socket, position_ms).await,
+            ClientMessage::Speed { speed } => match playback_speed(speed) {
+                Some(speed) => {
+                    self.clock = Clock::new(self.clock.position_ms(), speed);
+                    true
+                }
+                None => {
+                    let message = format!("invalid speed {speed}");
+                    send(socket, &ServerMessage::Error { message }).await
+                }
+            },
+        }
+    }
+}
+
+async fn run_playback(mut socket: WebSocket, state: AppState, file_id: Uuid, query: PlaybackQuery) {
+    let requested = query.speed.unwrap_or(1.0);
+    let Some(speed) = playback_speed(requested) else {
+        let message = format!("invalid speed {requested}");
+        send(&mut socket, &ServerMessage::Error { message }).await;
+        return;
+    };
+    let recording = match recording(&state, file_id).await {
+        Ok(recording) => recording,
+        Err(e) => {
+            let message = format!("{e:?}");
+            send(&mut socket, &ServerMessage::Error { message }).await;
+            return;
+        }
+    };
+
+    let mut playback = Playback {
+        recording,
+        clock: Clock::new(0, speed),
+        next_frame: 0,
+        ended: false,
+    };
+    if !playback
+        .seek(&mut socket, query.position_ms.unwrap_or(0))
+        .await
+    {
+        return;
+    }
+
+    loop {
+        if !playback.play_due(&mut socket).await {
+            return;
+        }
+        let due = playback
+            .recording
+            .frames
+            .get(playback.next_frame)
+            .and_then(|frame| playback.clock.due(frame.at_ms));
+        tokio::select! {
+            _ = tokio::time::sleep_until(due.unwrap_or_else(Instant::now)), if due.is_some() => {},
+            message = next_message(&mut socket) => {
+                let handled = match message {
+                    Some(Ok(message)) => playback.handle(&mut socket, message).await,
+                    Some(Err(message)) => send(&mut socket, &ServerMessage::Error { message }).await,
+                    None => false,
+                };
+                if !handled {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::infrastructure::http::{router, serve};
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::Value;
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+    use tower::ServiceExt;
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn state() -> AppState {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        AppState::new(CodeFileUsecasesImpl::new(repository))
+    }
+
+    fn create(state: &AppState) -> Uuid {
+        state
+            .files
+            .lock()
+            .unwrap()
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("playback_http_{}.rs", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn update(state: &AppState, id: Uuid, start: u64, content: &str) {
+        state
+            .files
+            .lock()
+            .unwrap()
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start,
+                end: start,
+                content: content.to_string(),
+                author: "grace".to_string(),
+            })
+            .unwrap();
+    }
+
+    async fn connect(state: &AppState, file_id: Uuid, query: &str) -> Client {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, state.clone()));
+        let url = format!("ws://{address}/files/{file_id}/playback?{query}");
+        let (client, _) = connect_async(url).await.unwrap();
+        client
+    }
+
+    async fn next_json(client: &mut Client) -> Value {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(2), client.next())
+                .await
+                .expect("timed out waiting for a message")
+                .unwrap()
+                .unwrap();
+            if message.is_text() {
+                return serde_json::from_str(message.to_text().unwrap()).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_playback_streams_with_timing_and_seeks() {
+        let state = state();
+        let id = create(&state);
+        update(&state, id, 0, "fn main");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        update(&state, id, 7, "() {}");
+
+        let mut client = connect(&state, id, "speed=2").await;
+        let started = Instant::now();
+        let position = next_json(&mut client).await;
+        assert_eq!(position["type"], "position");
+        assert_eq!(
+            (position["revision"].as_u64(), position["content"].as_str()),
+            (Some(0), Some(""))
+        );
+        assert!(position["duration_ms"].as_u64().unwrap() >= 200);
+
+        let first = next_json(&mut client).await;
+        assert_eq!(
+            (first["type"].as_str(), first["text"].as_str()),
+            (Some("operation"), Some("fn main"))
+        );
+        let second = next_json(&mut client).await;
+        assert_eq!(
+            (second["revision"].as_u64(), second["text"].as_str()),
+            (Some(2), Some("() {}"))
+        );
+        // Twice as fast: the second edit comes no sooner than 100ms in.
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(next_json(&mut client).await["type"], "end");
+
+        let seek = json!({ "type": "seek", "position_ms": 100 });
+        client.send(seek.to_string().into()).await.unwrap();
+        let position = next_json(&mut client).await;
+        assert_eq!(
+            (position["revision"].as_u64(), position["content"].as_str()),
+            (Some(1), Some("fn main"))
+        );
+        let speed = json!({ "type": "speed", "speed": 0 });
+        client.send(speed.to_string().into()).await.unwrap();
+        assert_eq!(next_json(&mut client).await["type"], "error");
+        assert_eq!(next_json(&mut client).await["type"], "operation");
+
+        state.files.lock().unwrap().delete_code_file(id).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_clock_scales_time_by_speed() {
+        tokio::time::pause();
+        let clock = Clock::new(100, playback_speed(2.0).unwrap());
+        assert_eq!(
+            clock.due(300),
+            Some(clock.since + Duration::from_millis(100))
+        );
+        tokio::time::advance(Duration::from_millis(50)).await;
+        assert_eq!(clock.position_ms(), 200);
+
+        assert_eq!(playback_speed(1000.0), Some(MAX_SPEED));
+        assert_eq!(playback_speed(0.0001), Some(MIN_SPEED));
+        assert_eq!(playback_speed(f64::NAN), None);
+    }
+
+    #[tokio::test]
+    async fn test_export_downloads_the_recording() {
+        let state = state();
+        let id = create(&state);
+        update(&state, id, 0, "let x = 1;");
+
+        let request = Request::get(format!("/files/{id}/recording"))
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let name = state.files.lock().unwrap().get_code_file(id).unwrap().name;
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            format!("attachment; filename=\"{name}.recording.json\"").as_str()
+        );
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let recording: Recording = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            recording.seek(u64::MAX).content,
+            "let x = 1;"
+        );
+
+        let request = Request::get(format!("/files/{}/recording", Uuid::new_v4()))
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        state.files.lock().unwrap().delete_code_file(id).unwrap();
+    }
+}
@@ -0,0 +1,685 @@
+use crate::application::dto::resume::{
+    AppliedEdit, CatchUp, MissedOperation, PendingEdit, ResumeRequest, ResumeResponse,
+};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::resume_usecases::ResumeUsecases;
+use crate::domain::events::DomainEvent;
+use crate::infrastructure::event_bus::PublishedEvent;
+use crate::infrastructure::http::AppState;
+use crate::infrastructure::operation_history::OperationHistory;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::mpsc::UnboundedReceiver;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Resume {
+        last_revision: u64,
+        author: String,
+        #[serde(default)]
+        pending: Vec<EditMessage>,
+    },
+    Edit {
+        base_revision: u64,
+        #[serde(flatten)]
+        edit: EditMessage,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EditMessage {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+struct OperationMessage {
+    revision: u64,
+    start: usize,
+    end: usize,
+    text: String,
+    author: String,
+}
+
+#[derive(Debug, Serialize)]
+struct AppliedMessage {
+    revision: u64,
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Operations {
+        operations: Vec<OperationMessage>,
+    },
+    Snapshot {
+        revision: u64,
+        content: String,
+    },
+    Ack {
+        revision: u64,
+        applied: Vec<AppliedMessage>,
+        rejected: Vec<EditMessage>,
+    },
+    Operation(OperationMessage),
+    Deleted {
+        revision: u64,
+    },
+    Renamed {
+        name: String,
+    },
+    Moved {
+        owner: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl From<MissedOperation> for OperationMessage {
+    fn from(operation: MissedOperation) -> Self {
+        Self {
+            revision: operation.revision,
+            start: operation.start,
+            end: operation.end,
+            text: operation.text,
+            author: operation.author,
+        }
+    }
+}
+
+impl From<AppliedEdit> for AppliedMessage {
+    fn from(edit: AppliedEdit) -> Self {
+        Self {
+            revision: edit.revision,
+            start: edit.start,
+            end: edit.end,
+            text: edit.text,
+        }
+    }
+}
+
+impl From<EditMessage> for PendingEdit {
+    fn from(edit: EditMessage) -> Self {
+        Self {
+            start: edit.start,
+            end: edit.end,
+            text: edit.text,
+        }
+    }
+}
+
+impl From<PendingEdit> for EditMessage {
+    fn from(edit: PendingEdit) -> Self {
+        Self {
+            start: edit.start,
+            end: edit.end,
+            text: edit.text,
+        }
+    }
+}
+
+fn ack(response: ResumeResponse) -> ServerMessage {
+    ServerMessage::Ack {
+        revision: response.revision,
+        applied: response.applied.into_iter().map(Into::into).collect(),
+        rejected: response.rejected.into_iter().map(Into::into).collect(),
+    }
+}
+
+fn error(e: ApplicationError) -> ServerMessage {
+    ServerMessage::Error {
+        message: format!("{e:?}"),
+    }
+}
+
+/// A live editing session on one file. The client opens with `resume`,
+/// giving the last revision it acknowledged and the edits it made since; it
+/// gets the missed operations (or a snapshot), then an `ack` for its edits,
+/// then every later operation by others, and `renamed` when the file gets a
+/// new name. Further edits are sent one at a time as `edit` against the
+/// revision the client has reached, each answered by an `ack` once applied.
+/// If the file moves to another node, the next edit is answered with `moved`
+/// and the session ends.
+pub async fn file_session(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| run_session(socket, state, file_id))
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> bool {
+    let text = serde_json::to_string(message).expect("session messages serialize to JSON");
+    socket.send(Message::Text(text.into())).await.is_ok()
+}
+
+async fn next_message(socket: &mut WebSocket) -> Option<Result<ClientMessage, String>> {
+    loop {
+        match socket.recv().await? {
+            Ok(Message::Text(text)) => {
+                return Some(serde_json::from_str(text.as_str()).map_err(|e| e.to_string()));
+            }
+            Ok(Message::Close(_)) | Err(_) => return None,
+            Ok(_) => {},
+        }
+    }
+}
+
+// The session's pin in the operation history, which keeps the edits after
+// the revision the client last built on. It is let go when the session ends,
+// however it ends.
+struct SessionPin {
+    history: OperationHistory,
+    file_id: Uuid,
+    holder: String,
+}
+
+impl SessionPin {
+    fn new(history: OperationHistory, file_id: Uuid, revision: u64) -> Self {
+        let holder = format!("session:{}", Uuid::new_v4());
+        history.pin(file_id, &holder, revision);
+        Self {
+            history,
+            file_id,
+            holder,
+        }
+    }
+
+    fn move_to(&self, revision: u64) {
+        self.history.pin(self.file_id, &self.holder, revision);
+    }
+}
+
+impl Drop for SessionPin {
+    fn drop(&mut self) {
+        self.history.unpin(self.file_id, &self.holder);
+    }
+}
+
+struct Session {
+    file_id: Uuid,
+    author: String,
+    pin: SessionPin,
+    // Revisions this session applied, which the client already has.
+    own: HashSet<u64>,
+    events: UnboundedReceiver<PublishedEvent>,
+}
+
+impl Session {
+    /// Sends `published` on if it is someone else's change to the file.
+    /// Returns false once the session should end.
+    async fn forward(&mut self, socket: &mut WebSocket, published: PublishedEvent) -> bool {
+        if published.event.file_id() != self.file_id {
+            return true;
+        }
+        match published.event {
+            DomainEvent::FileEdited {
+                range,
+                text,
+                author,
+                revision,
+                ..
+            } => {
+                if self.own.remove(&revision) {
+                    return true;
+                }
+                let operation = OperationMessage {
+                    revision,
+                    start: range.start,
+                    end: range.end,
+                    text,
+                    author,
+                };
+                send(socket, &ServerMessage::Operation(operation)).await
+            }
+            DomainEvent::FileDeleted { revision, .. } => {
+                send(socket, &ServerMessage::Deleted { revision }).await;
+                false
+            }
+            DomainEvent::FileRenamed { name, .. } => {
+                send(socket, &ServerMessage::Renamed { name }).await
+            }
+            DomainEvent::FileCreated { .. } => true,
+        }
+    }
+
+    async fn edit(
+        &mut self,
+        socket: &mut WebSocket,
+        state: &AppState,
+        base_revision: u64,
+        edit: EditMessage,
+    ) -> bool {
+        // The file was handed off to another node; the client reconnects there.
+        if let Some(cluster) = &state.cluster
+            && !cluster.is_owner(self.file_id)
+        {
+            let owner = cluster.owner(self.file_id);
+            send(socket, &ServerMessage::Moved { owner }).await;
+            return false;
+        }
+        self.pin.move_to(base_revision);
+        let request = ResumeRequest {
+            file_id: self.file_id,
+            last_revision: base_revision,
+            author: self.author.clone(),
+            pending: vec![edit.into()],
+        };
+        let result = state
+            .documents
+            .write(self.file_id, move |files| files.resume_session(request))
+            .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => return send(socket, &error(e)).await,
+        };
+        self.own
+            .extend(response.applied.iter().map(|edit| edit.revision));
+        // Changes by others that landed before this edit are already queued;
+        // send them first so the client sees revisions in order.
+        while let Ok(published) = self.events.try_recv() {
+            if !self.forward(socket, published).await {
+                return false;
+            }
+        }
+        send(socket, &ack(response)).await
+    }
+}
+
+async fn run_session(mut socket: WebSocket, state: AppState, file_id: Uuid) {
+    let (last_revision, author, pending) = match next_message(&mut socket).await {
+        Some(Ok(ClientMessage::Resume {
+            last_revision,
+            author,
+            pending,
+        })) => (last_revision, author, pending),
+        Some(Ok(_)) => {
+            let message = "the first message must be a resume".to_string();
+            send(&mut socket, &ServerMessage::Error { message }).await;
+            return;
+        }
+        Some(Err(message)) => {
+            send(&mut socket, &ServerMessage::Error { message }).await;
+            return;
+        }
+        None => return,
+    };
+
+    // Subscribing under the file's writer lock means every later change to
+    // it reaches the receiver and nothing before it does.
+    let request = ResumeRequest {
+        file_id,
+        last_revision,
+        author: author.clone(),
+        pending: pending.into_iter().map(Into::into).collect(),
+    };
+    let result = state
+        .documents
+        .write(file_id, move |files| {
+            let events = files.events.subscribe();
+            let response = files.resume_session(request)?;
+            let pin = SessionPin::new(files.history.clone(), file_id, response.revision);
+            Ok((events, response, pin))
+        })
+        .await;
+    let (events, response, pin) = match result {
+        Ok(resumed) => resumed,
+        Err(e) => {
+            send(&mut socket, &error(e)).await;
+            return;
+        }
+    };
+
+    let catch_up = match response.catch_up.clone() {
+        CatchUp::Operations(operations) => ServerMessage::Operations {
+            operations: operations.into_iter().map(Into::into).collect(),
+        },
+        CatchUp::Snapshot { revision, content } => ServerMessage::Snapshot { revision, content },
+    };
+    let mut session = Session {
+        file_id,
+        author,
+        pin,
+        own: response.applied.iter().map(|edit| edit.revision).collect(),
+        events,
+    };
+    if !send(&mut socket, &catch_up).await || !send(&mut socket, &ack(response)).await {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            message = next_message(&mut socket) => {
+                let open = match message {
+                    Some(Ok(ClientMessage::Edit { base_revision, edit })) => {
+                        session.edit(&mut socket, &state, base_revision, edit).await
+                    }
+                    Some(Ok(ClientMessage::Resume { .. })) => {
+                        let message = "the session has already resumed".to_string();
+                        send(&mut socket, &ServerMessage::Error { message }).await
+                    }
+                    Some(Err(message)) => send(&mut socket, &ServerMessage::Error { message }).await,
+                    None => false,
+                };
+                if !open {
+                    break;
+                }
+            }
+            published = session.events.recv() => {
+                let Some(published) = published else { break };
+                if !session.forward(&mut socket, published).await {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{
+        CreateCodeFileRequest, RenameCodeFileRequest, UpdateCodeRequest,
+    };
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::application::usecases::file_operations_usecases::FileOperationsUsecases;
+    use crate::infrastructure::http::serve;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::operation_history::OperationHistory;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::{Value, json};
+    use std::time::Duration;
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn state(history: OperationHistory) -> AppState {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut files = CodeFileUsecasesImpl::new(repository);
+        files.history = history;
+        AppState::new(files)
+    }
+
+    fn create(state: &AppState) -> Uuid {
+        state
+            .files
+            .lock()
+            .unwrap()
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("session_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn update(state: &AppState, id: Uuid, start: u64, content: &str) {
+        state
+            .files
+            .lock()
+            .unwrap()
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start,
+                end: start,
+                content: content.to_string(),
+                author: "grace".to_string(),
+            })
+            .unwrap();
+    }
+
+    fn content(state: &AppState, id: Uuid) -> String {
+        state
+            .files
+            .lock()
+            .unwrap()
+            .get_code_file(id)
+            .unwrap()
+            .viewport
+            .content
+    }
+
+    async fn connect(state: &AppState, file_id: Uuid) -> Client {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, state.clone()));
+        let (client, _) = connect_async(format!("ws://{address}/files/{file_id}/session"))
+            .await
+            .unwrap();
+        client
+    }
+
+    async fn send_json(client: &mut Client, value: Value) {
+        client.send(value.to_string().into()).await.unwrap();
+    }
+
+    async fn next_json(client: &mut Client) -> Value {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(2), client.next())
+                .await
+                .expect("timed out waiting for a message")
+                .unwrap()
+                .unwrap();
+            if message.is_text() {
+                return serde_json::from_str(message.to_text().unwrap()).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_replays_then_streams_live_edits() {
+        let state = state(OperationHistory::default());
+        let file_id = create(&state);
+        update(&state, file_id, 0, "let x = 1;");
+        update(&state, file_id, 0, "// config\n");
+
+        let mut client = connect(&state, file_id).await;
+        send_json(
+            &mut client,
+            json!({
+                "type": "resume",
+                "last_revision": 1,
+                "author": "ada",
+                "pending": [{"start": 8, "end": 9, "text": "2"}]
+            }),
+        )
+        .await;
+
+        assert_eq!(
+            next_json(&mut client).await,
+            json!({
+                "type": "operations",
+                "operations": [
+                    {"revision": 2, "start": 0, "end": 0, "text": "// config\n", "author": "grace"}
+                ]
+            })
+        );
+        assert_eq!(
+            next_json(&mut client).await,
+            json!({
+                "type": "ack",
+                "revision": 3,
+                "applied": [{"revision": 3, "start": 18, "end": 19, "text": "2"}],
+                "rejected": []
+            })
+        );
+        assert_eq!(content(&state, file_id), "// config\nlet x = 2;");
+
+        update(&state, file_id, 20, "\n");
+        let operation = next_json(&mut client).await;
+        assert_eq!(operation["type"], "operation");
+        assert_eq!(operation["revision"], 4);
+
+        send_json(
+            &mut client,
+            json!({"type": "edit", "base_revision": 4, "start": 21, "end": 21, "text": "x += 1;"}),
+        )
+        .await;
+        let ack = next_json(&mut client).await;
+        assert_eq!(
+            (ack["type"].clone(), ack["revision"].clone()),
+            (json!("ack"), json!(5))
+        );
+
+        // The session's own edit is not echoed back.
+        update(&state, file_id, 0, "\n");
+        assert_eq!(next_json(&mut client).await["revision"], 6);
+        assert_eq!(content(&state, file_id), "\n// config\nlet x = 2;\nx += 1;");
+
+        state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(file_id)
+            .unwrap();
+        assert_eq!(
+            next_json(&mut client).await,
+            json!({"type": "deleted", "revision": 6})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rename_is_sent_to_the_session() {
+        let state = state(OperationHistory::default());
+        let file_id = create(&state);
+        update(&state, file_id, 0, "a");
+
+        let mut client = connect(&state, file_id).await;
+        send_json(
+            &mut client,
+            json!({"type": "resume", "last_revision": 1, "author": "ada"}),
+        )
+        .await;
+        assert_eq!(next_json(&mut client).await["type"], "operations");
+        assert_eq!(next_json(&mut client).await["type"], "ack");
+
+        let name = format!("session_{}.txt", Uuid::new_v4());
+        state
+            .files
+            .lock()
+            .unwrap()
+            .rename_code_file(RenameCodeFileRequest {
+                id: file_id,
+                name: name.clone(),
+            })
+            .unwrap();
+        assert_eq!(
+            next_json(&mut client).await,
+            json!({"type": "renamed", "name": name})
+        );
+
+        update(&state, file_id, 1, "b");
+        assert_eq!(next_json(&mut client).await["revision"], 2);
+        assert_eq!(content(&state, file_id), "ab");
+
+        state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(file_id)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_sends_snapshot_when_history_is_gone() {
+        let state = state(OperationHistory::new(1));
+        let file_id = create(&state);
+        update(&state, file_id, 0, "a");
+        update(&state, file_id, 1, "b");
+
+        let mut client = connect(&state, file_id).await;
+        send_json(
+            &mut client,
+            json!({
+                "type": "resume",
+                "last_revision": 0,
+                "author": "ada",
+                "pending": [{"start": 0, "end": 0, "text": "z"}]
+            }),
+        )
+        .await;
+
+        assert_eq!(
+            next_json(&mut client).await,
+            json!({"type": "snapshot", "revision": 2, "content": "ab"})
+        );
+        assert_eq!(
+            next_json(&mut client).await,
+            json!({
+                "type": "ack",
+                "revision": 2,
+                "applied": [],
+                "rejected": [{"start": 0, "end": 0, "text": "z"}]
+            })
+        );
+
+        state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(file_id)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_must_start_with_resume() {
+        let state = state(OperationHistory::default());
+        let file_id = create(&state);
+
+        let mut client = connect(&state, file_id).await;
+        send_json(
+            &mut client,
+            json!({"type": "edit", "base_revision": 0, "start": 0, "end": 0, "text": "x"}),
+        )
+        .await;
+        assert_eq!(next_json(&mut client).await["type"], "error");
+        assert_eq!(content(&state, file_id), "");
+
+        state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(file_id)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_session_pin_is_released_when_the_session_panics() {
+        let history = OperationHistory::new(1);
+        let file_id = Uuid::new_v4();
+        let edit = |revision: u64| DomainEvent::FileEdited {
+            file_id,
+            range: 0..0,
+            text: "x".to_string(),
+            author: "ada".to_string(),
+            revision,
+        };
+
+        let session_history = history.clone();
+        let result = std::panic::catch_unwind(move || {
+            let _pin = SessionPin::new(session_history.clone(), file_id, 0);
+            session_history.record(&edit(1));
+            session_history.record(&edit(2));
+            assert_eq!(session_history.len(), 2);
+            panic!("session failed");
+        });
+        assert!(result.is_err());
+
+        // Nothing holds the edits back from the capacity any more.
+        history.record(&edit(3));
+        assert_eq!(history.len(), 1);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::application::dto::resume::PendingEdit;
+use crate::application::dto::sync::{SyncOperation, SyncRequest};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::sync_usecases::SyncUsecases;
+use crate::infrastructure::http::AppState;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct SyncBody {
+    base_revision: u64,
+    author: String,
+    #[serde(default)]
+    operations: Vec<OperationBody>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationBody {
+    start: usize,
+    end: usize,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncReply {
+    revision: u64,
+    applied: usize,
+    operations: Vec<OperationBody>,
+}
+
+impl From<SyncOperation> for OperationBody {
+    fn from(operation: SyncOperation) -> Self {
+        Self {
+            start: operation.start,
+            end: operation.end,
+            text: operation.text,
+            author: Some(operation.author),
+        }
+    }
+}
+
+fn status(e: &ApplicationError) -> StatusCode {
+    match e {
+        ApplicationError::FileNotFound(_) => StatusCode::NOT_FOUND,
+        ApplicationError::UnknownRevision(_) | ApplicationError::HistoryUnavailable(_) => {
+            StatusCode::CONFLICT
+        }
+        ApplicationError::InvalidRange(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Uploads the operation log of a client that was offline. A 409 means the
+/// server no longer has the history back to `base_revision`; the client has
+/// to fetch the file again and reapply its changes by hand.
+pub async fn sync_file(
+    State(state): State<AppState>,
+    Path(file_id): Path<Uuid>,
+    Json(body): Json<SyncBody>,
+) -> Result<Json<SyncReply>, (StatusCode, Json<Value>)> {
+    let request = SyncRequest {
+        file_id,
+        base_revision: body.base_revision,
+        author: body.author,
+        operations: body
+            .operations
+            .into_iter()
+            .map(|operation| PendingEdit {
+                start: operation.start,
+                end: operation.end,
+                text: operation.text,
+            })
+            .collect(),
+    };
+    let result = state
+        .documents
+        .write(file_id, move |files| files.sync_file(request))
+        .await;
+    match result {
+        Ok(response) => Ok(Json(SyncReply {
+            revision: response.revision,
+            applied: response.applied,
+            operations: response.operations.into_iter().map(Into::into).collect(),
+        })),
+        Err(e) => Err((status(&e), Json(json!({ "error": format!("{e:?}") })))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::{CreateCodeFileRequest, UpdateCodeRequest};
+    use crate::application::usecases::code_file_usecases::{
+        CodeFileUsecases, CodeFileUsecasesImpl,
+    };
+    use crate::infrastructure::http::router;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn state() -> AppState {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        AppState::new(CodeFileUsecasesImpl::new(repository))
+    }
+
+    fn create(state: &AppState, content: &str) -> Uuid {
+        let mut files = state.files.lock().unwrap();
+        let id = files
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("sync_http_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id;
+        files
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start: 0,
+                end: 0,
+                content: content.to_string(),
+                author: "grace".to_string(),
+            })
+            .unwrap();
+        id
+    }
+
+    async fn post_sync(state: &AppState, file_id: Uuid, body: Value) -> (StatusCode, Value) {
+        let request = Request::post(format!("/files/{file_id}/sync"))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_sync_endpoint_returns_operations_to_converge() {
+        let state = state();
+        let file_id = create(&state, "a = 1");
+        state
+            .files
+            .lock()
+            .unwrap()
+            .update_code_file(UpdateCodeRequest {
+                id: file_id,
+                start: 0,
+                end: 0,
+                content: "let ".to_string(),
+                author: "grace".to_string(),
+            })
+            .unwrap();
+
+        let (status, body) = post_sync(
+            &state,
+            file_id,
+            json!({
+                "base_revision": 1,
+                "author": "ada",
+                "operations": [{"start": 5, "end": 5, "text": ";"}]
+            }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "revision": 3,
+                "applied": 1,
+                "operations": [{"start": 0, "end": 0, "text": "let ", "author": "grace"}]
+            })
+        );
+        // The upload went through the file's document.
+        assert_eq!(state.documents.loaded(), vec![file_id]);
+        let files = state.files.lock().unwrap();
+        assert_eq!(
+            files.get_code_file(file_id).unwrap().viewport.content,
+            "let a = 1;"
+        );
+        drop(files);
+        state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(file_id)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_endpoint_rejects_ops_that_dont_fit_the_base() {
+        let state = state();
+        let file_id = create(&state, "abcdef");
+        state
+            .files
+            .lock()
+            .unwrap()
+            .update_code_file(UpdateCodeRequest {
+                id: file_id,
+                start: 6,
+                end: 6,
+                content: "!".to_string(),
+                author: "grace".to_string(),
+            })
+            .unwrap();
+
+        for operation in [
+            json!({"start": 5, "end": 2, "text": ""}),
+            json!({"start": 7, "end": 7, "text": "x"}),
+        ] {
+            let (status, _) = post_sync(
+                &state,
+                file_id,
+                json!({"base_revision": 1, "author": "ada", "operations": [operation]}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        // The server is still usable afterwards.
+        let (status, body) = post_sync(
+            &state,
+            file_id,
+            json!({"base_revision": 2, "author": "ada"}),
+        )
+        .await;
+        assert_eq!(
+            (status, body["revision"].clone()),
+            (StatusCode::OK, json!(2))
+        );
+
+        state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(file_id)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_endpoint_maps_errors() {
+        let state = state();
+        let file_id = create(&state, "x");
+
+        let (status, _) = post_sync(
+            &state,
+            file_id,
+            json!({"base_revision": 9, "author": "ada", "operations": []}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = post_sync(
+            &state,
+            file_id,
+            json!({
+                "base_revision": 1,
+                "author": "ada",
+                "operations": [{"start": 0, "end": 5, "text": ""}]
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, body) = post_sync(
+            &state,
+            Uuid::new_v4(),
+            json!({"base_revision": 0, "author": "ada"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body["error"].as_str().unwrap().starts_with("FileNotFound"));
+
+        state
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(file_id)
+            .unwrap();
+    }
+}
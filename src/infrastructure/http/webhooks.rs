@@ -0,0 +1,203 @@
+use crate::application::dto::webhook::{DeliveryResponse, RegisterWebhookRequest, WebhookResponse};
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::webhook_usecases::WebhookUsecases;
+use crate::infrastructure::http::AppState;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterBody {
+    url: String,
+    secret: String,
+    #[serde(default)]
+    file_id: Option<Uuid>,
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookBody {
+    id: Uuid,
+    url: String,
+    file_id: Option<Uuid>,
+    events: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeliveryBody {
+    delivery_id: Uuid,
+    sequence: u64,
+    event: String,
+    attempt: u32,
+    status: Option<u16>,
+    error: Option<String>,
+    delivered: bool,
+}
+
+impl From<WebhookResponse> for WebhookBody {
+    fn from(webhook: WebhookResponse) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            file_id: webhook.file_id,
+            events: webhook.events,
+        }
+    }
+}
+
+impl From<DeliveryResponse> for DeliveryBody {
+    fn from(delivery: DeliveryResponse) -> Self {
+        Self {
+            delivery_id: delivery.delivery_id,
+            sequence: delivery.sequence,
+            event: delivery.event,
+            attempt: delivery.attempt,
+            status: delivery.status,
+            error: delivery.error,
+            delivered: delivery.delivered,
+        }
+    }
+}
+
+fn error_reply(e: ApplicationError) -> (StatusCode, Json<Value>) {
+    let status = match e {
+        ApplicationError::WebhookNotFound(_) => StatusCode::NOT_FOUND,
+        ApplicationError::InvalidWebhook(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "error": format!("{e:?}") })))
+}
+
+/// Registers a webhook. The secret is only ever sent back as signatures.
+pub async fn register_webhook(
+    State(state): State<AppState>,
+    Json(body): Json<RegisterBody>,
+) -> Result<(StatusCode, Json<WebhookBody>), (StatusCode, Json<Value>)> {
+    let webhook = state
+        .webhooks
+        .lock()
+        .unwrap()
+        .register_webhook(RegisterWebhookRequest {
+            url: body.url,
+            secret: body.secret,
+            file_id: body.file_id,
+            events: body.events,
+        })
+        .map_err(error_reply)?;
+    Ok((StatusCode::CREATED, Json(webhook.into())))
+}
+
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<WebhookBody>>, (StatusCode, Json<Value>)> {
+    let webhooks = state
+        .webhooks
+        .lock()
+        .unwrap()
+        .list_webhooks()
+        .map_err(error_reply)?;
+    Ok(Json(webhooks.into_iter().map(Into::into).collect()))
+}
+
+pub async fn unregister_webhook(
+    State(state): State<AppState>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    state
+        .webhooks
+        .lock()
+        .unwrap()
+        .unregister_webhook(webhook_id)
+        .map_err(error_reply)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<Json<Vec<DeliveryBody>>, (StatusCode, Json<Value>)> {
+    let deliveries = state
+        .webhooks
+        .lock()
+        .unwrap()
+        .list_deliveries(webhook_id)
+        .map_err(error_reply)?;
+    Ok(Json(deliveries.into_iter().map(Into::into).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+    use crate::infrastructure::http::router;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn call(state: &AppState, request: Request<Body>) -> (StatusCode, Value) {
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_can_be_registered_listed_and_removed() {
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let state = AppState::new(CodeFileUsecasesImpl::new(repository));
+
+        let register = |body: Value| {
+            Request::post("/webhooks")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let (status, _) = call(
+            &state,
+            register(json!({ "url": "ftp://example.com/hook", "secret": "s3cret" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, webhook) = call(
+            &state,
+            register(json!({
+                "url": "http://example.com/hook",
+                "secret": "s3cret",
+                "events": ["deleted"]
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(webhook.get("secret").is_none());
+        let id = webhook["id"].as_str().unwrap().to_string();
+
+        let list = || Request::get("/webhooks").body(Body::empty()).unwrap();
+        let (_, webhooks) = call(&state, list()).await;
+        assert_eq!(webhooks, json!([webhook]));
+        let deliveries = Request::get(format!("/webhooks/{id}/deliveries"))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(call(&state, deliveries).await, (StatusCode::OK, json!([])));
+
+        let delete = || {
+            Request::delete(format!("/webhooks/{id}"))
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(call(&state, delete()).await.0, StatusCode::NO_CONTENT);
+        assert_eq!(call(&state, delete()).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(call(&state, list()).await.1, json!([]));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::domain::traits::dyn_file::{
+    DynemicFileCreateDelete, DynemicFileRead, DynemicFileResidency, DynemicFileSnapshot,
+    DynemicFileWrite,
+};
+use std::sync::Arc;
+
+// Every write builds new content, so clones and snapshots share the old one.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryFileSource {
+    content: Arc<str>,
+}
+
+impl InMemoryFileSource {
+    pub fn new(content: String) -> Self {
+        Self {
+            content: Arc::from(content),
+        }
+    }
+}
+
+impl DynemicFileRead for InMemoryFileSource {
+    fn get_slice(&self, start: usize, end: usize) -> String {
+        self.content
+            .chars()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect()
+    }
+
+    fn get_content(&self) -> String {
+        self.content.to_string()
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        Some(self.content.as_bytes())
+    }
+}
+
+impl DynemicFileWrite for InMemoryFileSource {
+    fn set_slice(&mut self, start: usize, end: usize, content: String) {
+        let mut chars: Vec<char> = self.content.chars().collect();
+        chars.splice(start..end, content.chars());
+        self.content = chars.into_iter().collect::<String>().into();
+    }
+
+    fn set_content(&mut self, content: String) {
+        self.content = Arc::from(content);
+    }
+}
+
+impl DynemicFileCreateDelete for InMemoryFileSource {
+    fn create_file(&self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn delete_file(&self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+// The content lives nowhere else, so it always stays loaded.
+impl DynemicFileResidency for InMemoryFileSource {
+    fn is_loaded(&self) -> bool {
+        true
+    }
+
+    fn resident_size(&self) -> usize {
+        self.content.len()
+    }
+
+    fn unload(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn load(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl DynemicFileSnapshot for InMemoryFileSource {
+    type Snapshot = InMemoryFileSource;
+
+    fn snapshot(&self) -> Self::Snapshot {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_slice_uses_chars() {
+        let source = InMemoryFileSource::new("Hello 世界!".to_string());
+        assert_eq!(source.get_slice(6, 8), "世界");
+        assert_eq!(source.get_slice(0, 5), "Hello");
+    }
+
+    #[test]
+    fn test_set_slice() {
+        let mut source = InMemoryFileSource::new("Hello, World!".to_string());
+        source.set_slice(0, 5, "Goodbye".to_string());
+        assert_eq!(source.get_content(), "Goodbye, World!");
+
+        source.set_slice(15, 15, " 🦀".to_string());
+        assert_eq!(source.get_content(), "Goodbye, World! 🦀");
+    }
+
+    #[test]
+    fn test_set_content() {
+        let mut source = InMemoryFileSource::default();
+        source.set_content("content".to_string());
+        assert_eq!(source.get_content(), "content");
+        assert_eq!(source.as_bytes(), Some("content".as_bytes()));
+    }
+}
@@ -0,0 +1,325 @@
+use serde::Deserialize;
+use serde::de::Error as _;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::domain::notebook::{Cell, CellKind, Notebook};
+use crate::domain::traits::dyn_file::DynemicFileRead;
+use crate::infrastructure::in_memory_file_source::InMemoryFileSource;
+
+const NBFORMAT: u64 = 4;
+const DEFAULT_NBFORMAT_MINOR: u64 = 5;
+// Cell ids were introduced in nbformat 4.5; older notebooks must not grow them.
+const CELL_ID_NBFORMAT_MINOR: u64 = 5;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MultilineString {
+    Single(String),
+    Lines(Vec<String>),
+}
+
+impl MultilineString {
+    fn join(self) -> String {
+        match self {
+            MultilineString::Single(text) => text,
+            MultilineString::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct IpynbNotebook {
+    nbformat: u64,
+    #[serde(default)]
+    nbformat_minor: Option<u64>,
+    #[serde(default)]
+    metadata: Map<String, Value>,
+    cells: Vec<IpynbCell>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct IpynbCell {
+    cell_type: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    metadata: Map<String, Value>,
+    source: MultilineString,
+    #[serde(default)]
+    outputs: Vec<Value>,
+    #[serde(default)]
+    execution_count: Option<u64>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+fn cell_kind(cell_type: &str) -> Result<CellKind, serde_json::Error> {
+    match cell_type {
+        "code" => Ok(CellKind::Code),
+        "markdown" => Ok(CellKind::Markdown),
+        "raw" => Ok(CellKind::Raw),
+        other => Err(serde_json::Error::custom(format!(
+            "unknown cell_type `{other}`"
+        ))),
+    }
+}
+
+fn cell_type(kind: CellKind) -> &'static str {
+    match kind {
+        CellKind::Code => "code",
+        CellKind::Markdown => "markdown",
+        CellKind::Raw => "raw",
+    }
+}
+
+// Jupyter stores sources as a list of lines that keep their trailing newline.
+fn split_source(source: &str) -> Vec<Value> {
+    source
+        .split_inclusive('\n')
+        .map(|line| Value::String(line.to_string()))
+        .collect()
+}
+
+/// Parses an nbformat v4 document. Fields this model has no place for are kept
+/// in the `extra` maps so that `to_ipynb` can write them back unchanged.
+pub fn parse_ipynb(
+    name: String,
+    content: &str,
+) -> Result<Notebook<InMemoryFileSource>, serde_json::Error> {
+    let document: IpynbNotebook = serde_json::from_str(content)?;
+    if document.nbformat != NBFORMAT {
+        return Err(serde_json::Error::custom(format!(
+            "unsupported nbformat {}, expected {}",
+            document.nbformat, NBFORMAT
+        )));
+    }
+
+    let mut notebook = Notebook::new(Uuid::new_v4(), name);
+    notebook.metadata = document.metadata;
+    notebook.extra = document.extra;
+    if let Some(minor) = document.nbformat_minor {
+        notebook
+            .extra
+            .insert("nbformat_minor".to_string(), Value::from(minor));
+    }
+
+    let mut previous = None;
+    for ipynb_cell in document.cells {
+        let kind = cell_kind(&ipynb_cell.cell_type)?;
+        let mut extra = ipynb_cell.extra;
+        // Ids that aren't written as a hyphenated Uuid, such as uppercase
+        // ones, are kept as they were for `to_ipynb`.
+        let id = match ipynb_cell.id {
+            Some(id) => {
+                let parsed = Uuid::parse_str(&id).ok();
+                if parsed.is_none_or(|parsed| parsed.to_string() != id) {
+                    extra.insert("id".to_string(), Value::String(id));
+                }
+                parsed.unwrap_or_else(Uuid::new_v4)
+            }
+            None => Uuid::new_v4(),
+        };
+
+        let mut cell = Cell::new(id, kind, InMemoryFileSource::new(ipynb_cell.source.join()));
+        cell.metadata = ipynb_cell.metadata;
+        cell.outputs = ipynb_cell.outputs;
+        cell.execution_count = ipynb_cell.execution_count;
+        cell.extra = extra;
+
+        notebook
+            .insert_cell(cell, previous)
+            .map_err(|e| serde_json::Error::custom(format!("{e:?}")))?;
+        previous = Some(id);
+    }
+
+    Ok(notebook)
+}
+
+pub fn to_ipynb(notebook: &Notebook<InMemoryFileSource>) -> Result<String, serde_json::Error> {
+    let mut document = notebook.extra.clone();
+    let minor = document
+        .get("nbformat_minor")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_NBFORMAT_MINOR);
+    document.insert("nbformat".to_string(), Value::from(NBFORMAT));
+    document.insert("nbformat_minor".to_string(), Value::from(minor));
+    document.insert(
+        "metadata".to_string(),
+        Value::Object(notebook.metadata.clone()),
+    );
+
+    let cells = notebook
+        .cells()
+        .map(|cell| {
+            let mut object = cell.extra.clone();
+            if minor >= CELL_ID_NBFORMAT_MINOR && !object.contains_key("id") {
+                object.insert("id".to_string(), Value::String(cell.id().to_string()));
+            }
+            object.insert(
+                "cell_type".to_string(),
+                Value::String(cell_type(cell.kind).to_string()),
+            );
+            object.insert("metadata".to_string(), Value::Object(cell.metadata.clone()));
+            object.insert(
+                "source".to_string(),
+                Value::Array(split_source(&cell.source.get_content())),
+            );
+            if cell.kind == CellKind::Code {
+                object.insert("outputs".to_string(), Value::Array(cell.outputs.clone()));
+                object.insert(
+                    "execution_count".to_string(),
+                    cell.execution_count.map_or(Value::Null, Value::from),
+                );
+            }
+            Value::Object(object)
+        })
+        .collect();
+    document.insert("cells".to_string(), Value::Array(cells));
+
+    serde_json::to_string_pretty(&Value::Object(document))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Value {
+        json!({
+            "nbformat": 4,
+            "nbformat_minor": 5,
+            "metadata": {
+                "kernelspec": {"name": "python3", "display_name": "Python 3"},
+                "custom": {"nested": [1, 2, 3]}
+            },
+            "x_vendor_field": "kept",
+            "cells": [
+                {
+                    "cell_type": "markdown",
+                    "id": "intro",
+                    "metadata": {"tags": ["header"]},
+                    "source": ["# Title\n", "Some text"],
+                    "attachments": {"img.png": {"image/png": "iVBOR"}}
+                },
+                {
+                    "cell_type": "code",
+                    "id": "0b6f9a0e-5d7c-4a39-9b86-0c5a3f0f4d21",
+                    "metadata": {"collapsed": false},
+                    "source": "x = 1\nprint(x)",
+                    "outputs": [
+                        {"output_type": "stream", "name": "stdout", "text": ["1\n"]}
+                    ],
+                    "execution_count": 3
+                },
+                {
+                    "cell_type": "raw",
+                    "id": "raw-cell",
+                    "metadata": {"format": "text/x-rst"},
+                    "source": []
+                }
+            ]
+        })
+    }
+
+    #[test]
+    fn test_parse_ipynb() {
+        let notebook = parse_ipynb("nb.ipynb".to_string(), &sample().to_string()).unwrap();
+
+        let cells: Vec<_> = notebook.cells().collect();
+        assert_eq!(cells.len(), 3);
+        assert_eq!(cells[0].kind, CellKind::Markdown);
+        assert_eq!(cells[0].source.get_content(), "# Title\nSome text");
+        assert_eq!(cells[0].extra["id"], json!("intro"));
+        assert!(cells[0].extra.contains_key("attachments"));
+        assert_eq!(
+            cells[1].id().to_string(),
+            "0b6f9a0e-5d7c-4a39-9b86-0c5a3f0f4d21"
+        );
+        assert_eq!(cells[1].execution_count, Some(3));
+        assert_eq!(cells[1].outputs.len(), 1);
+        assert_eq!(cells[2].kind, CellKind::Raw);
+        assert_eq!(notebook.extra["x_vendor_field"], json!("kept"));
+    }
+
+    #[test]
+    fn test_round_trip_preserves_document() {
+        let original = sample();
+        let notebook = parse_ipynb("nb.ipynb".to_string(), &original.to_string()).unwrap();
+        let exported: Value = serde_json::from_str(&to_ipynb(&notebook).unwrap()).unwrap();
+
+        let mut expected = original.clone();
+        expected["cells"][1]["source"] = json!(["x = 1\n", "print(x)"]);
+        assert_eq!(exported, expected);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_uppercase_ids() {
+        let mut original = sample();
+        original["cells"][1]["id"] = json!("0B6F9A0E-5D7C-4A39-9B86-0C5A3F0F4D21");
+        let notebook = parse_ipynb("nb.ipynb".to_string(), &original.to_string()).unwrap();
+        assert_eq!(
+            notebook.cells().nth(1).unwrap().id().to_string(),
+            "0b6f9a0e-5d7c-4a39-9b86-0c5a3f0f4d21"
+        );
+        let exported: Value = serde_json::from_str(&to_ipynb(&notebook).unwrap()).unwrap();
+
+        assert_eq!(exported["cells"][1]["id"], original["cells"][1]["id"]);
+    }
+
+    #[test]
+    fn test_older_minor_does_not_add_ids() {
+        let document = json!({
+            "nbformat": 4,
+            "nbformat_minor": 2,
+            "metadata": {},
+            "cells": [
+                {"cell_type": "code", "metadata": {}, "source": "1", "outputs": [], "execution_count": null}
+            ]
+        });
+        let notebook = parse_ipynb("old.ipynb".to_string(), &document.to_string()).unwrap();
+        let exported: Value = serde_json::from_str(&to_ipynb(&notebook).unwrap()).unwrap();
+
+        let mut expected = document.clone();
+        expected["cells"][0]["source"] = json!(["1"]);
+        assert_eq!(exported, expected);
+    }
+
+    #[test]
+    fn test_new_notebook_exports_current_format() {
+        let mut notebook = Notebook::new(Uuid::new_v4(), "new.ipynb".to_string());
+        let cell = Cell::new(
+            Uuid::new_v4(),
+            CellKind::Code,
+            InMemoryFileSource::new("1 + 1".to_string()),
+        );
+        let cell_id = cell.id();
+        notebook.insert_cell(cell, None).unwrap();
+
+        let exported: Value = serde_json::from_str(&to_ipynb(&notebook).unwrap()).unwrap();
+        assert_eq!(exported["nbformat"], json!(4));
+        assert_eq!(exported["nbformat_minor"], json!(5));
+        assert_eq!(exported["cells"][0]["id"], json!(cell_id.to_string()));
+        assert_eq!(exported["cells"][0]["execution_count"], Value::Null);
+        assert_eq!(exported["cells"][0]["outputs"], json!([]));
+    }
+
+    #[test]
+    fn test_rejects_other_nbformat() {
+        let document = json!({"nbformat": 3, "metadata": {}, "worksheets": []});
+        assert!(parse_ipynb("v3.ipynb".to_string(), &document.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_unknown_cell_type() {
+        let document = json!({
+            "nbformat": 4,
+            "nbformat_minor": 5,
+            "metadata": {},
+            "cells": [{"cell_type": "widget", "metadata": {}, "source": ""}]
+        });
+        assert!(parse_ipynb("bad.ipynb".to_string(), &document.to_string()).is_err());
+    }
+}
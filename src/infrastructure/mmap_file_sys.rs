@@ -1,78 +1,522 @@
-use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+use crate::domain::traits::dyn_file::{
+    DynemicFileCreateDelete, DynemicFileMove, DynemicFileRead, DynemicFileResidency,
+    DynemicFileSnapshot, DynemicFileWrite,
+};
 use memmap2::{Mmap, MmapMut};
+use std::collections::HashMap;
+use std::ffi::CString;
 use std::fs::{File, OpenOptions};
-use std::path::PathBuf;
+use std::io::Write;
+use std::ops::{Deref, Range};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+pub enum Mapping {
+    ReadOnly {
+        mmap: Mmap,
+        inode: u64,
+        extent: Extent,
+    },
+    Writable {
+        mmap: MmapMut,
+        dirty: Vec<Range<usize>>,
+        inode: u64,
+        extent: Extent,
+    },
+}
+
+// Bumped whenever a file may have shrunk underneath its mappings: by the file
+// watcher for other programs, and here for writes that truncate in place.
+static DISK_EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// Tells every mapping to check the length of its file on the next read.
+pub fn disk_changed() {
+    DISK_EPOCH.fetch_add(1, Ordering::AcqRel);
+}
+
+/// How much of a mapping the file still backs, as of the disk epoch it was
+/// last checked at.
+pub struct Extent {
+    epoch: AtomicU64,
+    len: AtomicUsize,
+}
 
+impl Extent {
+    fn new(len: usize) -> Self {
+        Self {
+            epoch: AtomicU64::new(DISK_EPOCH.load(Ordering::Acquire)),
+            len: AtomicUsize::new(len),
+        }
+    }
+}
+
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Mapping::ReadOnly { mmap, .. } => mmap,
+            Mapping::Writable { mmap, .. } => mmap,
+        }
+    }
+}
+
+impl Mapping {
+    fn inode(&self) -> u64 {
+        match self {
+            Mapping::ReadOnly { inode, .. } | Mapping::Writable { inode, .. } => *inode,
+        }
+    }
+
+    fn extent(&self) -> &Extent {
+        match self {
+            Mapping::ReadOnly { extent, .. } | Mapping::Writable { extent, .. } => extent,
+        }
+    }
+}
+
+/// A file mapped into memory. Clones share the mapping, so handing a source
+/// out is cheap; a write through a shared mapping first maps the file again
+/// for itself.
 pub struct MmapFileSystemSource {
     pub path: PathBuf,
-    pub mmap: Option<Mmap>,
+    pub mmap: Option<Arc<Mapping>>,
+}
+
+fn map_read_only(path: &PathBuf) -> std::io::Result<Mapping> {
+    let file = File::open(path)?;
+    let inode = file.metadata()?.ino();
+    let mmap = unsafe { Mmap::map(&file)? };
+    let extent = Extent::new(mmap.len());
+    Ok(Mapping::ReadOnly {
+        mmap,
+        inode,
+        extent,
+    })
+}
+
+fn map_writable(path: &PathBuf) -> std::io::Result<Mapping> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    let inode = file.metadata()?.ino();
+    let mmap = unsafe { MmapMut::map_mut(&file)? };
+    let extent = Extent::new(mmap.len());
+    Ok(Mapping::Writable {
+        mmap,
+        dirty: Vec::new(),
+        inode,
+        extent,
+    })
+}
+
+// Inodes that snapshots still read from. Writes never touch a pinned inode in
+// place: they write a new file and rename it over the path, and the snapshots
+// keep the old one. Inodes of different filesystems may collide, which only
+// costs a needless rename.
+static PINNED: LazyLock<Mutex<HashMap<u64, usize>>> = LazyLock::new(Default::default);
+
+fn is_pinned(inode: u64) -> bool {
+    PINNED.lock().unwrap().contains_key(&inode)
+}
+
+struct Pin(u64);
+
+impl Pin {
+    fn new(inode: u64) -> Self {
+        *PINNED.lock().unwrap().entry(inode).or_default() += 1;
+        Self(inode)
+    }
+}
+
+impl Drop for Pin {
+    fn drop(&mut self) {
+        let mut pinned = PINNED.lock().unwrap();
+        if let Some(count) = pinned.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                pinned.remove(&self.0);
+            }
+        }
+    }
+}
+
+fn read_slice(bytes: Option<&[u8]>, start: usize, end: usize) -> String {
+    if let Some(bytes) = bytes {
+        let end = end.min(bytes.len());
+        String::from_utf8_lossy(&bytes[start.min(end)..end]).to_string()
+    } else {
+        String::new()
+    }
+}
+
+// Tells apart the files staged by one process, whichever source stages them.
+static STAGED: AtomicU64 = AtomicU64::new(0);
+
+// Writes `content` to a new file next to `path` and renames it into place.
+// The new file keeps the mode of the old one, and its owner where this
+// process may hand files to another.
+fn replace_file(path: &PathBuf, content: &[u8]) -> std::io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let metadata = std::fs::metadata(path).ok();
+    let (staged, mut file) = loop {
+        let count = STAGED.fetch_add(1, Ordering::Relaxed);
+        let staged = path.with_file_name(format!(".{name}.{}.{count}", std::process::id()));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&staged)
+        {
+            Ok(file) => break (staged, file),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    };
+    let replaced = file
+        .write_all(content)
+        .and_then(|_| match &metadata {
+            Some(metadata) => {
+                let _ =
+                    std::os::unix::fs::fchown(&file, Some(metadata.uid()), Some(metadata.gid()));
+                file.set_permissions(metadata.permissions())
+            }
+            None => Ok(()),
+        })
+        .and_then(|_| std::fs::rename(&staged, path));
+    replaced.inspect_err(|_| {
+        let _ = std::fs::remove_file(&staged);
+    })
+}
+
+fn char_count(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| b & 0xC0 != 0x80).count()
+}
+
+fn char_to_byte(bytes: &[u8], char_index: usize) -> usize {
+    // A file never has more chars than bytes, so appends skip the scan.
+    if char_index >= bytes.len() {
+        return bytes.len();
+    }
+    let mut chars = 0;
+    for (index, &b) in bytes.iter().enumerate() {
+        if b & 0xC0 != 0x80 {
+            if chars == char_index {
+                return index;
+            }
+            chars += 1;
+        }
+    }
+    bytes.len()
+}
+
+fn mark_dirty(dirty: &mut Vec<Range<usize>>, range: Range<usize>) {
+    if range.is_empty() {
+        return;
+    }
+    if let Some(last) = dirty.last_mut()
+        && range.start <= last.end
+        && last.start <= range.end
+    {
+        last.start = last.start.min(range.start);
+        last.end = last.end.max(range.end);
+        return;
+    }
+    dirty.push(range);
 }
 
 impl MmapFileSystemSource {
     pub fn new(path: PathBuf) -> std::io::Result<Self> {
-        let file = File::open(&path)?;
-        let mmap = unsafe { Mmap::map(&file)? };
         Ok(Self {
+            mmap: Some(Arc::new(map_read_only(&path)?)),
             path,
-            mmap: Some(mmap),
         })
     }
+
+    /// Maps the file read-write so that same-length edits and appends are applied
+    /// in place instead of rewriting the whole file. Changes reach the page cache
+    /// immediately; call `flush` to push the dirty ranges to disk.
+    pub fn new_writable(path: PathBuf) -> std::io::Result<Self> {
+        Ok(Self {
+            mmap: Some(Arc::new(map_writable(&path)?)),
+            path,
+        })
+    }
+
+    /// Maps the file at `path` again, picking up changes made by other processes:
+    /// a replaced inode, a different length or a truncation. Unflushed in-place
+    /// edits are dropped with the old mapping.
+    pub fn reload(&mut self) -> std::io::Result<()> {
+        let writable = self.is_writable();
+        self.mmap = None;
+        *self = if writable {
+            Self::new_writable(self.path.clone())?
+        } else {
+            Self::new(self.path.clone())?
+        };
+        Ok(())
+    }
+
+    // Another process may truncate the file underneath the mapping, and touching
+    // pages past the new end of file raises SIGBUS. Reads are clamped to the
+    // length on disk, checked again only after `disk_changed`; `reload` then
+    // brings the mapping up to date.
+    fn mapped(&self) -> Option<&[u8]> {
+        let mapping = self.mmap.as_deref()?;
+        let extent = mapping.extent();
+        let epoch = DISK_EPOCH.load(Ordering::Acquire);
+        if extent.epoch.load(Ordering::Acquire) != epoch {
+            let len = std::fs::metadata(&self.path)
+                .map(|metadata| metadata.len() as usize)
+                .unwrap_or(mapping.len());
+            extent.len.store(len.min(mapping.len()), Ordering::Release);
+            extent.epoch.store(epoch, Ordering::Release);
+        }
+        Some(&mapping[..extent.len.load(Ordering::Acquire)])
+    }
+
+    pub fn is_writable(&self) -> bool {
+        matches!(self.mmap.as_deref(), Some(Mapping::Writable { .. }))
+    }
+
+    pub fn dirty_ranges(&self) -> &[Range<usize>] {
+        match self.mmap.as_deref() {
+            Some(Mapping::Writable { dirty, .. }) => dirty,
+            _ => &[],
+        }
+    }
+
+    /// Whether both sources read from the very same mapping.
+    pub fn shares_mapping_with(&self, other: &Self) -> bool {
+        match (&self.mmap, &other.mmap) {
+            (Some(mine), Some(theirs)) => Arc::ptr_eq(mine, theirs),
+            _ => false,
+        }
+    }
+
+    // Dirty ranges of a shared mapping may be flushed by every holder, but only
+    // the last one can clear them.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(Mapping::Writable { mmap, dirty, .. }) = self.mmap.as_deref() {
+            for range in dirty.iter() {
+                mmap.flush_range(range.start, range.len())?;
+            }
+        }
+        if let Some(Mapping::Writable { dirty, .. }) = self.mmap.as_mut().and_then(Arc::get_mut) {
+            dirty.clear();
+        }
+        Ok(())
+    }
+
+    // Gives a writable source a mapping of its own before it is written to.
+    fn unshare(&mut self) -> std::io::Result<()> {
+        let shared = self
+            .mmap
+            .as_ref()
+            .is_some_and(|mapping| Arc::strong_count(mapping) > 1);
+        if shared && self.is_writable() {
+            self.flush()?;
+            self.mmap = Some(Arc::new(map_writable(&self.path)?));
+        }
+        Ok(())
+    }
+
+    // Returns false when the edit changes the file length anywhere but at the
+    // end, in which case the caller falls back to rewriting the file.
+    fn write_in_place(
+        &mut self,
+        start: usize,
+        end: usize,
+        content: &[u8],
+    ) -> std::io::Result<bool> {
+        let metadata = std::fs::metadata(&self.path)?;
+        if is_pinned(metadata.ino()) {
+            return Ok(false);
+        }
+        self.unshare()?;
+        let Some(Mapping::Writable {
+            mmap, dirty, inode, ..
+        }) = self.mmap.as_mut().and_then(Arc::get_mut)
+        else {
+            return Ok(false);
+        };
+        // Tools like git replace files instead of writing them, and edits to the
+        // mapping of the old inode would never reach the new file.
+        if metadata.ino() != *inode || metadata.len() as usize != mmap.len() {
+            return Ok(false);
+        }
+        let byte_start = char_to_byte(mmap, start);
+        let byte_end = char_to_byte(mmap, end);
+
+        if byte_end.checked_sub(byte_start) == Some(content.len()) {
+            mmap[byte_start..byte_end].copy_from_slice(content);
+            mark_dirty(dirty, byte_start..byte_end);
+            return Ok(true);
+        }
+        if byte_start != mmap.len() || byte_end != mmap.len() {
+            return Ok(false);
+        }
+
+        let old_len = mmap.len();
+        let new_len = old_len + content.len();
+        self.flush()?;
+        // Drop the old mapping before the file grows under it.
+        self.mmap = None;
+        OpenOptions::new()
+            .write(true)
+            .open(&self.path)?
+            .set_len(new_len as u64)?;
+        let mut mapping = map_writable(&self.path)?;
+        if let Mapping::Writable { mmap, dirty, .. } = &mut mapping {
+            mmap[old_len..new_len].copy_from_slice(content);
+            mark_dirty(dirty, old_len..new_len);
+        }
+        self.mmap = Some(Arc::new(mapping));
+        Ok(true)
+    }
 }
 
 impl Clone for MmapFileSystemSource {
+    // The file may have been removed behind our back (a branch switch, `git rm`);
+    // the clone then starts out unmapped, like a source whose file is not created.
+    // A file replaced or resized since it was mapped is mapped afresh, anything
+    // else shares the mapping.
     fn clone(&self) -> Self {
-        if self.mmap.is_some() {
-            Self::new(self.path.clone()).expect("Failed to clone MmapFileSystemSource")
-        } else {
-            Self {
+        let unmapped = || Self {
+            path: self.path.clone(),
+            mmap: None,
+        };
+        let Some(mapping) = &self.mmap else {
+            return unmapped();
+        };
+        let Ok(metadata) = std::fs::metadata(&self.path) else {
+            return unmapped();
+        };
+        if metadata.ino() == mapping.inode() && metadata.len() as usize == mapping.len() {
+            return Self {
                 path: self.path.clone(),
-                mmap: None,
-            }
+                mmap: Some(Arc::clone(mapping)),
+            };
         }
+        let mapped = match mapping.as_ref() {
+            Mapping::ReadOnly { .. } => Self::new(self.path.clone()),
+            Mapping::Writable { .. } => Self::new_writable(self.path.clone()),
+        };
+        mapped.unwrap_or_else(|_| unmapped())
     }
 }
 
 impl DynemicFileRead for MmapFileSystemSource {
     fn get_slice(&self, start: usize, end: usize) -> String {
-        if let Some(mmap) = &self.mmap {
-            let slice = &mmap[start..end];
-            String::from_utf8_lossy(slice).to_string()
-        } else {
-            String::new()
-        }
+        read_slice(self.mapped(), start, end)
     }
 
     fn get_content(&self) -> String {
-        if let Some(mmap) = &self.mmap {
-            String::from_utf8_lossy(&mmap[..]).to_string()
-        } else {
-            String::new()
-        }
+        read_slice(self.mapped(), 0, usize::MAX)
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        self.mapped()
     }
 }
 
-impl DynemicFileWrite for MmapFileSystemSource {
-    fn set_slice(&mut self, start: usize, end: usize, content: String) {
+impl MmapFileSystemSource {
+    /// Replaces the chars `start..end` with `content` like `set_slice`, but
+    /// returns what went wrong instead of panicking, including a range that
+    /// is reversed or runs past the end.
+    pub fn try_set_slice(
+        &mut self,
+        start: usize,
+        end: usize,
+        content: String,
+    ) -> std::io::Result<()> {
+        // A char takes one to four bytes, so only ends past a quarter of the
+        // bytes need the chars counted.
+        let bytes = self.as_bytes().unwrap_or_default();
+        if start > end || (end > bytes.len() / 4 && end > char_count(bytes)) {
+            return Err(out_of_bounds(start, end));
+        }
+        if self.write_in_place(start, end, content.as_bytes())? {
+            return Ok(());
+        }
+
         let current_content = self.get_content();
         let mut chars: Vec<char> = current_content.chars().collect();
-        let new_chars: Vec<char> = content.chars().collect();
-
-        chars.splice(start..end, new_chars.iter().cloned());
+        if end > chars.len() {
+            return Err(out_of_bounds(start, end));
+        }
+        chars.splice(start..end, content.chars());
 
         let new_content: String = chars.into_iter().collect();
-        self.set_content(new_content);
+        self.try_set_content(new_content)
+    }
+
+    /// Replaces the whole content like `set_content`, but returns what went
+    /// wrong instead of panicking. A failed write keeps the old mapping.
+    pub fn try_set_content(&mut self, content: String) -> std::io::Result<()> {
+        self.try_set_bytes(content.as_bytes())
+    }
+
+    /// Replaces the whole content with bytes that need not be UTF-8, like
+    /// `try_set_content`.
+    pub fn try_set_bytes(&mut self, content: &[u8]) -> std::io::Result<()> {
+        let writable = self.is_writable();
+        if writable
+            && self.as_bytes().map(<[u8]>::len) == Some(content.len())
+            && self.write_in_place(0, usize::MAX, content)?
+        {
+            return Ok(());
+        }
+
+        let previous = self.mmap.take();
+        let pinned = std::fs::metadata(&self.path).is_ok_and(|metadata| is_pinned(metadata.ino()));
+        let written = if pinned {
+            replace_file(&self.path, content)
+        } else {
+            let written = std::fs::write(&self.path, content);
+            // Other sources may still map the file this truncated.
+            disk_changed();
+            written
+        };
+        if let Err(e) = written {
+            self.mmap = previous;
+            return Err(e);
+        }
+        drop(previous);
+        self.mmap = Some(Arc::new(if writable {
+            map_writable(&self.path)?
+        } else {
+            map_read_only(&self.path)?
+        }));
+        Ok(())
+    }
+}
+
+fn out_of_bounds(start: usize, end: usize) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("char range {start}..{end} is out of bounds"),
+    )
+}
+
+// The trait has no way to report a failed write; callers that can handle one
+// use `try_set_slice` and `try_set_content`.
+impl DynemicFileWrite for MmapFileSystemSource {
+    fn set_slice(&mut self, start: usize, end: usize, content: String) {
+        self.try_set_slice(start, end, content)
+            .expect("Failed to write file");
     }
 
     fn set_content(&mut self, content: String) {
-        std::fs::write(&self.path, content).expect("Failed to write file");
-        let file = File::open(&self.path).expect("Failed to open file");
-        self.mmap = Some(unsafe { Mmap::map(&file).expect("Failed to map file") });
+        self.try_set_content(content).expect("Failed to write file");
     }
 }
 
 impl DynemicFileCreateDelete for MmapFileSystemSource {
     fn create_file(&self) -> Result<(), std::io::Error> {
-        File::create(&self.path).map(|_| ())
+        let created = File::create(&self.path).map(|_| ());
+        disk_changed();
+        created
     }
 
     fn delete_file(&self) -> Result<(), std::io::Error> {
@@ -80,11 +524,160 @@ impl DynemicFileCreateDelete for MmapFileSystemSource {
     }
 }
 
+// Renames `from` to `to` in one step, failing with `AlreadyExists` instead of
+// replacing a file at `to`.
+fn rename_no_replace(from: &Path, to: &Path) -> std::io::Result<()> {
+    let from = CString::new(from.as_os_str().as_bytes())?;
+    let to = CString::new(to.as_os_str().as_bytes())?;
+    let renamed = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            from.as_ptr(),
+            libc::AT_FDCWD,
+            to.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if renamed == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+// Links `to` first, so a file there is never replaced, then unlinks `from`.
+// For filesystems without `RENAME_NOREPLACE`; both names exist in between.
+fn link_and_unlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::hard_link(from, to)?;
+    std::fs::remove_file(from).inspect_err(|_| {
+        let _ = std::fs::remove_file(to);
+    })
+}
+
+// Writes a copy at `to`, failing if a file is there, then unlinks `from`.
+fn copy_and_unlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let mut target = OpenOptions::new().write(true).create_new(true).open(to)?;
+    let copied = std::io::copy(&mut File::open(from)?, &mut target)
+        .and_then(|_| target.sync_all())
+        .and_then(|_| std::fs::remove_file(from));
+    copied.inspect_err(|_| {
+        let _ = std::fs::remove_file(to);
+    })
+}
+
+// Within a filesystem the inode stays the same, so the mapping and any
+// snapshots keep working. Across filesystems the content is copied and the
+// source maps the copy; snapshots keep reading the old inode.
+impl DynemicFileMove for MmapFileSystemSource {
+    fn move_file(&mut self, to: &Path) -> Result<(), std::io::Error> {
+        self.flush()?;
+        match rename_no_replace(&self.path, to) {
+            Ok(()) => {},
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) => {
+                link_and_unlink(&self.path, to)?
+            }
+            Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+                copy_and_unlink(&self.path, to)?;
+                self.path = to.to_path_buf();
+                if self.is_loaded() {
+                    self.reload()?;
+                }
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+        self.path = to.to_path_buf();
+        Ok(())
+    }
+}
+
+// Dropping the mapping closes the handle; in-place edits are flushed first so
+// nothing is lost. Sources come back writable when the file allows it.
+impl DynemicFileResidency for MmapFileSystemSource {
+    fn is_loaded(&self) -> bool {
+        self.mmap.is_some()
+    }
+
+    fn resident_size(&self) -> usize {
+        self.mmap.as_deref().map_or(0, |mapping| mapping.len())
+    }
+
+    fn unload(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.mmap = None;
+        Ok(())
+    }
+
+    fn load(&mut self) -> std::io::Result<()> {
+        if self.is_loaded() {
+            return Ok(());
+        }
+        *self = Self::new_writable(self.path.clone()).or_else(|_| Self::new(self.path.clone()))?;
+        Ok(())
+    }
+}
+
+/// The content of an `MmapFileSystemSource` as it was when the snapshot was
+/// taken. It reads from the same mapping without copying; writes made while it
+/// lives go to a new file instead of the pages it reads.
+#[derive(Clone)]
+pub struct MmapSnapshot {
+    path: PathBuf,
+    mapping: Option<Arc<Mapping>>,
+    len: usize,
+    _pin: Option<Arc<Pin>>,
+}
+
+impl MmapSnapshot {
+    // The pinned inode stays at the path until the next write, and another
+    // process could still truncate it meanwhile.
+    fn bytes(&self) -> Option<&[u8]> {
+        let mapping = self.mapping.as_deref()?;
+        let len = match std::fs::metadata(&self.path) {
+            Ok(metadata) if metadata.ino() == mapping.inode() => {
+                self.len.min(metadata.len() as usize)
+            }
+            _ => self.len,
+        };
+        Some(&mapping[..len])
+    }
+}
+
+impl DynemicFileRead for MmapSnapshot {
+    fn get_slice(&self, start: usize, end: usize) -> String {
+        read_slice(self.bytes(), start, end)
+    }
+
+    fn get_content(&self) -> String {
+        read_slice(self.bytes(), 0, usize::MAX)
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        self.bytes()
+    }
+}
+
+impl DynemicFileSnapshot for MmapFileSystemSource {
+    type Snapshot = MmapSnapshot;
+
+    fn snapshot(&self) -> Self::Snapshot {
+        MmapSnapshot {
+            path: self.path.clone(),
+            mapping: self.mmap.clone(),
+            len: self.mapped().map_or(0, <[u8]>::len),
+            _pin: self
+                .mmap
+                .as_deref()
+                .map(|mapping| Arc::new(Pin::new(mapping.inode()))),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
-    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
     use tempfile::TempDir;
 
     fn create_test_file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
@@ -250,6 +843,373 @@ mod tests {
         assert_eq!(source.get_slice(0, 100), "A".repeat(100));
     }
 
+    #[test]
+    fn test_as_bytes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "Hello, World!");
+
+        let source = MmapFileSystemSource::new(file_path.clone()).expect("Failed to create source");
+        assert_eq!(source.as_bytes(), Some("Hello, World!".as_bytes()));
+
+        let unmapped = MmapFileSystemSource {
+            path: file_path,
+            mmap: None,
+        };
+        assert_eq!(unmapped.as_bytes(), None);
+    }
+
+    fn dirty(source: &MmapFileSystemSource) -> Vec<(usize, usize)> {
+        source
+            .dirty_ranges()
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_writable_same_length_edit_in_place() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "Lorem ipsum dolor");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        assert!(source.is_writable());
+        source.set_slice(6, 11, "XXXXX".to_string());
+
+        assert_eq!(source.get_content(), "Lorem XXXXX dolor");
+        assert_eq!(dirty(&source), vec![(6, 11)]);
+        let file_content = fs::read_to_string(&file_path).expect("Failed to read file");
+        assert_eq!(file_content, "Lorem XXXXX dolor");
+    }
+
+    #[test]
+    fn test_writable_edit_uses_char_offsets() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "世界 abc");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        source.set_slice(3, 6, "xyz".to_string());
+
+        assert_eq!(source.get_content(), "世界 xyz");
+        assert_eq!(dirty(&source), vec![(7, 10)]);
+    }
+
+    #[test]
+    fn test_writable_append_grows_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "Hello");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        source.set_slice(5, 5, ", World!".to_string());
+
+        assert_eq!(source.get_content(), "Hello, World!");
+        assert_eq!(dirty(&source), vec![(5, 13)]);
+        let file_content = fs::read_to_string(&file_path).expect("Failed to read file");
+        assert_eq!(file_content, "Hello, World!");
+    }
+
+    #[test]
+    fn test_writable_append_to_empty_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "empty.txt", "");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        source.set_slice(0, 0, "first line".to_string());
+
+        assert_eq!(source.get_content(), "first line");
+    }
+
+    #[test]
+    fn test_writable_length_change_falls_back_to_rewrite() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "Hello, World!");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        source.set_slice(0, 5, "Goodbye".to_string());
+
+        assert_eq!(source.get_content(), "Goodbye, World!");
+        assert!(source.is_writable());
+        assert!(source.dirty_ranges().is_empty());
+
+        source.set_content("Short".to_string());
+        let file_content = fs::read_to_string(&file_path).expect("Failed to read file");
+        assert_eq!(file_content, "Short");
+    }
+
+    #[test]
+    fn test_writable_set_content_same_length() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "aaaa");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        source.set_content("bbbb".to_string());
+
+        assert_eq!(source.get_content(), "bbbb");
+        assert_eq!(dirty(&source), vec![(0, 4)]);
+    }
+
+    #[test]
+    fn test_dirty_ranges_coalesce_and_flush() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "0123456789");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        source.set_slice(0, 2, "ab".to_string());
+        source.set_slice(2, 4, "cd".to_string());
+        source.set_slice(7, 8, "x".to_string());
+        assert_eq!(dirty(&source), vec![(0, 4), (7, 8)]);
+
+        source.flush().expect("Failed to flush");
+        assert!(source.dirty_ranges().is_empty());
+        let file_content = fs::read_to_string(&file_path).expect("Failed to read file");
+        assert_eq!(file_content, "abcd456x89");
+    }
+
+    #[test]
+    fn test_clone_keeps_writable_mode() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "content");
+
+        let source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        assert!(source.clone().is_writable());
+
+        let read_only = MmapFileSystemSource::new(file_path).expect("Failed to create source");
+        assert!(!read_only.clone().is_writable());
+        assert!(read_only.dirty_ranges().is_empty());
+    }
+
+    #[test]
+    fn test_truncated_file_is_not_read_past_end() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", &"A".repeat(10000));
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&file_path)
+            .expect("Failed to open file")
+            .set_len(3)
+            .expect("Failed to truncate file");
+        // As the file watcher does when it sees the truncation.
+        disk_changed();
+
+        assert_eq!(source.get_content(), "AAA");
+        assert_eq!(source.get_slice(0, 5000), "AAA");
+        assert_eq!(source.as_bytes().map(<[u8]>::len), Some(3));
+
+        source.set_slice(0, 1, "B".to_string());
+        assert_eq!(source.get_content(), "BAA");
+        let file_content = fs::read_to_string(&file_path).expect("Failed to read file");
+        assert_eq!(file_content, "BAA");
+    }
+
+    #[test]
+    fn test_clone_is_not_read_past_a_shorter_rewrite() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", &"A".repeat(10000));
+
+        let source =
+            MmapFileSystemSource::new_writable(file_path).expect("Failed to create source");
+        let mut writer = source.clone();
+        writer
+            .try_set_content("short".to_string())
+            .expect("Failed to write file");
+
+        assert_eq!(source.get_content(), "short");
+    }
+
+    #[test]
+    fn test_reload_picks_up_replaced_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "old");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        let replacement = create_test_file(&temp_dir, "replacement.txt", "new content");
+        fs::rename(&replacement, &file_path).expect("Failed to replace file");
+        assert_eq!(source.get_content(), "old");
+
+        source.reload().expect("Failed to reload");
+        assert_eq!(source.get_content(), "new content");
+        assert!(source.is_writable());
+    }
+
+    #[test]
+    fn test_set_content_after_replacement_reaches_new_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "aaa");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        let replacement = create_test_file(&temp_dir, "replacement.txt", "bbb");
+        fs::rename(&replacement, &file_path).expect("Failed to replace file");
+
+        source.set_content("ccc".to_string());
+        assert_eq!(source.get_content(), "ccc");
+        let file_content = fs::read_to_string(&file_path).expect("Failed to read file");
+        assert_eq!(file_content, "ccc");
+    }
+
+    #[test]
+    fn test_clone_shares_mapping_until_written() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "Lorem ipsum");
+
+        let source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        let mut cloned = source.clone();
+        assert!(cloned.shares_mapping_with(&source));
+
+        cloned.set_slice(0, 5, "LOREM".to_string());
+        assert!(!cloned.shares_mapping_with(&source));
+        assert!(cloned.is_writable());
+        assert_eq!(dirty(&cloned), vec![(0, 5)]);
+        // Both map the same file, so the original sees the edit.
+        assert_eq!(source.get_content(), "LOREM ipsum");
+    }
+
+    #[test]
+    fn test_clone_of_replaced_file_maps_it_again() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "old");
+
+        let source = MmapFileSystemSource::new(file_path.clone()).expect("Failed to create source");
+        let replacement = create_test_file(&temp_dir, "replacement.txt", "new content");
+        fs::rename(&replacement, &file_path).expect("Failed to replace file");
+
+        let cloned = source.clone();
+        assert!(!cloned.shares_mapping_with(&source));
+        assert!(!cloned.is_writable());
+        assert_eq!(cloned.get_content(), "new content");
+    }
+
+    #[test]
+    fn test_clone_of_removed_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "content");
+
+        let source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        fs::remove_file(&file_path).expect("Failed to remove file");
+
+        let cloned = source.clone();
+        assert!(cloned.mmap.is_none());
+        assert_eq!(cloned.get_content(), "");
+    }
+
+    #[test]
+    fn test_snapshot_keeps_content_through_edits() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "Lorem ipsum");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        let snapshot = source.snapshot();
+
+        source.set_slice(0, 5, "LOREM".to_string());
+        source.set_slice(11, 11, " dolor".to_string());
+        assert_eq!(source.get_content(), "LOREM ipsum dolor");
+        assert!(source.is_writable());
+        let file_content = fs::read_to_string(&file_path).expect("Failed to read file");
+        assert_eq!(file_content, "LOREM ipsum dolor");
+
+        assert_eq!(snapshot.get_content(), "Lorem ipsum");
+        assert_eq!(snapshot.clone().get_slice(6, 11), "ipsum");
+        assert_eq!(snapshot.as_bytes(), Some("Lorem ipsum".as_bytes()));
+    }
+
+    #[test]
+    fn test_try_set_slice_rejects_bad_ranges() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "Lorem ipsum");
+
+        for writable in [true, false] {
+            let mut source = if writable {
+                MmapFileSystemSource::new_writable(file_path.clone())
+            } else {
+                MmapFileSystemSource::new(file_path.clone())
+            }
+            .expect("Failed to create source");
+            for (start, end) in [(5, 3), (12, 12), (3, 20)] {
+                let error = source
+                    .try_set_slice(start, end, "ab".to_string())
+                    .unwrap_err();
+                assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+            }
+            assert_eq!(source.get_content(), "Lorem ipsum");
+        }
+
+        // Past the last char, though not past the last byte.
+        let file_path = create_test_file(&temp_dir, "accents.txt", "héllo");
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path).expect("Failed to create source");
+        let error = source.try_set_slice(6, 6, "!".to_string()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        source.try_set_slice(5, 5, "!".to_string()).unwrap();
+        assert_eq!(source.get_content(), "héllo!");
+    }
+
+    #[test]
+    fn test_replaced_file_keeps_its_mode() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "Lorem ipsum");
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o640)).unwrap();
+        // Left behind by an earlier process with the same pid, under the
+        // name the next staged file would take.
+        let next = STAGED.load(Ordering::Relaxed);
+        let stale = temp_dir
+            .path()
+            .join(format!(".test.txt.{}.{next}", std::process::id()));
+        fs::write(&stale, "stale").unwrap();
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        let snapshot = source.snapshot();
+        source.set_slice(0, 5, "Hello,".to_string());
+        source.set_slice(0, 0, ">".to_string());
+
+        let metadata = fs::metadata(&file_path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), ">Hello, ipsum");
+        assert_eq!(snapshot.get_content(), "Lorem ipsum");
+        let names: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 2, "staged files left behind: {names:?}");
+        assert_eq!(fs::read_to_string(&stale).unwrap(), "stale");
+    }
+
+    #[test]
+    fn test_writes_are_in_place_again_once_snapshots_drop() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "test.txt", "aaaa");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        let snapshot = source.snapshot();
+        let cloned = source.clone();
+        drop(snapshot);
+
+        source.set_slice(0, 2, "bb".to_string());
+        assert_eq!(dirty(&source), vec![(0, 2)]);
+        assert_eq!(cloned.get_content(), "bbaa");
+
+        let empty = MmapFileSystemSource {
+            path: file_path,
+            mmap: None,
+        };
+        assert_eq!(empty.snapshot().get_content(), "");
+    }
+
     #[test]
     fn test_create_file() {
         let temp_dir = TempDir::new().expect("Failed to create temp dir");
@@ -361,4 +1321,76 @@ mod tests {
         assert!(result.is_ok());
         assert!(!file_path.exists());
     }
+
+    #[test]
+    fn test_move_file_keeps_mapping_and_never_replaces() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let file_path = create_test_file(&temp_dir, "from.txt", "Lorem ipsum");
+        let taken = create_test_file(&temp_dir, "taken.txt", "other");
+        let moved = temp_dir.path().join("to.txt");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        source.set_slice(0, 5, "LOREM".to_string());
+        let snapshot = source.snapshot();
+        source.move_file(&moved).unwrap();
+
+        assert!(!file_path.exists());
+        assert_eq!(source.path, moved);
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "LOREM ipsum");
+        source.set_slice(6, 11, "IPSUM".to_string());
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "LOREM IPSUM");
+        assert_eq!(snapshot.get_content(), "LOREM ipsum");
+
+        let error = source.move_file(&taken).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(source.path, moved);
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "other");
+    }
+
+    #[test]
+    fn test_move_fallbacks_never_replace() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let taken = create_test_file(&temp_dir, "taken.txt", "other");
+        for fallback in [link_and_unlink, copy_and_unlink] {
+            let from = create_test_file(&temp_dir, "from.txt", "Lorem ipsum");
+            let error = fallback(&from, &taken).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+            assert_eq!(fs::read_to_string(&from).unwrap(), "Lorem ipsum");
+            assert_eq!(fs::read_to_string(&taken).unwrap(), "other");
+
+            let to = temp_dir.path().join("to.txt");
+            fallback(&from, &to).unwrap();
+            assert!(!from.exists());
+            assert_eq!(fs::read_to_string(&to).unwrap(), "Lorem ipsum");
+            fs::remove_file(&to).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_move_file_across_filesystems_maps_the_copy() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        // Only meaningful where /dev/shm is a filesystem of its own.
+        let Ok(other_dir) = TempDir::new_in("/dev/shm") else {
+            return;
+        };
+        let device = |path: &Path| fs::metadata(path).unwrap().dev();
+        if device(temp_dir.path()) == device(other_dir.path()) {
+            return;
+        }
+        let file_path = create_test_file(&temp_dir, "from.txt", "Lorem ipsum");
+        let moved = other_dir.path().join("to.txt");
+
+        let mut source =
+            MmapFileSystemSource::new_writable(file_path.clone()).expect("Failed to create source");
+        let snapshot = source.snapshot();
+        source.move_file(&moved).unwrap();
+
+        assert!(!file_path.exists());
+        assert_eq!(source.path, moved);
+        assert!(source.is_writable());
+        source.set_slice(0, 5, "LOREM".to_string());
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "LOREM ipsum");
+        assert_eq!(snapshot.get_content(), "Lorem ipsum");
+    }
 }
@@ -1,2 +1,20 @@
+pub mod cluster;
+pub mod directory_scan;
+pub mod document_actor;
+pub mod event_bus;
+pub mod external_change_watch;
+pub mod file_locks;
+pub mod file_watcher;
+pub mod git_repository;
+pub mod history_compaction;
+pub mod http;
+pub mod in_memory_file_source;
+pub mod ipynb;
 pub mod mmap_file_sys;
+pub mod operation_history;
+pub mod periodic;
 pub mod persistence;
+pub mod process_kernel;
+pub mod replication;
+pub mod trash_purge;
+pub mod webhook_dispatcher;
@@ -0,0 +1,382 @@
+use crate::domain::events::DomainEvent;
+use crate::domain::history::{HistorySnapshot, RecordedOperation};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+/// How `OperationHistory::compact` thins out the log. Edits older than
+/// `squash_after` that came within `squash_gap` of each other are squashed
+/// into one. With `max_operations` set, the oldest edits of the workspace go
+/// once their text is covered by a snapshot. `snapshot_every` is how many
+/// revisions a `HistoryCompactor` lets pass between snapshots of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionPolicy {
+    pub squash_after: Duration,
+    pub squash_gap: Duration,
+    pub snapshot_every: u64,
+    pub max_operations: Option<usize>,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self {
+            squash_after: Duration::from_secs(10 * 60),
+            squash_gap: Duration::from_secs(2),
+            snapshot_every: 100,
+            max_operations: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionReport {
+    pub squashed: usize,
+    pub dropped: usize,
+}
+
+#[derive(Default)]
+struct FileLog {
+    operations: VecDeque<RecordedOperation>,
+    // Oldest first.
+    snapshots: Vec<HistorySnapshot>,
+    // The revisions someone still needs, by who holds them.
+    pins: HashMap<String, u64>,
+}
+
+impl FileLog {
+    fn oldest_pin(&self) -> u64 {
+        self.pins.values().min().copied().unwrap_or(u64::MAX)
+    }
+
+    // Edits up to the newest snapshot no pin predates are safe to drop: the
+    // text at every pinned revision can be rebuilt from there, and every
+    // client can be caught up from its pin.
+    fn floor(&self) -> Option<u64> {
+        let oldest_pin = self.oldest_pin();
+        self.snapshots
+            .iter()
+            .rev()
+            .map(|snapshot| snapshot.revision)
+            .find(|revision| *revision <= oldest_pin)
+    }
+
+    // A squash erases the revisions in between, so pinned revisions and those
+    // of snapshots stay edges.
+    fn squash(&mut self, policy: &CompactionPolicy, now: SystemTime) -> usize {
+        let old = |operation: &RecordedOperation| {
+            now.duration_since(operation.recorded_at)
+                .is_ok_and(|age| age >= policy.squash_after)
+        };
+        let kept: Vec<u64> = self
+            .pins
+            .values()
+            .copied()
+            .chain(self.snapshots.iter().map(|snapshot| snapshot.revision))
+            .collect();
+        let mut squashed = 0;
+        let mut compacted: VecDeque<RecordedOperation> = VecDeque::new();
+        for operation in self.operations.drain(..) {
+            if let Some(last) = compacted.back_mut()
+                && old(&operation)
+                && !kept.contains(&last.revision)
+                && operation
+                    .recorded_at
+                    .duration_since(last.recorded_at)
+                    .is_ok_and(|gap| gap <= policy.squash_gap)
+                && let Some(merged) = last.squash(&operation)
+            {
+                *last = merged;
+                squashed += 1;
+                continue;
+            }
+            compacted.push_back(operation);
+        }
+        self.operations = compacted;
+        squashed
+    }
+
+    fn droppable(&self) -> Option<SystemTime> {
+        let front = self.operations.front()?;
+        (front.revision <= self.floor()?).then_some(front.recorded_at)
+    }
+
+    // Once the edits before the floor are gone, so are the snapshots before it.
+    fn prune_snapshots(&mut self) {
+        let Some(floor) = self.floor() else {
+            return;
+        };
+        if self
+            .operations
+            .front()
+            .is_none_or(|front| front.base_revision >= floor)
+        {
+            self.snapshots.retain(|snapshot| snapshot.revision >= floor);
+        }
+    }
+}
+
+/// The edit log of every file, in revision order, so that clients that were
+/// away can be brought up to date. Clones share the same log. At most
+/// `capacity` operations are kept per file, dropping the oldest first, except
+/// that edits a pinned revision still needs are kept past it.
+///
+/// Besides the edits it keeps snapshots of the files and the revisions pinned
+/// by open clients, which `compact` never squashes over or drops.
+#[derive(Clone)]
+pub struct OperationHistory {
+    files: Arc<RwLock<HashMap<Uuid, FileLog>>>,
+    capacity: usize,
+}
+
+impl OperationHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            files: Arc::new(RwLock::new(HashMap::new())),
+            capacity,
+        }
+    }
+
+    /// Appends the edit carried by `event`; other events are ignored.
+    pub fn record(&self, event: &DomainEvent) {
+        let DomainEvent::FileEdited {
+            file_id,
+            range,
+            text,
+            author,
+            revision,
+        } = event
+        else {
+            return;
+        };
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut files = self.files.write().unwrap();
+        let log = files.entry(*file_id).or_default();
+        let oldest_pin = log.oldest_pin();
+        while log.operations.len() >= self.capacity
+            && log
+                .operations
+                .front()
+                .is_some_and(|front| front.revision <= oldest_pin)
+        {
+            log.operations.pop_front();
+        }
+        log.operations.push_back(RecordedOperation {
+            base_revision: revision.saturating_sub(1),
+            revision: *revision,
+            range: range.clone(),
+            text: text.clone(),
+            author: author.clone(),
+            recorded_at: SystemTime::now(),
+        });
+    }
+
+    /// The kept operations of `file_id` with a revision above `revision`.
+    pub fn operations_after(&self, file_id: Uuid, revision: u64) -> Vec<RecordedOperation> {
+        self.files
+            .read()
+            .unwrap()
+            .get(&file_id)
+            .map(|log| {
+                log.operations
+                    .iter()
+                    .filter(|operation| operation.revision > revision)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The number of operations kept across every file.
+    pub fn len(&self) -> usize {
+        let files = self.files.read().unwrap();
+        files.values().map(|log| log.operations.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keeps `content` as the text of `file_id` at `revision`, unless a
+    /// snapshot as recent is already kept.
+    pub fn record_snapshot(&self, file_id: Uuid, revision: u64, content: String) {
+        let mut files = self.files.write().unwrap();
+        let snapshots = &mut files.entry(file_id).or_default().snapshots;
+        if snapshots.last().is_none_or(|last| last.revision < revision) {
+            snapshots.push(HistorySnapshot {
+                revision,
+                content,
+                taken_at: SystemTime::now(),
+            });
+        }
+    }
+
+    pub fn snapshots(&self, file_id: Uuid) -> Vec<HistorySnapshot> {
+        let files = self.files.read().unwrap();
+        files
+            .get(&file_id)
+            .map(|log| log.snapshots.clone())
+            .unwrap_or_default()
+    }
+
+    /// The text of `file_id` at `revision`, rebuilt from the newest snapshot
+    /// before it and the edits since, or `None` when they don't reach it.
+    pub fn content_at(&self, file_id: Uuid, revision: u64) -> Option<String> {
+        let files = self.files.read().unwrap();
+        let log = files.get(&file_id)?;
+        let snapshot = log
+            .snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.revision <= revision)?;
+        let mut content = snapshot.content.clone();
+        let mut at = snapshot.revision;
+        for operation in &log.operations {
+            if at == revision {
+                break;
+            }
+            if operation.base_revision == at && operation.revision <= revision {
+                content = operation.apply(&content);
+                at = operation.revision;
+            }
+        }
+        (at == revision).then_some(content)
+    }
+
+    /// Keeps `revision` of `file_id` reachable for `holder` until it pins
+    /// another revision or lets go.
+    pub fn pin(&self, file_id: Uuid, holder: &str, revision: u64) {
+        let mut files = self.files.write().unwrap();
+        let log = files.entry(file_id).or_default();
+        log.pins.insert(holder.to_string(), revision);
+    }
+
+    pub fn unpin(&self, file_id: Uuid, holder: &str) {
+        if let Some(log) = self.files.write().unwrap().get_mut(&file_id) {
+            log.pins.remove(holder);
+        }
+    }
+
+    /// Drops everything kept of `file_id`: its edits, snapshots and pins.
+    pub fn forget(&self, file_id: Uuid) {
+        self.files.write().unwrap().remove(&file_id);
+    }
+
+    /// Squashes old runs of edits and drops the oldest edits of the workspace
+    /// while there are more than the policy allows.
+    pub fn compact(&self, policy: &CompactionPolicy) -> CompactionReport {
+        let now = SystemTime::now();
+        let mut files = self.files.write().unwrap();
+        let mut report = CompactionReport::default();
+        for log in files.values_mut() {
+            report.squashed += log.squash(policy, now);
+        }
+
+        if let Some(max) = policy.max_operations {
+            let mut kept: usize = files.values().map(|log| log.operations.len()).sum();
+            while kept > max {
+                let oldest = files
+                    .values_mut()
+                    .filter_map(|log| log.droppable().map(|recorded_at| (recorded_at, log)))
+                    .min_by_key(|(recorded_at, _)| *recorded_at);
+                let Some((_, log)) = oldest else {
+                    break;
+                };
+                log.operations.pop_front();
+                kept -= 1;
+                report.dropped += 1;
+            }
+        }
+        for log in files.values_mut() {
+            log.prune_snapshots();
+        }
+        report
+    }
+}
+
+impl Default for OperationHistory {
+    fn default() -> Self {
+        Self::new(usize::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(file_id: Uuid, revision: u64) -> DomainEvent {
+        DomainEvent::FileEdited {
+            file_id,
+            range: 0..0,
+            text: revision.to_string(),
+            author: "ada".to_string(),
+            revision,
+        }
+    }
+
+    #[test]
+    fn test_records_edits_per_file() {
+        let history = OperationHistory::default();
+        let file_id = Uuid::new_v4();
+        let other_id = Uuid::new_v4();
+        history.record(&DomainEvent::FileCreated {
+            file_id,
+            name: "main.rs".to_string(),
+            revision: 0,
+        });
+        for revision in 1..=3 {
+            history.record(&edit(file_id, revision));
+        }
+        history.clone().record(&edit(other_id, 1));
+
+        let revisions: Vec<u64> = history
+            .operations_after(file_id, 1)
+            .iter()
+            .map(|operation| operation.revision)
+            .collect();
+        assert_eq!(revisions, vec![2, 3]);
+        assert_eq!(history.operations_after(other_id, 0)[0].text, "1");
+        assert!(history.operations_after(Uuid::new_v4(), 0).is_empty());
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest() {
+        let history = OperationHistory::new(2);
+        let file_id = Uuid::new_v4();
+        for revision in 1..=3 {
+            history.record(&edit(file_id, revision));
+        }
+        let revisions: Vec<u64> = history
+            .operations_after(file_id, 0)
+            .iter()
+            .map(|operation| operation.revision)
+            .collect();
+        assert_eq!(revisions, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_capacity_keeps_edits_after_a_pin() {
+        let history = OperationHistory::new(2);
+        let file_id = Uuid::new_v4();
+        history.record(&edit(file_id, 1));
+        history.pin(file_id, "client", 1);
+        for revision in 2..=4 {
+            history.record(&edit(file_id, revision));
+        }
+        let revisions = |history: &OperationHistory| -> Vec<u64> {
+            history
+                .operations_after(file_id, 0)
+                .iter()
+                .map(|operation| operation.revision)
+                .collect()
+        };
+        assert_eq!(revisions(&history), vec![2, 3, 4]);
+
+        history.unpin(file_id, "client");
+        history.record(&edit(file_id, 5));
+        assert_eq!(revisions(&history), vec![4, 5]);
+    }
+}
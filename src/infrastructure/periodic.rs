@@ -0,0 +1,60 @@
+use crate::application::errors::ApplicationError;
+use std::thread;
+use std::time::Duration;
+
+/// Runs `task` every `interval` on a thread named `name` for as long as the
+/// process lives. The error of a run that fails goes to `failed`, and the
+/// next run still goes ahead.
+pub fn spawn_periodic<T, F, E>(
+    name: &'static str,
+    interval: Duration,
+    mut task: F,
+    mut failed: E,
+) -> thread::JoinHandle<()>
+where
+    F: FnMut() -> Result<T, ApplicationError> + Send + 'static,
+    E: FnMut(ApplicationError) + Send + 'static,
+{
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            loop {
+                thread::sleep(interval);
+                if let Err(e) = task() {
+                    failed(e);
+                }
+            }
+        })
+        .expect("failed to spawn a periodic task thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_failed_runs_do_not_stop_the_task() {
+        let (sender, receiver) = mpsc::channel();
+        let mut runs = 0;
+        spawn_periodic(
+            "test task",
+            Duration::from_millis(1),
+            move || {
+                runs += 1;
+                Err::<(), _>(ApplicationError::IoError(std::io::Error::other(format!(
+                    "flaky {runs}"
+                ))))
+            },
+            move |e| sender.send(e).unwrap(),
+        );
+        for expected in 1..=3 {
+            match receiver.recv_timeout(Duration::from_secs(2)).unwrap() {
+                ApplicationError::IoError(e) => {
+                    assert_eq!(e.to_string(), format!("flaky {expected}"))
+                }
+                _ => panic!("Expected IoError"),
+            }
+        }
+    }
+}
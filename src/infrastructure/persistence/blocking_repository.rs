@@ -0,0 +1,124 @@
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::code_file_repository::{
+    AsyncCodeFileRepository, CodeFileRepository,
+};
+use crate::domain::code_file::CodeFile;
+use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+
+/// Runs `task` on the blocking thread pool so file IO never stalls the async
+/// executor.
+pub async fn run_blocking<T, F>(task: F) -> Result<T, ApplicationError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, ApplicationError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| ApplicationError::IoError(std::io::Error::other(e)))?
+}
+
+/// Makes any synchronous repository usable as an `AsyncCodeFileRepository`.
+/// Lookups share a read lock and run side by side; saves, updates and deletes
+/// take the write lock. Every async call runs on the blocking thread pool.
+///
+/// It is a `CodeFileRepository` as well, used in place, so sync usecases and
+/// async callers can work on the same files through clones of it.
+pub struct BlockingCodeFileRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    inner: Arc<RwLock<Box<dyn CodeFileRepository<FileSource>>>>,
+}
+
+impl<FileSource> BlockingCodeFileRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    pub fn new(repository: Box<dyn CodeFileRepository<FileSource>>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(repository)),
+        }
+    }
+}
+
+impl<FileSource> Clone for BlockingCodeFileRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<FileSource> CodeFileRepository<FileSource> for BlockingCodeFileRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    fn save(
+        &mut self,
+        file: CodeFile<FileSource>,
+    ) -> Result<CodeFile<FileSource>, ApplicationError> {
+        self.inner.write().unwrap().save(file)
+    }
+
+    fn find_by_id(&self, id: Uuid) -> Result<CodeFile<FileSource>, ApplicationError> {
+        self.inner.read().unwrap().find_by_id(id)
+    }
+
+    fn update(&mut self, file: CodeFile<FileSource>) -> Result<(), ApplicationError> {
+        self.inner.write().unwrap().update(file)
+    }
+
+    fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError> {
+        self.inner.write().unwrap().delete(id)
+    }
+
+    fn list(&self) -> Result<Vec<CodeFile<FileSource>>, ApplicationError> {
+        self.inner.read().unwrap().list()
+    }
+
+    fn ids(&self) -> Result<Vec<Uuid>, ApplicationError> {
+        self.inner.read().unwrap().ids()
+    }
+}
+
+#[async_trait]
+impl<FileSource> AsyncCodeFileRepository<FileSource> for BlockingCodeFileRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete + Send + 'static,
+{
+    async fn save(
+        &self,
+        file: CodeFile<FileSource>,
+    ) -> Result<CodeFile<FileSource>, ApplicationError> {
+        let inner = Arc::clone(&self.inner);
+        run_blocking(move || inner.write().unwrap().save(file)).await
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<CodeFile<FileSource>, ApplicationError> {
+        let inner = Arc::clone(&self.inner);
+        run_blocking(move || inner.read().unwrap().find_by_id(id)).await
+    }
+
+    async fn update(&self, file: CodeFile<FileSource>) -> Result<(), ApplicationError> {
+        let inner = Arc::clone(&self.inner);
+        run_blocking(move || inner.write().unwrap().update(file)).await
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<(), ApplicationError> {
+        let inner = Arc::clone(&self.inner);
+        run_blocking(move || inner.write().unwrap().delete(id)).await
+    }
+
+    async fn list(&self) -> Result<Vec<CodeFile<FileSource>>, ApplicationError> {
+        let inner = Arc::clone(&self.inner);
+        run_blocking(move || inner.read().unwrap().list()).await
+    }
+}
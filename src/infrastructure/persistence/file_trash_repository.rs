@@ -0,0 +1,149 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::trash_repository::TrashRepository;
+use crate::domain::trash::TrashedFile;
+
+const INDEX: &str = "index.json";
+
+/// Keeps the trash in a directory the engine owns: trashed files are stored
+/// there by id, next to an index of them that is rewritten on every change,
+/// so the trash outlives restarts and is purged like before them.
+#[derive(Clone)]
+pub struct FileTrashRepository {
+    dir: PathBuf,
+    storage: Arc<RwLock<Vec<TrashedFile>>>,
+}
+
+impl FileTrashRepository {
+    /// Opens the trash in `dir`, creating it when missing. A file moved in
+    /// without making it into the index, as by a crash in between, can't be
+    /// restored and is removed.
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let storage: Vec<TrashedFile> = match fs::read(dir.join(INDEX)) {
+            Ok(index) => serde_json::from_slice(&index)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let orphan = entry.file_type()?.is_file()
+                && file_id(&path)
+                    .is_some_and(|id| storage.iter().all(|trashed| trashed.file_id != id));
+            if orphan {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(Self {
+            dir,
+            storage: Arc::new(RwLock::new(storage)),
+        })
+    }
+
+    // Written aside and renamed over the index, so a crash leaves either the
+    // old index or the new one.
+    fn persist(&self, storage: &[TrashedFile]) -> Result<(), ApplicationError> {
+        let index = serde_json::to_vec(storage).map_err(ApplicationError::ParseError)?;
+        let written = self.dir.join(format!("{INDEX}.tmp"));
+        fs::write(&written, index).map_err(ApplicationError::IoError)?;
+        fs::rename(&written, self.dir.join(INDEX)).map_err(ApplicationError::IoError)
+    }
+}
+
+fn file_id(path: &Path) -> Option<Uuid> {
+    Uuid::parse_str(path.file_name()?.to_str()?).ok()
+}
+
+impl TrashRepository for FileTrashRepository {
+    fn save(&self, file: TrashedFile) -> Result<TrashedFile, ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        match storage
+            .iter_mut()
+            .find(|saved| saved.file_id == file.file_id)
+        {
+            Some(saved) => *saved = file.clone(),
+            None => storage.push(file.clone()),
+        }
+        self.persist(&storage)?;
+        Ok(file)
+    }
+
+    fn find_by_id(&self, file_id: Uuid) -> Result<TrashedFile, ApplicationError> {
+        let storage = self.storage.read().unwrap();
+        storage
+            .iter()
+            .find(|file| file.file_id == file_id)
+            .cloned()
+            .ok_or_else(|| ApplicationError::TrashedFileNotFound(file_id.to_string()))
+    }
+
+    fn delete(&self, file_id: Uuid) -> Result<(), ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        let index = storage
+            .iter()
+            .position(|file| file.file_id == file_id)
+            .ok_or_else(|| ApplicationError::TrashedFileNotFound(file_id.to_string()))?;
+        storage.remove(index);
+        self.persist(&storage)
+    }
+
+    fn list(&self) -> Result<Vec<TrashedFile>, ApplicationError> {
+        Ok(self.storage.read().unwrap().clone())
+    }
+
+    fn trashed_path(&self, file_id: Uuid) -> PathBuf {
+        self.dir.join(file_id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use tempfile::TempDir;
+
+    fn trashed(trash: &FileTrashRepository, name: &str) -> TrashedFile {
+        let file_id = Uuid::new_v4();
+        let trashed_path = trash.trashed_path(file_id);
+        fs::write(&trashed_path, name).unwrap();
+        TrashedFile {
+            file_id,
+            name: name.to_string(),
+            revision: 2,
+            path: PathBuf::from("/checkout").join(name),
+            trashed_path,
+            deleted_at: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_trash_survives_a_restart() {
+        let dir = TempDir::new().unwrap();
+        let trash = FileTrashRepository::open(dir.path().join("trash")).unwrap();
+        let kept = trash.save(trashed(&trash, "kept.rs")).unwrap();
+        let restored = trash.save(trashed(&trash, "restored.rs")).unwrap();
+        trash.delete(restored.file_id).unwrap();
+        fs::remove_file(&restored.trashed_path).unwrap();
+        // Moved in, but never indexed.
+        let orphan = trash.trashed_path(Uuid::new_v4());
+        fs::write(&orphan, "lost").unwrap();
+        drop(trash);
+
+        let trash = FileTrashRepository::open(dir.path().join("trash")).unwrap();
+        assert_eq!(trash.list().unwrap(), vec![kept.clone()]);
+        assert_eq!(fs::read_to_string(&kept.trashed_path).unwrap(), "kept.rs");
+        assert!(!orphan.exists());
+        match trash.find_by_id(restored.file_id) {
+            Err(ApplicationError::TrashedFileNotFound(_)) => {},
+            _ => panic!("Expected TrashedFileNotFound error"),
+        }
+    }
+}
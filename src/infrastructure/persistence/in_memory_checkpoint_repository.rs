@@ -0,0 +1,52 @@
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::checkpoint_repository::CheckpointRepository;
+use crate::domain::checkpoint::Checkpoint;
+
+// Kept in creation order, which is the order `list` promises.
+#[derive(Clone, Default)]
+pub struct InMemoryCheckpointRepository {
+    storage: Arc<RwLock<Vec<Checkpoint>>>,
+}
+
+impl InMemoryCheckpointRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CheckpointRepository for InMemoryCheckpointRepository {
+    fn save(&mut self, checkpoint: Checkpoint) -> Result<Checkpoint, ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        match storage.iter_mut().find(|saved| saved.id == checkpoint.id) {
+            Some(saved) => *saved = checkpoint.clone(),
+            None => storage.push(checkpoint.clone()),
+        }
+        Ok(checkpoint)
+    }
+
+    fn find_by_id(&self, id: Uuid) -> Result<Checkpoint, ApplicationError> {
+        let storage = self.storage.read().unwrap();
+        storage
+            .iter()
+            .find(|checkpoint| checkpoint.id == id)
+            .cloned()
+            .ok_or_else(|| ApplicationError::CheckpointNotFound(id.to_string()))
+    }
+
+    fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        let index = storage
+            .iter()
+            .position(|checkpoint| checkpoint.id == id)
+            .ok_or_else(|| ApplicationError::CheckpointNotFound(id.to_string()))?;
+        storage.remove(index);
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<Checkpoint>, ApplicationError> {
+        Ok(self.storage.read().unwrap().clone())
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::notebook_repository::NotebookRepository;
+use crate::domain::notebook::Notebook;
+use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+
+pub struct InMemoryNotebookRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    storage: Arc<RwLock<HashMap<Uuid, Notebook<FileSource>>>>,
+}
+
+impl<FileSource> InMemoryNotebookRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    pub fn new() -> Self {
+        Self {
+            storage: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+impl<FileSource> Clone for InMemoryNotebookRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    fn clone(&self) -> Self {
+        Self {
+            storage: Arc::clone(&self.storage),
+        }
+    }
+}
+
+impl<FileSource> Default for InMemoryNotebookRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<FileSource> NotebookRepository<FileSource> for InMemoryNotebookRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete + Clone + Send + Sync,
+{
+    fn save(
+        &mut self,
+        notebook: Notebook<FileSource>,
+    ) -> Result<Notebook<FileSource>, ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        storage.insert(notebook.id(), notebook.clone());
+        Ok(notebook)
+    }
+
+    fn find_by_id(&self, id: Uuid) -> Result<Notebook<FileSource>, ApplicationError> {
+        let storage = self.storage.read().unwrap();
+        storage
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ApplicationError::NotebookNotFound(id.to_string()))
+    }
+
+    fn update(&mut self, notebook: Notebook<FileSource>) -> Result<(), ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        if !storage.contains_key(&notebook.id()) {
+            return Err(ApplicationError::NotebookNotFound(
+                notebook.id().to_string(),
+            ));
+        }
+        storage.insert(notebook.id(), notebook);
+        Ok(())
+    }
+
+    fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        storage
+            .remove(&id)
+            .ok_or_else(|| ApplicationError::NotebookNotFound(id.to_string()))?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<Notebook<FileSource>>, ApplicationError> {
+        let storage = self.storage.read().unwrap();
+        Ok(storage.values().cloned().collect())
+    }
+}
@@ -1,17 +1,116 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
 use uuid::Uuid;
 
 use crate::application::errors::ApplicationError;
 use crate::application::repositories::code_file_repository::CodeFileRepository;
 use crate::domain::code_file::CodeFile;
-use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead, DynemicFileWrite};
+use crate::domain::traits::dyn_file::{
+    DynemicFileCreateDelete, DynemicFileRead, DynemicFileResidency, DynemicFileWrite,
+};
+
+/// Caps on what the repository keeps open. Past either one the least recently
+/// used files are unloaded; they reopen on their next lookup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheLimits {
+    pub max_open: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+impl CacheLimits {
+    fn exceeded(&self, open: usize, bytes: usize) -> bool {
+        self.max_open.is_some_and(|max| open > max) || self.max_bytes.is_some_and(|max| bytes > max)
+    }
+}
+
+// Last use tick per file, and the same ticks ordered oldest first.
+#[derive(Default)]
+struct Recency {
+    used: HashMap<Uuid, u64>,
+    order: BTreeMap<u64, Uuid>,
+    tick: u64,
+}
+
+impl Recency {
+    fn touch(&mut self, id: Uuid) {
+        self.tick += 1;
+        if let Some(previous) = self.used.insert(id, self.tick) {
+            self.order.remove(&previous);
+        }
+        self.order.insert(self.tick, id);
+    }
+
+    fn forget(&mut self, id: Uuid) {
+        if let Some(previous) = self.used.remove(&id) {
+            self.order.remove(&previous);
+        }
+    }
+}
+
+// Uses are recorded behind a mutex of their own, so lookups of loaded files
+// share the read lock on the files.
+struct Storage<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    files: HashMap<Uuid, CodeFile<FileSource>>,
+    recency: Mutex<Recency>,
+}
+
+impl<FileSource> Storage<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete + DynemicFileResidency,
+{
+    fn touch(&self, id: Uuid) {
+        self.recency.lock().unwrap().touch(id);
+    }
+
+    fn forget(&mut self, id: Uuid) {
+        self.recency.get_mut().unwrap().forget(id);
+    }
+
+    fn loaded(&self) -> impl Iterator<Item = &CodeFile<FileSource>> {
+        self.files.values().filter(|file| file.source.is_loaded())
+    }
+
+    // Unloads the least recently used files until the limits hold again. `keep`
+    // is the file being handed out and is never unloaded.
+    fn evict(&mut self, limits: &CacheLimits, keep: Uuid) {
+        let mut open = self.loaded().count();
+        let mut bytes = self.loaded().map(|file| file.source.resident_size()).sum();
+        let oldest: Vec<Uuid> = self
+            .recency
+            .get_mut()
+            .unwrap()
+            .order
+            .values()
+            .copied()
+            .collect();
+        for id in oldest {
+            if !limits.exceeded(open, bytes) {
+                break;
+            }
+            let Some(file) = self.files.get_mut(&id) else {
+                continue;
+            };
+            if id == keep || !file.source.is_loaded() {
+                continue;
+            }
+            let size = file.source.resident_size();
+            if file.source.unload().is_ok() {
+                open -= 1;
+                bytes -= size;
+            }
+        }
+    }
+}
 
 pub struct InMemoryCodeFileRepository<FileSource>
 where
     FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
 {
-    storage: Arc<RwLock<HashMap<Uuid, CodeFile<FileSource>>>>,
+    storage: Arc<RwLock<Storage<FileSource>>>,
+    limits: CacheLimits,
 }
 
 impl<FileSource> InMemoryCodeFileRepository<FileSource>
@@ -19,49 +118,282 @@ where
     FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
 {
     pub fn new() -> Self {
+        Self::with_limits(CacheLimits::default())
+    }
+
+    pub fn with_limits(limits: CacheLimits) -> Self {
+        Self {
+            storage: Arc::new(RwLock::new(Storage {
+                files: HashMap::new(),
+                recency: Mutex::new(Recency::default()),
+            })),
+            limits,
+        }
+    }
+
+    pub fn limits(&self) -> CacheLimits {
+        self.limits
+    }
+}
+
+impl<FileSource> InMemoryCodeFileRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete + DynemicFileResidency,
+{
+    /// How many stored files are loaded right now.
+    pub fn open_files(&self) -> usize {
+        self.storage.read().unwrap().loaded().count()
+    }
+
+    /// The bytes held by the loaded files.
+    pub fn resident_bytes(&self) -> usize {
+        let storage = self.storage.read().unwrap();
+        storage
+            .loaded()
+            .map(|file| file.source.resident_size())
+            .sum()
+    }
+}
+
+// Clones share the same storage, so several usecases can work on one set of files.
+impl<FileSource> Clone for InMemoryCodeFileRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    fn clone(&self) -> Self {
         Self {
-            storage: Arc::new(RwLock::new(HashMap::new())),
+            storage: Arc::clone(&self.storage),
+            limits: self.limits,
         }
     }
 }
 
+impl<FileSource> Default for InMemoryCodeFileRepository<FileSource>
+where
+    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<FileSource> CodeFileRepository<FileSource> for InMemoryCodeFileRepository<FileSource>
 where
-    FileSource: DynemicFileRead + DynemicFileWrite + DynemicFileCreateDelete + Clone + Send + Sync,
+    FileSource: DynemicFileRead
+        + DynemicFileWrite
+        + DynemicFileCreateDelete
+        + DynemicFileResidency
+        + Clone
+        + Send
+        + Sync,
 {
-    fn save(&mut self, file: CodeFile<FileSource>) -> Result<CodeFile<FileSource>, ApplicationError> {
+    fn save(
+        &mut self,
+        file: CodeFile<FileSource>,
+    ) -> Result<CodeFile<FileSource>, ApplicationError> {
         let mut storage = self.storage.write().unwrap();
-        storage.insert(file.id(), file.clone());
+        storage.files.insert(file.id(), file.clone());
+        storage.touch(file.id());
+        storage.evict(&self.limits, file.id());
         Ok(file)
     }
 
+    // Only a file that has to reopen takes the write lock; files something
+    // unloaded reopen here even without limits.
     fn find_by_id(&self, id: Uuid) -> Result<CodeFile<FileSource>, ApplicationError> {
-        let storage = self.storage.read().unwrap();
-        storage
-            .get(&id)
-            .cloned()
-            .ok_or_else(|| ApplicationError::FileNotFound(id.to_string()))
+        {
+            let storage = self.storage.read().unwrap();
+            let file = storage
+                .files
+                .get(&id)
+                .ok_or_else(|| ApplicationError::FileNotFound(id.to_string()))?;
+            if file.source.is_loaded() {
+                storage.touch(id);
+                return Ok(file.clone());
+            }
+        }
+
+        let mut storage = self.storage.write().unwrap();
+        let file = storage
+            .files
+            .get_mut(&id)
+            .ok_or_else(|| ApplicationError::FileNotFound(id.to_string()))?;
+        file.source.load().map_err(ApplicationError::IoError)?;
+        let file = file.clone();
+        storage.touch(id);
+        storage.evict(&self.limits, id);
+        Ok(file)
     }
 
     fn update(&mut self, file: CodeFile<FileSource>) -> Result<(), ApplicationError> {
         let mut storage = self.storage.write().unwrap();
-        if !storage.contains_key(&file.id()) {
+        if !storage.files.contains_key(&file.id()) {
             return Err(ApplicationError::FileNotFound(file.id().to_string()));
         }
-        storage.insert(file.id(), file);
+        let id = file.id();
+        storage.files.insert(id, file);
+        storage.touch(id);
+        storage.evict(&self.limits, id);
         Ok(())
     }
 
     fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError> {
         let mut storage = self.storage.write().unwrap();
         storage
+            .files
             .remove(&id)
             .ok_or_else(|| ApplicationError::FileNotFound(id.to_string()))?;
+        storage.forget(id);
         Ok(())
     }
 
+    // Listing opens nothing, so it never pushes the cache past its limits.
     fn list(&self) -> Result<Vec<CodeFile<FileSource>>, ApplicationError> {
         let storage = self.storage.read().unwrap();
-        Ok(storage.values().cloned().collect())
+        Ok(storage.files.values().cloned().collect())
+    }
+
+    fn ids(&self) -> Result<Vec<Uuid>, ApplicationError> {
+        Ok(self.storage.read().unwrap().files.keys().copied().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use std::path::Path;
+
+    fn file(dir: &Path, name: &str, content: &str) -> CodeFile<MmapFileSystemSource> {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        let source = MmapFileSystemSource::new_writable(path).unwrap();
+        CodeFile::new(Uuid::new_v4(), name.to_string(), source)
+    }
+
+    #[test]
+    fn test_least_recently_used_files_are_unloaded_past_the_handle_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut repository = InMemoryCodeFileRepository::with_limits(CacheLimits {
+            max_open: Some(2),
+            max_bytes: None,
+        });
+        let ids: Vec<Uuid> = ["one", "two", "three"]
+            .iter()
+            .map(|name| repository.save(file(dir.path(), name, name)).unwrap().id())
+            .collect();
+        assert_eq!(repository.open_files(), 2);
+
+        // The first file reopens on lookup and pushes out the second.
+        let first = repository.find_by_id(ids[0]).unwrap();
+        assert_eq!(first.source.get_content(), "one");
+        assert_eq!(repository.open_files(), 2);
+        let storage = repository.storage.read().unwrap();
+        assert!(!storage.files[&ids[1]].source.is_loaded());
+        assert!(storage.files[&ids[2]].source.is_loaded());
+    }
+
+    #[test]
+    fn test_lookups_of_loaded_files_count_as_uses() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut repository = InMemoryCodeFileRepository::with_limits(CacheLimits {
+            max_open: Some(2),
+            max_bytes: None,
+        });
+        let first = repository.save(file(dir.path(), "one", "one")).unwrap().id();
+        let second = repository.save(file(dir.path(), "two", "two")).unwrap().id();
+
+        // Still loaded, so found without reopening, but now the newest.
+        repository.find_by_id(first).unwrap();
+        repository.save(file(dir.path(), "three", "three")).unwrap();
+        let storage = repository.storage.read().unwrap();
+        assert!(storage.files[&first].source.is_loaded());
+        assert!(!storage.files[&second].source.is_loaded());
+    }
+
+    #[test]
+    fn test_byte_limit_bounds_resident_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut repository = InMemoryCodeFileRepository::with_limits(CacheLimits {
+            max_open: None,
+            max_bytes: Some(10),
+        });
+        let first = repository
+            .save(file(dir.path(), "a.txt", "12345678"))
+            .unwrap()
+            .id();
+        let second = repository
+            .save(file(dir.path(), "b.txt", "abcdefgh"))
+            .unwrap()
+            .id();
+        assert_eq!(
+            (repository.open_files(), repository.resident_bytes()),
+            (1, 8)
+        );
+
+        // Listing leaves the evicted file closed; looking it up reopens it.
+        let loaded: Vec<(Uuid, bool)> = repository
+            .list()
+            .unwrap()
+            .iter()
+            .map(|file| (file.id(), file.source.is_loaded()))
+            .collect();
+        assert!(loaded.contains(&(first, false)) && loaded.contains(&(second, true)));
+        assert_eq!(repository.open_files(), 1);
+
+        assert_eq!(
+            repository.find_by_id(first).unwrap().source.get_content(),
+            "12345678"
+        );
+        repository.delete(second).unwrap();
+        assert_eq!(repository.resident_bytes(), 8);
+    }
+
+    #[test]
+    fn test_lookup_fails_when_an_evicted_file_cant_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut repository = InMemoryCodeFileRepository::with_limits(CacheLimits {
+            max_open: Some(1),
+            max_bytes: None,
+        });
+        let first = repository
+            .save(file(dir.path(), "a.txt", "hello"))
+            .unwrap()
+            .id();
+        repository.save(file(dir.path(), "b.txt", "other")).unwrap();
+        std::fs::remove_file(dir.path().join("a.txt")).unwrap();
+
+        match repository.find_by_id(first) {
+            Err(ApplicationError::IoError(_)) => {},
+            _ => panic!("Expected IoError"),
+        }
+        let mut ids = repository.ids().unwrap();
+        ids.sort();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&first));
+    }
+
+    #[test]
+    fn test_edits_survive_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut repository = InMemoryCodeFileRepository::with_limits(CacheLimits {
+            max_open: Some(1),
+            max_bytes: None,
+        });
+        let first = repository
+            .save(file(dir.path(), "a.txt", "hello"))
+            .unwrap()
+            .id();
+
+        let mut edited = repository.find_by_id(first).unwrap();
+        edited.source.set_slice(0, 5, "HELLO".to_string());
+        edited.bump_revision();
+        repository.update(edited).unwrap();
+        repository.save(file(dir.path(), "b.txt", "other")).unwrap();
+        assert_eq!(repository.open_files(), 1);
+
+        let reopened = repository.find_by_id(first).unwrap();
+        assert_eq!(reopened.source.get_content(), "HELLO");
+        assert_eq!(reopened.revision(), 1);
     }
 }
@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::trash_repository::TrashRepository;
+use crate::domain::trash::TrashedFile;
+
+// A directory of its own under the system temp dir, removed with whatever
+// is left in it once the last clone of the repository is gone.
+struct TrashDir(PathBuf);
+
+impl Drop for TrashDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// Kept in deletion order, which is the order `list` promises. Nothing outlives
+// the process, trashed files included.
+#[derive(Clone)]
+pub struct InMemoryTrashRepository {
+    dir: Arc<TrashDir>,
+    storage: Arc<RwLock<Vec<TrashedFile>>>,
+}
+
+impl InMemoryTrashRepository {
+    pub fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("colab-engine-trash-{}", Uuid::new_v4()));
+        Self {
+            dir: Arc::new(TrashDir(dir)),
+            storage: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+}
+
+impl Default for InMemoryTrashRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrashRepository for InMemoryTrashRepository {
+    fn save(&self, file: TrashedFile) -> Result<TrashedFile, ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        match storage
+            .iter_mut()
+            .find(|saved| saved.file_id == file.file_id)
+        {
+            Some(saved) => *saved = file.clone(),
+            None => storage.push(file.clone()),
+        }
+        Ok(file)
+    }
+
+    fn find_by_id(&self, file_id: Uuid) -> Result<TrashedFile, ApplicationError> {
+        let storage = self.storage.read().unwrap();
+        storage
+            .iter()
+            .find(|file| file.file_id == file_id)
+            .cloned()
+            .ok_or_else(|| ApplicationError::TrashedFileNotFound(file_id.to_string()))
+    }
+
+    fn delete(&self, file_id: Uuid) -> Result<(), ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        let index = storage
+            .iter()
+            .position(|file| file.file_id == file_id)
+            .ok_or_else(|| ApplicationError::TrashedFileNotFound(file_id.to_string()))?;
+        storage.remove(index);
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<TrashedFile>, ApplicationError> {
+        Ok(self.storage.read().unwrap().clone())
+    }
+
+    fn trashed_path(&self, file_id: Uuid) -> PathBuf {
+        self.dir.0.join(file_id.to_string())
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+use crate::application::errors::ApplicationError;
+use crate::application::repositories::webhook_repository::WebhookRepository;
+use crate::domain::webhook::{DeliveryAttempt, Webhook};
+
+/// How many delivery attempts are kept per webhook; older ones are dropped.
+pub const MAX_DELIVERIES: usize = 100;
+
+#[derive(Default)]
+struct Storage {
+    webhooks: HashMap<Uuid, Webhook>,
+    deliveries: HashMap<Uuid, VecDeque<DeliveryAttempt>>,
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryWebhookRepository {
+    storage: Arc<RwLock<Storage>>,
+}
+
+impl InMemoryWebhookRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl WebhookRepository for InMemoryWebhookRepository {
+    fn save(&mut self, webhook: Webhook) -> Result<Webhook, ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        storage.webhooks.insert(webhook.id, webhook.clone());
+        Ok(webhook)
+    }
+
+    fn find_by_id(&self, id: Uuid) -> Result<Webhook, ApplicationError> {
+        let storage = self.storage.read().unwrap();
+        storage
+            .webhooks
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ApplicationError::WebhookNotFound(id.to_string()))
+    }
+
+    fn delete(&mut self, id: Uuid) -> Result<(), ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        storage
+            .webhooks
+            .remove(&id)
+            .ok_or_else(|| ApplicationError::WebhookNotFound(id.to_string()))?;
+        storage.deliveries.remove(&id);
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<Webhook>, ApplicationError> {
+        let storage = self.storage.read().unwrap();
+        Ok(storage.webhooks.values().cloned().collect())
+    }
+
+    fn record_delivery(&mut self, attempt: DeliveryAttempt) -> Result<(), ApplicationError> {
+        let mut storage = self.storage.write().unwrap();
+        // Attempts still in flight for a removed webhook aren't kept.
+        if !storage.webhooks.contains_key(&attempt.webhook_id) {
+            return Ok(());
+        }
+        let log = storage.deliveries.entry(attempt.webhook_id).or_default();
+        if log.len() == MAX_DELIVERIES {
+            log.pop_front();
+        }
+        log.push_back(attempt);
+        Ok(())
+    }
+
+    fn deliveries(&self, webhook_id: Uuid) -> Result<Vec<DeliveryAttempt>, ApplicationError> {
+        let storage = self.storage.read().unwrap();
+        Ok(storage
+            .deliveries
+            .get(&webhook_id)
+            .map(|log| log.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::webhook::{DeliveryOutcome, WebhookScope};
+
+    #[test]
+    fn test_delivery_log_keeps_the_latest_attempts() {
+        let mut repository = InMemoryWebhookRepository::new();
+        let webhook = repository
+            .save(Webhook {
+                id: Uuid::new_v4(),
+                url: "http://127.0.0.1:1/hook".to_string(),
+                secret: "s3cret".to_string(),
+                scope: WebhookScope::Workspace,
+                events: Vec::new(),
+            })
+            .unwrap();
+        for sequence in 0..(MAX_DELIVERIES as u64 + 5) {
+            repository
+                .record_delivery(DeliveryAttempt {
+                    delivery_id: Uuid::new_v4(),
+                    webhook_id: webhook.id,
+                    sequence,
+                    event: "updated".to_string(),
+                    attempt: 1,
+                    outcome: DeliveryOutcome::Delivered(200),
+                })
+                .unwrap();
+        }
+
+        let deliveries = repository.deliveries(webhook.id).unwrap();
+        assert_eq!(deliveries.len(), MAX_DELIVERIES);
+        assert_eq!(deliveries[0].sequence, 5);
+
+        repository.delete(webhook.id).unwrap();
+        assert!(repository.deliveries(webhook.id).unwrap().is_empty());
+    }
+}
@@ -1 +1,7 @@
+pub mod blocking_repository;
+pub mod file_trash_repository;
+pub mod in_memory_checkpoint_repository;
+pub mod in_memory_notebook_repository;
 pub mod in_memory_repository;
+pub mod in_memory_trash_repository;
+pub mod in_memory_webhook_repository;
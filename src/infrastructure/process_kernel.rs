@@ -0,0 +1,655 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use uuid::Uuid;
+
+use crate::domain::execution::{ExecutionStatus, KernelEvent};
+
+// Reads length-prefixed snippets from stdin and runs them in one shared
+// namespace, writing the marker to both streams once a snippet is done.
+const PYTHON_DRIVER: &str = r#"
+import sys, traceback
+marker = sys.argv[1]
+namespace = {"__name__": "__main__"}
+while True:
+    try:
+        header = sys.stdin.readline()
+    except KeyboardInterrupt:
+        continue
+    if not header:
+        break
+    code = sys.stdin.read(int(header))
+    status = "ok"
+    try:
+        exec(compile(code, "<cell>", "exec"), namespace)
+    except KeyboardInterrupt:
+        status = "interrupted"
+        traceback.print_exc()
+    except BaseException:
+        status = "error"
+        traceback.print_exc()
+    sys.stderr.write(marker + "\n")
+    sys.stderr.flush()
+    sys.stdout.write(marker + " " + status + "\n")
+    sys.stdout.flush()
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelDriver {
+    Python,
+    Shell,
+}
+
+#[derive(Debug, Clone)]
+pub struct KernelSpec {
+    pub language: String,
+    pub program: String,
+    pub driver: KernelDriver,
+}
+
+impl KernelSpec {
+    pub fn python() -> Self {
+        Self {
+            language: "python".to_string(),
+            program: "python3".to_string(),
+            driver: KernelDriver::Python,
+        }
+    }
+
+    pub fn shell() -> Self {
+        Self {
+            language: "shell".to_string(),
+            program: "sh".to_string(),
+            driver: KernelDriver::Shell,
+        }
+    }
+
+    pub fn for_language(language: &str) -> Option<Self> {
+        match language {
+            "python" | "python3" => Some(Self::python()),
+            "shell" | "sh" => Some(Self::shell()),
+            _ => None,
+        }
+    }
+
+    // The kernel leads a process group of its own, so an interrupt reaches
+    // whatever the code started as well.
+    fn command(&self, marker: &str) -> Command {
+        let mut command = Command::new(&self.program);
+        if self.driver == KernelDriver::Python {
+            command.args(["-u", "-c", PYTHON_DRIVER, marker]);
+        }
+        command.process_group(0);
+        command
+    }
+
+    // Sent once before any code. An interrupt then stops the running command
+    // and not the shell itself.
+    fn prelude(&self) -> &'static str {
+        match self.driver {
+            KernelDriver::Python => "",
+            KernelDriver::Shell => "trap : INT\n",
+        }
+    }
+
+    fn frame(&self, code: &str, marker: &str) -> String {
+        match self.driver {
+            KernelDriver::Python => {
+                // Python reads stdin in text mode, which would fold "\r\n" and
+                // throw the char count off.
+                let code = code.replace("\r\n", "\n");
+                format!("{}\n{}", code.chars().count(), code)
+            }
+            // The code is read in full before it runs and never reads the
+            // shell's stdin, which holds the submissions after it. Through
+            // `command`, a syntax error fails the snippet, not the shell.
+            KernelDriver::Shell => format!(
+                "command eval '{}' </dev/null\n__status=$?\nprintf '%s\\n' {marker} >&2\n\
+                 if [ $__status -eq 0 ]; then printf '%s ok\\n' {marker}; \
+                 elif [ $__status -eq 130 ]; then printf '%s interrupted\\n' {marker}; \
+                 else printf '%s error\\n' {marker}; fi\n",
+                code.replace('\'', "'\\''")
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+struct ProcessState {
+    // Execution ids keep counting across restarts; `base` is the id that was
+    // current when this process was spawned.
+    base: u64,
+    submitted: u64,
+    stdout_done: u64,
+    stderr_done: u64,
+    statuses: HashMap<u64, ExecutionStatus>,
+    terminated: bool,
+}
+
+type Subscribers = Arc<Mutex<Vec<Sender<KernelEvent>>>>;
+
+fn broadcast(subscribers: &Subscribers, event: KernelEvent) {
+    let mut subscribers = subscribers.lock().unwrap();
+    subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+}
+
+fn parse_status(rest: &str) -> ExecutionStatus {
+    match rest.trim() {
+        "ok" => ExecutionStatus::Ok,
+        "interrupted" => ExecutionStatus::Interrupted,
+        _ => ExecutionStatus::Error,
+    }
+}
+
+fn read_stream<R: Read>(
+    stream: Stream,
+    reader: R,
+    marker: String,
+    state: Arc<Mutex<ProcessState>>,
+    subscribers: Subscribers,
+) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+
+    // Read as bytes, since the code may print anything; the text goes out
+    // lossily decoded.
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {},
+        }
+
+        let (text, rest) = match line
+            .windows(marker.len())
+            .position(|window| window == marker.as_bytes())
+        {
+            Some(index) => (&line[..index], Some(&line[index + marker.len()..])),
+            None => (line.as_slice(), None),
+        };
+
+        let mut state = state.lock().unwrap();
+        let done = match stream {
+            Stream::Stdout => state.stdout_done,
+            Stream::Stderr => state.stderr_done,
+        };
+        let execution_id = state.base + done + 1;
+
+        if !text.is_empty() {
+            let text = String::from_utf8_lossy(text).into_owned();
+            broadcast(
+                &subscribers,
+                match stream {
+                    Stream::Stdout => KernelEvent::Stdout { execution_id, text },
+                    Stream::Stderr => KernelEvent::Stderr { execution_id, text },
+                },
+            );
+        }
+
+        let Some(rest) = rest else { continue };
+        let other_done = match stream {
+            Stream::Stdout => {
+                state.stdout_done += 1;
+                state
+                    .statuses
+                    .insert(execution_id, parse_status(&String::from_utf8_lossy(rest)));
+                state.stderr_done
+            }
+            Stream::Stderr => {
+                state.stderr_done += 1;
+                state.stdout_done
+            }
+        };
+        if other_done > done {
+            let status = state
+                .statuses
+                .remove(&execution_id)
+                .unwrap_or(ExecutionStatus::Error);
+            broadcast(
+                &subscribers,
+                KernelEvent::Finished {
+                    execution_id,
+                    status,
+                },
+            );
+        }
+    }
+
+    // Only stdout reports the process going away, so subscribers see it once.
+    if let Stream::Stdout = stream {
+        let mut state = state.lock().unwrap();
+        if state.terminated {
+            return;
+        }
+        state.terminated = true;
+        for done in state.stdout_done..state.submitted {
+            broadcast(
+                &subscribers,
+                KernelEvent::Finished {
+                    execution_id: state.base + done + 1,
+                    status: ExecutionStatus::Died,
+                },
+            );
+        }
+        broadcast(&subscribers, KernelEvent::Died);
+    }
+}
+
+struct KernelProcess {
+    child: Child,
+    stdin: ChildStdin,
+    state: Arc<Mutex<ProcessState>>,
+    // Until the child is waited for, its pid stays taken, so its process
+    // group can't be some other one.
+    reaped: bool,
+}
+
+pub struct ProcessKernel {
+    spec: KernelSpec,
+    marker: String,
+    process: KernelProcess,
+    subscribers: Subscribers,
+}
+
+impl ProcessKernel {
+    pub fn spawn(spec: KernelSpec) -> std::io::Result<Self> {
+        let marker = format!("__colab_engine_done_{}__", Uuid::new_v4().simple());
+        let subscribers: Subscribers = Arc::new(Mutex::new(Vec::new()));
+        let process = Self::spawn_process(&spec, &marker, 0, &subscribers)?;
+        Ok(Self {
+            spec,
+            marker,
+            process,
+            subscribers,
+        })
+    }
+
+    fn spawn_process(
+        spec: &KernelSpec,
+        marker: &str,
+        base: u64,
+        subscribers: &Subscribers,
+    ) -> std::io::Result<KernelProcess> {
+        let mut child = spec
+            .command(marker)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(spec.prelude().as_bytes())?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let state = Arc::new(Mutex::new(ProcessState {
+            base,
+            submitted: 0,
+            stdout_done: 0,
+            stderr_done: 0,
+            statuses: HashMap::new(),
+            terminated: false,
+        }));
+
+        for (stream, reader) in [
+            (Stream::Stdout, Box::new(stdout) as Box<dyn Read + Send>),
+            (Stream::Stderr, Box::new(stderr) as Box<dyn Read + Send>),
+        ] {
+            let marker = marker.to_string();
+            let state = Arc::clone(&state);
+            let subscribers = Arc::clone(subscribers);
+            thread::spawn(move || read_stream(stream, reader, marker, state, subscribers));
+        }
+
+        Ok(KernelProcess {
+            child,
+            stdin,
+            state,
+            reaped: false,
+        })
+    }
+
+    pub fn language(&self) -> &str {
+        &self.spec.language
+    }
+
+    pub fn subscribe(&self) -> Receiver<KernelEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Queues `code` for execution and returns its id. Output and completion are
+    /// delivered to subscribers as `KernelEvent`s tagged with that id.
+    pub fn execute(&mut self, code: &str) -> std::io::Result<u64> {
+        let execution_id = {
+            let mut state = self.process.state.lock().unwrap();
+            if state.terminated {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "kernel process is not running",
+                ));
+            }
+            state.submitted += 1;
+            state.base + state.submitted
+        };
+
+        let framed = self.spec.frame(code, &self.marker);
+        self.process.stdin.write_all(framed.as_bytes())?;
+        self.process.stdin.flush()?;
+        Ok(execution_id)
+    }
+
+    /// Interrupts the running code along with every process it started.
+    pub fn interrupt(&self) -> std::io::Result<()> {
+        let pid = self.process.child.id() as libc::pid_t;
+        // SAFETY: `kill` has no memory-safety preconditions; `-pid` names the
+        // process group our own child leads.
+        if unsafe { libc::kill(-pid, libc::SIGINT) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    pub fn restart(&mut self) -> std::io::Result<()> {
+        self.terminate();
+        let base = {
+            let state = self.process.state.lock().unwrap();
+            state.base + state.submitted
+        };
+        self.process = Self::spawn_process(&self.spec, &self.marker, base, &self.subscribers)?;
+        broadcast(&self.subscribers, KernelEvent::Restarted);
+        Ok(())
+    }
+
+    pub fn shutdown(mut self) {
+        self.terminate();
+    }
+
+    fn terminate(&mut self) {
+        let pending = {
+            let mut state = self.process.state.lock().unwrap();
+            if state.terminated {
+                0..0
+            } else {
+                state.terminated = true;
+                (state.base + state.stdout_done + 1)..(state.base + state.submitted + 1)
+            }
+        };
+        // Whatever the code left running in the background goes with it.
+        if !self.process.reaped {
+            let pid = self.process.child.id() as libc::pid_t;
+            // SAFETY: `kill` has no memory-safety preconditions; `-pid` names
+            // the process group our own, not yet reaped, child leads.
+            unsafe { libc::kill(-pid, libc::SIGKILL) };
+            let _ = self.process.child.wait();
+            self.process.reaped = true;
+        }
+        for execution_id in pending {
+            broadcast(
+                &self.subscribers,
+                KernelEvent::Finished {
+                    execution_id,
+                    status: ExecutionStatus::Interrupted,
+                },
+            );
+        }
+    }
+}
+
+impl Drop for ProcessKernel {
+    fn drop(&mut self) {
+        self.terminate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn collect_until_finished(
+        receiver: &Receiver<KernelEvent>,
+        execution_id: u64,
+    ) -> (String, String, ExecutionStatus) {
+        let (mut stdout, mut stderr) = (String::new(), String::new());
+        loop {
+            match receiver.recv_timeout(TIMEOUT).expect("kernel event") {
+                KernelEvent::Stdout {
+                    execution_id: id,
+                    text,
+                } if id == execution_id => stdout.push_str(&text),
+                KernelEvent::Stderr {
+                    execution_id: id,
+                    text,
+                } if id == execution_id => stderr.push_str(&text),
+                KernelEvent::Finished {
+                    execution_id: id,
+                    status,
+                } if id == execution_id => {
+                    return (stdout, stderr, status);
+                }
+                _ => {},
+            }
+        }
+    }
+
+    #[test]
+    fn test_shell_kernel_streams_output() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::shell()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let id = kernel.execute("echo hello\necho oops >&2").unwrap();
+        let (stdout, stderr, status) = collect_until_finished(&receiver, id);
+        assert_eq!(stdout, "hello\n");
+        assert_eq!(stderr, "oops\n");
+        assert_eq!(status, ExecutionStatus::Ok);
+
+        let id = kernel.execute("false").unwrap();
+        let (_, _, status) = collect_until_finished(&receiver, id);
+        assert_eq!(status, ExecutionStatus::Error);
+    }
+
+    #[test]
+    fn test_shell_output_without_trailing_newline() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::shell()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let id = kernel.execute("printf partial").unwrap();
+        let (stdout, _, status) = collect_until_finished(&receiver, id);
+        assert_eq!(stdout, "partial");
+        assert_eq!(status, ExecutionStatus::Ok);
+    }
+
+    #[test]
+    fn test_output_that_is_not_utf8_keeps_the_kernel_going() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::shell()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let id = kernel.execute("printf '\\377\\n'\nprintf '\\377\\n' >&2").unwrap();
+        let (stdout, stderr, status) = collect_until_finished(&receiver, id);
+        assert_eq!((stdout.as_str(), stderr.as_str()), ("\u{FFFD}\n", "\u{FFFD}\n"));
+        assert_eq!(status, ExecutionStatus::Ok);
+
+        let id = kernel.execute("echo next\necho again >&2").unwrap();
+        let (stdout, stderr, status) = collect_until_finished(&receiver, id);
+        assert_eq!((stdout.as_str(), stderr.as_str()), ("next\n", "again\n"));
+        assert_eq!(status, ExecutionStatus::Ok);
+    }
+
+    #[test]
+    fn test_python_kernel_keeps_state() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::python()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let first = kernel.execute("x = 20").unwrap();
+        let second = kernel.execute("print(x + 22)").unwrap();
+        assert_eq!(second, first + 1);
+
+        collect_until_finished(&receiver, first);
+        let (stdout, _, status) = collect_until_finished(&receiver, second);
+        assert_eq!(stdout, "42\n");
+        assert_eq!(status, ExecutionStatus::Ok);
+
+        let id = kernel.execute("raise ValueError('boom')").unwrap();
+        let (_, stderr, status) = collect_until_finished(&receiver, id);
+        assert!(stderr.contains("ValueError: boom"));
+        assert_eq!(status, ExecutionStatus::Error);
+    }
+
+    #[test]
+    fn test_python_kernel_interrupt() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::python()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let id = kernel
+            .execute("import time\nprint('started', flush=True)\ntime.sleep(30)")
+            .unwrap();
+        loop {
+            if let KernelEvent::Stdout { .. } = receiver.recv_timeout(TIMEOUT).unwrap() {
+                break;
+            }
+        }
+        kernel.interrupt().unwrap();
+        let (_, stderr, status) = collect_until_finished(&receiver, id);
+        assert!(stderr.contains("KeyboardInterrupt"));
+        assert_eq!(status, ExecutionStatus::Interrupted);
+
+        let id = kernel.execute("print('still alive')").unwrap();
+        let (stdout, _, _) = collect_until_finished(&receiver, id);
+        assert_eq!(stdout, "still alive\n");
+    }
+
+    #[test]
+    fn test_shell_interrupt_reaches_the_running_command() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::shell()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let id = kernel.execute("x=kept\necho started\nsleep 30").unwrap();
+        loop {
+            if let KernelEvent::Stdout { .. } = receiver.recv_timeout(TIMEOUT).unwrap() {
+                break;
+            }
+        }
+        // A signal that lands between the echo and the sleep only runs the trap.
+        let status = loop {
+            kernel.interrupt().unwrap();
+            match receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(KernelEvent::Finished {
+                    execution_id,
+                    status,
+                }) if execution_id == id => break status,
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {},
+                Err(e) => panic!("kernel event: {e:?}"),
+            }
+        };
+        assert_eq!(status, ExecutionStatus::Interrupted);
+
+        let id = kernel.execute("echo \"$x\"").unwrap();
+        let (stdout, _, _) = collect_until_finished(&receiver, id);
+        assert_eq!(stdout, "kept\n");
+    }
+
+    #[test]
+    fn test_shell_code_does_not_read_later_submissions() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::shell()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let reading = kernel.execute("read line; echo \"[$line]\"").unwrap();
+        let quoted = kernel.execute("echo 'next' \"it's\"").unwrap();
+        let broken = kernel.execute("if").unwrap();
+        let after = kernel.execute("echo after").unwrap();
+
+        let mut stdout: HashMap<u64, String> = HashMap::new();
+        let mut statuses = HashMap::new();
+        while statuses.len() < 4 {
+            match receiver.recv_timeout(TIMEOUT).expect("kernel event") {
+                KernelEvent::Stdout { execution_id, text } => {
+                    stdout.entry(execution_id).or_default().push_str(&text)
+                }
+                KernelEvent::Finished {
+                    execution_id,
+                    status,
+                } => {
+                    statuses.insert(execution_id, status);
+                }
+                _ => {},
+            }
+        }
+        assert_eq!(stdout[&reading], "[]\n");
+        assert_eq!(stdout[&quoted], "next it's\n");
+        assert_eq!(statuses[&broken], ExecutionStatus::Error);
+        assert_eq!(stdout[&after], "after\n");
+    }
+
+    #[test]
+    fn test_restart_clears_state() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::python()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let id = kernel.execute("x = 1").unwrap();
+        collect_until_finished(&receiver, id);
+
+        kernel.restart().unwrap();
+        let id = kernel.execute("print('x' in globals())").unwrap();
+        assert_eq!(id, 2);
+        let (stdout, _, _) = collect_until_finished(&receiver, id);
+        assert_eq!(stdout, "False\n");
+        assert!(receiver.try_iter().all(|event| event != KernelEvent::Died));
+    }
+
+    #[test]
+    fn test_restart_kills_background_processes() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::shell()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let id = kernel.execute("sleep 30 &\necho $!").unwrap();
+        let (stdout, _, _) = collect_until_finished(&receiver, id);
+        let pid: libc::pid_t = stdout.trim().parse().unwrap();
+        // A killed sleep may linger as a zombie until whatever adopted it
+        // reaps it.
+        let alive = || {
+            std::fs::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| {
+                let state = stat.rsplit_once(") ").map(|(_, rest)| &rest[..1]);
+                state != Some("Z")
+            })
+        };
+        assert!(alive());
+
+        kernel.restart().unwrap();
+        let deadline = std::time::Instant::now() + TIMEOUT;
+        while alive() {
+            assert!(std::time::Instant::now() < deadline, "sleep survived");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_process_exit_reports_died() {
+        let mut kernel = ProcessKernel::spawn(KernelSpec::shell()).unwrap();
+        let receiver = kernel.subscribe();
+
+        let id = kernel.execute("exit 3").unwrap();
+        let (_, _, status) = collect_until_finished(&receiver, id);
+        assert_eq!(status, ExecutionStatus::Died);
+        assert_eq!(receiver.recv_timeout(TIMEOUT).unwrap(), KernelEvent::Died);
+        assert!(kernel.execute("echo again").is_err());
+
+        kernel.restart().unwrap();
+        let id = kernel.execute("echo again").unwrap();
+        let (stdout, _, _) = collect_until_finished(&receiver, id);
+        assert_eq!(stdout, "again\n");
+    }
+}
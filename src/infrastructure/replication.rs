@@ -0,0 +1,1025 @@
+use crate::application::dto::code_file::UpdateCodeRequest;
+use crate::application::dto::external_change::EXTERNAL_AUTHOR;
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::{CodeFileUsecases, CodeFileUsecasesImpl};
+use crate::application::usecases::trash_usecases::TrashUsecases;
+use crate::domain::code_file::CodeFile;
+use crate::domain::events::DomainEvent;
+use crate::domain::replica::{Change, ReplicatedOperation, ReplicatedText, VersionVector};
+use crate::domain::text_diff::{TextEdit, diff};
+use crate::domain::traits::dyn_file::{DynemicFileCreateDelete, DynemicFileRead};
+use crate::infrastructure::event_bus::PublishedEvent;
+use crate::infrastructure::http::auth::{ClusterToken, require_token};
+use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::middleware;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use ureq::Agent;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullBody {
+    pub node_id: Uuid,
+    pub version: VersionVector,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullReply {
+    pub node_id: Uuid,
+    pub version: VersionVector,
+    pub operations: Vec<ReplicatedOperation>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushBody {
+    pub operations: Vec<ReplicatedOperation>,
+}
+
+#[derive(Debug, Default)]
+struct FileReplica {
+    text: ReplicatedText,
+    // The local revision the replicated text matches.
+    revision: u64,
+    removed: bool,
+}
+
+#[derive(Debug)]
+struct ReplicationState {
+    node_id: Uuid,
+    seq: u64,
+    version: VersionVector,
+    // The operations known here, each after the ones it depends on. Past
+    // `log_limit` the oldest go once every known node has them; `pruned` is
+    // the last one gone from each origin.
+    log: Vec<ReplicatedOperation>,
+    log_limit: usize,
+    pruned: VersionVector,
+    // The version each node last reported, and the node behind each peer URL.
+    acknowledged: HashMap<Uuid, VersionVector>,
+    peer_ids: HashMap<String, Uuid>,
+    // The version at which deleted chars were last dropped.
+    purged_at: Option<VersionVector>,
+    files: HashMap<Uuid, FileReplica>,
+    // Local changes are found through the events of the files they touch;
+    // every file is looked at once, on the first ingest.
+    changes: UnboundedReceiver<PublishedEvent>,
+    dirty: HashSet<Uuid>,
+    scanned: bool,
+}
+
+impl ReplicationState {
+    fn record(&mut self, file_id: Uuid, author: String, change: Change) {
+        self.seq += 1;
+        self.version.insert(self.node_id, self.seq);
+        self.log.push(ReplicatedOperation {
+            origin: self.node_id,
+            seq: self.seq,
+            file_id,
+            author,
+            change,
+        });
+    }
+
+    fn acknowledge(&mut self, node_id: Uuid, version: VersionVector, peers: &[String]) {
+        self.acknowledged.insert(node_id, version);
+        // Until every peer was heard from, some node may still need anything.
+        if !peers.iter().all(|peer| self.peer_ids.contains_key(peer)) {
+            return;
+        }
+
+        let seen_by_all = |operation: &ReplicatedOperation| {
+            self.acknowledged.values().all(|version| {
+                version.get(&operation.origin).copied().unwrap_or(0) >= operation.seq
+            })
+        };
+        let prunable = self.log.len().saturating_sub(self.log_limit);
+        let stable = self.log[..prunable]
+            .iter()
+            .take_while(|operation| seen_by_all(operation))
+            .count();
+        for operation in self.log.drain(..stable) {
+            self.pruned.insert(operation.origin, operation.seq);
+        }
+
+        // Once every node has exactly what this one has, whatever is made
+        // next refers to visible chars only.
+        let settled = self
+            .acknowledged
+            .values()
+            .all(|version| *version == self.version);
+        if settled && self.purged_at.as_ref() != Some(&self.version) {
+            for replica in self.files.values_mut() {
+                replica.text.purge_tombstones();
+            }
+            self.purged_at = Some(self.version.clone());
+        }
+    }
+}
+
+/// How many operations are kept at least, whether every node has them or not.
+pub const DEFAULT_LOG_LIMIT: usize = 10_000;
+
+/// Keeps the files of one engine instance in step with its peers. Local
+/// changes are picked up from the operation history and recorded as
+/// operations on a replicated text per file; peers pull what they miss from
+/// each other, so there is no leader and a node that was away catches up from
+/// whichever peer it reaches first.
+///
+/// Old operations and deleted chars are dropped once every node this one
+/// knows of, its peers and any node that pulled from it, has them, so a node
+/// that stops replicating holds that back until it returns. A node first
+/// heard from after operations were dropped is refused with
+/// `HistoryUnavailable` and has to start from a copy of the files.
+#[derive(Clone)]
+pub struct ReplicationNode {
+    files: Arc<Mutex<CodeFileUsecasesImpl>>,
+    state: Arc<Mutex<ReplicationState>>,
+    data_dir: PathBuf,
+    peers: Arc<Vec<String>>,
+    agent: Agent,
+    token: Option<ClusterToken>,
+}
+
+impl ReplicationNode {
+    /// `peers` are base URLs such as `http://127.0.0.1:3001`; files created
+    /// by other nodes are stored under `data_dir`.
+    pub fn new(
+        node_id: Uuid,
+        files: Arc<Mutex<CodeFileUsecasesImpl>>,
+        data_dir: PathBuf,
+        peers: Vec<String>,
+    ) -> std::io::Result<Self> {
+        fs::create_dir_all(&data_dir)?;
+        let changes = files.lock().unwrap().events.subscribe();
+        let agent = Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(10)))
+            .build()
+            .into();
+        Ok(Self {
+            files,
+            state: Arc::new(Mutex::new(ReplicationState {
+                node_id,
+                seq: 0,
+                version: VersionVector::new(),
+                log: Vec::new(),
+                log_limit: DEFAULT_LOG_LIMIT,
+                pruned: VersionVector::new(),
+                acknowledged: HashMap::new(),
+                peer_ids: HashMap::new(),
+                purged_at: None,
+                files: HashMap::new(),
+                changes,
+                dirty: HashSet::new(),
+                scanned: false,
+            })),
+            data_dir,
+            peers: Arc::new(peers),
+            agent,
+            token: None,
+        })
+    }
+
+    pub fn token(&self) -> Option<ClusterToken> {
+        self.token.clone()
+    }
+
+    /// Keeps at least `limit` operations, so nodes that were never heard
+    /// from can still catch up on that many.
+    pub fn with_log_limit(self, limit: usize) -> Self {
+        self.state.lock().unwrap().log_limit = limit;
+        self
+    }
+
+    /// Presents `token` to peers and asks it of them.
+    pub fn with_token(mut self, token: ClusterToken) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    pub fn node_id(&self) -> Uuid {
+        self.state.lock().unwrap().node_id
+    }
+
+    pub fn version(&self) -> VersionVector {
+        self.state.lock().unwrap().version.clone()
+    }
+
+    /// The operations known here that a node at `version` has not seen yet,
+    /// or `HistoryUnavailable` with its version of the first origin it is
+    /// missing dropped operations of.
+    pub fn missing_for(
+        &self,
+        version: &VersionVector,
+    ) -> Result<Vec<ReplicatedOperation>, ApplicationError> {
+        let files = self.files.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        ingest(&files, &mut state)?;
+        for (origin, pruned) in &state.pruned {
+            let seen = version.get(origin).copied().unwrap_or(0);
+            if seen < *pruned {
+                return Err(ApplicationError::HistoryUnavailable(seen));
+            }
+        }
+        Ok(state
+            .log
+            .iter()
+            .filter(|operation| {
+                operation.seq > version.get(&operation.origin).copied().unwrap_or(0)
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Answers a pull from `node_id`, which is at `version`.
+    pub fn pull(
+        &self,
+        node_id: Uuid,
+        version: VersionVector,
+    ) -> Result<PullReply, ApplicationError> {
+        self.state
+            .lock()
+            .unwrap()
+            .acknowledge(node_id, version.clone(), &self.peers);
+        Ok(PullReply {
+            node_id: self.node_id(),
+            operations: self.missing_for(&version)?,
+            version: self.version(),
+        })
+    }
+
+    /// Applies operations from a peer to the local files and returns how many
+    /// were new. One that arrives before something it depends on is left out
+    /// and comes again with a later exchange.
+    pub fn apply_remote(
+        &self,
+        operations: Vec<ReplicatedOperation>,
+    ) -> Result<usize, ApplicationError> {
+        let mut files = self.files.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        ingest(&files, &mut state)?;
+
+        let mut applied = 0;
+        for operation in operations {
+            let seen = state.version.get(&operation.origin).copied().unwrap_or(0);
+            if operation.seq != seen + 1 {
+                continue;
+            }
+            // Local edits that landed since the ingest above are recorded
+            // first, and no other lands until the operation is written, so
+            // its positions still fit the replica's text.
+            let _writer = files.writers.lock(operation.file_id);
+            ingest_one(&files, &mut state, operation.file_id)?;
+            if !self.integrate(&mut files, &mut state, &operation)? {
+                continue;
+            }
+            state.version.insert(operation.origin, operation.seq);
+            state.log.push(operation);
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    fn integrate(
+        &self,
+        files: &mut CodeFileUsecasesImpl,
+        state: &mut ReplicationState,
+        operation: &ReplicatedOperation,
+    ) -> Result<bool, ApplicationError> {
+        let file_id = operation.file_id;
+        match &operation.change {
+            Change::Create { name } => {
+                // A file removed here and restored elsewhere starts over, its
+                // content coming again as one insert; the copy in the trash
+                // here would only stand in its way.
+                match state.files.get(&file_id) {
+                    Some(replica) if !replica.removed => return Ok(true),
+                    Some(_) => match files.purge_code_file(file_id) {
+                        Ok(()) | Err(ApplicationError::TrashedFileNotFound(_)) => {},
+                        Err(e) => return Err(e),
+                    },
+                    None => {},
+                }
+                let path = self.data_dir.join(file_id.to_string());
+                MmapFileSystemSource {
+                    path: path.clone(),
+                    mmap: None,
+                }
+                .create_file()
+                .map_err(ApplicationError::IoError)?;
+                let source =
+                    MmapFileSystemSource::new_writable(path).map_err(ApplicationError::IoError)?;
+
+                let code_file =
+                    files
+                        .repository
+                        .save(CodeFile::new(file_id, name.clone(), source))?;
+                files.publish(DomainEvent::FileCreated {
+                    file_id,
+                    name: name.clone(),
+                    revision: code_file.revision(),
+                });
+                state.files.insert(file_id, FileReplica::default());
+            }
+            Change::Text { operation: text } => {
+                let Some(replica) = state.files.get_mut(&file_id) else {
+                    return Ok(false);
+                };
+                if replica.removed {
+                    return Ok(true);
+                }
+                let Some(edits) = replica.text.integrate(text) else {
+                    return Ok(false);
+                };
+                for edit in edits {
+                    files.update_code_file(UpdateCodeRequest {
+                        id: file_id,
+                        start: edit.start as u64,
+                        end: edit.end as u64,
+                        content: edit.text,
+                        author: operation.author.clone(),
+                    })?;
+                    replica.revision += 1;
+                }
+            }
+            Change::Remove => {
+                let Some(replica) = state.files.get_mut(&file_id) else {
+                    return Ok(false);
+                };
+                if !replica.removed {
+                    replica.removed = true;
+                    replica.text = ReplicatedText::default();
+                    files.delete_code_file(file_id)?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// One anti-entropy exchange: pulls what this node misses from `peer`,
+    /// then pushes what the peer misses from here.
+    pub fn exchange(&self, peer: &str) -> Result<(), ApplicationError> {
+        let reply: PullReply = self.post(
+            peer,
+            "/replication/pull",
+            &PullBody {
+                node_id: self.node_id(),
+                version: self.version(),
+            },
+        )?;
+        self.apply_remote(reply.operations)?;
+        {
+            let mut state = self.state.lock().unwrap();
+            state.peer_ids.insert(peer.to_string(), reply.node_id);
+            state.acknowledge(reply.node_id, reply.version.clone(), &self.peers);
+        }
+
+        let operations = self.missing_for(&reply.version)?;
+        if !operations.is_empty() {
+            self.post::<_, Value>(peer, "/replication/push", &PushBody { operations })?;
+        }
+        Ok(())
+    }
+
+    /// Exchanges with every peer once; one that can't be reached is skipped.
+    pub fn anti_entropy_round(&self) -> Vec<(String, ApplicationError)> {
+        self.peers
+            .iter()
+            .filter_map(|peer| self.exchange(peer).err().map(|e| (peer.clone(), e)))
+            .collect()
+    }
+
+    pub fn spawn_anti_entropy(&self, interval: Duration) -> thread::JoinHandle<()> {
+        let node = self.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                node.anti_entropy_round();
+            }
+        })
+    }
+
+    fn post<B: Serialize, R: DeserializeOwned>(
+        &self,
+        peer: &str,
+        path: &str,
+        body: &B,
+    ) -> Result<R, ApplicationError> {
+        let body = serde_json::to_vec(body).map_err(ApplicationError::ParseError)?;
+        let mut request = self
+            .agent
+            .post(format!("{}{}", peer.trim_end_matches('/'), path))
+            .header("content-type", "application/json");
+        if let Some(token) = &self.token {
+            request = request.header(header::AUTHORIZATION, token.header_value());
+        }
+        let mut response = request
+            .send(&body)
+            .map_err(|e| ApplicationError::PeerUnreachable(format!("{peer}: {e}")))?;
+        let text = response
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| ApplicationError::PeerUnreachable(format!("{peer}: {e}")))?;
+        serde_json::from_str(&text).map_err(ApplicationError::ParseError)
+    }
+}
+
+// Records local changes made since the last call: files that appeared or went
+// away, and the edits in between, replayed from the operation history when it
+// still holds all of them and as one diff otherwise. Only files with events
+// since the last call are looked at.
+fn ingest(
+    files: &CodeFileUsecasesImpl,
+    state: &mut ReplicationState,
+) -> Result<(), ApplicationError> {
+    while let Ok(published) = state.changes.try_recv() {
+        state.dirty.insert(published.event.file_id());
+    }
+    if !state.scanned {
+        state.dirty.extend(files.repository.ids()?);
+        state.scanned = true;
+    }
+
+    let dirty: Vec<Uuid> = state.dirty.iter().copied().collect();
+    for file_id in dirty {
+        ingest_one(files, state, file_id)?;
+    }
+    Ok(())
+}
+
+// Records the local changes to one file under its writer lock, so its content
+// and revision are read together.
+fn ingest_one(
+    files: &CodeFileUsecasesImpl,
+    state: &mut ReplicationState,
+    file_id: Uuid,
+) -> Result<(), ApplicationError> {
+    let _writer = files.writers.lock(file_id);
+    for (author, change) in ingest_file(files, state, file_id)? {
+        state.record(file_id, author, change);
+    }
+    state.dirty.remove(&file_id);
+    Ok(())
+}
+
+fn ingest_file(
+    files: &CodeFileUsecasesImpl,
+    state: &mut ReplicationState,
+    file_id: Uuid,
+) -> Result<Vec<(String, Change)>, ApplicationError> {
+    let node_id = state.node_id;
+    let code_file = match files.repository.find_by_id(file_id) {
+        Ok(code_file) => code_file,
+        Err(ApplicationError::FileNotFound(_)) => {
+            return Ok(match state.files.get_mut(&file_id) {
+                Some(replica) if !replica.removed => {
+                    replica.removed = true;
+                    replica.text = ReplicatedText::default();
+                    vec![(String::new(), Change::Remove)]
+                }
+                _ => Vec::new(),
+            });
+        }
+        Err(e) => return Err(e),
+    };
+
+    // A file restored from the trash goes out again like a new one.
+    let mut changes = Vec::new();
+    let current = code_file.revision();
+    let fresh = state
+        .files
+        .get(&file_id)
+        .is_none_or(|replica| replica.removed);
+    if fresh {
+        changes.push((
+            String::new(),
+            Change::Create {
+                name: code_file.name.clone(),
+            },
+        ));
+        state.files.insert(file_id, FileReplica::default());
+    }
+    let replica = state.files.entry(file_id).or_default();
+    if !fresh && replica.revision == current {
+        return Ok(changes);
+    }
+
+    // A file seen for the first time may not have started out empty, so its
+    // whole content goes out as one insert.
+    let replayed = match fresh {
+        true => None,
+        false => files.missed_operations(file_id, replica.revision, current),
+    };
+    let edits: Vec<(String, TextEdit)> = match replayed {
+        Some(operations) => operations
+            .into_iter()
+            .map(|operation| {
+                let edit = TextEdit {
+                    start: operation.start,
+                    end: operation.end,
+                    text: operation.text,
+                };
+                (operation.author, edit)
+            })
+            .collect(),
+        // The diff is put down to whoever edited last, as far as the history
+        // still tells.
+        None => {
+            let author = files
+                .history
+                .operations_after(file_id, replica.revision)
+                .pop()
+                .map_or_else(|| EXTERNAL_AUTHOR.to_string(), |operation| operation.author);
+            diff(&replica.text.text(), &code_file.snapshot().get_content())
+                .map(|edit| (author, edit))
+                .into_iter()
+                .collect()
+        }
+    };
+    for (author, edit) in edits {
+        for operation in replica.text.local_edit(node_id, &edit) {
+            changes.push((author.clone(), Change::Text { operation }));
+        }
+    }
+    replica.revision = current;
+    Ok(changes)
+}
+
+fn error_reply(e: ApplicationError) -> (StatusCode, Json<Value>) {
+    let status = match e {
+        ApplicationError::HistoryUnavailable(_) => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "error": format!("{e:?}") })))
+}
+
+/// Returns the operations the calling peer misses, along with this node's
+/// version so the caller can push back what this node misses.
+pub async fn pull(
+    State(node): State<ReplicationNode>,
+    Json(body): Json<PullBody>,
+) -> Result<Json<PullReply>, (StatusCode, Json<Value>)> {
+    let reply = tokio::task::spawn_blocking(move || node.pull(body.node_id, body.version))
+        .await
+        .map_err(|e| error_reply(ApplicationError::IoError(e.into())))?
+        .map_err(error_reply)?;
+    Ok(Json(reply))
+}
+
+pub async fn push(
+    State(node): State<ReplicationNode>,
+    Json(body): Json<PushBody>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let applied = tokio::task::spawn_blocking(move || node.apply_remote(body.operations))
+        .await
+        .map_err(|e| error_reply(ApplicationError::IoError(e.into())))?
+        .map_err(error_reply)?;
+    Ok(Json(json!({ "applied": applied })))
+}
+
+pub fn router(node: ReplicationNode) -> Router {
+    Router::new()
+        .route("/replication/pull", post(pull))
+        .route("/replication/push", post(push))
+        .route_layer(middleware::from_fn_with_state(
+            node.token.clone(),
+            require_token,
+        ))
+        .with_state(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::CreateCodeFileRequest;
+    use crate::application::usecases::async_code_file_usecases::{
+        AsyncCodeFileUsecases, AsyncCodeFileUsecasesImpl,
+    };
+    use crate::domain::replica::TextOperation;
+    use crate::infrastructure::operation_history::OperationHistory;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use tempfile::TempDir;
+
+    struct Member {
+        node: ReplicationNode,
+        files: Arc<Mutex<CodeFileUsecasesImpl>>,
+        _data_dir: TempDir,
+    }
+
+    // Starts one node per entry of `peers`, each listing the indexes of the
+    // nodes it exchanges with.
+    async fn cluster(peers: &[&[usize]]) -> Vec<Member> {
+        cluster_with_tokens(peers, &vec![None; peers.len()]).await
+    }
+
+    async fn cluster_with_tokens(peers: &[&[usize]], tokens: &[Option<&str>]) -> Vec<Member> {
+        let mut listeners = Vec::new();
+        for _ in peers {
+            listeners.push(tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap());
+        }
+        let urls: Vec<String> = listeners
+            .iter()
+            .map(|listener| format!("http://{}", listener.local_addr().unwrap()))
+            .collect();
+
+        let mut members = Vec::new();
+        for ((listener, peers), token) in listeners.into_iter().zip(peers).zip(tokens) {
+            let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+            let files = Arc::new(Mutex::new(CodeFileUsecasesImpl::new(repository)));
+            let data_dir = TempDir::new().unwrap();
+            let node = ReplicationNode::new(
+                Uuid::new_v4(),
+                Arc::clone(&files),
+                data_dir.path().join("replicas"),
+                peers.iter().map(|&peer| urls[peer].clone()).collect(),
+            )
+            .unwrap();
+            let node = match token {
+                Some(token) => node.with_token(ClusterToken::new(token)),
+                None => node,
+            };
+            tokio::spawn(axum::serve(listener, router(node.clone())).into_future());
+            members.push(Member {
+                node,
+                files,
+                _data_dir: data_dir,
+            });
+        }
+        members
+    }
+
+    async fn round(member: &Member) {
+        let node = member.node.clone();
+        let failures = tokio::task::spawn_blocking(move || node.anti_entropy_round())
+            .await
+            .unwrap();
+        assert!(failures.is_empty(), "{failures:?}");
+    }
+
+    fn create(member: &Member) -> Uuid {
+        member
+            .files
+            .lock()
+            .unwrap()
+            .create_code_file(CreateCodeFileRequest {
+                name: format!("replica_{}.txt", Uuid::new_v4()),
+            })
+            .unwrap()
+            .id
+    }
+
+    fn edit(member: &Member, id: Uuid, start: u64, end: u64, text: &str, author: &str) {
+        member
+            .files
+            .lock()
+            .unwrap()
+            .update_code_file(UpdateCodeRequest {
+                id,
+                start,
+                end,
+                content: text.to_string(),
+                author: author.to_string(),
+            })
+            .unwrap();
+    }
+
+    fn content(member: &Member, id: Uuid) -> Option<String> {
+        let files = member.files.lock().unwrap();
+        files
+            .get_code_file(id)
+            .ok()
+            .map(|code_file| code_file.viewport.content)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_nodes_converge_on_concurrent_edits() {
+        let members = cluster(&[&[1, 2], &[0, 2], &[0, 1]]).await;
+        let id = create(&members[0]);
+        edit(&members[0], id, 0, 0, "fn main() {}", "grace");
+        round(&members[0]).await;
+        assert_eq!(content(&members[2], id).as_deref(), Some("fn main() {}"));
+
+        // All three edit the same text before hearing from each other.
+        edit(&members[0], id, 11, 11, " serve(); ", "grace");
+        edit(&members[1], id, 0, 0, "pub ", "ada");
+        edit(&members[2], id, 3, 7, "start", "linus");
+        for member in members.iter().chain(&members) {
+            round(member).await;
+        }
+
+        for member in &members {
+            assert_eq!(
+                content(member, id).as_deref(),
+                Some("pub fn start() { serve(); }")
+            );
+        }
+        let authors: Vec<String> = members[1]
+            .files
+            .lock()
+            .unwrap()
+            .history
+            .operations_after(id, 0)
+            .into_iter()
+            .map(|operation| operation.author)
+            .collect();
+        assert!(authors.contains(&"linus".to_string()));
+        assert!(authors.contains(&"grace".to_string()));
+
+        members[0]
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(id)
+            .unwrap();
+        round(&members[0]).await;
+        assert_eq!(content(&members[1], id), None);
+        assert_eq!(content(&members[2], id), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_writes_during_remote_integration_are_kept() {
+        let members = cluster(&[&[1], &[0]]).await;
+        let id = create(&members[0]);
+        round(&members[0]).await;
+        let local = AsyncCodeFileUsecasesImpl::new(&members[0].files.lock().unwrap());
+
+        // The first node takes writes that don't go through its mutex while
+        // the second keeps pushing its own edits to it.
+        let writes = tokio::spawn(async move {
+            for _ in 0..40 {
+                local
+                    .update_code_file(UpdateCodeRequest {
+                        id,
+                        start: 0,
+                        end: 0,
+                        content: "a".to_string(),
+                        author: "grace".to_string(),
+                    })
+                    .await
+                    .unwrap();
+            }
+        });
+        for _ in 0..40 {
+            edit(&members[1], id, 0, 0, "b", "ada");
+            round(&members[1]).await;
+        }
+        writes.await.unwrap();
+        round(&members[0]).await;
+        round(&members[1]).await;
+
+        let first = content(&members[0], id).unwrap();
+        assert_eq!(content(&members[1], id), Some(first.clone()));
+        assert_eq!(first.matches('a').count(), 40);
+        assert_eq!(first.matches('b').count(), 40);
+        let files = members[0].files.lock().unwrap();
+        let state = members[0].node.state.lock().unwrap();
+        assert_eq!(
+            state.files[&id].revision,
+            files.get_code_file(id).unwrap().revision
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_restored_files_replicate_again() {
+        let members = cluster(&[&[1], &[0]]).await;
+        let id = create(&members[0]);
+        edit(&members[0], id, 0, 0, "kept", "grace");
+        round(&members[0]).await;
+        // Removed on the node that didn't create it, restored where it did.
+        members[1]
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(id)
+            .unwrap();
+        round(&members[1]).await;
+        assert_eq!(content(&members[0], id), None);
+
+        members[0]
+            .files
+            .lock()
+            .unwrap()
+            .restore_code_file(id)
+            .unwrap();
+        round(&members[0]).await;
+        assert_eq!(content(&members[1], id).as_deref(), Some("kept"));
+        assert!(members[1].files.lock().unwrap().list_trash().unwrap().is_empty());
+
+        edit(&members[1], id, 4, 4, " going", "ada");
+        round(&members[1]).await;
+        assert_eq!(content(&members[0], id).as_deref(), Some("kept going"));
+
+        members[0]
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(id)
+            .unwrap();
+        round(&members[0]).await;
+        assert_eq!(content(&members[1], id), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lagging_peer_catches_up_from_any_peer() {
+        // The third node only ever talks to the second, and the first two
+        // don't know about it.
+        let members = cluster(&[&[1], &[0], &[1]]).await;
+        let id = create(&members[0]);
+        for (index, word) in ["alpha", "beta", "gamma", "delta"].iter().enumerate() {
+            let member = &members[index % 2];
+            round(member).await;
+            let length = content(member, id).unwrap().chars().count() as u64;
+            edit(member, id, length, length, &format!("{word}\n"), "grace");
+        }
+        round(&members[0]).await;
+        let expected = content(&members[0], id).unwrap();
+        assert_eq!(expected, "alpha\nbeta\ngamma\ndelta\n");
+        assert_eq!(content(&members[2], id), None);
+
+        round(&members[2]).await;
+        assert_eq!(content(&members[2], id), Some(expected));
+        assert_eq!(members[2].node.version(), members[0].node.version());
+
+        // Exchanging again has nothing left to send.
+        let version = members[2].node.version();
+        assert!(members[0].node.missing_for(&version).unwrap().is_empty());
+
+        members[0]
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(id)
+            .unwrap();
+        round(&members[0]).await;
+        round(&members[2]).await;
+        assert_eq!(content(&members[2], id), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_operations_every_node_has_are_dropped() {
+        let members = cluster(&[&[1], &[0]]).await;
+        for member in &members {
+            member.node.clone().with_log_limit(0);
+        }
+        let id = create(&members[0]);
+        edit(&members[0], id, 0, 0, "fn main() {}", "grace");
+        edit(&members[0], id, 3, 7, "", "grace");
+        round(&members[0]).await;
+        round(&members[1]).await;
+        round(&members[0]).await;
+
+        for member in &members {
+            let state = member.node.state.lock().unwrap();
+            assert!(state.log.is_empty());
+            assert_eq!(state.files[&id].text.len(), "fn () {}".len());
+        }
+        match members[0].node.missing_for(&VersionVector::new()) {
+            Err(ApplicationError::HistoryUnavailable(0)) => {},
+            _ => panic!("Expected HistoryUnavailable error"),
+        }
+
+        // Edits after the drop still meet in the same text.
+        edit(&members[0], id, 3, 3, "run", "grace");
+        edit(&members[1], id, 3, 3, "go", "ada");
+        for member in members.iter().chain(&members) {
+            round(member).await;
+        }
+        assert_eq!(content(&members[0], id), content(&members[1], id));
+        assert_eq!(
+            content(&members[0], id).unwrap().len(),
+            "fn rungo() {}".len()
+        );
+
+        members[0]
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(id)
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_peers_need_the_cluster_token() {
+        let members = cluster_with_tokens(
+            &[&[1, 2], &[0], &[0]],
+            &[Some("s3cret"), Some("s3cret"), Some("guess")],
+        )
+        .await;
+        let id = create(&members[0]);
+        edit(&members[0], id, 0, 0, "private", "grace");
+
+        let node = members[0].node.clone();
+        let failures = tokio::task::spawn_blocking(move || node.anti_entropy_round())
+            .await
+            .unwrap();
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(
+            failures[0].1,
+            ApplicationError::PeerUnreachable(_)
+        ));
+        assert_eq!(content(&members[1], id).as_deref(), Some("private"));
+        assert_eq!(content(&members[2], id), None);
+
+        members[0]
+            .files
+            .lock()
+            .unwrap()
+            .delete_code_file(id)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_out_of_order_operations_wait_for_their_dependencies() {
+        let data_dir = TempDir::new().unwrap();
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let files = Arc::new(Mutex::new(CodeFileUsecasesImpl::new(repository)));
+        let source = ReplicationNode::new(
+            Uuid::new_v4(),
+            Arc::clone(&files),
+            data_dir.path().join("source"),
+            Vec::new(),
+        )
+        .unwrap();
+        let member = Member {
+            node: source,
+            files,
+            _data_dir: data_dir,
+        };
+        let id = create(&member);
+        edit(&member, id, 0, 0, "abc", "grace");
+        let operations = member.node.missing_for(&VersionVector::new()).unwrap();
+        assert_eq!(operations.len(), 2);
+
+        let target_dir = TempDir::new().unwrap();
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let target_files = Arc::new(Mutex::new(CodeFileUsecasesImpl::new(repository)));
+        let target = ReplicationNode::new(
+            Uuid::new_v4(),
+            Arc::clone(&target_files),
+            target_dir.path().to_path_buf(),
+            Vec::new(),
+        )
+        .unwrap();
+
+        assert_eq!(target.apply_remote(vec![operations[1].clone()]).unwrap(), 0);
+        assert_eq!(target.apply_remote(operations.clone()).unwrap(), 2);
+        assert_eq!(target.apply_remote(operations).unwrap(), 0);
+        let code_file = target_files.lock().unwrap().get_code_file(id).unwrap();
+        assert_eq!(
+            (code_file.revision, code_file.viewport.content.as_str()),
+            (1, "abc")
+        );
+        assert!(target_dir.path().join(id.to_string()).exists());
+
+        member.files.lock().unwrap().delete_code_file(id).unwrap();
+    }
+
+    #[test]
+    fn test_diff_fallback_is_put_down_to_the_last_author() {
+        let data_dir = TempDir::new().unwrap();
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let mut usecases = CodeFileUsecasesImpl::new(repository);
+        usecases.history = OperationHistory::new(1);
+        let files = Arc::new(Mutex::new(usecases));
+        let node = ReplicationNode::new(
+            Uuid::new_v4(),
+            Arc::clone(&files),
+            data_dir.path().join("replicas"),
+            Vec::new(),
+        )
+        .unwrap();
+        let member = Member {
+            node,
+            files,
+            _data_dir: data_dir,
+        };
+        let id = create(&member);
+        member.node.missing_for(&VersionVector::new()).unwrap();
+
+        // The history only keeps the second edit, so both go out as one diff.
+        edit(&member, id, 0, 0, "ab", "grace");
+        edit(&member, id, 2, 2, "c", "ada");
+        let operations = member.node.missing_for(&VersionVector::new()).unwrap();
+        let last = operations.last().unwrap();
+        assert_eq!(last.author, "ada");
+        assert!(matches!(
+            &last.change,
+            Change::Text {
+                operation: TextOperation::Insert { text, .. }
+            } if text == "abc"
+        ));
+
+        member.files.lock().unwrap().delete_code_file(id).unwrap();
+    }
+}
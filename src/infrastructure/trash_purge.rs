@@ -0,0 +1,82 @@
+use crate::application::errors::ApplicationError;
+use crate::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use crate::application::usecases::trash_usecases::TrashUsecases;
+use crate::infrastructure::periodic::spawn_periodic;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Empties the trash of a workspace: files deleted longer than `retention`
+/// ago are purged.
+#[derive(Clone)]
+pub struct TrashPurger {
+    files: Arc<Mutex<CodeFileUsecasesImpl>>,
+    retention: Duration,
+}
+
+impl TrashPurger {
+    pub fn new(files: Arc<Mutex<CodeFileUsecasesImpl>>, retention: Duration) -> Self {
+        Self { files, retention }
+    }
+
+    pub fn run_once(&self) -> Result<Vec<Uuid>, ApplicationError> {
+        self.files.lock().unwrap().purge_expired(self.retention)
+    }
+
+    /// Runs every `interval` until the process exits, handing the error of
+    /// a run that fails to `failed`.
+    pub fn spawn<E>(&self, interval: Duration, failed: E) -> thread::JoinHandle<()>
+    where
+        E: FnMut(ApplicationError) + Send + 'static,
+    {
+        let purger = self.clone();
+        spawn_periodic("trash purge", interval, move || purger.run_once(), failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::dto::code_file::CreateCodeFileRequest;
+    use crate::application::usecases::code_file_usecases::CodeFileUsecases;
+    use crate::infrastructure::mmap_file_sys::MmapFileSystemSource;
+    use crate::infrastructure::persistence::file_trash_repository::FileTrashRepository;
+    use crate::infrastructure::persistence::in_memory_repository::InMemoryCodeFileRepository;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_spawned_purger_empties_the_trash() {
+        // The purger thread outlives the test; the trash doesn't.
+        let dir = TempDir::new().unwrap();
+        let trash = FileTrashRepository::open(dir.path().to_path_buf()).unwrap();
+        let repository = Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::new());
+        let files = Arc::new(Mutex::new(
+            CodeFileUsecasesImpl::new(repository).with_trash(Arc::new(trash)),
+        ));
+        let id = {
+            let mut files = files.lock().unwrap();
+            let id = files
+                .create_code_file(CreateCodeFileRequest {
+                    name: format!("purge_{}.txt", Uuid::new_v4()),
+                })
+                .unwrap()
+                .id;
+            files.delete_code_file(id).unwrap();
+            id
+        };
+
+        let purger = TrashPurger::new(Arc::clone(&files), Duration::from_millis(20));
+        assert!(purger.run_once().unwrap().is_empty());
+        purger.spawn(Duration::from_millis(10), |e| {
+            panic!("trash purge failed: {e:?}")
+        });
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while !files.lock().unwrap().list_trash().unwrap().is_empty() {
+            assert!(std::time::Instant::now() < deadline, "trash never purged");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(files.lock().unwrap().restore_code_file(id).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::application::repositories::webhook_repository::WebhookRepository;
+use crate::domain::events::DomainEvent;
+use crate::domain::traits::event_subscriber::EventSubscriber;
+use crate::domain::webhook::{DeliveryAttempt, DeliveryOutcome, Webhook, WebhookPayload};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use ureq::Agent;
+use uuid::Uuid;
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `body` keyed with `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={digest}")
+}
+
+struct Job {
+    delivery_id: Uuid,
+    webhook_id: Uuid,
+    sequence: u64,
+    event: &'static str,
+    body: Vec<u8>,
+}
+
+type SharedRepository = Arc<Mutex<Box<dyn WebhookRepository>>>;
+
+/// Turns bus events into POSTs to every matching webhook. Each webhook has its
+/// own worker thread, so a slow or failing endpoint only delays itself; within
+/// a webhook, events are delivered in bus order.
+pub struct WebhookDispatcher {
+    repository: SharedRepository,
+    agent: Agent,
+    policy: RetryPolicy,
+    workers: HashMap<Uuid, Sender<Job>>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(repository: Box<dyn WebhookRepository>, policy: RetryPolicy) -> Self {
+        let agent = Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_global(Some(policy.timeout))
+            .build()
+            .into();
+        Self {
+            repository: Arc::new(Mutex::new(repository)),
+            agent,
+            policy,
+            workers: HashMap::new(),
+        }
+    }
+
+    fn spawn_worker(&self) -> Sender<Job> {
+        let (sender, receiver) = channel();
+        let repository = Arc::clone(&self.repository);
+        let agent = self.agent.clone();
+        let policy = self.policy;
+        thread::spawn(move || deliver(repository, agent, policy, receiver));
+        sender
+    }
+}
+
+impl EventSubscriber for WebhookDispatcher {
+    fn handle(
+        &mut self,
+        sequence: u64,
+        event: &DomainEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let webhooks = self
+            .repository
+            .lock()
+            .unwrap()
+            .list()
+            .map_err(|e| format!("{e:?}"))?;
+        // Dropping the sender lets the worker of a removed webhook finish.
+        self.workers
+            .retain(|id, _| webhooks.iter().any(|webhook| webhook.id == *id));
+
+        for webhook in webhooks.iter().filter(|webhook| webhook.matches(event)) {
+            let delivery_id = Uuid::new_v4();
+            let body = serde_json::to_vec(&WebhookPayload {
+                delivery_id,
+                webhook_id: webhook.id,
+                sequence,
+                event,
+            })?;
+            let job = Job {
+                delivery_id,
+                webhook_id: webhook.id,
+                sequence,
+                event: event.name(),
+                body,
+            };
+            if !self.workers.contains_key(&webhook.id) {
+                let sender = self.spawn_worker();
+                self.workers.insert(webhook.id, sender);
+            }
+            self.workers[&webhook.id].send(job)?;
+        }
+        Ok(())
+    }
+}
+
+fn post(agent: &Agent, webhook: &Webhook, job: &Job) -> DeliveryOutcome {
+    let result = agent
+        .post(&webhook.url)
+        .header("content-type", "application/json")
+        .header(EVENT_HEADER, job.event)
+        .header(DELIVERY_HEADER, job.delivery_id.to_string())
+        .header(SIGNATURE_HEADER, sign(&webhook.secret, &job.body))
+        .send(&job.body);
+    match result {
+        Ok(response) if response.status().is_success() => {
+            DeliveryOutcome::Delivered(response.status().as_u16())
+        }
+        Ok(response) => DeliveryOutcome::Rejected(response.status().as_u16()),
+        Err(e) => DeliveryOutcome::Failed(e.to_string()),
+    }
+}
+
+// Anything but a 2xx is retried with doubling delays until the policy runs
+// out of attempts. The webhook is looked up again before every attempt so a
+// changed secret applies and a removed webhook stops being called.
+fn deliver(repository: SharedRepository, agent: Agent, policy: RetryPolicy, jobs: Receiver<Job>) {
+    while let Ok(job) = jobs.recv() {
+        let mut delay = policy.initial_delay;
+        for attempt in 1..=policy.max_attempts {
+            let Ok(webhook) = repository.lock().unwrap().find_by_id(job.webhook_id) else {
+                return;
+            };
+            let outcome = post(&agent, &webhook, &job);
+            let delivered = outcome.is_delivered();
+            let _ = repository.lock().unwrap().record_delivery(DeliveryAttempt {
+                delivery_id: job.delivery_id,
+                webhook_id: job.webhook_id,
+                sequence: job.sequence,
+                event: job.event.to_string(),
+                attempt,
+                outcome,
+            });
+            if delivered {
+                break;
+            }
+            if attempt < policy.max_attempts {
+                thread::sleep(delay);
+                delay = (delay * 2).min(policy.max_delay);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_matches_reference_hmac() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
@@ -1,7 +1,107 @@
-mod application;
-mod domain;
-mod infrastructure;
+use colab_engine::application::usecases::code_file_usecases::CodeFileUsecasesImpl;
+use colab_engine::application::usecases::webhook_usecases::WebhookUsecasesImpl;
+use colab_engine::infrastructure::cluster::Cluster;
+use colab_engine::infrastructure::external_change_watch::ExternalChangeWatcher;
+use colab_engine::infrastructure::history_compaction::HistoryCompactor;
+use colab_engine::infrastructure::http::auth::ClusterToken;
+use colab_engine::infrastructure::http::{AppState, router, serve};
+use colab_engine::infrastructure::mmap_file_sys::MmapFileSystemSource;
+use colab_engine::infrastructure::operation_history::CompactionPolicy;
+use colab_engine::infrastructure::persistence::file_trash_repository::FileTrashRepository;
+use colab_engine::infrastructure::persistence::in_memory_repository::{
+    CacheLimits, InMemoryCodeFileRepository,
+};
+use colab_engine::infrastructure::persistence::in_memory_webhook_repository::InMemoryWebhookRepository;
+use colab_engine::infrastructure::replication::{self, ReplicationNode};
+use colab_engine::infrastructure::trash_purge::TrashPurger;
+use colab_engine::infrastructure::webhook_dispatcher::{RetryPolicy, WebhookDispatcher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
 
-fn main() {
-    println!("Hello, world!");
+// Unset or unparsable limits leave the cache unbounded.
+fn limit(name: &str) -> Option<usize> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let address =
+        std::env::var("COLAB_ENGINE_ADDR").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    let limits = CacheLimits {
+        max_open: limit("COLAB_ENGINE_MAX_OPEN_FILES"),
+        max_bytes: limit("COLAB_ENGINE_MAX_MAPPED_BYTES"),
+    };
+    let repository =
+        Box::new(InMemoryCodeFileRepository::<MmapFileSystemSource>::with_limits(limits));
+    // What the engine keeps of its own, such as the trash and replicated files.
+    let data_dir = PathBuf::from(
+        std::env::var("COLAB_ENGINE_DATA_DIR").unwrap_or_else(|_| "/tmp/colab-engine".to_string()),
+    );
+    let trash = FileTrashRepository::open(data_dir.join("trash"))?;
+    let state = AppState::new(CodeFileUsecasesImpl::new(repository).with_trash(Arc::new(trash)));
+    let webhooks = InMemoryWebhookRepository::new();
+    state.events.register(Box::new(WebhookDispatcher::new(
+        Box::new(webhooks.clone()),
+        RetryPolicy::default(),
+    )));
+    let state = state.with_webhooks(WebhookUsecasesImpl::new(Box::new(webhooks)));
+    let policy = CompactionPolicy {
+        max_operations: limit("COLAB_ENGINE_MAX_HISTORY_OPERATIONS"),
+        ..CompactionPolicy::default()
+    };
+    HistoryCompactor::new(Arc::clone(&state.files), policy).spawn(Duration::from_secs(60), |e| {
+        eprintln!("history compaction failed: {e:?}")
+    });
+    // Edits other programs make to the files on disk are applied as they land.
+    ExternalChangeWatcher::new(Arc::clone(&state.files))
+        .map_err(|e| std::io::Error::other(format!("{e:?}")))?
+        .spawn(Duration::from_secs(1), |e| {
+            eprintln!("external change watch failed: {e:?}")
+        });
+    // Deleted files stay in the trash for a week unless told otherwise.
+    let retention = limit("COLAB_ENGINE_TRASH_RETENTION_SECS").unwrap_or(7 * 24 * 60 * 60);
+    TrashPurger::new(
+        Arc::clone(&state.files),
+        Duration::from_secs(retention as u64),
+    )
+    .spawn(Duration::from_secs(60), |e| {
+        eprintln!("trash purge failed: {e:?}")
+    });
+
+    // A comma separated list of peer base URLs turns on replication.
+    let peers: Vec<String> = std::env::var("COLAB_ENGINE_PEERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|peer| peer.trim().to_string())
+        .filter(|peer| !peer.is_empty())
+        .collect();
+    if peers.is_empty() {
+        return serve(listener, state).await;
+    }
+
+    // Peers prove they belong to the cluster with a secret they all share.
+    let token = std::env::var("COLAB_ENGINE_CLUSTER_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| {
+            std::io::Error::other("COLAB_ENGINE_PEERS needs COLAB_ENGINE_CLUSTER_TOKEN set")
+        })?;
+    let node = ReplicationNode::new(
+        Uuid::new_v4(),
+        Arc::clone(&state.files),
+        data_dir.join("replicas"),
+        peers.clone(),
+    )?
+    .with_token(ClusterToken::new(&token));
+    node.spawn_anti_entropy(Duration::from_secs(1));
+
+    // Every node owns a share of the files, named by the URL peers use for it.
+    let local = std::env::var("COLAB_ENGINE_URL").unwrap_or_else(|_| format!("http://{address}"));
+    let members = peers.into_iter().chain([local.clone()]).collect();
+    let cluster = Cluster::new(local, members, Arc::clone(&state.files), node.clone());
+    let state = state.with_cluster(cluster);
+    axum::serve(listener, router(state).merge(replication::router(node))).await
 }